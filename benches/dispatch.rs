@@ -0,0 +1,124 @@
+//! Rough cost of decoding and dispatching instructions: building a CPU,
+//! stepping a loop of mixed instructions on both cores and disassembling.
+//! The "table built per call" lines are the baseline: `CPU::new` and
+//! `disasm::decode` used to build the opcode table on every call, which
+//! they now share as the compile-time `OPCODES`. No benchmark framework,
+//! just the best wall-clock time of a few runs:
+//!
+//! ```text
+//! cargo bench --no-default-features --bench dispatch
+//! ```
+
+use nes_core::bus::{FlatBus, Mem};
+use nes_core::cpu::{opcode_table, CpuCore, CPU};
+use nes_core::disasm;
+use std::hint::black_box;
+use std::time::Instant;
+
+/// One pass through every kind of operand access, unofficial opcodes
+/// included, then back to the start.
+const PROGRAM: [u8; 25] = [
+    0xA9, 0x01, // LDA #$01
+    0x65, 0x10, // ADC $10
+    0x9D, 0x00, 0x02, // STA $0200,X
+    0xE8, // INX
+    0x51, 0x20, // EOR ($20),Y
+    0x0A, // ASL A
+    0x26, 0x11, // ROL $11
+    0xC9, 0x80, // CMP #$80
+    0x24, 0x12, // BIT $12
+    0xA7, 0x13, // *LAX $13
+    0xC7, 0x14, // *DCP $14
+    0xEA, // NOP
+    0x4C, 0x00, 0x06, // JMP $0600
+];
+const INSTRUCTIONS_PER_PASS: u32 = 13;
+
+const RUNS: usize = 5;
+
+/// Times `run`, which does `iterations` of something, and prints the cost
+/// of one in its fastest run.
+fn measure<F>(name: &str, unit: &str, iterations: u32, mut run: F)
+where
+    F: FnMut(),
+{
+    let fastest = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            run();
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default();
+    let nanos = fastest.as_nanos() as f64 / iterations as f64;
+    println!("{:38} {:10.1} ns/{}", name, nanos, unit);
+}
+
+fn cpu_with_program(core: CpuCore) -> CPU<FlatBus> {
+    let mut cpu = CPU::with_bus(FlatBus::new());
+    cpu.set_core(core);
+    for (offset, &byte) in PROGRAM.iter().enumerate() {
+        cpu.bus.mem_write(0x0600 + offset as u16, byte);
+    }
+    cpu.program_counter = 0x0600;
+    cpu
+}
+
+fn bench_new() {
+    let iterations = 100_000;
+    measure("CPU::new", "cpu", iterations, || {
+        for _ in 0..iterations {
+            black_box(CPU::new());
+        }
+    });
+    measure("CPU::new, table built per call", "cpu", iterations, || {
+        for _ in 0..iterations {
+            black_box(opcode_table());
+            black_box(CPU::new());
+        }
+    });
+}
+
+fn bench_step(name: &str, core: CpuCore) {
+    let instructions = 1_000_000 * INSTRUCTIONS_PER_PASS;
+    let mut cpu = cpu_with_program(core);
+    measure(name, "instruction", instructions, || {
+        for _ in 0..instructions {
+            black_box(cpu.step()).unwrap();
+        }
+    });
+}
+
+fn bench_decode() {
+    let iterations = 200_000;
+    let decode = |i: u32| {
+        let code = i as u8;
+        disasm::decode(0x8000, |address| match address {
+            0x8000 => code,
+            _ => 0x12,
+        })
+    };
+    measure("disasm::decode", "instruction", iterations, || {
+        for i in 0..iterations {
+            black_box(decode(i));
+        }
+    });
+    measure(
+        "disasm::decode, table built per call",
+        "instruction",
+        iterations,
+        || {
+            for i in 0..iterations {
+                black_box(opcode_table());
+                black_box(decode(i));
+            }
+        },
+    );
+}
+
+fn main() {
+    bench_new();
+    bench_step("step (instruction core)", CpuCore::Instruction);
+    bench_step("step (cycle-accurate core)", CpuCore::CycleAccurate);
+    bench_decode();
+}
@@ -0,0 +1,366 @@
+mod dmc;
+mod envelope;
+mod length_counter;
+mod mixer;
+mod noise;
+mod pulse;
+mod triangle;
+
+#[cfg(test)]
+mod apu_tests;
+
+use dmc::Dmc;
+use mixer::Filter;
+use noise::Noise;
+use pulse::Pulse;
+use triangle::Triangle;
+
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+/// NTSC CPU clock in Hz; the APU runs off the same clock.
+pub const CPU_CLOCK: f64 = 1_789_773.0;
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+
+const STATUS: u16 = 0x4015;
+const FRAME_COUNTER: u16 = 0x4017;
+
+/// # APU registers http://wiki.nesdev.com/w/index.php/APU_registers
+///
+///  $4000-$4003 pulse 1    DDLC VVVV  EPPP NSSS  TTTT TTTT  LLLL LTTT
+///  $4004-$4007 pulse 2    same as pulse 1
+///  $4008-$400B triangle   CRRR RRRR  ---- ----  TTTT TTTT  LLLL LTTT
+///  $400C-$400F noise      --LC VVVV  ---- ----  M--- PPPP  LLLL L---
+///  $4010-$4013 DMC        IL-- RRRR  -DDD DDDD  AAAA AAAA  LLLL LLLL
+///  $4015       status     write: ---D NT21 channel enables
+///                         read:  IF-D NT21 DMC IRQ (I), frame IRQ (F),
+///                                DMC active (D), length counters > 0 (NT21)
+///  $4017       frame      MI-- ----  5-step mode (M), IRQ inhibit (I)
+///
+/// The frame counter clocks envelopes and the triangle's linear counter on
+/// quarter frames, and length counters and sweeps on half frames.
+/// http://wiki.nesdev.com/w/index.php/APU_Frame_Counter
+pub struct NesAPU {
+    pulse1: Pulse,
+    pulse2: Pulse,
+    triangle: Triangle,
+    noise: Noise,
+    dmc: Dmc,
+
+    five_step_mode: bool,
+    frame_irq_inhibit: bool,
+    frame_irq: bool,
+    frame_cycle: u32,
+    /// A write to $4017 takes effect 3 or 4 CPU cycles later.
+    frame_counter_write: Option<(u8, u8)>,
+    cycles: u64,
+
+    sample_rate: u32,
+    cycles_per_sample: f64,
+    sample_clock: f64,
+    sample_sum: f32,
+    sample_count: u32,
+    filters: [Filter; 3],
+    samples: Vec<f32>,
+}
+
+impl NesAPU {
+    pub fn new(sample_rate: u32) -> Self {
+        NesAPU {
+            pulse1: Pulse::new(true),
+            pulse2: Pulse::new(false),
+            triangle: Triangle::default(),
+            noise: Noise::new(),
+            dmc: Dmc::new(),
+            five_step_mode: false,
+            frame_irq_inhibit: false,
+            frame_irq: false,
+            frame_cycle: 0,
+            frame_counter_write: None,
+            cycles: 0,
+            sample_rate,
+            cycles_per_sample: CPU_CLOCK / sample_rate as f64,
+            sample_clock: 0.0,
+            sample_sum: 0.0,
+            sample_count: 0,
+            filters: NesAPU::output_filters(sample_rate),
+            samples: Vec::new(),
+        }
+    }
+
+    fn output_filters(sample_rate: u32) -> [Filter; 3] {
+        [
+            Filter::high_pass(sample_rate, 90.0),
+            Filter::high_pass(sample_rate, 440.0),
+            Filter::low_pass(sample_rate, 14_000.0),
+        ]
+    }
+
+    /// The reset button silences all channels and restarts the frame
+    /// counter in the mode it was in, dropping a pending frame IRQ.
+    pub fn reset(&mut self) {
+        self.write_status(0);
+        self.frame_irq = false;
+        self.frame_cycle = 0;
+        self.frame_counter_write = None;
+    }
+
+    /// Generated samples and the output filters belong to the host side
+    /// and are left out.
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        self.pulse1.save_state(state);
+        self.pulse2.save_state(state);
+        self.triangle.save_state(state);
+        self.noise.save_state(state);
+        self.dmc.save_state(state);
+        state.write_bool(self.five_step_mode);
+        state.write_bool(self.frame_irq_inhibit);
+        state.write_bool(self.frame_irq);
+        state.write_u32(self.frame_cycle);
+        // a pending $4017 write: its delay, absent when there is none, then
+        // the value written
+        let (delay, data) = self.frame_counter_write.unzip();
+        state.write_option_u8(delay);
+        state.write_u8(data.unwrap_or(0));
+        state.write_u64(self.cycles);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.pulse1.load_state(state)?;
+        self.pulse2.load_state(state)?;
+        self.triangle.load_state(state)?;
+        self.noise.load_state(state)?;
+        self.dmc.load_state(state)?;
+        self.five_step_mode = state.read_bool()?;
+        self.frame_irq_inhibit = state.read_bool()?;
+        self.frame_irq = state.read_bool()?;
+        self.frame_cycle = state.read_u32()?;
+        let delay = state.read_option_u8()?;
+        let data = state.read_u8()?;
+        self.frame_counter_write = delay.map(|delay| (delay, data));
+        self.cycles = state.read_u64()?;
+        Ok(())
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Changes the rate of the generated samples. Fractional rates let the
+    /// audio output nudge it to keep its buffer level steady.
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.cycles_per_sample = CPU_CLOCK / sample_rate;
+        let rounded = sample_rate.round() as u32;
+        if rounded != self.sample_rate {
+            self.sample_rate = rounded;
+            self.filters = NesAPU::output_filters(rounded);
+        }
+    }
+
+    /// Removes and returns the samples generated so far, as mono f32 in
+    /// -1.0..=1.0. At most a second of them is kept when nobody takes them.
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+
+    pub fn write_register(&mut self, address: u16, data: u8) {
+        match address {
+            0x4000 => self.pulse1.write_control(data),
+            0x4001 => self.pulse1.write_sweep(data),
+            0x4002 => self.pulse1.write_timer_low(data),
+            0x4003 => self.pulse1.write_timer_high(data),
+            0x4004 => self.pulse2.write_control(data),
+            0x4005 => self.pulse2.write_sweep(data),
+            0x4006 => self.pulse2.write_timer_low(data),
+            0x4007 => self.pulse2.write_timer_high(data),
+            0x4008 => self.triangle.write_linear_counter(data),
+            0x400A => self.triangle.write_timer_low(data),
+            0x400B => self.triangle.write_timer_high(data),
+            0x400C => self.noise.write_control(data),
+            0x400E => self.noise.write_period(data),
+            0x400F => self.noise.write_length(data),
+            0x4010 => self.dmc.write_control(data),
+            0x4011 => self.dmc.write_direct_load(data),
+            0x4012 => self.dmc.write_sample_address(data),
+            0x4013 => self.dmc.write_sample_length(data),
+            STATUS => self.write_status(data),
+            FRAME_COUNTER => self.write_frame_counter(data),
+            _ => {}
+        }
+    }
+
+    fn write_status(&mut self, data: u8) {
+        self.pulse1.length.set_enabled(data & 0b0001 != 0);
+        self.pulse2.length.set_enabled(data & 0b0010 != 0);
+        self.triangle.length.set_enabled(data & 0b0100 != 0);
+        self.noise.length.set_enabled(data & 0b1000 != 0);
+        self.dmc.set_enabled(data & 0b1_0000 != 0);
+    }
+
+    fn write_frame_counter(&mut self, data: u8) {
+        self.frame_irq_inhibit = data & 0b0100_0000 != 0;
+        if self.frame_irq_inhibit {
+            self.frame_irq = false;
+        }
+        let delay = if self.cycles & 1 == 0 { 3 } else { 4 };
+        self.frame_counter_write = Some((delay, data));
+    }
+
+    /// Reads $4015, which acknowledges the frame IRQ.
+    pub fn read_status(&mut self) -> u8 {
+        let status = self.peek_status();
+        self.frame_irq = false;
+        status
+    }
+
+    /// $4015 without acknowledging the frame IRQ.
+    pub fn peek_status(&self) -> u8 {
+        let mut status = 0;
+        if self.pulse1.length.active() {
+            status |= 0b0000_0001;
+        }
+        if self.pulse2.length.active() {
+            status |= 0b0000_0010;
+        }
+        if self.triangle.length.active() {
+            status |= 0b0000_0100;
+        }
+        if self.noise.length.active() {
+            status |= 0b0000_1000;
+        }
+        if self.dmc.active() {
+            status |= 0b0001_0000;
+        }
+        if self.frame_irq {
+            status |= 0b0100_0000;
+        }
+        if self.dmc.irq() {
+            status |= 0b1000_0000;
+        }
+        status
+    }
+
+    pub fn frame_irq(&self) -> bool {
+        self.frame_irq
+    }
+
+    pub fn dmc_irq(&self) -> bool {
+        self.dmc.irq()
+    }
+
+    /// The address the DMC wants read; the bus answers with `dmc_fill` and
+    /// stalls the CPU for the fetch.
+    pub fn dmc_fetch_address(&self) -> Option<u16> {
+        self.dmc.fetch_address()
+    }
+
+    pub fn dmc_fill(&mut self, data: u8) {
+        self.dmc.fill(data);
+    }
+
+    /// Advances the APU by one CPU cycle.
+    pub fn tick(&mut self) {
+        self.clock_frame_counter();
+
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+        if self.cycles & 1 == 1 {
+            self.pulse1.clock_timer();
+            self.pulse2.clock_timer();
+        }
+        self.cycles += 1;
+
+        self.sample_sum += mixer::mix(
+            self.pulse1.output(),
+            self.pulse2.output(),
+            self.triangle.output(),
+            self.noise.output(),
+            self.dmc.output(),
+        );
+        self.sample_count += 1;
+        self.sample_clock += 1.0;
+        if self.sample_clock >= self.cycles_per_sample {
+            self.sample_clock -= self.cycles_per_sample;
+            self.push_sample();
+        }
+    }
+
+    /// Averages the mixer output over the cycles of one host sample, then
+    /// runs it through the console's output filters.
+    fn push_sample(&mut self) {
+        let mut sample = self.sample_sum / self.sample_count as f32;
+        self.sample_sum = 0.0;
+        self.sample_count = 0;
+        for filter in self.filters.iter_mut() {
+            sample = filter.process(sample);
+        }
+        if self.samples.len() < self.sample_rate as usize {
+            self.samples.push(sample);
+        }
+    }
+
+    fn clock_frame_counter(&mut self) {
+        if let Some((delay, data)) = self.frame_counter_write {
+            if delay > 1 {
+                self.frame_counter_write = Some((delay - 1, data));
+            } else {
+                self.frame_counter_write = None;
+                self.five_step_mode = data & 0b1000_0000 != 0;
+                self.frame_cycle = 0;
+                if self.five_step_mode {
+                    self.clock_quarter_frame();
+                    self.clock_half_frame();
+                }
+            }
+        }
+
+        self.frame_cycle += 1;
+        match (self.five_step_mode, self.frame_cycle) {
+            (_, 7457) | (_, 22371) => self.clock_quarter_frame(),
+            (_, 14913) | (true, 37281) => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+            }
+            (false, 29828) => self.set_frame_irq(),
+            (false, 29829) => {
+                self.set_frame_irq();
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+            }
+            (false, 29830) => {
+                self.set_frame_irq();
+                self.frame_cycle = 0;
+            }
+            (true, 37282) => self.frame_cycle = 0,
+            _ => {}
+        }
+    }
+
+    fn set_frame_irq(&mut self) {
+        if !self.frame_irq_inhibit {
+            self.frame_irq = true;
+        }
+    }
+
+    fn clock_quarter_frame(&mut self) {
+        self.pulse1.envelope.clock();
+        self.pulse2.envelope.clock();
+        self.noise.envelope.clock();
+        self.triangle.clock_linear_counter();
+    }
+
+    fn clock_half_frame(&mut self) {
+        self.pulse1.length.clock();
+        self.pulse2.length.clock();
+        self.triangle.length.clock();
+        self.noise.length.clock();
+        self.pulse1.clock_sweep();
+        self.pulse2.clock_sweep();
+    }
+}
+
+impl Default for NesAPU {
+    fn default() -> Self {
+        NesAPU::new(DEFAULT_SAMPLE_RATE)
+    }
+}
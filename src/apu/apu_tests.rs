@@ -0,0 +1,249 @@
+use super::mixer::mix;
+use super::{NesAPU, DEFAULT_SAMPLE_RATE};
+use crate::bus::{Bus, Mem};
+use crate::savestate::{StateReader, StateWriter};
+
+fn run(apu: &mut NesAPU, cycles: u32) {
+    for _ in 0..cycles {
+        apu.tick();
+    }
+}
+
+/// The output filters remove any constant level, such as the one the
+/// triangle leaves when it stops, so silence only shows once they settle.
+fn settled(samples: &[f32]) -> bool {
+    samples[samples.len() - 100..]
+        .iter()
+        .all(|sample| sample.abs() < 0.001)
+}
+
+#[test]
+fn status_reports_loaded_length_counters() {
+    let mut apu = NesAPU::new(DEFAULT_SAMPLE_RATE);
+    apu.write_register(0x4015, 0b0000_1111);
+    apu.write_register(0x4003, 0x08);
+    apu.write_register(0x4007, 0x08);
+    apu.write_register(0x400B, 0x08);
+    apu.write_register(0x400F, 0x08);
+    assert_eq!(apu.read_status(), 0b0000_1111);
+
+    apu.write_register(0x4015, 0b0000_0101);
+    assert_eq!(apu.read_status(), 0b0000_0101);
+}
+
+#[test]
+fn length_counter_is_not_loaded_while_disabled() {
+    let mut apu = NesAPU::new(DEFAULT_SAMPLE_RATE);
+    apu.write_register(0x4003, 0x08);
+    assert_eq!(apu.read_status(), 0);
+}
+
+#[test]
+fn length_counter_counts_half_frames() {
+    let mut apu = NesAPU::new(DEFAULT_SAMPLE_RATE);
+    apu.write_register(0x4015, 0b0000_0001);
+    // length index 3 loads 2
+    apu.write_register(0x4003, 0b0001_1000);
+
+    run(&mut apu, 14913);
+    assert_eq!(apu.read_status() & 1, 1);
+    run(&mut apu, 29829 - 14913);
+    assert_eq!(apu.read_status() & 1, 0);
+}
+
+#[test]
+fn halted_length_counter_keeps_playing() {
+    let mut apu = NesAPU::new(DEFAULT_SAMPLE_RATE);
+    apu.write_register(0x4015, 0b0000_0001);
+    apu.write_register(0x4000, 0b0010_0000);
+    apu.write_register(0x4003, 0b0001_1000);
+
+    run(&mut apu, 29830 * 2);
+    assert_eq!(apu.read_status() & 1, 1);
+}
+
+#[test]
+fn four_step_mode_raises_frame_irq() {
+    let mut apu = NesAPU::new(DEFAULT_SAMPLE_RATE);
+    run(&mut apu, 29827);
+    assert!(!apu.frame_irq());
+    run(&mut apu, 1);
+    assert!(apu.frame_irq());
+    assert_eq!(apu.peek_status() & 0x40, 0x40);
+
+    assert_eq!(apu.read_status() & 0x40, 0x40);
+    assert!(!apu.frame_irq());
+}
+
+#[test]
+fn frame_irq_can_be_inhibited() {
+    let mut apu = NesAPU::new(DEFAULT_SAMPLE_RATE);
+    run(&mut apu, 29830);
+    assert!(apu.frame_irq());
+
+    apu.write_register(0x4017, 0x40);
+    assert!(!apu.frame_irq());
+    run(&mut apu, 29830 * 2);
+    assert!(!apu.frame_irq());
+}
+
+#[test]
+fn five_step_mode_has_no_irq_and_clocks_immediately() {
+    let mut apu = NesAPU::new(DEFAULT_SAMPLE_RATE);
+    apu.write_register(0x4015, 0b0000_0001);
+    // length index 1 loads 254, index 3 loads 2
+    apu.write_register(0x4003, 0b0001_1000);
+    apu.write_register(0x4017, 0x80);
+
+    // the write takes effect after 3 or 4 cycles and clocks a half frame
+    run(&mut apu, 4);
+    run(&mut apu, 14913);
+    assert_eq!(apu.read_status() & 1, 0);
+
+    run(&mut apu, 37282 * 2);
+    assert!(!apu.frame_irq());
+}
+
+#[test]
+fn saves_a_pending_frame_counter_write() {
+    let mut apu = NesAPU::new(DEFAULT_SAMPLE_RATE);
+    apu.write_register(0x4017, 0x80);
+    let mut state = StateWriter::new();
+    apu.save_state(&mut state);
+    let state = state.into_bytes();
+    // present, a delay of 3 cycles on an even cycle, $80; then the cycles
+    assert_eq!(state[state.len() - 11..state.len() - 8], [1, 3, 0x80]);
+
+    let mut loaded = NesAPU::new(DEFAULT_SAMPLE_RATE);
+    loaded.load_state(&mut StateReader::new(&state)).unwrap();
+    run(&mut apu, 3);
+    run(&mut loaded, 3);
+    assert!(apu.five_step_mode);
+    assert!(loaded.five_step_mode);
+}
+
+#[test]
+fn generates_samples_at_host_rate() {
+    let mut apu = NesAPU::new(48_000);
+    // pulse 1 at constant volume 15, 50% duty, about 440 Hz
+    apu.write_register(0x4015, 0b0000_0001);
+    apu.write_register(0x4000, 0b1011_1111);
+    apu.write_register(0x4002, 0xFD);
+    apu.write_register(0x4003, 0x00);
+
+    run(&mut apu, 1_789_773 / 10);
+    let samples = apu.take_samples();
+    assert!((4799..=4801).contains(&samples.len()));
+    assert!(samples.iter().all(|sample| (-1.0..=1.0).contains(sample)));
+    let max = samples.iter().cloned().fold(f32::MIN, f32::max);
+    let min = samples.iter().cloned().fold(f32::MAX, f32::min);
+    assert!(max - min > 0.1);
+
+    assert!(apu.take_samples().is_empty());
+}
+
+#[test]
+fn silent_when_nothing_plays() {
+    let mut apu = NesAPU::new(DEFAULT_SAMPLE_RATE);
+    run(&mut apu, 100_000);
+    assert!(settled(&apu.take_samples()));
+}
+
+#[test]
+fn pulse_with_too_low_period_is_muted() {
+    let mut apu = NesAPU::new(DEFAULT_SAMPLE_RATE);
+    apu.write_register(0x4015, 0b0000_0001);
+    apu.write_register(0x4000, 0b1011_1111);
+    apu.write_register(0x4002, 0x07);
+    apu.write_register(0x4003, 0x00);
+
+    run(&mut apu, 100_000);
+    assert_eq!(apu.read_status() & 1, 1);
+    assert!(settled(&apu.take_samples()));
+}
+
+#[test]
+fn sample_buffer_is_capped_at_one_second() {
+    let mut apu = NesAPU::new(DEFAULT_SAMPLE_RATE);
+    run(&mut apu, 1_789_773 * 2);
+    assert_eq!(apu.take_samples().len(), DEFAULT_SAMPLE_RATE as usize);
+}
+
+#[test]
+fn mixer_is_non_linear() {
+    assert_eq!(mix(0, 0, 0, 0, 0), 0.0);
+    let full = mix(15, 15, 15, 15, 127);
+    assert!(full > 0.95 && full < 1.05);
+    // two pulses at 15 are quieter than twice one pulse at 15
+    assert!(mix(15, 15, 0, 0, 0) < 2.0 * mix(15, 0, 0, 0, 0));
+}
+
+#[test]
+fn dmc_direct_load_sets_output() {
+    let mut apu = NesAPU::new(DEFAULT_SAMPLE_RATE);
+    apu.write_register(0x4011, 0x7F);
+    run(&mut apu, 1000);
+    assert!(apu.take_samples().iter().any(|&sample| sample != 0.0));
+}
+
+#[test]
+fn dmc_fetches_through_bus_and_stalls_cpu() {
+    let mut bus = Bus::new();
+    bus.mem_write(0xC040, 0xFF);
+    // IRQ enabled, fastest rate, one byte at $C040
+    bus.mem_write(0x4010, 0x8F);
+    bus.mem_write(0x4012, 0x01);
+    bus.mem_write(0x4013, 0x00);
+    bus.mem_write(0x4015, 0x10);
+    assert_eq!(bus.mem_read(0x4015) & 0x10, 0x10);
+
+    bus.tick(1);
+    assert_eq!(bus.take_dma_stall_cycles(), 4);
+    assert_eq!(bus.mem_read(0x4015) & 0x90, 0x80);
+    assert!(bus.irq_asserted());
+
+    // writing $4015 acknowledges the DMC IRQ
+    bus.mem_write(0x4015, 0x00);
+    bus.tick(1);
+    assert!(!bus.irq_asserted());
+}
+
+#[test]
+fn dmc_loops_without_irq() {
+    let mut bus = Bus::new();
+    bus.mem_write(0x4010, 0xCF);
+    bus.mem_write(0x4013, 0x00);
+    bus.mem_write(0x4015, 0x10);
+
+    for _ in 0..10 {
+        bus.tick(100);
+    }
+    assert_eq!(bus.mem_read(0x4015) & 0x90, 0x10);
+    assert!(!bus.irq_asserted());
+}
+
+#[test]
+fn frame_irq_reaches_the_bus() {
+    let mut bus = Bus::new();
+    for _ in 0..2983 {
+        bus.tick(10);
+    }
+    assert!(bus.irq_asserted());
+    bus.mem_read(0x4015);
+    bus.tick(1);
+    assert!(!bus.irq_asserted());
+}
+
+#[test]
+fn reset_silences_channels_and_drops_frame_irq() {
+    let mut apu = NesAPU::new(DEFAULT_SAMPLE_RATE);
+    apu.write_register(0x4015, 0b0000_0001);
+    apu.write_register(0x4003, 0x08);
+    run(&mut apu, 29830);
+    assert_eq!(apu.peek_status(), 0b0100_0001);
+
+    apu.reset();
+    assert_eq!(apu.peek_status(), 0);
+    run(&mut apu, 29827);
+    assert!(!apu.frame_irq());
+}
@@ -0,0 +1,200 @@
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+/// Timer periods in CPU cycles (NTSC).
+const RATES: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+
+/// Delta modulation channel at $4010-$4013. It plays 1-bit delta encoded
+/// samples that it fetches from $8000-$FFFF itself, stalling the CPU.
+/// http://wiki.nesdev.com/w/index.php/APU_DMC
+pub struct Dmc {
+    irq_enabled: bool,
+    looping: bool,
+    timer_period: u16,
+    timer: u16,
+    output_level: u8,
+
+    sample_address: u16,
+    sample_length: u16,
+    current_address: u16,
+    bytes_remaining: u16,
+    sample_buffer: Option<u8>,
+
+    shift_register: u8,
+    bits_remaining: u8,
+    silence: bool,
+    irq: bool,
+}
+
+impl Dmc {
+    pub fn new() -> Self {
+        Dmc {
+            irq_enabled: false,
+            looping: false,
+            timer_period: RATES[0],
+            timer: 0,
+            output_level: 0,
+            sample_address: 0xC000,
+            sample_length: 1,
+            current_address: 0xC000,
+            bytes_remaining: 0,
+            sample_buffer: None,
+            shift_register: 0,
+            bits_remaining: 8,
+            silence: true,
+            irq: false,
+        }
+    }
+
+    /// IL-- RRRR: IRQ enable, loop, rate index.
+    pub fn write_control(&mut self, data: u8) {
+        self.irq_enabled = data & 0b1000_0000 != 0;
+        if !self.irq_enabled {
+            self.irq = false;
+        }
+        self.looping = data & 0b0100_0000 != 0;
+        self.timer_period = RATES[(data & 0b1111) as usize];
+    }
+
+    /// -DDD DDDD: sets the output level directly.
+    pub fn write_direct_load(&mut self, data: u8) {
+        self.output_level = data & 0b0111_1111;
+    }
+
+    /// Sample address = $C000 + data * 64.
+    pub fn write_sample_address(&mut self, data: u8) {
+        self.sample_address = 0xC000 | (data as u16) << 6;
+    }
+
+    /// Sample length = data * 16 + 1 bytes.
+    pub fn write_sample_length(&mut self, data: u8) {
+        self.sample_length = (data as u16) << 4 | 1;
+    }
+
+    /// Driven by $4015: starts the sample if it has finished, or stops it.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.irq = false;
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    pub fn active(&self) -> bool {
+        self.bytes_remaining > 0
+    }
+
+    pub fn irq(&self) -> bool {
+        self.irq
+    }
+
+    /// The address the memory reader wants to fetch, when the sample buffer
+    /// is empty and the sample is not over.
+    pub fn fetch_address(&self) -> Option<u16> {
+        match self.sample_buffer {
+            None if self.bytes_remaining > 0 => Some(self.current_address),
+            _ => None,
+        }
+    }
+
+    /// Completes the fetch requested through `fetch_address`.
+    pub fn fill(&mut self, data: u8) {
+        self.sample_buffer = Some(data);
+        self.current_address = match self.current_address {
+            0xFFFF => 0x8000,
+            address => address + 1,
+        };
+        self.bytes_remaining -= 1;
+        if self.bytes_remaining == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq = true;
+            }
+        }
+    }
+
+    /// Clocked every CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.timer_period - 1;
+
+        if !self.silence {
+            if self.shift_register & 1 != 0 {
+                if self.output_level <= 125 {
+                    self.output_level += 2;
+                }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
+            }
+        }
+        self.shift_register >>= 1;
+
+        self.bits_remaining -= 1;
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(data) => {
+                    self.silence = false;
+                    self.shift_register = data;
+                }
+                None => self.silence = true,
+            }
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        self.output_level
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.irq_enabled);
+        state.write_bool(self.looping);
+        state.write_u16(self.timer_period);
+        state.write_u16(self.timer);
+        state.write_u8(self.output_level);
+        state.write_u16(self.sample_address);
+        state.write_u16(self.sample_length);
+        state.write_u16(self.current_address);
+        state.write_u16(self.bytes_remaining);
+        state.write_option_u8(self.sample_buffer);
+        state.write_u8(self.shift_register);
+        state.write_u8(self.bits_remaining);
+        state.write_bool(self.silence);
+        state.write_bool(self.irq);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.irq_enabled = state.read_bool()?;
+        self.looping = state.read_bool()?;
+        self.timer_period = state.read_u16()?;
+        if !RATES.contains(&self.timer_period) {
+            return Err(SaveStateError::Invalid("DMC rate"));
+        }
+        self.timer = state.read_u16()?;
+        self.output_level = state.read_u8()?;
+        self.sample_address = state.read_u16()?;
+        self.sample_length = state.read_u16()?;
+        self.current_address = state.read_u16()?;
+        self.bytes_remaining = state.read_u16()?;
+        self.sample_buffer = state.read_option_u8()?;
+        self.shift_register = state.read_u8()?;
+        self.bits_remaining = state.read_u8()?;
+        if !(1..=8).contains(&self.bits_remaining) {
+            return Err(SaveStateError::Invalid("DMC bit counter"));
+        }
+        self.silence = state.read_bool()?;
+        self.irq = state.read_bool()?;
+        Ok(())
+    }
+}
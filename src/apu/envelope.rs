@@ -0,0 +1,71 @@
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+/// Volume unit of the pulse and noise channels: either a constant volume or
+/// a sawtooth decaying from 15, clocked by quarter frames.
+/// http://wiki.nesdev.com/w/index.php/APU_Envelope
+#[derive(Default)]
+pub struct Envelope {
+    start: bool,
+    divider: u8,
+    decay: u8,
+    period: u8,
+    looping: bool,
+    constant: bool,
+}
+
+impl Envelope {
+    /// Takes the --LC VVVV bits of the channel's first register.
+    pub fn write(&mut self, data: u8) {
+        self.looping = data & 0b0010_0000 != 0;
+        self.constant = data & 0b0001_0000 != 0;
+        self.period = data & 0b0000_1111;
+    }
+
+    pub fn restart(&mut self) {
+        self.start = true;
+    }
+
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.period;
+        } else if self.divider > 0 {
+            self.divider -= 1;
+        } else {
+            self.divider = self.period;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.looping {
+                self.decay = 15;
+            }
+        }
+    }
+
+    pub fn volume(&self) -> u8 {
+        if self.constant {
+            self.period
+        } else {
+            self.decay
+        }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.start);
+        state.write_u8(self.divider);
+        state.write_u8(self.decay);
+        state.write_u8(self.period);
+        state.write_bool(self.looping);
+        state.write_bool(self.constant);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.start = state.read_bool()?;
+        self.divider = state.read_u8()?;
+        self.decay = state.read_u8()?;
+        self.period = state.read_u8()?;
+        self.looping = state.read_bool()?;
+        self.constant = state.read_bool()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,60 @@
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+/// http://wiki.nesdev.com/w/index.php/APU_Length_Counter
+const LENGTH_TABLE: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22,
+    192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+/// Silences a channel once its note has played for the loaded number of
+/// half frames, unless halted.
+#[derive(Default)]
+pub struct LengthCounter {
+    enabled: bool,
+    halted: bool,
+    value: u8,
+}
+
+impl LengthCounter {
+    /// Driven by $4015; disabling the channel clears the counter at once.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.value = 0;
+        }
+    }
+
+    pub fn set_halted(&mut self, halted: bool) {
+        self.halted = halted;
+    }
+
+    /// Loads the length selected by the upper five bits of `data`.
+    pub fn load(&mut self, data: u8) {
+        if self.enabled {
+            self.value = LENGTH_TABLE[(data >> 3) as usize];
+        }
+    }
+
+    pub fn clock(&mut self) {
+        if !self.halted && self.value > 0 {
+            self.value -= 1;
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        self.value > 0
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.enabled);
+        state.write_bool(self.halted);
+        state.write_u8(self.value);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.enabled = state.read_bool()?;
+        self.halted = state.read_bool()?;
+        self.value = state.read_u8()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+/// Non-linear DAC of the 2A03, from channel levels to 0.0..=1.0.
+/// http://wiki.nesdev.com/w/index.php/APU_Mixer
+pub fn mix(pulse1: u8, pulse2: u8, triangle: u8, noise: u8, dmc: u8) -> f32 {
+    let pulse = pulse1 as f32 + pulse2 as f32;
+    let pulse_out = if pulse == 0.0 {
+        0.0
+    } else {
+        95.88 / (8128.0 / pulse + 100.0)
+    };
+
+    let tnd = triangle as f32 / 8227.0 + noise as f32 / 12241.0 + dmc as f32 / 22638.0;
+    let tnd_out = if tnd == 0.0 {
+        0.0
+    } else {
+        159.79 / (1.0 / tnd + 100.0)
+    };
+
+    pulse_out + tnd_out
+}
+
+/// First-order filter; the console's output stage has two high-passes, at
+/// 90 Hz and 440 Hz, and a low-pass at 14 kHz.
+pub struct Filter {
+    high_pass: bool,
+    alpha: f32,
+    previous_input: f32,
+    previous_output: f32,
+}
+
+impl Filter {
+    pub fn high_pass(sample_rate: u32, cutoff: f32) -> Self {
+        let (rc, dt) = Filter::constants(sample_rate, cutoff);
+        Filter {
+            high_pass: true,
+            alpha: rc / (rc + dt),
+            previous_input: 0.0,
+            previous_output: 0.0,
+        }
+    }
+
+    pub fn low_pass(sample_rate: u32, cutoff: f32) -> Self {
+        let (rc, dt) = Filter::constants(sample_rate, cutoff);
+        Filter {
+            high_pass: false,
+            alpha: dt / (rc + dt),
+            previous_input: 0.0,
+            previous_output: 0.0,
+        }
+    }
+
+    fn constants(sample_rate: u32, cutoff: f32) -> (f32, f32) {
+        (
+            1.0 / (2.0 * std::f32::consts::PI * cutoff),
+            1.0 / sample_rate as f32,
+        )
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = if self.high_pass {
+            self.alpha * (self.previous_output + input - self.previous_input)
+        } else {
+            self.previous_output + self.alpha * (input - self.previous_output)
+        };
+        self.previous_input = input;
+        self.previous_output = output;
+        output
+    }
+}
@@ -0,0 +1,93 @@
+use super::envelope::Envelope;
+use super::length_counter::LengthCounter;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+/// Timer periods in CPU cycles (NTSC).
+const PERIODS: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+
+/// Pseudo-random noise channel at $400C-$400F.
+/// http://wiki.nesdev.com/w/index.php/APU_Noise
+pub struct Noise {
+    /// Short mode takes the feedback from bit 6 instead of bit 1, giving a
+    /// 93-step metallic tone instead of hiss.
+    short_mode: bool,
+    timer_period: u16,
+    timer: u16,
+    shift_register: u16,
+    pub envelope: Envelope,
+    pub length: LengthCounter,
+}
+
+impl Noise {
+    pub fn new() -> Self {
+        Noise {
+            short_mode: false,
+            timer_period: PERIODS[0],
+            timer: 0,
+            shift_register: 1,
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+        }
+    }
+
+    /// --LC VVVV: length counter halt, constant volume, volume/period.
+    pub fn write_control(&mut self, data: u8) {
+        self.length.set_halted(data & 0b0010_0000 != 0);
+        self.envelope.write(data);
+    }
+
+    /// M--- PPPP: mode, period index.
+    pub fn write_period(&mut self, data: u8) {
+        self.short_mode = data & 0b1000_0000 != 0;
+        self.timer_period = PERIODS[(data & 0b1111) as usize];
+    }
+
+    /// LLLL L---: length counter load. Restarts the envelope.
+    pub fn write_length(&mut self, data: u8) {
+        self.length.load(data);
+        self.envelope.restart();
+    }
+
+    /// Clocked every CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period - 1;
+            let tap = if self.short_mode { 6 } else { 1 };
+            let feedback = (self.shift_register ^ (self.shift_register >> tap)) & 1;
+            self.shift_register = (self.shift_register >> 1) | (feedback << 14);
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.shift_register & 1 != 0 || !self.length.active() {
+            0
+        } else {
+            self.envelope.volume()
+        }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.short_mode);
+        state.write_u16(self.timer_period);
+        state.write_u16(self.timer);
+        state.write_u16(self.shift_register);
+        self.envelope.save_state(state);
+        self.length.save_state(state);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.short_mode = state.read_bool()?;
+        self.timer_period = state.read_u16()?;
+        if !PERIODS.contains(&self.timer_period) {
+            return Err(SaveStateError::Invalid("noise period"));
+        }
+        self.timer = state.read_u16()?;
+        self.shift_register = state.read_u16()?;
+        self.envelope.load_state(state)?;
+        self.length.load_state(state)
+    }
+}
@@ -0,0 +1,162 @@
+use super::envelope::Envelope;
+use super::length_counter::LengthCounter;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+const DUTY_CYCLES: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+/// Square wave channel at $4000-$4003 or $4004-$4007.
+/// http://wiki.nesdev.com/w/index.php/APU_Pulse
+pub struct Pulse {
+    /// The first channel's sweep negates with one's complement, the second
+    /// one with two's complement.
+    ones_complement: bool,
+    duty: u8,
+    step: u8,
+    timer_period: u16,
+    timer: u16,
+    pub envelope: Envelope,
+    pub length: LengthCounter,
+
+    sweep_enabled: bool,
+    sweep_period: u8,
+    sweep_negate: bool,
+    sweep_shift: u8,
+    sweep_reload: bool,
+    sweep_divider: u8,
+}
+
+impl Pulse {
+    pub fn new(ones_complement: bool) -> Self {
+        Pulse {
+            ones_complement,
+            duty: 0,
+            step: 0,
+            timer_period: 0,
+            timer: 0,
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+            sweep_enabled: false,
+            sweep_period: 0,
+            sweep_negate: false,
+            sweep_shift: 0,
+            sweep_reload: false,
+            sweep_divider: 0,
+        }
+    }
+
+    /// DDLC VVVV: duty, length counter halt, constant volume, volume/period.
+    pub fn write_control(&mut self, data: u8) {
+        self.duty = data >> 6;
+        self.length.set_halted(data & 0b0010_0000 != 0);
+        self.envelope.write(data);
+    }
+
+    /// EPPP NSSS: enabled, period, negate, shift.
+    pub fn write_sweep(&mut self, data: u8) {
+        self.sweep_enabled = data & 0b1000_0000 != 0;
+        self.sweep_period = (data >> 4) & 0b111;
+        self.sweep_negate = data & 0b0000_1000 != 0;
+        self.sweep_shift = data & 0b111;
+        self.sweep_reload = true;
+    }
+
+    pub fn write_timer_low(&mut self, data: u8) {
+        self.timer_period = (self.timer_period & 0x0700) | data as u16;
+    }
+
+    /// LLLL LTTT: length counter load, timer high bits. Restarts the note.
+    pub fn write_timer_high(&mut self, data: u8) {
+        self.timer_period = (self.timer_period & 0x00FF) | ((data as u16 & 0b111) << 8);
+        self.length.load(data);
+        self.step = 0;
+        self.envelope.restart();
+    }
+
+    /// Clocked every APU cycle, i.e. every other CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.step = (self.step + 1) % 8;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// Clocked by half frames.
+    pub fn clock_sweep(&mut self) {
+        if self.sweep_divider == 0 && self.sweep_enabled && self.sweep_shift > 0 && !self.muted() {
+            self.timer_period = self.sweep_target();
+        }
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+
+    fn sweep_target(&self) -> u16 {
+        let change = self.timer_period >> self.sweep_shift;
+        if self.sweep_negate {
+            self.timer_period
+                .saturating_sub(change + self.ones_complement as u16)
+        } else {
+            self.timer_period + change
+        }
+    }
+
+    /// The sweep unit silences the channel even while disabled.
+    fn muted(&self) -> bool {
+        self.timer_period < 8 || self.sweep_target() > 0x07FF
+    }
+
+    pub fn output(&self) -> u8 {
+        if !self.length.active()
+            || self.muted()
+            || DUTY_CYCLES[self.duty as usize][self.step as usize] == 0
+        {
+            0
+        } else {
+            self.envelope.volume()
+        }
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.duty);
+        state.write_u8(self.step);
+        state.write_u16(self.timer_period);
+        state.write_u16(self.timer);
+        self.envelope.save_state(state);
+        self.length.save_state(state);
+        state.write_bool(self.sweep_enabled);
+        state.write_u8(self.sweep_period);
+        state.write_bool(self.sweep_negate);
+        state.write_u8(self.sweep_shift);
+        state.write_bool(self.sweep_reload);
+        state.write_u8(self.sweep_divider);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.duty = state.read_u8()?;
+        self.step = state.read_u8()?;
+        if self.duty as usize >= DUTY_CYCLES.len() || self.step >= 8 {
+            return Err(SaveStateError::Invalid("pulse sequencer"));
+        }
+        self.timer_period = state.read_u16()?;
+        self.timer = state.read_u16()?;
+        self.envelope.load_state(state)?;
+        self.length.load_state(state)?;
+        self.sweep_enabled = state.read_bool()?;
+        self.sweep_period = state.read_u8()?;
+        self.sweep_negate = state.read_bool()?;
+        self.sweep_shift = state.read_u8()?;
+        self.sweep_reload = state.read_bool()?;
+        self.sweep_divider = state.read_u8()?;
+        Ok(())
+    }
+}
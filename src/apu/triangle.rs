@@ -0,0 +1,96 @@
+use super::length_counter::LengthCounter;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+const SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12,
+    13, 14, 15,
+];
+
+/// Triangle channel at $4008-$400B. It has no volume control; the linear
+/// counter gives a finer note length than the length counter.
+/// http://wiki.nesdev.com/w/index.php/APU_Triangle
+#[derive(Default)]
+pub struct Triangle {
+    timer_period: u16,
+    timer: u16,
+    step: u8,
+    control: bool,
+    linear_period: u8,
+    linear_counter: u8,
+    linear_reload: bool,
+    pub length: LengthCounter,
+}
+
+impl Triangle {
+    /// CRRR RRRR: length counter halt and linear counter control, reload value.
+    pub fn write_linear_counter(&mut self, data: u8) {
+        self.control = data & 0b1000_0000 != 0;
+        self.length.set_halted(self.control);
+        self.linear_period = data & 0b0111_1111;
+    }
+
+    pub fn write_timer_low(&mut self, data: u8) {
+        self.timer_period = (self.timer_period & 0x0700) | data as u16;
+    }
+
+    /// LLLL LTTT: length counter load, timer high bits.
+    pub fn write_timer_high(&mut self, data: u8) {
+        self.timer_period = (self.timer_period & 0x00FF) | ((data as u16 & 0b111) << 8);
+        self.length.load(data);
+        self.linear_reload = true;
+    }
+
+    /// Clocked every CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            if self.length.active() && self.linear_counter > 0 {
+                self.step = (self.step + 1) % 32;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// Clocked by quarter frames.
+    pub fn clock_linear_counter(&mut self) {
+        if self.linear_reload {
+            self.linear_counter = self.linear_period;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+        if !self.control {
+            self.linear_reload = false;
+        }
+    }
+
+    /// A silenced triangle holds its last step instead of dropping to 0.
+    pub fn output(&self) -> u8 {
+        SEQUENCE[self.step as usize]
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.timer_period);
+        state.write_u16(self.timer);
+        state.write_u8(self.step);
+        state.write_bool(self.control);
+        state.write_u8(self.linear_period);
+        state.write_u8(self.linear_counter);
+        state.write_bool(self.linear_reload);
+        self.length.save_state(state);
+    }
+
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.timer_period = state.read_u16()?;
+        self.timer = state.read_u16()?;
+        self.step = state.read_u8()?;
+        if self.step as usize >= SEQUENCE.len() {
+            return Err(SaveStateError::Invalid("triangle sequencer"));
+        }
+        self.control = state.read_bool()?;
+        self.linear_period = state.read_u8()?;
+        self.linear_counter = state.read_u8()?;
+        self.linear_reload = state.read_bool()?;
+        self.length.load_state(state)
+    }
+}
@@ -0,0 +1,104 @@
+#[cfg(test)]
+mod audio_tests;
+
+use crate::apu::NesAPU;
+
+/// Largest change of the generated sample rate, as a fraction of the
+/// device rate. 0.5% is below what ears notice as a pitch change.
+const MAX_RATE_DELTA: f64 = 0.005;
+/// Buffer level the rate control steers towards, in seconds.
+const TARGET_LATENCY: f64 = 0.05;
+
+/// Destination of the APU's samples.
+pub trait AudioSink {
+    /// Sample rate the sink plays at.
+    fn sample_rate(&self) -> u32;
+
+    /// Number of samples queued and not yet played.
+    fn queued(&self) -> usize;
+
+    fn queue(&mut self, samples: &[f32]);
+}
+
+/// Sink that throws everything away, for `--mute` and for running without
+/// an audio device. It always reports the target buffer level, so the APU
+/// keeps generating at the nominal rate.
+pub struct NullSink {
+    sample_rate: u32,
+}
+
+impl NullSink {
+    pub fn new(sample_rate: u32) -> Self {
+        NullSink { sample_rate }
+    }
+}
+
+impl AudioSink for NullSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn queued(&self) -> usize {
+        (self.sample_rate as f64 * TARGET_LATENCY) as usize
+    }
+
+    fn queue(&mut self, _samples: &[f32]) {}
+}
+
+/// Moves samples from the APU to a sink, applying the volume.
+///
+/// Emulation is paced by video vsync, which never runs at exactly the rate
+/// the console would, so a fixed sample rate slowly underruns or overflows
+/// the audio buffer. Instead the APU's output rate is nudged by up to
+/// `MAX_RATE_DELTA` depending on how full the buffer is, which keeps it
+/// near `TARGET_LATENCY` without audible pitch changes.
+pub struct AudioOutput {
+    sink: Box<dyn AudioSink>,
+    volume: f32,
+    target: usize,
+}
+
+impl AudioOutput {
+    pub fn new(sink: Box<dyn AudioSink>) -> Self {
+        let target = (sink.sample_rate() as f64 * TARGET_LATENCY) as usize;
+        AudioOutput {
+            sink,
+            volume: 1.0,
+            target,
+        }
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// Sets the volume, clamped to 0.0..=1.0.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+    }
+
+    /// Queues the samples the APU generated since the last call and sets
+    /// the rate of the next ones. Meant to be called once per frame.
+    pub fn update(&mut self, apu: &mut NesAPU) {
+        let mut samples = apu.take_samples();
+        let queued = self.sink.queued();
+
+        // after a stall the buffer holds more than anyone wants to hear;
+        // drop the new samples rather than growing the latency further
+        if queued < self.target * 4 {
+            for sample in samples.iter_mut() {
+                *sample *= self.volume;
+            }
+            self.sink.queue(&samples);
+        }
+
+        apu.set_sample_rate(self.next_rate(self.sink.queued()));
+    }
+
+    /// Rate for the next samples: above the device rate while the buffer is
+    /// below target, below it while above.
+    fn next_rate(&self, queued: usize) -> f64 {
+        let fill = (queued as f64 / (2 * self.target) as f64).min(1.0);
+        self.sink.sample_rate() as f64 * (1.0 + MAX_RATE_DELTA * (1.0 - 2.0 * fill))
+    }
+}
@@ -0,0 +1,113 @@
+use super::{AudioOutput, AudioSink, NullSink, MAX_RATE_DELTA};
+use crate::apu::NesAPU;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Default)]
+struct Recorded {
+    queued: usize,
+    samples: Vec<f32>,
+}
+
+/// Sink that keeps what it is given and reports a buffer level set by the test.
+struct TestSink(Rc<RefCell<Recorded>>);
+
+impl AudioSink for TestSink {
+    fn sample_rate(&self) -> u32 {
+        48_000
+    }
+
+    fn queued(&self) -> usize {
+        self.0.borrow().queued
+    }
+
+    fn queue(&mut self, samples: &[f32]) {
+        self.0.borrow_mut().samples.extend_from_slice(samples);
+    }
+}
+
+fn output() -> (AudioOutput, Rc<RefCell<Recorded>>) {
+    let recorded = Rc::new(RefCell::new(Recorded::default()));
+    (
+        AudioOutput::new(Box::new(TestSink(recorded.clone()))),
+        recorded,
+    )
+}
+
+/// An APU playing pulse 1 at full volume.
+fn playing_apu() -> NesAPU {
+    let mut apu = NesAPU::new(48_000);
+    apu.write_register(0x4015, 0b0000_0001);
+    apu.write_register(0x4000, 0b1011_1111);
+    apu.write_register(0x4002, 0xFD);
+    apu.write_register(0x4003, 0x00);
+    for _ in 0..29830 {
+        apu.tick();
+    }
+    apu
+}
+
+#[test]
+fn rate_follows_buffer_level() {
+    let (output, _) = output();
+    let target = output.target;
+
+    assert_eq!(output.next_rate(target), 48_000.0);
+    assert_eq!(output.next_rate(0), 48_000.0 * (1.0 + MAX_RATE_DELTA));
+    assert_eq!(
+        output.next_rate(target * 2),
+        48_000.0 * (1.0 - MAX_RATE_DELTA)
+    );
+    assert_eq!(
+        output.next_rate(target * 10),
+        48_000.0 * (1.0 - MAX_RATE_DELTA)
+    );
+    assert!(output.next_rate(target / 2) > output.next_rate(target));
+}
+
+#[test]
+fn update_moves_samples_and_sets_rate() {
+    let (mut output, recorded) = output();
+    let mut apu = playing_apu();
+
+    output.update(&mut apu);
+    assert_eq!(recorded.borrow().samples.len(), 800);
+    assert!(apu.take_samples().is_empty());
+    // empty buffer: generate faster
+    assert_eq!(apu.sample_rate(), 48_240);
+}
+
+#[test]
+fn volume_scales_samples() {
+    let (mut output, recorded) = output();
+    let mut apu = playing_apu();
+    let mut reference = playing_apu();
+    output.set_volume(0.5);
+
+    output.update(&mut apu);
+    let expected: Vec<f32> = reference.take_samples().iter().map(|s| s * 0.5).collect();
+    assert_eq!(recorded.borrow().samples, expected);
+
+    output.set_volume(3.0);
+    assert_eq!(output.volume(), 1.0);
+}
+
+#[test]
+fn drops_samples_when_far_behind() {
+    let (mut output, recorded) = output();
+    recorded.borrow_mut().queued = output.target * 4;
+    let mut apu = playing_apu();
+
+    output.update(&mut apu);
+    assert!(recorded.borrow().samples.is_empty());
+    assert!(apu.take_samples().is_empty());
+}
+
+#[test]
+fn null_sink_keeps_nominal_rate() {
+    let mut output = AudioOutput::new(Box::new(NullSink::new(44_100)));
+    let mut apu = playing_apu();
+
+    output.update(&mut apu);
+    assert_eq!(apu.sample_rate(), 44_100);
+}
@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod listing_tests;
+
+use nes_core::cartridge::Rom;
+use nes_core::disasm;
+use std::collections::BTreeMap;
+
+pub const BANK_SIZE: usize = 0x4000;
+const VECTORS: u16 = 0xFFFA;
+
+/// Where `bank` is seen by the CPU when nothing else is known: the last
+/// bank is fixed at $C000 by NROM, UxROM, MMC1 and MMC3 alike, the others
+/// are switched in at $8000.
+pub fn default_origin(rom: &Rom, bank: usize) -> u16 {
+    if bank + 1 == rom.prg_rom.len() / BANK_SIZE {
+        0xC000
+    } else {
+        0x8000
+    }
+}
+
+/// Disassembly of one 16 KiB PRG bank loaded at `origin`. When the bank
+/// ends at $FFFF its last six bytes are shown as the NMI, reset and IRQ
+/// vectors, and the handlers they point to are labelled; operands naming
+/// PPU, APU or controller registers are written as the register names.
+pub fn listing(rom: &Rom, bank: usize, origin: u16) -> Result<Vec<String>, String> {
+    let banks = rom.prg_rom.len() / BANK_SIZE;
+    if bank >= banks {
+        return Err(format!("There is no bank {}, the ROM has {}", bank, banks));
+    }
+    let bytes = &rom.prg_rom[bank * BANK_SIZE..(bank + 1) * BANK_SIZE];
+    let end = origin as usize + BANK_SIZE - 1;
+    if end > 0xFFFF {
+        return Err(format!("Bank at ${:04X} would run past $FFFF", origin));
+    }
+
+    let mut labels = BTreeMap::new();
+    let mut vectors = Vec::new();
+    let mut code = bytes;
+    if end == 0xFFFF {
+        code = &bytes[..BANK_SIZE - 6];
+        for index in 0..3 {
+            let offset = BANK_SIZE - 6 + index * 2;
+            let target = u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+            vectors.push((VECTORS + index as u16 * 2, target));
+        }
+        // a handler shared by several vectors is named after the first of
+        // reset, nmi and irq
+        for &(index, name) in &[(1, "reset"), (0, "nmi"), (2, "irq")] {
+            let target = vectors[index].1;
+            labels.entry(target).or_insert_with(|| name.to_string());
+        }
+    }
+
+    let mut lines = vec![format!(
+        "; PRG bank {} of {} at ${:04X}-${:04X}",
+        bank, banks, origin, end
+    )];
+    for instruction in disasm::disassemble(code, origin) {
+        if let Some(label) = labels.get(&instruction.address) {
+            lines.push(format!("{}:", label));
+        }
+        lines.push(instruction.format(|address| {
+            labels
+                .get(&address)
+                .cloned()
+                .or_else(|| disasm::hardware_register(address).map(str::to_string))
+        }));
+    }
+    for (address, target) in vectors {
+        let [low, high] = target.to_le_bytes();
+        let hex = format!("{:02X} {:02X}", low, high);
+        lines.push(format!(
+            "{:04X}  {:8}  .dw {}",
+            address, hex, labels[&target]
+        ));
+    }
+    Ok(lines)
+}
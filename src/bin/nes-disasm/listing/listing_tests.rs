@@ -0,0 +1,64 @@
+use super::{default_origin, listing, BANK_SIZE};
+use nes_core::cartridge::{Rom, RomBuilder};
+
+/// Image with `banks` PRG banks; the last one starts with `code` and has
+/// its vectors pointing into it.
+fn rom(banks: u8, code: &[u8]) -> Rom {
+    // NMI $C005, reset $C000, IRQ $C005
+    RomBuilder::new()
+        .prg_rom(vec![0xEA; banks as usize * BANK_SIZE])
+        .program(0xC000, code)
+        .vectors(0xC005, 0xC000, 0xC005)
+        .rom()
+        .unwrap()
+}
+
+#[test]
+fn labels_vectors_and_registers() {
+    let code = [
+        0x8D, 0x00, 0x20, // STA PPUCTRL
+        0xD0, 0xFB, // BNE reset
+        0x40, // nmi: RTI
+    ];
+    let rom = rom(1, &code);
+    let lines = listing(&rom, 0, default_origin(&rom, 0)).unwrap();
+    assert_eq!(
+        lines[..6].to_vec(),
+        vec![
+            "; PRG bank 0 of 1 at $C000-$FFFF",
+            "reset:",
+            "C000  8D 00 20  STA PPUCTRL",
+            "C003  D0 FB     BNE reset",
+            "nmi:",
+            "C005  40        RTI",
+        ]
+    );
+    assert_eq!(
+        lines[lines.len() - 3..].to_vec(),
+        vec![
+            "FFFA  05 C0     .dw nmi",
+            "FFFC  00 C0     .dw reset",
+            "FFFE  05 C0     .dw nmi",
+        ]
+    );
+    assert_eq!(lines[lines.len() - 4], "FFF9  EA        NOP");
+}
+
+#[test]
+fn switchable_banks_have_no_vectors() {
+    let rom = rom(2, &[]);
+    assert_eq!(0x8000, default_origin(&rom, 0));
+    assert_eq!(0xC000, default_origin(&rom, 1));
+
+    let lines = listing(&rom, 0, 0x8000).unwrap();
+    assert_eq!(lines[0], "; PRG bank 0 of 2 at $8000-$BFFF");
+    assert_eq!(lines.last().unwrap(), "BFFF  EA        NOP");
+    assert_eq!(lines.len(), 1 + BANK_SIZE);
+}
+
+#[test]
+fn refuses_missing_banks_and_bad_origins() {
+    let rom = rom(2, &[]);
+    assert!(listing(&rom, 2, 0x8000).is_err());
+    assert!(listing(&rom, 0, 0xD000).is_err());
+}
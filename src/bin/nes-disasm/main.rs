@@ -0,0 +1,80 @@
+//! Disassembles a PRG bank of an iNES file to stdout.
+
+mod listing;
+
+use nes_core::cartridge::Rom;
+
+struct Args {
+    rom_path: String,
+    bank: Option<usize>,
+    origin: Option<u16>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut rom_path = None;
+    let mut bank = None;
+    let mut origin = None;
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--bank" => {
+                let value = iter.next().ok_or("--bank needs a number")?;
+                let number = value
+                    .parse()
+                    .map_err(|_| format!("Invalid bank {}", value))?;
+                bank = Some(number);
+            }
+            "--origin" => {
+                let value = iter.next().ok_or("--origin needs an address")?;
+                let digits = value.trim_start_matches('$').trim_start_matches("0x");
+                let address = u16::from_str_radix(digits, 16)
+                    .map_err(|_| format!("Invalid origin {}, expected hex", value))?;
+                origin = Some(address);
+            }
+            flag if flag.starts_with("--") => return Err(format!("Unknown option {}", flag)),
+            _ if rom_path.is_none() => rom_path = Some(arg),
+            _ => return Err(format!("Unexpected argument {}", arg)),
+        }
+    }
+    Ok(Args {
+        rom_path: rom_path.ok_or("Missing ROM path")?,
+        bank,
+        origin,
+    })
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        eprintln!("Usage: nes-disasm [--bank <n>] [--origin <hex address>] rom.nes");
+        std::process::exit(2);
+    });
+    let path = &args.rom_path;
+    let rom = std::fs::read(path)
+        .map_err(|err| err.to_string())
+        .and_then(|raw| Rom::new(&raw).map_err(|err| err.to_string()))
+        .unwrap_or_else(|err| {
+            eprintln!("Cannot load {}: {}", path, err);
+            std::process::exit(1);
+        });
+
+    // the last bank holds the vectors
+    let bank = args
+        .bank
+        .unwrap_or_else(|| (rom.prg_rom.len() / listing::BANK_SIZE).saturating_sub(1));
+    let origin = args
+        .origin
+        .unwrap_or_else(|| listing::default_origin(&rom, bank));
+    match listing::listing(&rom, bank, origin) {
+        Ok(lines) => {
+            println!("; {}", path);
+            for line in lines {
+                println!("{}", line);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
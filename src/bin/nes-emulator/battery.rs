@@ -0,0 +1,50 @@
+#[cfg(test)]
+mod battery_tests;
+
+use nes_core::bus::Bus;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Keeps the battery-backed RAM of the cartridge in a `.sav` file next to
+/// the ROM, the way most emulators do, so save files can be shared.
+pub struct BatteryFile {
+    path: PathBuf,
+}
+
+impl BatteryFile {
+    /// Loads `<rom name>.sav` into the cartridge, if there is one. Returns
+    /// `None` for cartridges without a battery.
+    pub fn open(rom_path: &Path, bus: &mut Bus) -> Result<Option<BatteryFile>, String> {
+        if bus.battery_ram().is_none() {
+            return Ok(None);
+        }
+        let path = rom_path.with_extension("sav");
+        match std::fs::read(&path) {
+            Ok(data) => bus
+                .load_battery_ram(&data)
+                .map_err(|err| format!("Cannot load {}: {}", path.display(), err))?,
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(format!("Cannot read {}: {}", path.display(), err)),
+        }
+        Ok(Some(BatteryFile { path }))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the RAM out if the game changed it since the last flush.
+    /// Returns whether it did. The file is replaced in one step, so a crash
+    /// while writing leaves the previous save intact.
+    pub fn flush(&self, bus: &mut Bus) -> Result<bool, String> {
+        if !bus.take_battery_ram_changed() {
+            return Ok(false);
+        }
+        let data = bus.battery_ram().unwrap_or_default();
+        let temporary = self.path.with_extension("sav.tmp");
+        std::fs::write(&temporary, data)
+            .and_then(|_| std::fs::rename(&temporary, &self.path))
+            .map_err(|err| format!("Cannot write {}: {}", self.path.display(), err))?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,65 @@
+use super::BatteryFile;
+use nes_core::bus::{Bus, Mem};
+use nes_core::cartridge::RomBuilder;
+use std::path::PathBuf;
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nes-battery-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn cartridge(battery: bool) -> Bus {
+    let mut bus = Bus::new();
+    bus.insert_cartridge(RomBuilder::new().battery(battery).rom().unwrap())
+        .unwrap();
+    bus
+}
+
+#[test]
+fn cartridges_without_battery_have_no_file() {
+    let dir = scratch_dir("no-battery");
+    let mut bus = cartridge(false);
+    assert!(BatteryFile::open(&dir.join("game.nes"), &mut bus)
+        .unwrap()
+        .is_none());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn saves_only_after_changes_and_loads_back() {
+    let dir = scratch_dir("round-trip");
+    let rom_path = dir.join("game.nes");
+
+    let mut bus = cartridge(true);
+    let file = BatteryFile::open(&rom_path, &mut bus).unwrap().unwrap();
+    assert_eq!(file.path(), dir.join("game.sav"));
+    assert!(!file.flush(&mut bus).unwrap());
+    assert!(!file.path().exists());
+
+    bus.mem_write(0x6042, 0x99);
+    assert!(file.flush(&mut bus).unwrap());
+    assert!(!file.flush(&mut bus).unwrap());
+    assert_eq!(std::fs::read(file.path()).unwrap()[0x42], 0x99);
+
+    let mut bus = cartridge(true);
+    BatteryFile::open(&rom_path, &mut bus).unwrap().unwrap();
+    assert_eq!(bus.mem_read(0x6042), 0x99);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn refuses_save_files_of_the_wrong_size() {
+    let dir = scratch_dir("wrong-size");
+    std::fs::write(dir.join("game.sav"), [0; 16]).unwrap();
+
+    let mut bus = cartridge(true);
+    let err = BatteryFile::open(&dir.join("game.nes"), &mut bus)
+        .err()
+        .unwrap();
+    assert!(err.contains("16 bytes"), "{}", err);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
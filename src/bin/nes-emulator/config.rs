@@ -0,0 +1,361 @@
+#[cfg(test)]
+mod config_tests;
+
+use nes_core::joypad::JoypadButton;
+use sdl2::controller::Button;
+use sdl2::keyboard::Keycode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+const HEADER: &str = "\
+# Key bindings of the NES emulator.
+#
+# Keyboard keys use SDL key names, e.g. \"X\", \"Return\", \"Right Shift\", \"F5\".
+# Game controller buttons use SDL button names: a, b, x, y, back, guide,
+# start, leftstick, rightstick, leftshoulder, rightshoulder, dpup, dpdown,
+# dpleft, dpright. Leave a binding out to unbind it.
+#
+# [rewind] keeps `seconds` of history with a snapshot every `interval` frames,
+# in at most `memory_limit_mib` MiB; `seconds = 0` turns rewind off.
+
+";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    Io {
+        path: PathBuf,
+        message: String,
+    },
+    Parse {
+        path: PathBuf,
+        message: String,
+    },
+    UnknownKey {
+        setting: String,
+        name: String,
+    },
+    UnknownButton {
+        setting: String,
+        name: String,
+    },
+    DuplicateBinding {
+        name: String,
+        first: String,
+        second: String,
+    },
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io { path, message } => {
+                write!(f, "cannot access {}: {}", path.display(), message)
+            }
+            ConfigError::Parse { path, message } => {
+                write!(f, "invalid config {}: {}", path.display(), message)
+            }
+            ConfigError::UnknownKey { setting, name } => {
+                write!(f, "{}: unknown key name {:?}", setting, name)
+            }
+            ConfigError::UnknownButton { setting, name } => {
+                write!(f, "{}: unknown game controller button {:?}", setting, name)
+            }
+            ConfigError::DuplicateBinding {
+                name,
+                first,
+                second,
+            } => write!(f, "{:?} is bound to both {} and {}", name, first, second),
+        }
+    }
+}
+
+/// Contents of the config file. Sections left out of the file keep their
+/// defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub port1: PortBindings,
+    pub port2: PortBindings,
+    pub hotkeys: HotkeyBindings,
+    pub rewind: RewindSettings,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PortBindings {
+    pub keyboard: ButtonBindings,
+    pub controller: ButtonBindings,
+}
+
+/// Key or button name for each button of a controller port.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ButtonBindings {
+    pub a: Option<String>,
+    pub b: Option<String>,
+    pub select: Option<String>,
+    pub start: Option<String>,
+    pub up: Option<String>,
+    pub down: Option<String>,
+    pub left: Option<String>,
+    pub right: Option<String>,
+}
+
+/// Keyboard keys of the emulator's own functions.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HotkeyBindings {
+    pub pause: Option<String>,
+    pub reset: Option<String>,
+    pub save_state: Option<String>,
+    pub load_state: Option<String>,
+    pub next_slot: Option<String>,
+    pub previous_slot: Option<String>,
+    pub fast_forward: Option<String>,
+    pub rewind: Option<String>,
+    pub screenshot: Option<String>,
+    pub quit: Option<String>,
+}
+
+/// How much rewind history is kept; `seconds = 0` turns rewind off.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RewindSettings {
+    pub seconds: u32,
+    /// Frames between snapshots; each rewind step goes back this far.
+    pub interval: u32,
+    pub memory_limit_mib: u32,
+}
+
+impl Default for RewindSettings {
+    fn default() -> Self {
+        RewindSettings {
+            seconds: 30,
+            interval: 1,
+            memory_limit_mib: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hotkey {
+    Pause,
+    Reset,
+    /// Saves to the selected state slot.
+    SaveState,
+    LoadState,
+    NextSlot,
+    PreviousSlot,
+    /// Runs unthrottled while held.
+    FastForward,
+    /// Steps backwards through the rewind history while held.
+    Rewind,
+    Screenshot,
+    Quit,
+}
+
+fn bind(names: [&str; 8]) -> ButtonBindings {
+    let [a, b, select, start, up, down, left, right] = names.map(|name| Some(name.to_string()));
+    ButtonBindings {
+        a,
+        b,
+        select,
+        start,
+        up,
+        down,
+        left,
+        right,
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        // game controllers are bound by position: the right face button is A
+        let controller = bind([
+            "b", "a", "back", "start", "dpup", "dpdown", "dpleft", "dpright",
+        ]);
+        let hotkey = |name: &str| Some(name.to_string());
+        Config {
+            port1: PortBindings {
+                keyboard: bind([
+                    "X",
+                    "Z",
+                    "Right Shift",
+                    "Return",
+                    "Up",
+                    "Down",
+                    "Left",
+                    "Right",
+                ]),
+                controller: controller.clone(),
+            },
+            port2: PortBindings {
+                keyboard: bind(["H", "G", "T", "Y", "W", "S", "A", "D"]),
+                controller,
+            },
+            hotkeys: HotkeyBindings {
+                pause: hotkey("P"),
+                reset: hotkey("F1"),
+                save_state: hotkey("F5"),
+                load_state: hotkey("F7"),
+                next_slot: hotkey("F6"),
+                previous_slot: hotkey("F8"),
+                fast_forward: hotkey("Tab"),
+                rewind: hotkey("Backspace"),
+                screenshot: hotkey("F12"),
+                quit: hotkey("Escape"),
+            },
+            rewind: RewindSettings::default(),
+        }
+    }
+}
+
+impl Config {
+    /// `config.toml` in the user's config directory, e.g.
+    /// `~/.config/nes-emulator/config.toml` on Linux.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("nes-emulator").join("config.toml"))
+    }
+
+    /// Reads the config at `path`, writing the defaults there first when
+    /// the file does not exist yet.
+    pub fn load_or_create(path: &Path) -> Result<Config, ConfigError> {
+        if !path.exists() {
+            let config = Config::default();
+            config.save(path)?;
+            return Ok(config);
+        }
+        let text = std::fs::read_to_string(path).map_err(|err| ConfigError::Io {
+            path: path.to_path_buf(),
+            message: err.to_string(),
+        })?;
+        Config::parse(&text).map_err(|message| ConfigError::Parse {
+            path: path.to_path_buf(),
+            message,
+        })
+    }
+
+    pub fn parse(text: &str) -> Result<Config, String> {
+        toml::from_str(text).map_err(|err| err.to_string())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        let io_error = |err: std::io::Error| ConfigError::Io {
+            path: path.to_path_buf(),
+            message: err.to_string(),
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let text = toml::to_string_pretty(self).expect("config always serializes");
+        std::fs::write(path, HEADER.to_string() + &text).map_err(io_error)
+    }
+}
+
+/// Controller port a binding drives; 0 for port 1.
+pub type Port = usize;
+
+/// The config resolved to SDL keys and buttons.
+#[derive(Debug, Default)]
+pub struct Bindings {
+    pub keys: HashMap<Keycode, (Port, JoypadButton)>,
+    pub hotkeys: HashMap<Keycode, Hotkey>,
+    /// Game controller buttons per port.
+    pub controller: [HashMap<Button, JoypadButton>; 2],
+}
+
+impl Bindings {
+    pub fn new(config: &Config) -> Result<Bindings, ConfigError> {
+        let mut bindings = Bindings::default();
+        // setting each key is bound by, to report duplicates
+        let mut key_settings: HashMap<Keycode, String> = HashMap::new();
+
+        for (port, (name, port_bindings)) in [("port1", &config.port1), ("port2", &config.port2)]
+            .iter()
+            .enumerate()
+        {
+            for (button, setting, key_name) in buttons(&port_bindings.keyboard) {
+                let setting = format!("{}.keyboard.{}", name, setting);
+                let key = parse_key(&setting, key_name)?;
+                claim_key(&mut key_settings, key, key_name, setting)?;
+                bindings.keys.insert(key, (port, button));
+            }
+            for (button, setting, button_name) in buttons(&port_bindings.controller) {
+                let setting = format!("{}.controller.{}", name, setting);
+                let controller_button =
+                    Button::from_string(button_name).ok_or_else(|| ConfigError::UnknownButton {
+                        setting,
+                        name: button_name.to_string(),
+                    })?;
+                bindings.controller[port].insert(controller_button, button);
+            }
+        }
+
+        let hotkeys = &config.hotkeys;
+        for (hotkey, setting, key_name) in [
+            (Hotkey::Pause, "pause", &hotkeys.pause),
+            (Hotkey::Reset, "reset", &hotkeys.reset),
+            (Hotkey::SaveState, "save_state", &hotkeys.save_state),
+            (Hotkey::LoadState, "load_state", &hotkeys.load_state),
+            (Hotkey::NextSlot, "next_slot", &hotkeys.next_slot),
+            (
+                Hotkey::PreviousSlot,
+                "previous_slot",
+                &hotkeys.previous_slot,
+            ),
+            (Hotkey::FastForward, "fast_forward", &hotkeys.fast_forward),
+            (Hotkey::Rewind, "rewind", &hotkeys.rewind),
+            (Hotkey::Screenshot, "screenshot", &hotkeys.screenshot),
+            (Hotkey::Quit, "quit", &hotkeys.quit),
+        ] {
+            if let Some(key_name) = key_name {
+                let setting = format!("hotkeys.{}", setting);
+                let key = parse_key(&setting, key_name)?;
+                claim_key(&mut key_settings, key, key_name, setting)?;
+                bindings.hotkeys.insert(key, hotkey);
+            }
+        }
+        Ok(bindings)
+    }
+}
+
+/// The bound buttons of `bindings`, with their setting names.
+fn buttons(bindings: &ButtonBindings) -> impl Iterator<Item = (JoypadButton, &str, &str)> {
+    IntoIterator::into_iter([
+        (JoypadButton::A, "a", &bindings.a),
+        (JoypadButton::B, "b", &bindings.b),
+        (JoypadButton::Select, "select", &bindings.select),
+        (JoypadButton::Start, "start", &bindings.start),
+        (JoypadButton::Up, "up", &bindings.up),
+        (JoypadButton::Down, "down", &bindings.down),
+        (JoypadButton::Left, "left", &bindings.left),
+        (JoypadButton::Right, "right", &bindings.right),
+    ])
+    .filter_map(|(button, setting, name)| Some((button, setting, name.as_deref()?)))
+}
+
+fn parse_key(setting: &str, name: &str) -> Result<Keycode, ConfigError> {
+    Keycode::from_name(name).ok_or_else(|| ConfigError::UnknownKey {
+        setting: setting.to_string(),
+        name: name.to_string(),
+    })
+}
+
+fn claim_key(
+    key_settings: &mut HashMap<Keycode, String>,
+    key: Keycode,
+    name: &str,
+    setting: String,
+) -> Result<(), ConfigError> {
+    if let Some(first) = key_settings.get(&key) {
+        return Err(ConfigError::DuplicateBinding {
+            name: name.to_string(),
+            first: first.clone(),
+            second: setting,
+        });
+    }
+    key_settings.insert(key, setting);
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use super::{Bindings, Config, ConfigError, Hotkey};
+use nes_core::joypad::JoypadButton;
+use sdl2::controller::Button;
+use sdl2::keyboard::Keycode;
+
+fn scratch_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("nes-config-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn defaults_resolve() {
+    let bindings = Bindings::new(&Config::default()).unwrap();
+    assert_eq!(bindings.keys[&Keycode::X], (0, JoypadButton::A));
+    assert_eq!(bindings.keys[&Keycode::Right], (0, JoypadButton::Right));
+    assert_eq!(bindings.keys[&Keycode::W], (1, JoypadButton::Up));
+    assert_eq!(bindings.hotkeys[&Keycode::F5], Hotkey::SaveState);
+    assert_eq!(bindings.hotkeys[&Keycode::F6], Hotkey::NextSlot);
+    assert_eq!(bindings.controller[1][&Button::B], JoypadButton::A);
+    assert_eq!(bindings.keys.len(), 16);
+    assert_eq!(bindings.hotkeys.len(), 10);
+}
+
+#[test]
+fn first_run_writes_defaults() {
+    let dir = scratch_dir("first-run");
+    let path = dir.join("nested").join("config.toml");
+
+    assert_eq!(Config::load_or_create(&path).unwrap(), Config::default());
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert!(text.starts_with("# Key bindings"));
+    assert!(text.contains("[port1.keyboard]"));
+    assert_eq!(Config::parse(&text).unwrap(), Config::default());
+    assert_eq!(Config::load_or_create(&path).unwrap(), Config::default());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn missing_sections_keep_defaults() {
+    let config = Config::parse("[hotkeys]\npause = \"Space\"\n").unwrap();
+    assert_eq!(config.port1, Config::default().port1);
+    assert_eq!(config.hotkeys.pause.as_deref(), Some("Space"));
+    // a section that is given replaces the defaults, leaving keys out unbinds them
+    assert_eq!(config.hotkeys.reset, None);
+}
+
+#[test]
+fn rewind_settings_default_per_field() {
+    let config = Config::parse("[rewind]\nseconds = 10\n").unwrap();
+    assert_eq!(config.rewind.seconds, 10);
+    assert_eq!(config.rewind.interval, 1);
+    assert_eq!(config.rewind.memory_limit_mib, 64);
+}
+
+#[test]
+fn reports_syntax_errors_with_location() {
+    let dir = scratch_dir("syntax");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.toml");
+    std::fs::write(&path, "[port1.keyboard]\na = X\n").unwrap();
+
+    let err = Config::load_or_create(&path).unwrap_err();
+    assert!(matches!(err, ConfigError::Parse { .. }));
+    let message = err.to_string();
+    assert!(message.contains("config.toml"), "{}", message);
+    assert!(message.contains("line 2"), "{}", message);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn rejects_unknown_settings() {
+    let err = Config::parse("[port1.keyboard]\nturbo = \"Q\"\n").unwrap_err();
+    assert!(err.contains("turbo"), "{}", err);
+}
+
+#[test]
+fn rejects_unknown_key_names() {
+    let mut config = Config::default();
+    config.port2.keyboard.start = Some("Enterr".to_string());
+    assert_eq!(
+        Bindings::new(&config).unwrap_err(),
+        ConfigError::UnknownKey {
+            setting: "port2.keyboard.start".to_string(),
+            name: "Enterr".to_string(),
+        }
+    );
+}
+
+#[test]
+fn rejects_unknown_controller_buttons() {
+    let mut config = Config::default();
+    config.port1.controller.a = Some("triangle".to_string());
+    let err = Bindings::new(&config).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "port1.controller.a: unknown game controller button \"triangle\""
+    );
+}
+
+#[test]
+fn rejects_keys_bound_twice() {
+    let mut config = Config::default();
+    config.hotkeys.pause = Some("Z".to_string());
+    assert_eq!(
+        Bindings::new(&config).unwrap_err().to_string(),
+        "\"Z\" is bound to both port1.keyboard.b and hotkeys.pause"
+    );
+}
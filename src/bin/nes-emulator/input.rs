@@ -0,0 +1,123 @@
+use crate::config::{Bindings, Hotkey};
+use nes_core::bus::Bus;
+use nes_core::joypad::Joypad;
+use sdl2::controller::{Button, GameController};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::{EventPump, GameControllerSubsystem, Sdl};
+use std::collections::HashSet;
+
+/// Maps the keyboard and SDL game controllers onto the two controller ports
+/// and the emulator's hotkeys, as configured in `Bindings`. Game controllers
+/// take ports 1 and 2 in the order they are connected.
+pub struct Input {
+    bindings: Bindings,
+    subsystem: Option<GameControllerSubsystem>,
+    controllers: Vec<GameController>,
+    held_hotkeys: HashSet<Hotkey>,
+}
+
+impl Input {
+    pub fn new(sdl_context: &Sdl, bindings: Bindings) -> Self {
+        let subsystem = sdl_context
+            .game_controller()
+            .map_err(|err| eprintln!("No game controller support: {}", err))
+            .ok();
+        Input {
+            bindings,
+            subsystem,
+            controllers: Vec::new(),
+            held_hotkeys: HashSet::new(),
+        }
+    }
+
+    /// Applies pending events to the joypads on `bus` and returns the
+    /// hotkeys pressed since the last call. Closing the window counts as
+    /// `Hotkey::Quit`.
+    pub fn handle_events(&mut self, event_pump: &mut EventPump, bus: &mut Bus) -> Vec<Hotkey> {
+        let mut pressed = Vec::new();
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. } => pressed.push(Hotkey::Quit),
+                Event::KeyDown {
+                    keycode: Some(key),
+                    repeat,
+                    ..
+                } => {
+                    if let Some(&hotkey) = self.bindings.hotkeys.get(&key) {
+                        if !repeat {
+                            self.held_hotkeys.insert(hotkey);
+                            pressed.push(hotkey);
+                        }
+                    }
+                    self.set_key(bus, key, true);
+                }
+                Event::KeyUp {
+                    keycode: Some(key), ..
+                } => {
+                    if let Some(hotkey) = self.bindings.hotkeys.get(&key) {
+                        self.held_hotkeys.remove(hotkey);
+                    }
+                    self.set_key(bus, key, false);
+                }
+                Event::ControllerDeviceAdded { which, .. } => self.add_controller(which),
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    self.controllers
+                        .retain(|controller| controller.instance_id() != which);
+                }
+                Event::ControllerButtonDown { which, button, .. } => {
+                    self.set_controller_button(bus, which, button, true)
+                }
+                Event::ControllerButtonUp { which, button, .. } => {
+                    self.set_controller_button(bus, which, button, false)
+                }
+                _ => { /* do nothing */ }
+            }
+        }
+        pressed
+    }
+
+    pub fn is_held(&self, hotkey: Hotkey) -> bool {
+        self.held_hotkeys.contains(&hotkey)
+    }
+
+    fn add_controller(&mut self, joystick_index: u32) {
+        if let Some(subsystem) = &self.subsystem {
+            match subsystem.open(joystick_index) {
+                Ok(controller) => self.controllers.push(controller),
+                Err(err) => eprintln!("Cannot open game controller: {}", err),
+            }
+        }
+    }
+
+    fn set_key(&self, bus: &mut Bus, key: Keycode, pressed: bool) {
+        if let Some(&(port, button)) = self.bindings.keys.get(&key) {
+            joypad(bus, port).set_button_pressed(button, pressed);
+        }
+    }
+
+    fn set_controller_button(
+        &self,
+        bus: &mut Bus,
+        instance_id: u32,
+        button: Button,
+        pressed: bool,
+    ) {
+        let port = self
+            .controllers
+            .iter()
+            .position(|controller| controller.instance_id() == instance_id);
+        if let Some(port) = port.filter(|&port| port < 2) {
+            if let Some(&button) = self.bindings.controller[port].get(&button) {
+                joypad(bus, port).set_button_pressed(button, pressed);
+            }
+        }
+    }
+}
+
+fn joypad(bus: &mut Bus, port: usize) -> &mut Joypad {
+    match port {
+        0 => &mut bus.joypad1,
+        _ => &mut bus.joypad2,
+    }
+}
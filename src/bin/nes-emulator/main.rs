@@ -0,0 +1,548 @@
+mod battery;
+mod config;
+mod input;
+mod sdl_audio;
+mod slots;
+use battery::BatteryFile;
+use config::{Bindings, Config, Hotkey, RewindSettings};
+use input::Input;
+use nes_core::apu;
+use nes_core::audio::{AudioOutput, AudioSink, NullSink};
+use nes_core::bus::{Bus, Mem};
+use nes_core::cartridge::Rom;
+use nes_core::cpu;
+use nes_core::debugger::Debugger;
+use nes_core::ppu::Frame;
+use nes_core::rewind::Rewind;
+use nes_core::test_rom;
+use rand::Rng;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::EventPump;
+use sdl_audio::SdlAudioSink;
+use slots::SaveSlots;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Battery RAM is written out at most this often while it keeps changing.
+const BATTERY_FLUSH_FRAMES: u64 = 5 * 60;
+
+struct Args {
+    rom_path: Option<String>,
+    trace_path: Option<String>,
+    test_rom: bool,
+    debug: bool,
+    core: cpu::CpuCore,
+    mute: bool,
+    volume: f32,
+    config_path: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        rom_path: None,
+        trace_path: None,
+        test_rom: false,
+        debug: false,
+        core: cpu::CpuCore::Instruction,
+        mute: false,
+        volume: 1.0,
+        config_path: None,
+    };
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--trace" => {
+                let path = iter.next().ok_or("--trace needs a file name")?;
+                args.trace_path = Some(path);
+            }
+            "--config" => {
+                let path = iter.next().ok_or("--config needs a file name")?;
+                args.config_path = Some(PathBuf::from(path));
+            }
+            "--test-rom" => args.test_rom = true,
+            "--debug" => args.debug = true,
+            "--cycle-accurate" => args.core = cpu::CpuCore::CycleAccurate,
+            "--mute" => args.mute = true,
+            "--volume" => {
+                let value = iter.next().ok_or("--volume needs a value")?;
+                let percent: u8 = value
+                    .parse()
+                    .ok()
+                    .filter(|percent| *percent <= 100)
+                    .ok_or(format!("Invalid volume {}, expected 0-100", value))?;
+                args.volume = percent as f32 / 100.0;
+            }
+            flag if flag.starts_with("--") => return Err(format!("Unknown option {}", flag)),
+            _ if args.rom_path.is_none() => args.rom_path = Some(arg),
+            _ => return Err(format!("Unexpected argument {}", arg)),
+        }
+    }
+    Ok(args)
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        eprintln!("Usage: nes-emulator [--config <file>] [--trace <file>] [--test-rom] [--debug] [--cycle-accurate] [--mute] [--volume <0-100>] [rom.nes]");
+        std::process::exit(2);
+    });
+    let rom_path = args.rom_path;
+    if args.test_rom {
+        let path = rom_path.unwrap_or_else(|| {
+            eprintln!("--test-rom needs a ROM path");
+            std::process::exit(2);
+        });
+        run_test_rom(&path, args.core);
+    }
+    if args.debug {
+        let path = rom_path.unwrap_or_else(|| {
+            eprintln!("--debug needs a ROM path");
+            std::process::exit(2);
+        });
+        run_debugger(&path, args.core);
+    }
+    let mut trace_file = args.trace_path.map(|path| {
+        let file = File::create(&path).unwrap_or_else(|err| {
+            eprintln!("Cannot create {}: {}", path, err);
+            std::process::exit(1);
+        });
+        BufWriter::new(file)
+    });
+
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+    // The demo draws a 32x32 screen from RAM, cartridges get the PPU picture
+    let (width, height, scale) = match rom_path {
+        Some(_) => (Frame::WIDTH as u32, Frame::HEIGHT as u32, 3.0),
+        None => (32, 32, 10.0),
+    };
+    let window = video_subsystem
+        .window(
+            rom_path.as_deref().unwrap_or("Snake game"),
+            (width as f32 * scale) as u32,
+            (height as f32 * scale) as u32,
+        )
+        .position_centered()
+        .build()
+        .unwrap();
+
+    let mut audio = AudioOutput::new(open_audio_sink(&sdl_context, args.mute));
+    audio.set_volume(args.volume);
+
+    let mut canvas = window.into_canvas().present_vsync().build().unwrap();
+    let mut event_pump = sdl_context.event_pump().unwrap();
+    let (config, bindings) = load_config(args.config_path.as_deref());
+    let mut input = Input::new(&sdl_context, bindings);
+    let mut rewind = create_rewind(&config.rewind);
+    canvas.set_scale(scale, scale).unwrap();
+
+    let creator = canvas.texture_creator();
+    let mut texture = creator
+        .create_texture_target(PixelFormatEnum::RGB24, width, height)
+        .unwrap();
+
+    let game_code = vec![
+        0x20, 0x06, 0x06, 0x20, 0x38, 0x06, 0x20, 0x0d, 0x06, 0x20, 0x2a, 0x06, 0x60, 0xa9, 0x02,
+        0x85, 0x02, 0xa9, 0x04, 0x85, 0x03, 0xa9, 0x11, 0x85, 0x10, 0xa9, 0x10, 0x85, 0x12, 0xa9,
+        0x0f, 0x85, 0x14, 0xa9, 0x04, 0x85, 0x11, 0x85, 0x13, 0x85, 0x15, 0x60, 0xa5, 0xfe, 0x85,
+        0x00, 0xa5, 0xfe, 0x29, 0x03, 0x18, 0x69, 0x02, 0x85, 0x01, 0x60, 0x20, 0x4d, 0x06, 0x20,
+        0x8d, 0x06, 0x20, 0xc3, 0x06, 0x20, 0x19, 0x07, 0x20, 0x20, 0x07, 0x20, 0x2d, 0x07, 0x4c,
+        0x38, 0x06, 0xa5, 0xff, 0xc9, 0x77, 0xf0, 0x0d, 0xc9, 0x64, 0xf0, 0x14, 0xc9, 0x73, 0xf0,
+        0x1b, 0xc9, 0x61, 0xf0, 0x22, 0x60, 0xa9, 0x04, 0x24, 0x02, 0xd0, 0x26, 0xa9, 0x01, 0x85,
+        0x02, 0x60, 0xa9, 0x08, 0x24, 0x02, 0xd0, 0x1b, 0xa9, 0x02, 0x85, 0x02, 0x60, 0xa9, 0x01,
+        0x24, 0x02, 0xd0, 0x10, 0xa9, 0x04, 0x85, 0x02, 0x60, 0xa9, 0x02, 0x24, 0x02, 0xd0, 0x05,
+        0xa9, 0x08, 0x85, 0x02, 0x60, 0x60, 0x20, 0x94, 0x06, 0x20, 0xa8, 0x06, 0x60, 0xa5, 0x00,
+        0xc5, 0x10, 0xd0, 0x0d, 0xa5, 0x01, 0xc5, 0x11, 0xd0, 0x07, 0xe6, 0x03, 0xe6, 0x03, 0x20,
+        0x2a, 0x06, 0x60, 0xa2, 0x02, 0xb5, 0x10, 0xc5, 0x10, 0xd0, 0x06, 0xb5, 0x11, 0xc5, 0x11,
+        0xf0, 0x09, 0xe8, 0xe8, 0xe4, 0x03, 0xf0, 0x06, 0x4c, 0xaa, 0x06, 0x4c, 0x35, 0x07, 0x60,
+        0xa6, 0x03, 0xca, 0x8a, 0xb5, 0x10, 0x95, 0x12, 0xca, 0x10, 0xf9, 0xa5, 0x02, 0x4a, 0xb0,
+        0x09, 0x4a, 0xb0, 0x19, 0x4a, 0xb0, 0x1f, 0x4a, 0xb0, 0x2f, 0xa5, 0x10, 0x38, 0xe9, 0x20,
+        0x85, 0x10, 0x90, 0x01, 0x60, 0xc6, 0x11, 0xa9, 0x01, 0xc5, 0x11, 0xf0, 0x28, 0x60, 0xe6,
+        0x10, 0xa9, 0x1f, 0x24, 0x10, 0xf0, 0x1f, 0x60, 0xa5, 0x10, 0x18, 0x69, 0x20, 0x85, 0x10,
+        0xb0, 0x01, 0x60, 0xe6, 0x11, 0xa9, 0x06, 0xc5, 0x11, 0xf0, 0x0c, 0x60, 0xc6, 0x10, 0xa5,
+        0x10, 0x29, 0x1f, 0xc9, 0x1f, 0xf0, 0x01, 0x60, 0x4c, 0x35, 0x07, 0xa0, 0x00, 0xa5, 0xfe,
+        0x91, 0x00, 0x60, 0xa6, 0x03, 0xa9, 0x00, 0x81, 0x10, 0xa2, 0x00, 0xa9, 0x01, 0x81, 0x10,
+        0x60, 0xa2, 0x00, 0xea, 0xea, 0xca, 0xd0, 0xfb, 0x60,
+    ];
+
+    let mut cpu = cpu::CPU::new();
+    cpu.set_core(args.core);
+    cpu.set_unstable_opcode_policy(cpu::UnstableOpcodePolicy::Log);
+    let demo = match &rom_path {
+        Some(path) => {
+            cpu.bus
+                .insert_cartridge(load_rom(path))
+                .unwrap_or_else(|err| {
+                    eprintln!("Cannot load {}: {}", path, err);
+                    std::process::exit(1);
+                });
+            false
+        }
+        None => {
+            // the demo runs with interrupts enabled and has no IRQ handler
+            cpu.mem_write(0x4017, 0x40);
+            cpu.load(game_code);
+            true
+        }
+    };
+    let battery = rom_path.as_deref().and_then(|path| {
+        BatteryFile::open(Path::new(path), &mut cpu.bus).unwrap_or_else(|err| {
+            eprintln!("{}; the game's saves will not be kept", err);
+            None
+        })
+    });
+    if let Some(battery) = &battery {
+        println!("Battery-backed RAM is kept in {}", battery.path().display());
+    }
+    cpu.reset();
+
+    let mut screen_state = [0_u8; 32 * 3 * 32];
+    let mut rng = rand::thread_rng();
+    let mut paused = false;
+    let mut frames: u64 = 0;
+    let mut next_battery_flush = BATTERY_FLUSH_FRAMES;
+    // only cartridges get hotkeys, the demo has no use for the slots
+    let slot_rom = PathBuf::from(rom_path.unwrap_or_default());
+    let mut slots = SaveSlots::new(SaveSlots::default_directory(&slot_rom), &slot_rom);
+    let mut rewinding = false;
+    let battery = battery.as_ref();
+
+    let mut frame = move |cpu: &mut cpu::CPU| {
+        if let Some(trace_file) = trace_file.as_mut() {
+            writeln!(trace_file, "{}", cpu::trace(cpu)).unwrap_or_else(|err| {
+                eprintln!("Cannot write trace: {}", err);
+                std::process::exit(1);
+            });
+        }
+
+        let mut quit = false;
+        if !demo {
+            if cpu.bus.poll_frame_complete() {
+                frames += 1;
+                print_opcode_reports(cpu);
+                // while fast forwarding only every 8th frame waits for vsync
+                if !input.is_held(Hotkey::FastForward) || frames & 0b111 == 0 {
+                    texture
+                        .update(None, &cpu.bus.ppu.frame.data, Frame::WIDTH * 3)
+                        .unwrap();
+                    canvas.copy(&texture, None, None).unwrap();
+                    canvas.present();
+                }
+                if rewinding {
+                    // the frames replayed while rewinding would sound choppy
+                    cpu.bus.apu.take_samples();
+                } else {
+                    audio.update(&mut cpu.bus.apu);
+                }
+
+                loop {
+                    for hotkey in input.handle_events(&mut event_pump, &mut cpu.bus) {
+                        match hotkey {
+                            Hotkey::Quit => quit = true,
+                            Hotkey::Pause => paused = !paused,
+                            Hotkey::Reset => cpu.reset(),
+                            Hotkey::SaveState => match slots.save(cpu) {
+                                Ok(path) => println!("Saved state to {}", path.display()),
+                                Err(err) => eprintln!("{}", err),
+                            },
+                            Hotkey::LoadState => match slots.load(cpu) {
+                                Ok(path) => println!("Loaded state from {}", path.display()),
+                                Err(err) => eprintln!("{}", err),
+                            },
+                            Hotkey::NextSlot => {
+                                slots.select_next();
+                                println!("State slot {}", slots.selected());
+                            }
+                            Hotkey::PreviousSlot => {
+                                slots.select_previous();
+                                println!("State slot {}", slots.selected());
+                            }
+                            Hotkey::Screenshot => save_screenshot(&cpu.bus.ppu.frame),
+                            Hotkey::FastForward | Hotkey::Rewind => {}
+                        }
+                    }
+                    if !paused || quit {
+                        break;
+                    }
+                    std::thread::sleep(Duration::from_millis(16));
+                }
+                if frames >= next_battery_flush {
+                    next_battery_flush = frames + BATTERY_FLUSH_FRAMES;
+                    flush_battery(battery, &mut cpu.bus);
+                }
+
+                if let Some(rewind) = rewind.as_mut() {
+                    let held = input.is_held(Hotkey::Rewind);
+                    if held && !rewinding {
+                        println!(
+                            "Rewinding through {:.1} s of history ({} KiB)",
+                            rewind.frames() as f64 / 60.0,
+                            rewind.memory_usage() / 1024
+                        );
+                    }
+                    rewinding = held;
+                    if rewinding {
+                        rewind.step_back(cpu);
+                    } else {
+                        rewind.record(cpu);
+                    }
+                }
+            }
+        } else {
+            quit = handle_user_input(cpu, &mut event_pump);
+        }
+        if quit {
+            if let Some(trace_file) = trace_file.as_mut() {
+                let _ = trace_file.flush();
+            }
+            flush_battery(battery, &mut cpu.bus);
+            std::process::exit(0);
+        }
+        if !demo {
+            return;
+        }
+
+        cpu.mem_write(0xfe, rng.gen_range(1..16));
+
+        if read_screen_state(cpu, &mut screen_state) {
+            texture.update(None, &screen_state, 32 * 3).unwrap();
+            canvas.copy(&texture, None, None).unwrap();
+            canvas.present();
+        }
+
+        ::std::thread::sleep(std::time::Duration::new(0, 1_000));
+    };
+    loop {
+        match cpu.run_with_callback(&mut frame) {
+            // a bad stack is worth knowing about, but games survive it
+            Err(err) => eprintln!("CPU error: {}", err),
+            Ok(reason) => {
+                print_opcode_reports(&mut cpu);
+                eprintln!("CPU stopped: {}", reason);
+                flush_battery(battery, &mut cpu.bus);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Games rarely use the unstable opcodes on purpose, so they hint at an
+/// emulation bug when one shows up.
+fn print_opcode_reports(cpu: &mut cpu::CPU) {
+    for report in cpu.take_opcode_reports() {
+        eprintln!("CPU: {}", report);
+    }
+}
+
+/// Loads the config from `path`, or from the default config file, which is
+/// created with the defaults on first run, and resolves its key bindings.
+/// Invalid configs are fatal so that mistakes don't go unnoticed.
+fn load_config(path: Option<&Path>) -> (Config, Bindings) {
+    let config = match path.map(Path::to_path_buf).or_else(Config::default_path) {
+        Some(path) => Config::load_or_create(&path),
+        None => Ok(Config::default()),
+    };
+    config
+        .and_then(|config| Bindings::new(&config).map(|bindings| (config, bindings)))
+        .unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        })
+}
+
+fn create_rewind(settings: &RewindSettings) -> Option<Rewind> {
+    if settings.seconds == 0 {
+        return None;
+    }
+    let interval = settings.interval.max(1);
+    let snapshots = (settings.seconds * 60 / interval) as usize;
+    let memory_limit = settings.memory_limit_mib as usize * 1024 * 1024;
+    Some(Rewind::new(interval, snapshots, memory_limit))
+}
+
+fn flush_battery(battery: Option<&BatteryFile>, bus: &mut Bus) {
+    if let Some(Err(err)) = battery.map(|battery| battery.flush(bus)) {
+        eprintln!("{}", err);
+    }
+}
+
+/// Saves the current picture as a BMP file in the working directory.
+fn save_screenshot(frame: &Frame) {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis())
+        .unwrap_or_default();
+    let path = format!("screenshot-{}.bmp", timestamp);
+
+    let mut data = frame.data.clone();
+    let result = sdl2::surface::Surface::from_data(
+        &mut data,
+        Frame::WIDTH as u32,
+        Frame::HEIGHT as u32,
+        Frame::WIDTH as u32 * 3,
+        PixelFormatEnum::RGB24,
+    )
+    .and_then(|surface| surface.save_bmp(&path));
+    match result {
+        Ok(()) => println!("Saved {}", path),
+        Err(err) => eprintln!("Cannot save {}: {}", path, err),
+    }
+}
+
+/// Runs a ROM using the $6000 result protocol without opening a window and
+/// exits with 0 when it passes.
+fn run_test_rom(path: &str, core: cpu::CpuCore) -> ! {
+    // the slowest suites need about 30 seconds of emulated time
+    const MAX_FRAMES: u32 = 60 * 60;
+
+    let result = test_rom::run_test_rom(load_rom(path), MAX_FRAMES, core).unwrap_or_else(|err| {
+        eprintln!("Cannot load {}: {}", path, err);
+        std::process::exit(1);
+    });
+    println!("{}", result.text.trim_end());
+    match result.status {
+        test_rom::TestRomStatus::Passed => std::process::exit(0),
+        test_rom::TestRomStatus::Failed(code) => {
+            eprintln!("{}: failed with code {}", path, code);
+            std::process::exit(1);
+        }
+        test_rom::TestRomStatus::TimedOut => {
+            eprintln!("{}: no result after {} frames", path, result.frames);
+            std::process::exit(1);
+        }
+        test_rom::TestRomStatus::Stopped(reason) => {
+            eprintln!("{}: CPU stopped: {}", path, reason);
+            std::process::exit(1);
+        }
+        test_rom::TestRomStatus::Crashed(err) => {
+            eprintln!("{}: CPU error: {}", path, err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Runs the ROM under the command-line debugger, without opening a window.
+fn run_debugger(path: &str, core: cpu::CpuCore) -> ! {
+    let mut cpu = cpu::CPU::new();
+    cpu.set_core(core);
+    cpu.bus
+        .insert_cartridge(load_rom(path))
+        .unwrap_or_else(|err| {
+            eprintln!("Cannot load {}: {}", path, err);
+            std::process::exit(1);
+        });
+    cpu.reset();
+
+    println!("Debugging {}, type help for the commands", path);
+    let stdin = std::io::stdin();
+    if let Err(err) = Debugger::new().run(&mut cpu, stdin.lock(), std::io::stdout()) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+    std::process::exit(0);
+}
+
+/// Opens the default audio device, falling back to a sink that discards the
+/// samples when muted or when there is no device to play them on.
+fn open_audio_sink(sdl_context: &sdl2::Sdl, mute: bool) -> Box<dyn AudioSink> {
+    if mute {
+        return Box::new(NullSink::new(apu::DEFAULT_SAMPLE_RATE));
+    }
+    match sdl_context
+        .audio()
+        .and_then(|audio| SdlAudioSink::new(&audio, apu::DEFAULT_SAMPLE_RATE))
+    {
+        Ok(sink) => Box::new(sink),
+        Err(err) => {
+            eprintln!("No audio output: {}", err);
+            Box::new(NullSink::new(apu::DEFAULT_SAMPLE_RATE))
+        }
+    }
+}
+
+fn load_rom(path: &str) -> Rom {
+    let raw = std::fs::read(path).unwrap_or_else(|err| {
+        eprintln!("Cannot read {}: {}", path, err);
+        std::process::exit(1);
+    });
+    Rom::new(&raw).unwrap_or_else(|err| {
+        eprintln!("Cannot load {}: {}", path, err);
+        std::process::exit(1);
+    })
+}
+
+/// Applies the Snake demo's WASD keys, which it reads as ASCII from $FF, and
+/// returns true when the user asked to quit.
+fn handle_user_input(cpu: &mut cpu::CPU, event_pump: &mut EventPump) -> bool {
+    for event in event_pump.poll_iter() {
+        match event {
+            Event::Quit { .. }
+            | Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                ..
+            } => return true,
+            Event::KeyDown {
+                keycode: Some(Keycode::W),
+                ..
+            } => {
+                cpu.mem_write(0xff, 0x77);
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::S),
+                ..
+            } => {
+                cpu.mem_write(0xff, 0x73);
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::A),
+                ..
+            } => {
+                cpu.mem_write(0xff, 0x61);
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::D),
+                ..
+            } => {
+                cpu.mem_write(0xff, 0x64);
+            }
+            _ => { /* do nothing */ }
+        }
+    }
+    false
+}
+
+fn color(byte: u8) -> Color {
+    match byte {
+        0 => sdl2::pixels::Color::BLACK,
+        1 => sdl2::pixels::Color::WHITE,
+        2 | 9 => sdl2::pixels::Color::GREY,
+        3 | 10 => sdl2::pixels::Color::RED,
+        4 | 11 => sdl2::pixels::Color::GREEN,
+        5 | 12 => sdl2::pixels::Color::BLUE,
+        6 | 13 => sdl2::pixels::Color::MAGENTA,
+        7 | 14 => sdl2::pixels::Color::YELLOW,
+        _ => sdl2::pixels::Color::CYAN,
+    }
+}
+
+fn read_screen_state(cpu: &mut cpu::CPU, frame: &mut [u8; 32 * 3 * 32]) -> bool {
+    let mut frame_idx = 0;
+    let mut update = false;
+    for i in 0x0200..0x600 {
+        let color_idx = cpu.mem_read(i as u16);
+        let (b1, b2, b3) = color(color_idx).rgb();
+        if frame[frame_idx] != b1 || frame[frame_idx + 1] != b2 || frame[frame_idx + 2] != b3 {
+            frame[frame_idx] = b1;
+            frame[frame_idx + 1] = b2;
+            frame[frame_idx + 2] = b3;
+            update = true;
+        }
+        frame_idx += 3;
+    }
+    update
+}
@@ -0,0 +1,35 @@
+use nes_core::audio::AudioSink;
+use sdl2::audio::{AudioQueue, AudioSpecDesired};
+use sdl2::AudioSubsystem;
+
+/// Mono f32 output through an SDL audio queue.
+pub struct SdlAudioSink {
+    queue: AudioQueue<f32>,
+}
+
+impl SdlAudioSink {
+    pub fn new(audio: &AudioSubsystem, sample_rate: u32) -> Result<Self, String> {
+        let desired = AudioSpecDesired {
+            freq: Some(sample_rate as i32),
+            channels: Some(1),
+            samples: Some(1024),
+        };
+        let queue = audio.open_queue::<f32, _>(None, &desired)?;
+        queue.resume();
+        Ok(SdlAudioSink { queue })
+    }
+}
+
+impl AudioSink for SdlAudioSink {
+    fn sample_rate(&self) -> u32 {
+        self.queue.spec().freq as u32
+    }
+
+    fn queued(&self) -> usize {
+        self.queue.size() as usize / std::mem::size_of::<f32>()
+    }
+
+    fn queue(&mut self, samples: &[f32]) {
+        self.queue.queue(samples);
+    }
+}
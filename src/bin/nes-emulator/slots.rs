@@ -0,0 +1,73 @@
+#[cfg(test)]
+mod slots_tests;
+
+use nes_core::cpu::CPU;
+use std::path::{Path, PathBuf};
+
+const SLOTS: u8 = 10;
+
+/// Numbered save state files of the running game, `<rom name>.ss0` to
+/// `<rom name>.ss9`.
+pub struct SaveSlots {
+    directory: PathBuf,
+    name: String,
+    selected: u8,
+}
+
+impl SaveSlots {
+    pub fn new(directory: PathBuf, rom_path: &Path) -> Self {
+        let name = rom_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "game".to_string());
+        SaveSlots {
+            directory,
+            name,
+            selected: 0,
+        }
+    }
+
+    /// `states` in the user's data directory, e.g.
+    /// `~/.local/share/nes-emulator/states` on Linux, or the directory of
+    /// the ROM when there is none.
+    pub fn default_directory(rom_path: &Path) -> PathBuf {
+        dirs::data_dir()
+            .map(|dir| dir.join("nes-emulator").join("states"))
+            .or_else(|| rom_path.parent().map(Path::to_path_buf))
+            .unwrap_or_default()
+    }
+
+    pub fn selected(&self) -> u8 {
+        self.selected
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % SLOTS;
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = (self.selected + SLOTS - 1) % SLOTS;
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.directory
+            .join(format!("{}.ss{}", self.name, self.selected))
+    }
+
+    pub fn save(&self, cpu: &CPU) -> Result<PathBuf, String> {
+        let path = self.path();
+        std::fs::create_dir_all(&self.directory)
+            .and_then(|_| std::fs::write(&path, cpu.save_state()))
+            .map_err(|err| format!("Cannot save {}: {}", path.display(), err))?;
+        Ok(path)
+    }
+
+    pub fn load(&self, cpu: &mut CPU) -> Result<PathBuf, String> {
+        let path = self.path();
+        let error =
+            |err: &dyn std::fmt::Display| format!("Cannot load {}: {}", path.display(), err);
+        let data = std::fs::read(&path).map_err(|err| error(&err))?;
+        cpu.load_state(&data).map_err(|err| error(&err))?;
+        Ok(path)
+    }
+}
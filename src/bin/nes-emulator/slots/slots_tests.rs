@@ -0,0 +1,46 @@
+use super::SaveSlots;
+use nes_core::cpu::CPU;
+use std::path::{Path, PathBuf};
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nes-slots-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn slots_wrap_around() {
+    let mut slots = SaveSlots::new(PathBuf::from("states"), Path::new("roms/Game.nes"));
+    assert_eq!(slots.path(), Path::new("states/Game.ss0"));
+
+    slots.select_previous();
+    assert_eq!(slots.selected(), 9);
+    assert_eq!(slots.path(), Path::new("states/Game.ss9"));
+    slots.select_next();
+    slots.select_next();
+    assert_eq!(slots.selected(), 1);
+}
+
+#[test]
+fn saves_and_loads_the_selected_slot() {
+    let dir = scratch_dir("round-trip");
+    let mut slots = SaveSlots::new(dir.join("nested"), Path::new("game.nes"));
+    slots.select_next();
+
+    let mut cpu = CPU::new();
+    cpu.accumulator = 0x42;
+    assert_eq!(
+        slots.save(&cpu).unwrap(),
+        dir.join("nested").join("game.ss1")
+    );
+
+    cpu.accumulator = 0;
+    slots.load(&mut cpu).unwrap();
+    assert_eq!(cpu.accumulator, 0x42);
+
+    slots.select_next();
+    let err = slots.load(&mut cpu).unwrap_err();
+    assert!(err.starts_with("Cannot load"), "{}", err);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
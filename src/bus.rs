@@ -0,0 +1,505 @@
+use crate::apu::{self, NesAPU};
+use crate::cartridge::{BatteryRamError, Rom, RomError};
+use crate::joypad::Joypad;
+use crate::mapper::{self, FlatMemory, Mapper};
+use crate::ppu::NesPPU;
+use crate::savestate::{self, SaveStateError, StateReader, StateWriter};
+
+mod flat;
+
+#[cfg(test)]
+mod bus_tests;
+
+pub use flat::FlatBus;
+
+const RAM: u16 = 0x0000;
+const RAM_MIRRORS_END: u16 = 0x1FFF;
+const PPU_REGISTERS: u16 = 0x2000;
+const PPU_REGISTERS_MIRRORS_END: u16 = 0x3FFF;
+const APU_IO_REGISTERS: u16 = 0x4000;
+const APU_IO_REGISTERS_END: u16 = 0x401F;
+const APU_CHANNELS_END: u16 = 0x4013;
+const OAM_DMA: u16 = 0x4014;
+const APU_STATUS: u16 = 0x4015;
+const JOYPAD1: u16 = 0x4016;
+const JOYPAD2: u16 = 0x4017;
+const APU_FRAME_COUNTER: u16 = 0x4017;
+/// Bits of a $4016/$4017 read that the controller ports leave floating.
+const JOYPAD_OPEN_BUS_BITS: u8 = 0b1110_0000;
+const CARTRIDGE_SPACE: u16 = 0x4020;
+/// The CPU is halted for up to 4 cycles while the DMC fetches a sample byte.
+const DMC_STALL_CYCLES: u16 = 4;
+
+/// Devices that can pull the shared IRQ line low. The line stays asserted
+/// while at least one of them holds it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IrqSource {
+    Mapper = 0b0001,
+    FrameCounter = 0b0010,
+    Dmc = 0b0100,
+    External = 0b1000,
+}
+
+/// Kind of memory access a watchpoint triggers on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// A CPU access to memory, as recorded by watchpoints (`Bus::watch`) and by
+/// the access log (`Bus::log_accesses`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BusAccess {
+    pub address: u16,
+    pub access: Access,
+    pub data: u8,
+}
+
+pub trait Mem {
+    fn mem_read(&mut self, address: u16) -> u8;
+
+    fn mem_write(&mut self, address: u16, data: u8);
+
+    fn mem_read_u16(&mut self, position: u16) -> u16 {
+        let lo = self.mem_read(position) as u16;
+        let hi = self.mem_read(position.wrapping_add(1)) as u16;
+
+        (hi << 8) | lo
+    }
+
+    fn mem_write_u16(&mut self, address: u16, data: u16) {
+        let hi = (data >> 8) as u8;
+        let lo = (data & 0xFF) as u8;
+        self.mem_write(address, lo);
+        self.mem_write(address.wrapping_add(1), hi);
+    }
+}
+
+/// What the CPU needs from the system around it besides memory: clocking
+/// it, DMA stalls and the interrupt lines. `Bus` is the NES, `FlatBus` plain
+/// RAM for CPU tests.
+pub trait CpuBus: Mem {
+    /// Runs the rest of the system for `cycles` CPU cycles.
+    fn tick(&mut self, cycles: u16);
+
+    fn cycles(&self) -> u64;
+
+    fn take_dma_stall_cycles(&mut self) -> u16;
+
+    fn poll_nmi_status(&mut self) -> bool;
+
+    fn irq_asserted(&self) -> bool;
+
+    /// The console's reset line.
+    fn reset(&mut self);
+}
+
+/// # CPU memory map http://wiki.nesdev.com/w/index.php/CPU_memory_map
+///
+///  _______________ $10000  _______________
+/// | PRG-ROM       |       |               |
+/// | Upper Bank    |       |               |
+/// |_ _ _ _ _ _ _ _| $C000 | PRG-ROM       |
+/// | PRG-ROM       |       |               |
+/// | Lower Bank    |       |               |
+/// |_______________| $8000 |_______________|
+/// | SRAM          |       | SRAM          |
+/// |_______________| $6000 |_______________|
+/// | Expansion ROM |       | Expansion ROM |
+/// |_______________| $4020 |_______________|
+/// | I/O Registers |       |               |
+/// |_ _ _ _ _ _ _ _| $4000 |               |
+/// | Mirrors       |       | I/O Registers |
+/// | $2000-$2007   |       |               |
+/// |_ _ _ _ _ _ _ _| $2008 |               |
+/// | I/O Registers |       |               |
+/// |_______________| $2000 |_______________|
+/// | Mirrors       |       |               |
+/// | $0000-$07FF   |       |               |
+/// |_ _ _ _ _ _ _ _| $0800 |               |
+/// | RAM           |       | RAM           |
+/// |_ _ _ _ _ _ _ _| $0200 |               |
+/// | Stack         |       |               |
+/// |_ _ _ _ _ _ _ _| $0100 |               |
+/// | Zero Page     |       |               |
+/// |_______________| $0000 |_______________|
+///
+pub struct Bus {
+    cpu_vram: [u8; 0x0800],
+    apu_io_registers: [u8; 0x20],
+    /// The inserted cartridge, `FlatMemory` until there is one.
+    mapper: Box<dyn Mapper>,
+    /// Identifies the inserted ROM in save states, 0 without a cartridge.
+    cartridge_fingerprint: u32,
+    pub ppu: NesPPU,
+    pub apu: NesAPU,
+    pub joypad1: Joypad,
+    pub joypad2: Joypad,
+    /// Last value on the CPU data bus, seen in the undriven bits of reads.
+    open_bus: u8,
+    cycles: u64,
+    dma_stall_cycles: u16,
+    irq_lines: u8,
+    nmi_line: bool,
+    nmi_pending: bool,
+    watchpoints: Vec<(u16, Access)>,
+    watch_hits: Vec<BusAccess>,
+    access_log: Option<Vec<BusAccess>>,
+}
+
+impl Bus {
+    pub fn new() -> Self {
+        Bus {
+            cpu_vram: [0; 0x0800],
+            apu_io_registers: [0; 0x20],
+            mapper: Box::new(FlatMemory::new()),
+            cartridge_fingerprint: 0,
+            ppu: NesPPU::new(),
+            apu: NesAPU::new(apu::DEFAULT_SAMPLE_RATE),
+            joypad1: Joypad::new(),
+            joypad2: Joypad::new(),
+            open_bus: 0,
+            cycles: 0,
+            dma_stall_cycles: 0,
+            irq_lines: 0,
+            nmi_line: false,
+            nmi_pending: false,
+            watchpoints: Vec::new(),
+            watch_hits: Vec::new(),
+            access_log: None,
+        }
+    }
+
+    pub fn insert_cartridge(&mut self, rom: Rom) -> Result<(), RomError> {
+        let fingerprint = savestate::fingerprint(&[&rom.prg_rom, &rom.chr_rom]);
+        self.mapper = mapper::create(rom)?;
+        self.cartridge_fingerprint = fingerprint;
+        Ok(())
+    }
+
+    /// Battery-backed PRG RAM of the cartridge, the contents of a `.sav`
+    /// file; `None` when the cartridge has no battery.
+    pub fn battery_ram(&self) -> Option<&[u8]> {
+        self.mapper.battery_ram()
+    }
+
+    /// Replaces the battery-backed RAM with `data`, e.g. from a `.sav` file,
+    /// which must have the size of the cartridge's RAM.
+    pub fn load_battery_ram(&mut self, data: &[u8]) -> Result<(), BatteryRamError> {
+        let ram = self
+            .mapper
+            .battery_ram_mut()
+            .ok_or(BatteryRamError::NoBattery)?;
+        if ram.len() != data.len() {
+            return Err(BatteryRamError::SizeMismatch {
+                expected: ram.len(),
+                found: data.len(),
+            });
+        }
+        ram.copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Returns `true` once after the game wrote to battery-backed RAM, so
+    /// that it only gets saved when it changed.
+    pub fn take_battery_ram_changed(&mut self) -> bool {
+        self.mapper.take_battery_ram_changed()
+    }
+
+    pub(crate) fn cartridge_fingerprint(&self) -> u32 {
+        self.cartridge_fingerprint
+    }
+
+    /// Resets the devices that see the console's reset line.
+    pub fn reset(&mut self) {
+        self.apu.reset();
+        self.irq_lines &= !(IrqSource::FrameCounter as u8 | IrqSource::Dmc as u8);
+    }
+
+    /// Runs the PPU and the APU for the time the CPU spent on `cycles` cycles.
+    pub fn tick(&mut self, cycles: u16) {
+        self.cycles += cycles as u64;
+        self.ppu.tick(cycles * 3, self.mapper.as_mut());
+        for _ in 0..cycles {
+            self.apu.tick();
+            if let Some(address) = self.apu.dmc_fetch_address() {
+                // samples always come from $8000-$FFFF
+                let data = self.mapper.cpu_read(address);
+                self.apu.dmc_fill(data);
+                self.dma_stall_cycles += DMC_STALL_CYCLES;
+            }
+        }
+
+        let mapper_irq = self.mapper.irq();
+        self.set_irq(IrqSource::Mapper, mapper_irq);
+        let frame_irq = self.apu.frame_irq();
+        self.set_irq(IrqSource::FrameCounter, frame_irq);
+        let dmc_irq = self.apu.dmc_irq();
+        self.set_irq(IrqSource::Dmc, dmc_irq);
+    }
+
+    /// CPU cycles the bus has been clocked for since power-up.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Returns the cycles the CPU has to be suspended for because of OAM or
+    /// DMC DMA started since the last call.
+    pub fn take_dma_stall_cycles(&mut self) -> u16 {
+        std::mem::take(&mut self.dma_stall_cycles)
+    }
+
+    /// Returns `true` once for every NMI edge, from the PPU or from
+    /// `set_nmi`, since the last poll.
+    pub fn poll_nmi_status(&mut self) -> bool {
+        let ppu_nmi = self.ppu.poll_nmi();
+        std::mem::replace(&mut self.nmi_pending, false) || ppu_nmi
+    }
+
+    /// Drives the NMI line. NMI is edge triggered: only the transition to
+    /// asserted raises an interrupt, holding the line does not repeat it.
+    pub fn set_nmi(&mut self, asserted: bool) {
+        if asserted && !self.nmi_line {
+            self.nmi_pending = true;
+        }
+        self.nmi_line = asserted;
+    }
+
+    /// Asserts or releases the IRQ line on behalf of `source`.
+    pub fn set_irq(&mut self, source: IrqSource, asserted: bool) {
+        if asserted {
+            self.irq_lines |= source as u8;
+        } else {
+            self.irq_lines &= !(source as u8);
+        }
+    }
+
+    /// Level of the IRQ line: `true` while any source holds it.
+    pub fn irq_asserted(&self) -> bool {
+        self.irq_lines != 0
+    }
+
+    /// Returns `true` once when the PPU has finished a frame since the last poll.
+    pub fn poll_frame_complete(&mut self) -> bool {
+        self.ppu.poll_frame_complete()
+    }
+
+    /// Records CPU reads or writes of `address` for `take_watch_hits`. The
+    /// address is compared as accessed, mirrors are not folded.
+    pub fn watch(&mut self, address: u16, access: Access) {
+        if !self.watchpoints.contains(&(address, access)) {
+            self.watchpoints.push((address, access));
+        }
+    }
+
+    /// Removes the watchpoints on `address`; returns `false` if there were none.
+    pub fn unwatch(&mut self, address: u16) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints.retain(|&(watched, _)| watched != address);
+        self.watchpoints.len() != count
+    }
+
+    pub fn watchpoints(&self) -> &[(u16, Access)] {
+        &self.watchpoints
+    }
+
+    /// Returns the watched accesses since the last call, oldest first.
+    pub fn take_watch_hits(&mut self) -> Vec<BusAccess> {
+        std::mem::take(&mut self.watch_hits)
+    }
+
+    /// Starts or stops recording every CPU read and write for
+    /// `take_access_log`.
+    pub fn log_accesses(&mut self, enabled: bool) {
+        self.access_log = if enabled { Some(Vec::new()) } else { None };
+    }
+
+    /// Returns the accesses logged since the last call, oldest first.
+    pub fn take_access_log(&mut self) -> Vec<BusAccess> {
+        self.access_log
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn record_access(&mut self, address: u16, access: Access, data: u8) {
+        let record = BusAccess {
+            address,
+            access,
+            data,
+        };
+        if self.watchpoints.contains(&(address, access)) {
+            self.watch_hits.push(record);
+        }
+        if let Some(log) = self.access_log.as_mut() {
+            log.push(record);
+        }
+    }
+
+    /// Reads memory the way the CPU would, but without side effects such as
+    /// clearing vblank or advancing the PPU address. Meant for tracing and
+    /// debugging; PPU registers show the last value on the PPU data bus.
+    pub fn peek(&self, address: u16) -> u8 {
+        match address {
+            RAM..=RAM_MIRRORS_END => self.cpu_vram[(address & 0b0000_0111_1111_1111) as usize],
+            PPU_REGISTERS..=PPU_REGISTERS_MIRRORS_END => self.ppu.open_bus(),
+            APU_STATUS => self.apu.peek_status(),
+            JOYPAD1 => self.open_bus & JOYPAD_OPEN_BUS_BITS | self.joypad1.peek(),
+            JOYPAD2 => self.open_bus & JOYPAD_OPEN_BUS_BITS | self.joypad2.peek(),
+            APU_IO_REGISTERS..=APU_IO_REGISTERS_END => {
+                self.apu_io_registers[(address - APU_IO_REGISTERS) as usize]
+            }
+            CARTRIDGE_SPACE..=0xFFFF => self.mapper.cpu_read(address),
+        }
+    }
+
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.cpu_vram);
+        state.write_bytes(&self.apu_io_registers);
+        state.write_u8(self.open_bus);
+        state.write_u64(self.cycles);
+        state.write_u16(self.dma_stall_cycles);
+        state.write_u8(self.irq_lines);
+        state.write_bool(self.nmi_line);
+        state.write_bool(self.nmi_pending);
+        self.ppu.save_state(state);
+        self.apu.save_state(state);
+        self.joypad1.save_state(state);
+        self.joypad2.save_state(state);
+        self.mapper.save_state(state);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_bytes_into(&mut self.cpu_vram, "RAM size")?;
+        state.read_bytes_into(&mut self.apu_io_registers, "I/O register count")?;
+        self.open_bus = state.read_u8()?;
+        self.cycles = state.read_u64()?;
+        self.dma_stall_cycles = state.read_u16()?;
+        self.irq_lines = state.read_u8()?;
+        self.nmi_line = state.read_bool()?;
+        self.nmi_pending = state.read_bool()?;
+        self.ppu.load_state(state)?;
+        self.apu.load_state(state)?;
+        self.joypad1.load_state(state)?;
+        self.joypad2.load_state(state)?;
+        self.mapper.load_state(state)
+    }
+
+    fn read_ppu_register(&mut self, address: u16) -> u8 {
+        match address & 0b111 {
+            2 => self.ppu.read_status(),
+            4 => self.ppu.read_oam_data(),
+            7 => self.ppu.read_data(self.mapper.as_mut()),
+            // write-only registers return whatever was last on the PPU data bus
+            _ => self.ppu.open_bus(),
+        }
+    }
+
+    fn write_ppu_register(&mut self, address: u16, data: u8) {
+        match address & 0b111 {
+            0 => self.ppu.write_to_ctrl(data),
+            1 => self.ppu.write_to_mask(data),
+            3 => self.ppu.write_to_oam_addr(data),
+            4 => self.ppu.write_to_oam_data(data),
+            5 => self.ppu.write_to_scroll(data),
+            6 => self.ppu.write_to_ppu_addr(data),
+            7 => self.ppu.write_to_data(data, self.mapper.as_mut()),
+            _ => self.ppu.write_to_status(data),
+        }
+    }
+
+    fn oam_dma(&mut self, page: u8) {
+        let base = (page as u16) << 8;
+        let mut data = [0; 256];
+        for (offset, byte) in data.iter_mut().enumerate() {
+            *byte = self.mem_read(base + offset as u16);
+        }
+        self.ppu.write_oam_dma(&data);
+        // 513 cycles, plus one to align with a read cycle when started on an odd one
+        self.dma_stall_cycles += 513 + (self.cycles % 2) as u16;
+    }
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Bus::new()
+    }
+}
+
+impl Mem for Bus {
+    fn mem_read(&mut self, address: u16) -> u8 {
+        let data = match address {
+            RAM..=RAM_MIRRORS_END => {
+                let mirror_down_address = address & 0b0000_0111_1111_1111;
+                self.cpu_vram[mirror_down_address as usize]
+            }
+            PPU_REGISTERS..=PPU_REGISTERS_MIRRORS_END => self.read_ppu_register(address),
+            APU_STATUS => self.apu.read_status(),
+            JOYPAD1 => self.open_bus & JOYPAD_OPEN_BUS_BITS | self.joypad1.read(),
+            JOYPAD2 => self.open_bus & JOYPAD_OPEN_BUS_BITS | self.joypad2.read(),
+            APU_IO_REGISTERS..=APU_IO_REGISTERS_END => {
+                self.apu_io_registers[(address - APU_IO_REGISTERS) as usize]
+            }
+            CARTRIDGE_SPACE..=0xFFFF => self.mapper.cpu_read(address),
+        };
+        self.open_bus = data;
+        if !self.watchpoints.is_empty() || self.access_log.is_some() {
+            self.record_access(address, Access::Read, data);
+        }
+        data
+    }
+
+    fn mem_write(&mut self, address: u16, data: u8) {
+        self.open_bus = data;
+        if !self.watchpoints.is_empty() || self.access_log.is_some() {
+            self.record_access(address, Access::Write, data);
+        }
+        match address {
+            RAM..=RAM_MIRRORS_END => {
+                let mirror_down_address = address & 0b0000_0111_1111_1111;
+                self.cpu_vram[mirror_down_address as usize] = data;
+            }
+            PPU_REGISTERS..=PPU_REGISTERS_MIRRORS_END => self.write_ppu_register(address, data),
+            OAM_DMA => self.oam_dma(data),
+            // the strobe goes to both ports
+            JOYPAD1 => {
+                self.joypad1.write(data);
+                self.joypad2.write(data);
+            }
+            APU_IO_REGISTERS..=APU_CHANNELS_END | APU_STATUS | APU_FRAME_COUNTER => {
+                self.apu.write_register(address, data)
+            }
+            APU_IO_REGISTERS..=APU_IO_REGISTERS_END => {
+                self.apu_io_registers[(address - APU_IO_REGISTERS) as usize] = data;
+            }
+            CARTRIDGE_SPACE..=0xFFFF => self.mapper.cpu_write(address, data),
+        }
+    }
+}
+
+impl CpuBus for Bus {
+    fn tick(&mut self, cycles: u16) {
+        Bus::tick(self, cycles)
+    }
+
+    fn cycles(&self) -> u64 {
+        Bus::cycles(self)
+    }
+
+    fn take_dma_stall_cycles(&mut self) -> u16 {
+        Bus::take_dma_stall_cycles(self)
+    }
+
+    fn poll_nmi_status(&mut self) -> bool {
+        Bus::poll_nmi_status(self)
+    }
+
+    fn irq_asserted(&self) -> bool {
+        Bus::irq_asserted(self)
+    }
+
+    fn reset(&mut self) {
+        Bus::reset(self)
+    }
+}
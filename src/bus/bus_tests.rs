@@ -0,0 +1,250 @@
+use super::{Access, Bus, BusAccess, CpuBus, FlatBus, Mem};
+use crate::cartridge::{BatteryRamError, Rom, RomBuilder};
+use crate::joypad::JoypadButton;
+
+/// NROM image whose PRG ROM bytes hold the high byte of their offset.
+fn test_rom(prg_banks: u8) -> RomBuilder {
+    RomBuilder::new().prg_rom(
+        (0..prg_banks as usize * 0x4000)
+            .map(|i| (i >> 8) as u8)
+            .collect(),
+    )
+}
+
+#[test]
+fn ram_is_mirrored_every_2_kib() {
+    let mut bus = Bus::new();
+    bus.mem_write(0x0001, 0x55);
+    assert_eq!(bus.mem_read(0x0801), 0x55);
+    assert_eq!(bus.mem_read(0x1001), 0x55);
+    assert_eq!(bus.mem_read(0x1801), 0x55);
+
+    bus.mem_write(0x1FFF, 0xAA);
+    assert_eq!(bus.mem_read(0x07FF), 0xAA);
+}
+
+#[test]
+fn ppu_registers_are_mirrored_every_8_bytes() {
+    let mut bus = Bus::new();
+    // PPUADDR through $3456 and $200E, PPUDATA through $3FFF
+    bus.mem_write(0x3456, 0x23);
+    bus.mem_write(0x200E, 0x05);
+    bus.mem_write(0x3FFF, 0x42);
+
+    bus.mem_write(0x2006, 0x23);
+    bus.mem_write(0x2006, 0x05);
+    bus.mem_read(0x2007);
+    assert_eq!(bus.mem_read(0x200F), 0x42);
+}
+
+#[test]
+fn u16_access_is_little_endian() {
+    let mut bus = Bus::new();
+    bus.mem_write_u16(0xFFFC, 0x8000);
+    assert_eq!(bus.mem_read(0xFFFC), 0x00);
+    assert_eq!(bus.mem_read(0xFFFD), 0x80);
+    assert_eq!(bus.mem_read_u16(0xFFFC), 0x8000);
+}
+
+#[test]
+fn prg_rom_16_kib_is_mirrored() {
+    let mut bus = Bus::new();
+    bus.insert_cartridge(test_rom(1).rom().unwrap()).unwrap();
+    assert_eq!(bus.mem_read(0x8100), 0x01);
+    assert_eq!(bus.mem_read(0xC100), 0x01);
+    assert_eq!(bus.mem_read(0xFFFF), 0x3F);
+}
+
+#[test]
+fn prg_rom_32_kib_is_not_mirrored() {
+    let mut bus = Bus::new();
+    bus.insert_cartridge(test_rom(2).rom().unwrap()).unwrap();
+    assert_eq!(bus.mem_read(0xC100), 0x41);
+}
+
+#[test]
+fn prg_rom_ignores_writes_and_prg_ram_keeps_them() {
+    let mut bus = Bus::new();
+    bus.insert_cartridge(test_rom(1).rom().unwrap()).unwrap();
+    bus.mem_write(0x8100, 0xFF);
+    assert_eq!(bus.mem_read(0x8100), 0x01);
+
+    bus.mem_write(0x6010, 0xAB);
+    assert_eq!(bus.mem_read(0x6010), 0xAB);
+}
+
+fn battery_rom() -> Rom {
+    test_rom(1).battery(true).rom().unwrap()
+}
+
+#[test]
+fn battery_ram_is_prg_ram_of_battery_cartridges() {
+    let mut bus = Bus::new();
+    assert_eq!(bus.battery_ram(), None);
+    bus.insert_cartridge(test_rom(1).rom().unwrap()).unwrap();
+    bus.mem_write(0x6000, 0x01);
+    assert_eq!(bus.battery_ram(), None);
+    assert!(!bus.take_battery_ram_changed());
+
+    bus.insert_cartridge(battery_rom()).unwrap();
+    bus.mem_write(0x6010, 0xAB);
+    let ram = bus.battery_ram().unwrap();
+    assert_eq!(ram.len(), 0x2000);
+    assert_eq!(ram[0x10], 0xAB);
+}
+
+#[test]
+fn battery_ram_leaves_out_volatile_prg_ram() {
+    let mut bus = Bus::new();
+    let rom = test_rom(1).battery(true).prg_ram(0x2000, 0x2000);
+    bus.insert_cartridge(rom.rom().unwrap()).unwrap();
+    // the 8 KiB window shows the volatile half, mirrored
+    bus.mem_write(0x6000, 0x11);
+    assert!(!bus.take_battery_ram_changed());
+    assert_eq!(bus.battery_ram().unwrap(), &[0; 0x2000][..]);
+
+    let mut save = vec![0; 0x2000];
+    save[0] = 0x22;
+    bus.load_battery_ram(&save).unwrap();
+    assert_eq!(bus.mem_read(0x6000), 0x11);
+}
+
+#[test]
+fn battery_ram_reports_changes_once() {
+    let mut bus = Bus::new();
+    bus.insert_cartridge(battery_rom()).unwrap();
+    assert!(!bus.take_battery_ram_changed());
+
+    bus.mem_write(0x7FFF, 0x12);
+    assert!(bus.take_battery_ram_changed());
+    assert!(!bus.take_battery_ram_changed());
+
+    // writing the value already there is not a change
+    bus.mem_write(0x7FFF, 0x12);
+    assert!(!bus.take_battery_ram_changed());
+}
+
+#[test]
+fn battery_ram_loads_save_data() {
+    let mut bus = Bus::new();
+    assert_eq!(
+        bus.load_battery_ram(&[0; 0x2000]),
+        Err(BatteryRamError::NoBattery)
+    );
+
+    bus.insert_cartridge(battery_rom()).unwrap();
+    let mut save = vec![0; 0x2000];
+    save[0x123] = 0x45;
+    bus.load_battery_ram(&save).unwrap();
+    assert_eq!(bus.mem_read(0x6123), 0x45);
+    assert!(!bus.take_battery_ram_changed());
+
+    assert_eq!(
+        bus.load_battery_ram(&[0; 0x800]),
+        Err(BatteryRamError::SizeMismatch {
+            expected: 0x2000,
+            found: 0x800
+        })
+    );
+}
+
+#[test]
+fn joypads_are_read_through_4016_and_4017() {
+    let mut bus = Bus::new();
+    bus.joypad1.set_button_pressed(JoypadButton::B, true);
+    bus.joypad2.set_button_pressed(JoypadButton::A, true);
+    bus.mem_write(0x4016, 1);
+    bus.mem_write(0x4016, 0);
+
+    assert_eq!(bus.mem_read(0x4016) & 1, 0);
+    assert_eq!(bus.mem_read(0x4016) & 1, 1);
+    assert_eq!(bus.mem_read(0x4017) & 1, 1);
+    assert_eq!(bus.mem_read(0x4017) & 1, 0);
+}
+
+#[test]
+fn joypad_reads_keep_open_bus_upper_bits() {
+    let mut bus = Bus::new();
+    bus.joypad1.set_button_pressed(JoypadButton::A, true);
+    bus.mem_write(0x4016, 1);
+    bus.mem_write(0x4016, 0);
+
+    // LDA $4016 leaves the high byte of the address on the bus
+    bus.mem_write(0x0010, 0x40);
+    bus.mem_read(0x0010);
+    assert_eq!(bus.peek(0x4016), 0x41);
+    assert_eq!(bus.mem_read(0x4016), 0x41);
+    bus.mem_read(0x0010);
+    assert_eq!(bus.mem_read(0x4016), 0x40);
+}
+
+#[test]
+fn watchpoints_record_cpu_accesses_but_not_peeks() {
+    let mut bus = Bus::new();
+    bus.watch(0x0010, Access::Write);
+    bus.watch(0x0011, Access::Read);
+    bus.mem_write(0x0010, 0x12);
+    bus.mem_write(0x0011, 0x34);
+    bus.mem_read(0x0010);
+    bus.mem_read(0x0011);
+    bus.peek(0x0011);
+    assert_eq!(
+        bus.take_watch_hits(),
+        vec![
+            BusAccess {
+                address: 0x0010,
+                access: Access::Write,
+                data: 0x12,
+            },
+            BusAccess {
+                address: 0x0011,
+                access: Access::Read,
+                data: 0x34,
+            },
+        ]
+    );
+    assert!(bus.take_watch_hits().is_empty());
+
+    assert!(bus.unwatch(0x0010));
+    assert!(!bus.unwatch(0x0010));
+    assert_eq!(bus.watchpoints(), &[(0x0011, Access::Read)]);
+}
+
+#[test]
+fn flat_bus_is_plain_ram_everywhere() {
+    let mut bus = FlatBus::new();
+    for &address in &[0x0800, 0x2002, 0x4014, 0x4016, 0xFFFF] {
+        bus.mem_write(address, 0x5A);
+        assert_eq!(bus.mem_read(address), 0x5A, "${:04X}", address);
+    }
+    assert_eq!(bus.mem_read(0x0000), 0x00);
+    assert_eq!(bus.take_dma_stall_cycles(), 0);
+
+    bus.set_nmi(true);
+    bus.set_nmi(true);
+    assert!(bus.poll_nmi_status());
+    assert!(!bus.poll_nmi_status());
+}
+
+#[test]
+fn access_log_records_every_access() {
+    let mut bus = Bus::new();
+    assert!(bus.take_access_log().is_empty());
+    bus.log_accesses(true);
+    bus.mem_write(0x0001, 0x12);
+    bus.mem_read(0x0001);
+    bus.peek(0x0001);
+    let log: Vec<_> = bus
+        .take_access_log()
+        .iter()
+        .map(|access| (access.address, access.access, access.data))
+        .collect();
+    assert_eq!(
+        log,
+        vec![(0x0001, Access::Write, 0x12), (0x0001, Access::Read, 0x12)]
+    );
+
+    bus.log_accesses(false);
+    bus.mem_read(0x0001);
+    assert!(bus.take_access_log().is_empty());
+}
@@ -0,0 +1,105 @@
+use super::{Access, BusAccess, CpuBus, Mem};
+
+/// 64 KiB of plain RAM instead of the NES memory map, and no PPU, APU or
+/// controllers to clock or interrupt the CPU. For CPU test suites that place
+/// code and data anywhere, such as SingleStepTests:
+/// `CPU::with_bus(FlatBus::new())`.
+pub struct FlatBus {
+    memory: Vec<u8>,
+    cycles: u64,
+    nmi_line: bool,
+    nmi_pending: bool,
+    access_log: Option<Vec<BusAccess>>,
+}
+
+impl FlatBus {
+    pub fn new() -> Self {
+        FlatBus {
+            memory: vec![0; 0x10000],
+            cycles: 0,
+            nmi_line: false,
+            nmi_pending: false,
+            access_log: None,
+        }
+    }
+
+    /// Reads memory without logging the access.
+    pub fn peek(&self, address: u16) -> u8 {
+        self.memory[address as usize]
+    }
+
+    /// Drives the NMI line, edge triggered like `Bus::set_nmi`.
+    pub fn set_nmi(&mut self, asserted: bool) {
+        if asserted && !self.nmi_line {
+            self.nmi_pending = true;
+        }
+        self.nmi_line = asserted;
+    }
+
+    /// Starts or stops recording every CPU read and write for
+    /// `take_access_log`.
+    pub fn log_accesses(&mut self, enabled: bool) {
+        self.access_log = if enabled { Some(Vec::new()) } else { None };
+    }
+
+    /// Returns the accesses logged since the last call, oldest first.
+    pub fn take_access_log(&mut self) -> Vec<BusAccess> {
+        self.access_log
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn record_access(&mut self, address: u16, access: Access, data: u8) {
+        if let Some(log) = self.access_log.as_mut() {
+            log.push(BusAccess {
+                address,
+                access,
+                data,
+            });
+        }
+    }
+}
+
+impl Default for FlatBus {
+    fn default() -> Self {
+        FlatBus::new()
+    }
+}
+
+impl Mem for FlatBus {
+    fn mem_read(&mut self, address: u16) -> u8 {
+        let data = self.memory[address as usize];
+        self.record_access(address, Access::Read, data);
+        data
+    }
+
+    fn mem_write(&mut self, address: u16, data: u8) {
+        self.record_access(address, Access::Write, data);
+        self.memory[address as usize] = data;
+    }
+}
+
+impl CpuBus for FlatBus {
+    fn tick(&mut self, cycles: u16) {
+        self.cycles += cycles as u64;
+    }
+
+    fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    fn take_dma_stall_cycles(&mut self) -> u16 {
+        0
+    }
+
+    fn poll_nmi_status(&mut self) -> bool {
+        std::mem::replace(&mut self.nmi_pending, false)
+    }
+
+    fn irq_asserted(&self) -> bool {
+        false
+    }
+
+    fn reset(&mut self) {}
+}
@@ -0,0 +1,308 @@
+use std::fmt::Display;
+
+mod builder;
+
+pub use builder::RomBuilder;
+
+#[cfg(test)]
+mod cartridge_tests;
+
+const NES_TAG: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A];
+const HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
+const PRG_ROM_PAGE_SIZE: usize = 16 * 1024;
+const CHR_ROM_PAGE_SIZE: usize = 8 * 1024;
+const PRG_RAM_PAGE_SIZE: usize = 8 * 1024;
+const CHR_RAM_SIZE: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mirroring {
+    Vertical,
+    Horizontal,
+    FourScreen,
+    SingleScreenLower,
+    SingleScreenUpper,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Timing {
+    Ntsc,
+    Pal,
+    MultipleRegion,
+    Dendy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RomFormat {
+    INes,
+    Nes2,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RomError {
+    MissingHeader {
+        length: usize,
+    },
+    InvalidSignature([u8; 4]),
+    Truncated {
+        section: &'static str,
+        expected: usize,
+        available: usize,
+    },
+    EmptyPrgRom,
+    UnsupportedMapper {
+        mapper: u16,
+    },
+    InvalidRamSize {
+        field: &'static str,
+        shift: u8,
+    },
+}
+
+impl Display for RomError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RomError::MissingHeader { length } => write!(
+                f,
+                "file is {} bytes long, too short for a {}-byte iNES header",
+                length, HEADER_SIZE
+            ),
+            RomError::InvalidSignature(tag) => write!(
+                f,
+                "not an iNES file: expected signature 4E 45 53 1A, found {:02X} {:02X} {:02X} {:02X}",
+                tag[0], tag[1], tag[2], tag[3]
+            ),
+            RomError::Truncated {
+                section,
+                expected,
+                available,
+            } => write!(
+                f,
+                "file is truncated: {} needs {} bytes but only {} are left",
+                section, expected, available
+            ),
+            RomError::EmptyPrgRom => write!(f, "header declares no PRG ROM"),
+            RomError::UnsupportedMapper { mapper } => write!(f, "mapper {} is not supported", mapper),
+            RomError::InvalidRamSize { field, shift } => {
+                write!(f, "invalid {} shift count {} in NES 2.0 header", field, shift)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RomError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatteryRamError {
+    NoBattery,
+    SizeMismatch { expected: usize, found: usize },
+}
+
+impl Display for BatteryRamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BatteryRamError::NoBattery => write!(f, "cartridge has no battery-backed RAM"),
+            BatteryRamError::SizeMismatch { expected, found } => write!(
+                f,
+                "save data is {} bytes, the cartridge has {} bytes of battery-backed RAM",
+                found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BatteryRamError {}
+
+/// # iNES / NES 2.0 file https://wiki.nesdev.com/w/index.php/INES
+///
+///  Byte  Contents
+///  0-3   "NES" followed by MS-DOS end-of-file ($1A)
+///  4     PRG ROM size in 16 KiB units (LSB for NES 2.0)
+///  5     CHR ROM size in 8 KiB units (LSB for NES 2.0), 0 means CHR RAM
+///  6     Flags 6: mapper low nibble, four-screen, trainer, battery, mirroring
+///  7     Flags 7: mapper middle nibble, NES 2.0 identifier (bits 2-3 == 0b10)
+///  8     iNES: PRG RAM size in 8 KiB units
+///        NES 2.0: submapper (high nibble), mapper bits 8-11 (low nibble)
+///  9     iNES: TV system
+///        NES 2.0: CHR ROM size MSB (high nibble), PRG ROM size MSB (low nibble)
+///  10    NES 2.0: PRG NVRAM shift (high nibble), PRG RAM shift (low nibble)
+///  11    NES 2.0: CHR NVRAM shift (high nibble), CHR RAM shift (low nibble)
+///  12    NES 2.0: CPU/PPU timing
+///  13-15 NES 2.0: system type, miscellaneous ROMs, default expansion device
+///
+pub struct Rom {
+    pub prg_rom: Vec<u8>,
+    pub chr_rom: Vec<u8>,
+    pub trainer: Option<Vec<u8>>,
+    pub mapper: u16,
+    pub submapper: u8,
+    pub screen_mirroring: Mirroring,
+    pub battery: bool,
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+    pub timing: Timing,
+    pub format: RomFormat,
+}
+
+impl Rom {
+    pub fn new(raw: &[u8]) -> Result<Rom, RomError> {
+        if raw.len() < HEADER_SIZE {
+            return Err(RomError::MissingHeader { length: raw.len() });
+        }
+        if raw[0..4] != NES_TAG {
+            return Err(RomError::InvalidSignature([raw[0], raw[1], raw[2], raw[3]]));
+        }
+
+        let format = if raw[7] & 0b0000_1100 == 0b0000_1000 {
+            RomFormat::Nes2
+        } else {
+            RomFormat::INes
+        };
+
+        let four_screen = raw[6] & 0b1000 != 0;
+        let vertical_mirroring = raw[6] & 0b1 != 0;
+        let screen_mirroring = match (four_screen, vertical_mirroring) {
+            (true, _) => Mirroring::FourScreen,
+            (false, true) => Mirroring::Vertical,
+            (false, false) => Mirroring::Horizontal,
+        };
+        let battery = raw[6] & 0b10 != 0;
+        let has_trainer = raw[6] & 0b100 != 0;
+
+        let (mut rom, prg_rom_size, chr_rom_size) = match format {
+            RomFormat::INes => Rom::parse_ines_header(raw),
+            RomFormat::Nes2 => Rom::parse_nes2_header(raw)?,
+        };
+        rom.screen_mirroring = screen_mirroring;
+        rom.battery = battery;
+        if battery && format == RomFormat::INes {
+            // iNES has no NVRAM size, the battery keeps all of the PRG RAM
+            rom.prg_nvram_size = std::mem::take(&mut rom.prg_ram_size);
+        }
+
+        if prg_rom_size == 0 {
+            return Err(RomError::EmptyPrgRom);
+        }
+
+        let mut position = HEADER_SIZE;
+        if has_trainer {
+            let trainer = Rom::section(raw, position, TRAINER_SIZE, "trainer")?;
+            rom.trainer = Some(trainer.to_vec());
+            position += TRAINER_SIZE;
+        }
+        rom.prg_rom = Rom::section(raw, position, prg_rom_size, "PRG ROM")?.to_vec();
+        position += prg_rom_size;
+        rom.chr_rom = Rom::section(raw, position, chr_rom_size, "CHR ROM")?.to_vec();
+
+        Ok(rom)
+    }
+
+    fn parse_ines_header(raw: &[u8]) -> (Rom, usize, usize) {
+        // Headers written by old tools often carry garbage such as "DiskDude!"
+        // in bytes 7-15, in which case only the low mapper nibble can be trusted.
+        let dirty_header = raw[12..16].iter().any(|&byte| byte != 0);
+        let mapper_high = if dirty_header { 0 } else { raw[7] & 0xF0 };
+        let mapper = (mapper_high | (raw[6] >> 4)) as u16;
+
+        let prg_ram_banks = if dirty_header || raw[8] == 0 {
+            1
+        } else {
+            raw[8] as usize
+        };
+        let prg_rom_size = raw[4] as usize * PRG_ROM_PAGE_SIZE;
+        let chr_rom_size = raw[5] as usize * CHR_ROM_PAGE_SIZE;
+        let timing = if !dirty_header && raw[9] & 0b1 != 0 {
+            Timing::Pal
+        } else {
+            Timing::Ntsc
+        };
+
+        let rom = Rom {
+            prg_rom: Vec::new(),
+            chr_rom: Vec::new(),
+            trainer: None,
+            mapper,
+            submapper: 0,
+            screen_mirroring: Mirroring::Horizontal,
+            battery: false,
+            prg_ram_size: prg_ram_banks * PRG_RAM_PAGE_SIZE,
+            prg_nvram_size: 0,
+            chr_ram_size: if chr_rom_size == 0 { CHR_RAM_SIZE } else { 0 },
+            chr_nvram_size: 0,
+            timing,
+            format: RomFormat::INes,
+        };
+        (rom, prg_rom_size, chr_rom_size)
+    }
+
+    fn parse_nes2_header(raw: &[u8]) -> Result<(Rom, usize, usize), RomError> {
+        let mapper = (raw[6] >> 4) as u16 | (raw[7] & 0xF0) as u16 | ((raw[8] & 0x0F) as u16) << 8;
+        let submapper = raw[8] >> 4;
+
+        let prg_rom_size = Rom::nes2_rom_size(raw[4], raw[9] & 0x0F, PRG_ROM_PAGE_SIZE);
+        let chr_rom_size = Rom::nes2_rom_size(raw[5], raw[9] >> 4, CHR_ROM_PAGE_SIZE);
+
+        let timing = match raw[12] & 0b11 {
+            0 => Timing::Ntsc,
+            1 => Timing::Pal,
+            2 => Timing::MultipleRegion,
+            _ => Timing::Dendy,
+        };
+
+        let rom = Rom {
+            prg_rom: Vec::new(),
+            chr_rom: Vec::new(),
+            trainer: None,
+            mapper,
+            submapper,
+            screen_mirroring: Mirroring::Horizontal,
+            battery: false,
+            prg_ram_size: Rom::nes2_ram_size(raw[10] & 0x0F, "PRG RAM")?,
+            prg_nvram_size: Rom::nes2_ram_size(raw[10] >> 4, "PRG NVRAM")?,
+            chr_ram_size: Rom::nes2_ram_size(raw[11] & 0x0F, "CHR RAM")?,
+            chr_nvram_size: Rom::nes2_ram_size(raw[11] >> 4, "CHR NVRAM")?,
+            timing,
+            format: RomFormat::Nes2,
+        };
+        Ok((rom, prg_rom_size, chr_rom_size))
+    }
+
+    /// ROM sizes with MSB nibble $F use the exponent-multiplier notation:
+    /// the LSB byte is EEEEEEMM and the size is 2^E * (MM * 2 + 1) bytes.
+    fn nes2_rom_size(lsb: u8, msb: u8, page_size: usize) -> usize {
+        if msb == 0x0F {
+            let exponent = (lsb >> 2) as u32;
+            let multiplier = (lsb & 0b11) as usize * 2 + 1;
+            2_usize.saturating_pow(exponent).saturating_mul(multiplier)
+        } else {
+            ((msb as usize) << 8 | lsb as usize) * page_size
+        }
+    }
+
+    fn nes2_ram_size(shift: u8, field: &'static str) -> Result<usize, RomError> {
+        match shift {
+            0 => Ok(0),
+            1..=14 => Ok(64 << shift),
+            _ => Err(RomError::InvalidRamSize { field, shift }),
+        }
+    }
+
+    fn section<'r>(
+        raw: &'r [u8],
+        position: usize,
+        size: usize,
+        section: &'static str,
+    ) -> Result<&'r [u8], RomError> {
+        let available = raw.len().saturating_sub(position);
+        if available < size {
+            return Err(RomError::Truncated {
+                section,
+                expected: size,
+                available,
+            });
+        }
+        Ok(&raw[position..position + size])
+    }
+}
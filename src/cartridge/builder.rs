@@ -0,0 +1,170 @@
+use super::{
+    Mirroring, Rom, RomError, CHR_RAM_SIZE, CHR_ROM_PAGE_SIZE, NES_TAG, PRG_ROM_PAGE_SIZE,
+};
+
+// distance of the vectors from the end of PRG ROM
+const NMI: usize = 6;
+const RESET: usize = 4;
+const IRQ: usize = 2;
+
+/// Writes iNES images, for tests and tools that make cartridges instead of
+/// loading them. Starts out as NROM with 16 KiB of PRG ROM full of NOPs and
+/// 8 KiB of blank CHR ROM:
+///
+/// ```
+/// use nes_core::cartridge::RomBuilder;
+///
+/// let rom = RomBuilder::new()
+///     .program(0xC000, &[0x4C, 0x00, 0xC0]) // JMP $C000
+///     .rom()
+///     .unwrap();
+/// assert_eq!(rom.prg_rom[0x3FFC..0x3FFE], [0x00, 0xC0]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RomBuilder {
+    mapper: u8,
+    prg_rom: Vec<u8>,
+    chr_rom: Vec<u8>,
+    mirroring: Mirroring,
+    battery: bool,
+    trainer: Option<Vec<u8>>,
+    prg_ram: Option<(usize, usize)>,
+}
+
+impl RomBuilder {
+    pub fn new() -> Self {
+        RomBuilder {
+            mapper: 0,
+            prg_rom: vec![0xEA; PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![0; CHR_ROM_PAGE_SIZE],
+            mirroring: Mirroring::Horizontal,
+            battery: false,
+            trainer: None,
+            prg_ram: None,
+        }
+    }
+
+    pub fn mapper(mut self, mapper: u8) -> Self {
+        self.mapper = mapper;
+        self
+    }
+
+    /// The whole PRG ROM, a multiple of 16 KiB.
+    pub fn prg_rom(mut self, prg_rom: Vec<u8>) -> Self {
+        self.prg_rom = prg_rom;
+        self
+    }
+
+    /// The whole CHR ROM, a multiple of 8 KiB; empty for CHR RAM.
+    pub fn chr_rom(mut self, chr_rom: Vec<u8>) -> Self {
+        self.chr_rom = chr_rom;
+        self
+    }
+
+    /// Copies `code` to `origin` and points the reset vector at it. PRG ROM
+    /// is placed at the top of the address space, where NROM and the fixed
+    /// banks of the other mappers have it.
+    pub fn program(mut self, origin: u16, code: &[u8]) -> Self {
+        let start = (self.prg_rom.len() + origin as usize)
+            .checked_sub(0x10000)
+            .expect("program origin below the start of PRG ROM");
+        self.prg_rom[start..start + code.len()].copy_from_slice(code);
+        self.set_vector(RESET, origin);
+        self
+    }
+
+    /// Sets the NMI, reset and IRQ vectors at the end of PRG ROM.
+    pub fn vectors(mut self, nmi: u16, reset: u16, irq: u16) -> Self {
+        self.set_vector(NMI, nmi);
+        self.set_vector(RESET, reset);
+        self.set_vector(IRQ, irq);
+        self
+    }
+
+    /// Horizontal, vertical or four-screen, the ones a header can declare.
+    pub fn mirroring(mut self, mirroring: Mirroring) -> Self {
+        self.mirroring = mirroring;
+        self
+    }
+
+    pub fn battery(mut self, battery: bool) -> Self {
+        self.battery = battery;
+        self
+    }
+
+    /// The 512 bytes loaded at $7000.
+    pub fn trainer(mut self, trainer: Vec<u8>) -> Self {
+        self.trainer = Some(trainer);
+        self
+    }
+
+    /// Volatile and battery-backed PRG RAM sizes, powers of two from 128
+    /// bytes or 0. Only NES 2.0 headers have room for them, so this makes
+    /// the image one.
+    pub fn prg_ram(mut self, volatile: usize, nonvolatile: usize) -> Self {
+        self.prg_ram = Some((volatile, nonvolatile));
+        self
+    }
+
+    /// The image as it would be read from a `.nes` file.
+    pub fn build(&self) -> Vec<u8> {
+        let mut flags6 = self.mapper << 4;
+        flags6 |= match self.mirroring {
+            Mirroring::Vertical => 0b0001,
+            Mirroring::FourScreen => 0b1000,
+            _ => 0,
+        };
+        if self.battery {
+            flags6 |= 0b0010;
+        }
+        if self.trainer.is_some() {
+            flags6 |= 0b0100;
+        }
+        let mut flags7 = self.mapper & 0xF0;
+
+        let mut header = [0; 16];
+        header[..4].copy_from_slice(&NES_TAG);
+        header[4] = (self.prg_rom.len() / PRG_ROM_PAGE_SIZE) as u8;
+        header[5] = (self.chr_rom.len() / CHR_ROM_PAGE_SIZE) as u8;
+        if let Some((volatile, nonvolatile)) = self.prg_ram {
+            flags7 |= 0b1000;
+            header[10] = ram_shift(volatile) | ram_shift(nonvolatile) << 4;
+            if self.chr_rom.is_empty() {
+                header[11] = ram_shift(CHR_RAM_SIZE);
+            }
+        }
+        header[6] = flags6;
+        header[7] = flags7;
+
+        let mut raw = header.to_vec();
+        if let Some(trainer) = &self.trainer {
+            raw.extend(trainer);
+        }
+        raw.extend(&self.prg_rom);
+        raw.extend(&self.chr_rom);
+        raw
+    }
+
+    pub fn rom(&self) -> Result<Rom, RomError> {
+        Rom::new(&self.build())
+    }
+
+    fn set_vector(&mut self, offset: usize, address: u16) {
+        let index = self.prg_rom.len() - offset;
+        self.prg_rom[index..index + 2].copy_from_slice(&address.to_le_bytes());
+    }
+}
+
+impl Default for RomBuilder {
+    fn default() -> Self {
+        RomBuilder::new()
+    }
+}
+
+/// NES 2.0 RAM sizes are 64 << shift bytes, with 0 meaning none.
+fn ram_shift(size: usize) -> u8 {
+    match size {
+        0 => 0,
+        _ => (size / 64).trailing_zeros() as u8,
+    }
+}
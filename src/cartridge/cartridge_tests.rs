@@ -0,0 +1,170 @@
+use super::{Mirroring, Rom, RomBuilder, RomError, RomFormat, Timing};
+
+fn ines_header(prg_banks: u8, chr_banks: u8, flags6: u8, flags7: u8) -> Vec<u8> {
+    vec![
+        0x4E, 0x45, 0x53, 0x1A, prg_banks, chr_banks, flags6, flags7, 0, 0, 0, 0, 0, 0, 0, 0,
+    ]
+}
+
+fn image(header: Vec<u8>, trainer: usize, prg: usize, chr: usize) -> Vec<u8> {
+    let mut raw = header;
+    raw.extend(vec![0x7F; trainer]);
+    raw.extend(vec![0xEA; prg]);
+    raw.extend(vec![0x55; chr]);
+    raw
+}
+
+#[test]
+fn parses_ines_header() {
+    let raw = image(ines_header(2, 1, 0x31, 0x40), 0, 2 * 0x4000, 0x2000);
+    let rom = Rom::new(&raw).unwrap();
+
+    assert_eq!(rom.format, RomFormat::INes);
+    assert_eq!(rom.mapper, 0x43);
+    assert_eq!(rom.screen_mirroring, Mirroring::Vertical);
+    assert!(!rom.battery);
+    assert!(rom.trainer.is_none());
+    assert_eq!(rom.prg_rom.len(), 0x8000);
+    assert_eq!(rom.chr_rom.len(), 0x2000);
+    assert_eq!(rom.chr_ram_size, 0);
+    assert_eq!(rom.prg_ram_size, 0x2000);
+    assert_eq!(rom.timing, Timing::Ntsc);
+}
+
+#[test]
+fn ines_without_chr_rom_gets_chr_ram() {
+    let raw = image(ines_header(1, 0, 0x00, 0x00), 0, 0x4000, 0);
+    let rom = Rom::new(&raw).unwrap();
+
+    assert_eq!(rom.screen_mirroring, Mirroring::Horizontal);
+    assert!(rom.chr_rom.is_empty());
+    assert_eq!(rom.chr_ram_size, 0x2000);
+}
+
+#[test]
+fn ines_dirty_header_ignores_upper_mapper_nibble() {
+    let mut header = ines_header(1, 1, 0x10, 0x40);
+    header[12..16].copy_from_slice(b"Dude");
+    let rom = Rom::new(&image(header, 0, 0x4000, 0x2000)).unwrap();
+
+    assert_eq!(rom.mapper, 1);
+}
+
+#[test]
+fn reads_battery_four_screen_and_trainer() {
+    let raw = image(ines_header(1, 1, 0b1110, 0x00), 512, 0x4000, 0x2000);
+    let rom = Rom::new(&raw).unwrap();
+
+    assert!(rom.battery);
+    assert_eq!(rom.prg_ram_size, 0);
+    assert_eq!(rom.prg_nvram_size, 0x2000);
+    assert_eq!(rom.screen_mirroring, Mirroring::FourScreen);
+    assert_eq!(rom.trainer, Some(vec![0x7F; 512]));
+    assert!(rom.prg_rom.iter().all(|&byte| byte == 0xEA));
+    assert!(rom.chr_rom.iter().all(|&byte| byte == 0x55));
+}
+
+#[test]
+fn parses_nes2_header() {
+    let mut header = ines_header(2, 0, 0x42, 0x18);
+    header[8] = 0x31;
+    header[10] = 0x70;
+    header[11] = 0x07;
+    header[12] = 0x01;
+    let rom = Rom::new(&image(header, 0, 2 * 0x4000, 0)).unwrap();
+
+    assert_eq!(rom.format, RomFormat::Nes2);
+    assert_eq!(rom.mapper, 0x114);
+    assert_eq!(rom.submapper, 3);
+    assert_eq!(rom.prg_ram_size, 0);
+    assert_eq!(rom.prg_nvram_size, 64 << 7);
+    assert_eq!(rom.chr_ram_size, 64 << 7);
+    assert_eq!(rom.chr_nvram_size, 0);
+    assert_eq!(rom.timing, Timing::Pal);
+}
+
+#[test]
+fn nes2_exponent_multiplier_rom_size() {
+    let mut header = ines_header(0b0000_1101, 0, 0x00, 0x08);
+    header[9] = 0x0F;
+    let rom = Rom::new(&image(header, 0, 3 * 8, 0)).unwrap();
+
+    assert_eq!(rom.prg_rom.len(), 24);
+}
+
+#[test]
+fn rejects_short_file() {
+    assert_eq!(
+        Rom::new(&[0x4E, 0x45, 0x53]).err(),
+        Some(RomError::MissingHeader { length: 3 })
+    );
+}
+
+#[test]
+fn rejects_wrong_signature() {
+    let mut raw = image(ines_header(1, 1, 0, 0), 0, 0x4000, 0x2000);
+    raw[3] = 0x00;
+    assert_eq!(
+        Rom::new(&raw).err(),
+        Some(RomError::InvalidSignature([0x4E, 0x45, 0x53, 0x00]))
+    );
+}
+
+#[test]
+fn rejects_truncated_chr_rom() {
+    let raw = image(ines_header(1, 1, 0, 0), 0, 0x4000, 0x1000);
+    assert_eq!(
+        Rom::new(&raw).err(),
+        Some(RomError::Truncated {
+            section: "CHR ROM",
+            expected: 0x2000,
+            available: 0x1000
+        })
+    );
+}
+
+#[test]
+fn rejects_missing_prg_rom() {
+    let raw = image(ines_header(0, 1, 0, 0), 0, 0, 0x2000);
+    assert_eq!(Rom::new(&raw).err(), Some(RomError::EmptyPrgRom));
+}
+
+#[test]
+fn rejects_invalid_nes2_ram_shift() {
+    let mut header = ines_header(1, 0, 0x00, 0x08);
+    header[10] = 0x0F;
+    assert_eq!(
+        Rom::new(&image(header, 0, 0x4000, 0)).err(),
+        Some(RomError::InvalidRamSize {
+            field: "PRG RAM",
+            shift: 15
+        })
+    );
+}
+
+#[test]
+fn builder_writes_what_the_parser_reads() {
+    let rom = RomBuilder::new()
+        .mapper(0x14)
+        .prg_rom(vec![0x11; 2 * 0x4000])
+        .chr_rom(Vec::new())
+        .program(0x8000, &[0xA9, 0x01])
+        .mirroring(Mirroring::Vertical)
+        .battery(true)
+        .trainer(vec![0x7F; 512])
+        .prg_ram(0x2000, 0x800)
+        .rom()
+        .unwrap();
+
+    assert_eq!(rom.format, RomFormat::Nes2);
+    assert_eq!(rom.mapper, 0x14);
+    assert_eq!(rom.screen_mirroring, Mirroring::Vertical);
+    assert!(rom.battery);
+    assert_eq!(rom.trainer, Some(vec![0x7F; 512]));
+    assert_eq!(&rom.prg_rom[..3], &[0xA9, 0x01, 0x11]);
+    assert_eq!(&rom.prg_rom[0x7FFC..0x7FFE], &[0x00, 0x80]);
+    assert!(rom.chr_rom.is_empty());
+    assert_eq!(rom.chr_ram_size, 0x2000);
+    assert_eq!(rom.prg_ram_size, 0x2000);
+    assert_eq!(rom.prg_nvram_size, 0x800);
+}
@@ -1,17 +1,56 @@
+mod cycle;
 mod opcodes;
+mod trace;
+mod unofficial;
+
+use crate::bus::{Bus, CpuBus, Mem};
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+use std::collections::BTreeSet;
+use std::fmt;
+
+pub use opcodes::{opcode_table, Instruction, Opcode, OPCODES};
+pub use trace::trace;
 
 #[cfg(test)]
 mod cpu_tests;
-
-pub struct CPU<'a> {
+#[cfg(test)]
+mod nestest_tests;
+
+const NMI_VECTOR: u16 = 0xFFFA;
+const RESET_VECTOR: u16 = 0xFFFC;
+const IRQ_VECTOR: u16 = 0xFFFE;
+const INTERRUPT_CYCLES: u16 = 7;
+/// The stack lives in page 1, SP holds the low byte of the next free slot.
+const STACK: u16 = 0x0100;
+const STACK_RESET: u8 = 0xfd;
+/// Reports beyond this are dropped, so a game looping over an unstable opcode
+/// does not grow the queue while nobody takes it.
+pub const MAX_OPCODE_REPORTS: usize = 256;
+
+/// The 2A03's 6502 core on `bus`, the NES unless a test says otherwise.
+#[allow(clippy::upper_case_acronyms)]
+pub struct CPU<B = Bus> {
     pub accumulator: u8,
     pub status: Status,
     pub program_counter: u16,
-    pub stack_pointer: u16,
+    pub stack_pointer: u8,
     pub register_x: u8,
     pub register_y: u8,
-    memory: [u8; 0xFFFF],
-    opcode_table: [opcodes::Opcode<'a>; 0xFF],
+    pub bus: B,
+    pub cycles: u64,
+    halted: bool,
+    halt_on_brk: bool,
+    unstable_opcode_policy: UnstableOpcodePolicy,
+    core: CpuCore,
+    jammed: bool,
+    delayed_interrupt_disable: Option<bool>,
+    extra_cycles: u8,
+    breakpoints: BTreeSet<u16>,
+    cycle_limit: Option<u64>,
+    stack_diagnostics: bool,
+    opcode_reports: Vec<StopReason>,
+    /// Set by the instruction being executed, reported by `step`.
+    fault: Option<Fault>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -20,12 +59,154 @@ pub enum AddressingMode {
     Immediate,
     ZeroPage,
     ZeroPage_X,
+    ZeroPage_Y,
     Absolute,
     Absolute_X,
     Absolute_Y,
     Indirect_X,
     Indirect_Y,
-    None,
+    /// No operand, or registers only.
+    Implied,
+    /// The shifts and rotates working on A.
+    Accumulator,
+    /// Branches: a signed offset from the next instruction.
+    Relative,
+    /// JMP ($xxxx).
+    Indirect,
+}
+
+/// How the CPU executes instructions and clocks the rest of the system.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CpuCore {
+    /// Whole instructions at once, only accessing memory for their operands,
+    /// then the system is clocked for the cycles they took. The fastest.
+    Instruction,
+    /// One bus access per cycle in the order of the 2A03, with the dummy
+    /// reads and writes of indexed addressing, read-modify-write and stack
+    /// instructions, clocking the system after each of them.
+    CycleAccurate,
+}
+
+/// What the CPU does with opcodes whose result depends on the chip (XAA,
+/// LXA, SHA, SHX, SHY, TAS) and with the KIL/JAM opcodes that lock it up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnstableOpcodePolicy {
+    /// Emulate the most common behaviour; JAM freezes the CPU until reset.
+    Execute,
+    /// Like `Execute`, and queue a report of the opcode and its address for
+    /// `take_opcode_reports`.
+    Log,
+    /// Stop `run` with PC left on the opcode.
+    Halt,
+}
+
+/// Why `step`, `run` or `run_with_callback` returned. Every reason leaves
+/// the CPU able to continue with another `step` or `run`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason {
+    /// `step` executed an instruction, or serviced an interrupt, taking
+    /// `cycles` cycles. `run` never returns this.
+    Stepped { cycles: u16 },
+    /// A BRK at `pc` with `set_halt_on_brk`; PC is left after it.
+    Brk { pc: u16 },
+    /// A JAM opcode at `pc` locked the CPU up. Stepping it again refetches
+    /// the opcode, only `reset` gets the CPU going.
+    Jammed { pc: u16, opcode: u8 },
+    /// An unstable opcode at `pc` with `UnstableOpcodePolicy::Halt`; PC is
+    /// left on it.
+    UnstableOpcode { pc: u16, opcode: u8 },
+    /// PC reached an address given to `add_breakpoint`.
+    Breakpoint { pc: u16 },
+    /// The cycles given to `set_cycle_budget` have been executed.
+    CycleBudgetExhausted { cycles: u64 },
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StopReason::Stepped { cycles } => write!(f, "stepped {} cycles", cycles),
+            StopReason::Brk { pc } => write!(f, "BRK at ${:04X}", pc),
+            StopReason::Jammed { pc, opcode } => {
+                write!(f, "jammed by opcode ${:02X} at ${:04X}", opcode, pc)
+            }
+            StopReason::UnstableOpcode { pc, opcode } => {
+                write!(f, "unstable opcode ${:02X} at ${:04X}", opcode, pc)
+            }
+            StopReason::Breakpoint { pc } => write!(f, "breakpoint at ${:04X}", pc),
+            StopReason::CycleBudgetExhausted { cycles } => {
+                write!(f, "cycle budget exhausted at cycle {}", cycles)
+            }
+        }
+    }
+}
+
+/// An instruction, or the interrupt sequence (reported as opcode $00, which
+/// shares it), did something the program most likely did not mean, or that
+/// the emulator cannot do. The instruction is completed as well as possible,
+/// so the machine stays usable and can be resumed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CpuError {
+    /// A push wrapped SP from $00 to $FF; only with `set_stack_diagnostics`.
+    StackOverflow { pc: u16, opcode: u8 },
+    /// A pull wrapped SP from $FF to $00; only with `set_stack_diagnostics`.
+    StackUnderflow { pc: u16, opcode: u8 },
+    /// The opcode table gives the instruction no operand to address.
+    InvalidAddressingMode { pc: u16, opcode: u8 },
+}
+
+impl CpuError {
+    /// Address of the faulting instruction.
+    pub fn pc(&self) -> u16 {
+        match *self {
+            CpuError::StackOverflow { pc, .. }
+            | CpuError::StackUnderflow { pc, .. }
+            | CpuError::InvalidAddressingMode { pc, .. } => pc,
+        }
+    }
+
+    pub fn opcode(&self) -> u8 {
+        match *self {
+            CpuError::StackOverflow { opcode, .. }
+            | CpuError::StackUnderflow { opcode, .. }
+            | CpuError::InvalidAddressingMode { opcode, .. } => opcode,
+        }
+    }
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let problem = match self {
+            CpuError::StackOverflow { .. } => "stack overflow",
+            CpuError::StackUnderflow { .. } => "stack underflow",
+            CpuError::InvalidAddressingMode { .. } => "invalid addressing mode",
+        };
+        write!(
+            f,
+            "{} in opcode ${:02X} at ${:04X}",
+            problem,
+            self.opcode(),
+            self.pc()
+        )
+    }
+}
+
+impl std::error::Error for CpuError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Fault {
+    StackOverflow,
+    StackUnderflow,
+    InvalidAddressingMode,
+}
+
+impl Fault {
+    fn error(self, pc: u16, opcode: u8) -> CpuError {
+        match self {
+            Fault::StackOverflow => CpuError::StackOverflow { pc, opcode },
+            Fault::StackUnderflow => CpuError::StackUnderflow { pc, opcode },
+            Fault::InvalidAddressingMode => CpuError::InvalidAddressingMode { pc, opcode },
+        }
+    }
 }
 
 /// # Status Register (P) http://wiki.nesdev.com/w/index.php/Status_flags
@@ -62,12 +243,16 @@ impl Status {
         self.status &= !flag;
     }
 
-    fn get(&self) -> u8 {
+    /// The flags as a byte. B and bit 5 only exist on the stack, so they
+    /// read as 0 here.
+    pub fn get(&self) -> u8 {
         self.status
     }
 
-    fn insert(&mut self, data: u8) {
-        self.status = data;
+    /// Replaces all flags, e.g. to set up a test case. B and bit 5 are
+    /// dropped.
+    pub fn insert(&mut self, data: u8) {
+        self.status = data & !(Status::BREAK | Status::BREAK2);
     }
 
     fn contains(&self, flag: u8) -> bool {
@@ -75,53 +260,120 @@ impl Status {
     }
 }
 
-impl<'a> CPU<'a> {
-    pub fn new() -> Self {
-        let opcodes = CPU::create_opcode_table();
+impl<B: CpuBus> Mem for CPU<B> {
+    fn mem_read(&mut self, address: u16) -> u8 {
+        self.bus.mem_read(address)
+    }
 
-        CPU {
-            accumulator: 0,
-            status: Status { status: 0 },
-            program_counter: 0,
-            stack_pointer: 0x01fd,
-            register_x: 0,
-            register_y: 0,
-            memory: [0; 0xFFFF],
-            opcode_table: opcodes,
-        }
+    fn mem_write(&mut self, address: u16, data: u8) {
+        self.bus.mem_write(address, data)
     }
+}
 
-    pub fn mem_read(&self, address: u16) -> u8 {
-        self.memory[address as usize]
+impl CPU {
+    pub fn new() -> Self {
+        CPU::with_bus(Bus::new())
     }
 
-    pub fn mem_read_u16(&self, position: u16) -> u16 {
-        let lo = self.mem_read(position) as u16;
-        let hi = self.mem_read(position + 1) as u16;
+    /// Snapshot of the whole machine, see `savestate` for the format.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new();
+        state.write_header(self.bus.cartridge_fingerprint());
+        self.save_components(&mut state);
+        state.into_bytes()
+    }
 
-        (hi << 8) | lo
+    /// Restores a snapshot taken by `save_state`. A state of another format
+    /// version or another cartridge, or a damaged one, is refused and leaves
+    /// the machine as it was.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), SaveStateError> {
+        let mut state = StateReader::new(data);
+        state.read_header(self.bus.cartridge_fingerprint())?;
+
+        let mut backup = StateWriter::new();
+        self.save_components(&mut backup);
+        let result = self
+            .load_components(&mut state)
+            .and_then(|_| state.finish());
+        if result.is_err() {
+            let backup = backup.into_bytes();
+            self.load_components(&mut StateReader::new(&backup))
+                .expect("a state saved by this build loads");
+        }
+        result
     }
 
-    pub fn mem_write(&mut self, address: u16, data: u8) {
-        self.memory[address as usize] = data;
+    fn save_components(&self, state: &mut StateWriter) {
+        state.write_u8(self.accumulator);
+        state.write_u8(self.status.get());
+        state.write_u16(self.program_counter);
+        state.write_u8(self.stack_pointer);
+        state.write_u8(self.register_x);
+        state.write_u8(self.register_y);
+        state.write_u64(self.cycles);
+        state.write_bool(self.halted);
+        state.write_bool(self.jammed);
+        state.write_option_u8(self.delayed_interrupt_disable.map(|flag| flag as u8));
+        self.bus.save_state(state);
     }
 
-    pub fn mem_write_u16(&mut self, address: u16, data: u16) {
-        let hi = (data >> 8) as u8;
-        let lo = (data & 0xFF) as u8;
-        self.mem_write(address, lo);
-        self.mem_write(address + 1, hi);
+    fn load_components(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.accumulator = state.read_u8()?;
+        self.status.status = state.read_u8()?;
+        self.program_counter = state.read_u16()?;
+        self.stack_pointer = state.read_u8()?;
+        self.register_x = state.read_u8()?;
+        self.register_y = state.read_u8()?;
+        self.cycles = state.read_u64()?;
+        self.halted = state.read_bool()?;
+        self.jammed = state.read_bool()?;
+        self.delayed_interrupt_disable = match state.read_option_u8()? {
+            None => None,
+            Some(0) => Some(false),
+            Some(1) => Some(true),
+            Some(_) => return Err(SaveStateError::Invalid("interrupt disable delay")),
+        };
+        self.bus.load_state(state)
+    }
+}
+
+impl<B: CpuBus> CPU<B> {
+    pub fn with_bus(bus: B) -> Self {
+        CPU {
+            accumulator: 0,
+            status: Status { status: 0 },
+            program_counter: 0,
+            stack_pointer: STACK_RESET,
+            register_x: 0,
+            register_y: 0,
+            bus,
+            cycles: 0,
+            halted: false,
+            halt_on_brk: false,
+            unstable_opcode_policy: UnstableOpcodePolicy::Execute,
+            core: CpuCore::Instruction,
+            jammed: false,
+            delayed_interrupt_disable: None,
+            extra_cycles: 0,
+            breakpoints: BTreeSet::new(),
+            cycle_limit: None,
+            stack_diagnostics: false,
+            opcode_reports: Vec::new(),
+            fault: None,
+        }
     }
 
-    pub fn load_and_run(&mut self, program: Vec<u8>) {
+    pub fn load_and_run(&mut self, program: Vec<u8>) -> Result<StopReason, CpuError> {
         self.load(program);
         self.reset();
-        self.run();
+        self.run()
     }
 
     pub fn load(&mut self, program: Vec<u8>) {
-        self.memory[0x0600..(0x0600 + program.len())].copy_from_slice(&program[..]);
-        self.mem_write_u16(0xFFFC, 0x0600);
+        for (i, byte) in program.iter().enumerate() {
+            self.mem_write(0x0600 + i as u16, *byte);
+        }
+        self.mem_write_u16(RESET_VECTOR, 0x0600);
     }
 
     pub fn reset(&mut self) {
@@ -129,83 +381,298 @@ impl<'a> CPU<'a> {
         self.register_x = 0;
         self.register_y = 0;
         self.status.reset(0xff);
-        self.stack_pointer = 0x01fd;
+        self.stack_pointer = STACK_RESET;
+        self.halted = false;
+        self.jammed = false;
+        self.delayed_interrupt_disable = None;
+        self.bus.reset();
 
-        self.program_counter = self.mem_read_u16(0xFFFC);
+        self.program_counter = self.mem_read_u16(RESET_VECTOR);
+
+        // the reset sequence takes 7 cycles before the first opcode fetch
+        self.cycles = 7;
+        self.bus.tick(7);
     }
 
-    pub fn run(&mut self) {
-        self.run_with_callback(|_| {});
+    pub fn run(&mut self) -> Result<StopReason, CpuError> {
+        self.run_with_callback(|_| {})
     }
 
-    pub fn run_with_callback<F>(&mut self, mut callback: F)
+    /// Steps until one of the `StopReason`s other than `Stepped` or an error
+    /// occurs, calling `callback` before every instruction. A breakpoint on
+    /// the first instruction does not stop the run, so that it can continue
+    /// from the breakpoint it stopped at.
+    pub fn run_with_callback<F>(&mut self, mut callback: F) -> Result<StopReason, CpuError>
     where
-        F: FnMut(&mut CPU),
+        F: FnMut(&mut Self),
     {
-        let mut continue_execution = true;
-        while continue_execution {
+        let mut first = true;
+        loop {
+            if !first && self.breakpoints.contains(&self.program_counter) {
+                return Ok(StopReason::Breakpoint {
+                    pc: self.program_counter,
+                });
+            }
+            first = false;
+            if let Some(limit) = self.cycle_limit {
+                if self.cycles >= limit {
+                    self.cycle_limit = None;
+                    return Ok(StopReason::CycleBudgetExhausted {
+                        cycles: self.cycles,
+                    });
+                }
+            }
+
             callback(self);
-            let opcode_number = self.mem_read(self.program_counter);
-            let opcode = self.opcode_table[opcode_number as usize];
-            self.program_counter += 1;
+            match self.step()? {
+                StopReason::Stepped { .. } => {}
+                reason => return Ok(reason),
+            }
+        }
+    }
+
+    /// Makes `run` stop at the first instruction at `address`.
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    /// Returns `false` if there was no breakpoint at `address`.
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    pub fn has_breakpoint(&self, address: u16) -> bool {
+        self.breakpoints.contains(&address)
+    }
+
+    /// Makes `run` stop once about `cycles` more cycles have been executed,
+    /// finishing the instruction that crosses the limit. `None` removes the
+    /// budget; it is also removed when it runs out.
+    pub fn set_cycle_budget(&mut self, cycles: Option<u64>) {
+        self.cycle_limit = cycles.map(|cycles| self.cycles + cycles);
+    }
+
+    /// Reports the stack pointer wrapping around page 1 as a `CpuError` from
+    /// `step` and `run`. The 2A03 wraps silently and some games rely on it,
+    /// so this is off by default; it helps find runaway pushes and pulls.
+    pub fn set_stack_diagnostics(&mut self, enabled: bool) {
+        self.stack_diagnostics = enabled;
+    }
+
+    pub fn stack_diagnostics(&self) -> bool {
+        self.stack_diagnostics
+    }
+
+    /// Stops `run` when a BRK is executed instead of jumping through the IRQ
+    /// vector. Meant for unit tests that end their programs with 0x00.
+    pub fn set_halt_on_brk(&mut self, halt: bool) {
+        self.halt_on_brk = halt;
+    }
+
+    pub fn set_unstable_opcode_policy(&mut self, policy: UnstableOpcodePolicy) {
+        self.unstable_opcode_policy = policy;
+    }
 
-            continue_execution = self.interpret(&opcode);
+    /// The `UnstableOpcode` and `Jammed` reports queued under
+    /// `UnstableOpcodePolicy::Log` since the last call, oldest first. Only the
+    /// first `MAX_OPCODE_REPORTS` are kept until they are taken.
+    pub fn take_opcode_reports(&mut self) -> Vec<StopReason> {
+        std::mem::take(&mut self.opcode_reports)
+    }
+
+    /// Switches the execution core, which takes effect with the next
+    /// instruction. The state of the machine is shared by both.
+    pub fn set_core(&mut self, core: CpuCore) {
+        self.core = core;
+    }
+
+    pub fn core(&self) -> CpuCore {
+        self.core
+    }
+
+    /// `true` after `step` stopped on a BRK with `set_halt_on_brk`, or on an
+    /// unstable opcode with `UnstableOpcodePolicy::Halt`.
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// `true` while a JAM opcode keeps the CPU locked up.
+    pub fn is_jammed(&self) -> bool {
+        self.jammed
+    }
+
+    /// Executes one instruction, or services a pending interrupt, and clocks
+    /// the rest of the system for the cycles it took. Returns those cycles as
+    /// `StopReason::Stepped`, or why the instruction stopped the CPU.
+    pub fn step(&mut self) -> Result<StopReason, CpuError> {
+        let pc = self.program_counter;
+        let mut opcode_number = 0x00;
+        self.halted = false;
+        let cycle_accurate = self.core == CpuCore::CycleAccurate;
+        let start = self.bus.cycles();
+        let irq_masked = self
+            .delayed_interrupt_disable
+            .take()
+            .unwrap_or_else(|| self.status.contains(Status::INTERRUPT_DISABLE));
+
+        // a jammed CPU keeps refetching the JAM opcode and ignores interrupts
+        let vector = if self.bus.poll_nmi_status() && !self.jammed {
+            Some(NMI_VECTOR)
+        } else if !self.jammed && self.bus.irq_asserted() && !irq_masked {
+            Some(IRQ_VECTOR)
+        } else {
+            None
+        };
+        let mut cycles = if let Some(vector) = vector {
+            if cycle_accurate {
+                self.interrupt_cycles(vector);
+            } else {
+                self.interrupt(vector);
+            }
+            INTERRUPT_CYCLES
+        } else {
+            opcode_number = if cycle_accurate {
+                self.read_opcode()
+            } else {
+                let code = self.mem_read(self.program_counter);
+                self.program_counter = self.program_counter.wrapping_add(1);
+                code
+            };
+            let opcode = &OPCODES[opcode_number as usize];
+
+            let interrupt_disable = self.status.contains(Status::INTERRUPT_DISABLE);
+            self.extra_cycles = 0;
+            self.halted = !if cycle_accurate {
+                self.interpret_cycles(opcode)
+            } else {
+                self.interpret(opcode)
+            };
+
+            // CLI, SEI and PLP change the flag after the interrupt poll of
+            // their last cycle, so the old value holds for one more instruction
+            if let Instruction::Cli | Instruction::Sei | Instruction::Plp = opcode.instruction {
+                self.delayed_interrupt_disable = Some(interrupt_disable);
+            }
+            (opcode.cycles + self.extra_cycles) as u16
+        };
+        if cycle_accurate {
+            // the core clocked the bus for every access it made
+            cycles = (self.bus.cycles() - start) as u16;
+        } else {
+            self.bus.tick(cycles);
         }
+
+        let stall = self.bus.take_dma_stall_cycles();
+        if stall > 0 {
+            self.bus.tick(stall);
+            cycles += stall;
+        }
+
+        self.cycles += cycles as u64;
+
+        if let Some(fault) = self.fault.take() {
+            return Err(fault.error(pc, opcode_number));
+        }
+        let opcode = opcode_number;
+        Ok(match OPCODES[opcode as usize].instruction {
+            _ if self.jammed => StopReason::Jammed { pc, opcode },
+            _ if !self.halted => StopReason::Stepped { cycles },
+            Instruction::Brk => StopReason::Brk { pc },
+            Instruction::Jam => StopReason::Jammed { pc, opcode },
+            _ => StopReason::UnstableOpcode { pc, opcode },
+        })
+    }
+
+    /// Hardware interrupt sequence: pushes PC and P with B clear, sets I and
+    /// jumps through `vector`. BRK shares it but pushes B set, see `brk`.
+    fn interrupt(&mut self, vector: u16) {
+        self.push_u16(self.program_counter);
+        self.push((self.status.get() | Status::BREAK2) & !Status::BREAK);
+        self.status.set(Status::INTERRUPT_DISABLE);
+
+        self.program_counter = self.mem_read_u16(vector);
     }
 
-    fn get_operand_address(&self, mode: AddressingMode) -> u16 {
+    /// Resolves the effective address of the operand, together with whether
+    /// indexing crossed into another page.
+    fn get_operand_address(&mut self, mode: AddressingMode) -> (u16, bool) {
         match mode {
-            AddressingMode::Immediate => self.program_counter,
-            AddressingMode::ZeroPage => self.mem_read(self.program_counter) as u16,
+            AddressingMode::Immediate => (self.program_counter, false),
+            AddressingMode::ZeroPage => (self.mem_read(self.program_counter) as u16, false),
             AddressingMode::ZeroPage_X => {
                 let position = self.mem_read(self.program_counter);
-                let address = position.wrapping_add(self.register_x) as u16;
-                address
+                (position.wrapping_add(self.register_x) as u16, false)
             }
-            AddressingMode::Absolute => self.mem_read_u16(self.program_counter),
+            AddressingMode::ZeroPage_Y => {
+                let position = self.mem_read(self.program_counter);
+                (position.wrapping_add(self.register_y) as u16, false)
+            }
+            AddressingMode::Absolute => (self.mem_read_u16(self.program_counter), false),
             AddressingMode::Absolute_X => {
                 let base = self.mem_read_u16(self.program_counter);
                 let address = base.wrapping_add(self.register_x as u16);
-                address
+                (address, page_crossed(base, address))
             }
             AddressingMode::Absolute_Y => {
                 let base = self.mem_read_u16(self.program_counter);
                 let address = base.wrapping_add(self.register_y as u16);
-                address
+                (address, page_crossed(base, address))
             }
             AddressingMode::Indirect_X => {
                 let base = self.mem_read(self.program_counter);
 
-                let ptr = (base as u8).wrapping_add(self.register_x);
+                let ptr = base.wrapping_add(self.register_x);
                 let lo = self.mem_read(ptr as u16);
                 let hi = self.mem_read(ptr.wrapping_add(1) as u16);
-                (hi as u16) << 8 | (lo as u16)
+                ((hi as u16) << 8 | (lo as u16), false)
             }
             AddressingMode::Indirect_Y => {
                 let base = self.mem_read(self.program_counter);
 
                 let lo = self.mem_read(base as u16);
-                let hi = self.mem_read((base as u8).wrapping_add(1) as u16);
+                let hi = self.mem_read(base.wrapping_add(1) as u16);
                 let deref_base = (hi as u16) << 8 | (lo as u16);
-                let deref = deref_base.wrapping_add(self.register_y as u16);
-                deref
+                let address = deref_base.wrapping_add(self.register_y as u16);
+                (address, page_crossed(deref_base, address))
             }
-            AddressingMode::None => {
-                panic!("Wrong addressing mode!");
+            AddressingMode::Implied
+            | AddressingMode::Accumulator
+            | AddressingMode::Relative
+            | AddressingMode::Indirect => {
+                self.fault = Some(Fault::InvalidAddressingMode);
+                (0, false)
             }
         }
     }
 
+    /// Reads the operand of a read instruction, which takes an extra cycle
+    /// when indexing crosses a page boundary.
+    fn read_operand(&mut self, mode: AddressingMode) -> u8 {
+        let (address, page_crossed) = self.get_operand_address(mode);
+        if page_crossed {
+            self.extra_cycles += 1;
+        }
+        self.mem_read(address)
+    }
+
     fn increment_program_counter(&mut self, step: u8) {
-        self.program_counter += step as u16 - 1;
+        self.program_counter = self.program_counter.wrapping_add(step as u16 - 1);
     }
 
     fn push(&mut self, data: u8) {
-        self.mem_write(self.stack_pointer, data);
-        self.stack_pointer -= 1;
-        if self.stack_pointer < 0x0100 {
-            panic!("Stack overflow");
+        self.mem_write(STACK | self.stack_pointer as u16, data);
+        if self.stack_pointer == 0x00 && self.stack_diagnostics {
+            self.fault = Some(Fault::StackOverflow);
         }
+        self.stack_pointer = self.stack_pointer.wrapping_sub(1);
     }
 
     fn push_u16(&mut self, data: u16) {
@@ -216,13 +683,11 @@ impl<'a> CPU<'a> {
     }
 
     fn pop(&mut self) -> u8 {
-        self.stack_pointer += 1;
-        if self.stack_pointer > 0x01ff {
-            panic!("Read from empty stack");
+        if self.stack_pointer == 0xff && self.stack_diagnostics {
+            self.fault = Some(Fault::StackUnderflow);
         }
-        let data = self.mem_read(self.stack_pointer);
-
-        data
+        self.stack_pointer = self.stack_pointer.wrapping_add(1);
+        self.mem_read(STACK | self.stack_pointer as u16)
     }
 
     fn pop_u16(&mut self) -> u16 {
@@ -232,3 +697,13 @@ impl<'a> CPU<'a> {
         hi << 8 | lo
     }
 }
+
+impl Default for CPU {
+    fn default() -> Self {
+        CPU::new()
+    }
+}
+
+fn page_crossed(base: u16, address: u16) -> bool {
+    base & 0xFF00 != address & 0xFF00
+}
@@ -1,17 +1,32 @@
-use super::{Status, CPU};
+use super::{
+    AddressingMode, CpuCore, CpuError, Instruction, Status, StopReason, UnstableOpcodePolicy, CPU,
+    OPCODES,
+};
+use crate::bus::{FlatBus, IrqSource, Mem};
+
+impl CPU {
+    /// A CPU that stops at BRK, so test programs can end with 0x00.
+    pub fn debug_new() -> Self {
+        let mut cpu = CPU::new();
+        cpu.set_halt_on_brk(true);
+        cpu
+    }
 
-impl<'a> CPU<'a> {
     pub fn debug_load_and_run(&mut self, program: Vec<u8>) {
         self.load(program);
         self.program_counter = self.mem_read_u16(0xFFFC);
-        self.run();
+        self.run().unwrap();
     }
 }
 
+fn stepped(cycles: u16) -> Result<StopReason, CpuError> {
+    Ok(StopReason::Stepped { cycles })
+}
+
 #[test]
 fn lda_immidiate_load_data_accumulator() {
-    let mut cpu = CPU::new();
-    cpu.load_and_run(vec![0xa9, 0x05, 0x00]);
+    let mut cpu = CPU::debug_new();
+    cpu.load_and_run(vec![0xa9, 0x05, 0x00]).unwrap();
     assert_eq!(cpu.accumulator, 0x05);
     assert!(cpu.status.get() & Status::ZERO == 0b00);
     assert!(cpu.status.get() & Status::NEGATIV == 0);
@@ -19,8 +34,8 @@ fn lda_immidiate_load_data_accumulator() {
 
 #[test]
 fn ldx_immidiate_load_data_register_x() {
-    let mut cpu = CPU::new();
-    cpu.load_and_run(vec![0xa2, 0x05, 0x00]);
+    let mut cpu = CPU::debug_new();
+    cpu.load_and_run(vec![0xa2, 0x05, 0x00]).unwrap();
     assert_eq!(cpu.register_x, 0x05);
     assert!(cpu.status.get() & Status::ZERO == 0b00);
     assert!(cpu.status.get() & Status::NEGATIV == 0);
@@ -28,8 +43,8 @@ fn ldx_immidiate_load_data_register_x() {
 
 #[test]
 fn ldy_immidiate_load_data_register_y() {
-    let mut cpu = CPU::new();
-    cpu.load_and_run(vec![0xa0, 0x05, 0x00]);
+    let mut cpu = CPU::debug_new();
+    cpu.load_and_run(vec![0xa0, 0x05, 0x00]).unwrap();
     assert_eq!(cpu.register_y, 0x05);
     assert!(cpu.status.get() & Status::ZERO == 0b00);
     assert!(cpu.status.get() & Status::NEGATIV == 0);
@@ -37,82 +52,83 @@ fn ldy_immidiate_load_data_register_y() {
 
 #[test]
 fn lda_zero_flag() {
-    let mut cpu = CPU::new();
-    cpu.load_and_run(vec![0xa9, 0x00, 0x00]);
+    let mut cpu = CPU::debug_new();
+    cpu.load_and_run(vec![0xa9, 0x00, 0x00]).unwrap();
     assert!(cpu.status.get() & Status::ZERO == 0b10);
 }
 
 #[test]
 fn tax_move_a_to_x() {
-    let mut cpu = CPU::new();
-    cpu.load_and_run(vec![0xa9, 0x0a, 0xaa, 0x00]);
+    let mut cpu = CPU::debug_new();
+    cpu.load_and_run(vec![0xa9, 0x0a, 0xaa, 0x00]).unwrap();
 
     assert_eq!(cpu.register_x, 10);
 }
 
 #[test]
 fn tay_move_a_to_y() {
-    let mut cpu = CPU::new();
-    cpu.load_and_run(vec![0xa9, 0x0a, 0xa8, 0x00]);
+    let mut cpu = CPU::debug_new();
+    cpu.load_and_run(vec![0xa9, 0x0a, 0xa8, 0x00]).unwrap();
 
     assert_eq!(cpu.register_y, 10);
 }
 
 #[test]
 fn inx_increment_x() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.register_x = 0;
-    cpu.load_and_run(vec![0xe8, 0x00]);
+    cpu.load_and_run(vec![0xe8, 0x00]).unwrap();
 
     assert_eq!(cpu.register_x, 1);
 }
 
 #[test]
 fn inx_overflow() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     let mut program = vec![0xe8; 260];
     program.push(0x00);
-    cpu.load_and_run(program);
+    cpu.load_and_run(program).unwrap();
 
     assert_eq!(cpu.register_x, 4)
 }
 
 #[test]
 fn iny_overflow() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     let mut program = vec![0xc8; 260];
     program.push(0x00);
-    cpu.load_and_run(program);
+    cpu.load_and_run(program).unwrap();
 
     assert_eq!(cpu.register_y, 4)
 }
 
 #[test]
 fn registers_set_to_0_after_reset() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.accumulator = 5;
     cpu.register_x = 6;
     cpu.register_y = 7;
     cpu.program_counter = 8;
-    cpu.load_and_run(vec![0x00]);
+    cpu.load_and_run(vec![0x00]).unwrap();
 
     assert_eq!(cpu.accumulator, 0);
     assert_eq!(cpu.register_x, 0);
     assert_eq!(cpu.register_y, 0);
-    assert_eq!(cpu.program_counter, 0x8001);
+    assert_eq!(cpu.program_counter, 0x0601);
 }
 
 #[test]
 fn test_5_ops_working_together() {
-    let mut cpu = CPU::new();
-    cpu.load_and_run(vec![0xa9, 0xc0, 0xaa, 0xe8, 0x00]);
+    let mut cpu = CPU::debug_new();
+    cpu.load_and_run(vec![0xa9, 0xc0, 0xaa, 0xe8, 0x00])
+        .unwrap();
 
     assert_eq!(cpu.register_x, 0xc1)
 }
 
 #[test]
 fn adc_basic() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
 
     cpu.debug_load_and_run(vec![0xa9, 0x01, 0x69, 0x02, 0x00]);
     assert_eq!(cpu.accumulator, 3);
@@ -125,7 +141,7 @@ fn adc_basic() {
 
 #[test]
 fn adc_overflow_and_carry_flag() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
 
     cpu.debug_load_and_run(vec![0xa9, 0x7F, 0x69, 0x01, 0x00]);
     assert_eq!(cpu.accumulator, 128);
@@ -140,7 +156,7 @@ fn adc_overflow_and_carry_flag() {
 
 #[test]
 fn adc_overflow() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
 
     cpu.accumulator = 0xff;
     cpu.debug_load_and_run(vec![0x69, 0x01, 0x00]);
@@ -150,36 +166,38 @@ fn adc_overflow() {
 
 #[test]
 fn and_same_values() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.debug_load_and_run(vec![0xa9, 0x11, 0x29, 0x11, 0x00]);
     assert_eq!(cpu.accumulator, 0x11);
 }
 
 #[test]
 fn and_different_values() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.debug_load_and_run(vec![0xa9, 0x11, 0x29, 0x01, 0x00]);
     assert_eq!(cpu.accumulator, 0x01);
 }
 
 #[test]
 fn asl_number_in_accumulator() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.debug_load_and_run(vec![0xa9, 0x08, 0x0a, 0x00]);
     assert_eq!(cpu.accumulator, 0x10);
 }
 
 #[test]
 fn asl_number_in_memory() {
-    let mut cpu = CPU::new();
-    cpu.mem_write(0x00, 0x08);
+    let mut cpu = CPU::debug_new();
+    cpu.mem_write(0x00, 0x88);
     cpu.debug_load_and_run(vec![0x06, 0x00, 0x00]);
-    assert_eq!(cpu.accumulator, 0x10);
+    assert_eq!(cpu.mem_read(0x00), 0x10);
+    assert_eq!(cpu.accumulator, 0x00);
+    assert_eq!(cpu.status.get(), Status::CARRY);
 }
 
 #[test]
 fn asl_carry_and_negative_flag() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.debug_load_and_run(vec![0xa9, 0xFF, 0x0a, 0x00]);
     assert_eq!(cpu.accumulator, 0xFE);
     assert_eq!(cpu.status.get(), Status::NEGATIV | Status::CARRY);
@@ -187,7 +205,7 @@ fn asl_carry_and_negative_flag() {
 
 #[test]
 fn clc_clear_carry_flag() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.status.set(Status::CARRY);
     cpu.debug_load_and_run(vec![0x18, 0x00]);
     assert_eq!(cpu.status.get(), 0x00);
@@ -195,14 +213,14 @@ fn clc_clear_carry_flag() {
 
 #[test]
 fn sec_set_carry_flag() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.debug_load_and_run(vec![0x38, 0x00]);
     assert_eq!(cpu.status.get(), Status::CARRY);
 }
 
 #[test]
 fn cld_clear_decimal_flag() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.status.set(Status::DECIMAL_MODE);
     cpu.debug_load_and_run(vec![0xD8, 0x00]);
     assert_eq!(cpu.status.get(), 0x00);
@@ -210,14 +228,14 @@ fn cld_clear_decimal_flag() {
 
 #[test]
 fn sed_set_decimal_flag() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.debug_load_and_run(vec![0xF8, 0x00]);
     assert_eq!(cpu.status.get(), Status::DECIMAL_MODE);
 }
 
 #[test]
 fn cli_clear_interrupt_disable_flag() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.status.set(Status::INTERRUPT_DISABLE);
     cpu.debug_load_and_run(vec![0x58, 0x00]);
     assert_eq!(cpu.status.get(), 0x00);
@@ -225,14 +243,14 @@ fn cli_clear_interrupt_disable_flag() {
 
 #[test]
 fn sei_set_interrupt_disable_flag() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.debug_load_and_run(vec![0x78, 0x00]);
     assert_eq!(cpu.status.get(), Status::INTERRUPT_DISABLE);
 }
 
 #[test]
 fn clv_clear_overflow_flag() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.status.set(Status::OVERFLOW);
     cpu.debug_load_and_run(vec![0xB8, 0x00]);
     assert_eq!(cpu.status.get(), 0x00);
@@ -240,51 +258,51 @@ fn clv_clear_overflow_flag() {
 
 #[test]
 fn cmp_with_smaller_number() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.debug_load_and_run(vec![0xa9, 0x05, 0xc9, 0x04, 0x00]);
     assert_eq!(cpu.status.get(), Status::CARRY);
 }
 
 #[test]
 fn cmp_with_bigger_number() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.debug_load_and_run(vec![0xa9, 0x05, 0xc9, 0x06, 0x00]);
     assert_eq!(cpu.status.get(), Status::NEGATIV);
 }
 
 #[test]
 fn cmp_with_same_number() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.debug_load_and_run(vec![0xa9, 0x05, 0xc9, 0x05, 0x00]);
     assert_eq!(cpu.status.get(), Status::ZERO | Status::CARRY);
 }
 
 #[test]
 fn cpx_with_bigger_number() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.debug_load_and_run(vec![0xa2, 0x05, 0xe0, 0x06, 0x00]);
     assert_eq!(cpu.status.get(), Status::NEGATIV);
 }
 
 #[test]
 fn cpy_with_bigger_number() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.debug_load_and_run(vec![0xa2, 0x05, 0xc0, 0x06, 0x00]);
     assert_eq!(cpu.status.get(), Status::NEGATIV);
 }
 
 #[test]
 fn dec_decrement_value_in_memory() {
-    let mut cpu = CPU::new();
-    cpu.memory[0x02] = 5;
+    let mut cpu = CPU::debug_new();
+    cpu.mem_write(0x02, 5);
     cpu.debug_load_and_run(vec![0xc6, 0x02, 0x00]);
-    assert_eq!(cpu.memory[0x02], 4);
+    assert_eq!(cpu.mem_read(0x02), 4);
     assert_eq!(cpu.status.get(), 0);
 }
 
 #[test]
 fn dex_decrement_register_x() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.register_x = 1;
     cpu.debug_load_and_run(vec![0xca, 0x00]);
     assert_eq!(cpu.register_x, 0);
@@ -293,7 +311,7 @@ fn dex_decrement_register_x() {
 
 #[test]
 fn dey_decrement_register_y() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.register_y = 1;
     cpu.debug_load_and_run(vec![0x88, 0x00]);
     assert_eq!(cpu.register_y, 0);
@@ -302,7 +320,7 @@ fn dey_decrement_register_y() {
 
 #[test]
 fn eor_accumulator_with_value() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.accumulator = 0x0f;
     cpu.debug_load_and_run(vec![0x49, 0xf0, 0x00]);
     assert_eq!(cpu.accumulator, 0xff);
@@ -311,16 +329,16 @@ fn eor_accumulator_with_value() {
 
 #[test]
 fn inc_increment_memory_with_overflow() {
-    let mut cpu = CPU::new();
-    cpu.memory[0x02] = 0xff;
+    let mut cpu = CPU::debug_new();
+    cpu.mem_write(0x02, 0xff);
     cpu.debug_load_and_run(vec![0xe6, 0x02, 0x00]);
-    assert_eq!(cpu.memory[0x02], 0x00);
+    assert_eq!(cpu.mem_read(0x02), 0x00);
     assert_eq!(cpu.status.get(), Status::ZERO);
 }
 
 #[test]
 fn lsr_shift_accumulator_left() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.accumulator = 0x03;
     cpu.debug_load_and_run(vec![0x4a, 0x00]);
     assert_eq!(cpu.accumulator, 0x01);
@@ -329,18 +347,18 @@ fn lsr_shift_accumulator_left() {
 
 #[test]
 fn nop_do_nothing() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.debug_load_and_run(vec![0xea, 0x00]);
     assert_eq!(cpu.accumulator, 0);
     assert_eq!(cpu.register_x, 0);
     assert_eq!(cpu.register_y, 0);
     assert_eq!(cpu.status.get(), 0);
-    assert_eq!(cpu.program_counter, 0x8002);
+    assert_eq!(cpu.program_counter, 0x0602);
 }
 
 #[test]
 fn ora_accumulator_memory() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.accumulator = 0x0f;
     cpu.debug_load_and_run(vec![0x09, 0xf0, 0x00]);
     assert_eq!(cpu.accumulator, 0xff);
@@ -348,23 +366,26 @@ fn ora_accumulator_memory() {
 
 #[test]
 fn pha_push_value_to_stack() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.accumulator = 0x0f;
     cpu.debug_load_and_run(vec![0x48, 0x00]);
-    assert_eq!(cpu.memory[0x01ff], 0x0f);
+    assert_eq!(cpu.mem_read(0x01fd), 0x0f);
 }
 
 #[test]
 fn php_push_status_to_stack() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.status.set(Status::CARRY | Status::OVERFLOW);
     cpu.debug_load_and_run(vec![0x08, 0x00]);
-    assert_eq!(cpu.memory[0x01ff], Status::CARRY | Status::OVERFLOW);
+    assert_eq!(
+        cpu.mem_read(0x01fd),
+        Status::CARRY | Status::OVERFLOW | Status::BREAK | Status::BREAK2
+    );
 }
 
 #[test]
 fn pla_pop_value_from_stack() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.accumulator = 0xf0;
     cpu.debug_load_and_run(vec![0x48, 0xa9, 0x00, 0x68, 0x00]);
     assert_eq!(cpu.accumulator, 0xf0);
@@ -373,7 +394,7 @@ fn pla_pop_value_from_stack() {
 
 #[test]
 fn plp_pop_status_from_stack() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.push(Status::CARRY | Status::OVERFLOW);
     cpu.debug_load_and_run(vec![0x28, 0x00]);
     assert_eq!(cpu.status.get(), Status::CARRY | Status::OVERFLOW);
@@ -381,7 +402,7 @@ fn plp_pop_status_from_stack() {
 
 #[test]
 fn rol_accumulator() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.accumulator = 0xf0;
     cpu.status.set(Status::CARRY);
     cpu.debug_load_and_run(vec![0x2a, 0x00]);
@@ -392,22 +413,19 @@ fn rol_accumulator() {
 
 #[test]
 fn rol_memory() {
-    let mut cpu = CPU::new();
-    cpu.memory[0x01] = 0xf0;
+    let mut cpu = CPU::debug_new();
+    cpu.mem_write(0x01, 0xf0);
     cpu.status.set(Status::CARRY);
     cpu.accumulator = 0x00;
     cpu.debug_load_and_run(vec![0x26, 0x01, 0x00]);
 
-    assert_eq!(cpu.memory[0x01], 0xe1);
-    assert_eq!(
-        cpu.status.get(),
-        Status::NEGATIV | Status::ZERO | Status::CARRY
-    );
+    assert_eq!(cpu.mem_read(0x01), 0xe1);
+    assert_eq!(cpu.status.get(), Status::NEGATIV | Status::CARRY);
 }
 
 #[test]
 fn ror_accumulator() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.accumulator = 0x0f;
     cpu.status.set(Status::CARRY);
     cpu.debug_load_and_run(vec![0x6a, 0x00]);
@@ -418,39 +436,39 @@ fn ror_accumulator() {
 
 #[test]
 fn ror_memory() {
-    let mut cpu = CPU::new();
-    cpu.memory[0x01] = 0x0f;
+    let mut cpu = CPU::debug_new();
+    cpu.mem_write(0x01, 0x0f);
     cpu.status.set(Status::CARRY);
     cpu.accumulator = 0x00;
     cpu.debug_load_and_run(vec![0x66, 0x01, 0x00]);
 
-    assert_eq!(cpu.memory[0x01], 0x87);
-    assert_eq!(
-        cpu.status.get(),
-        Status::NEGATIV | Status::ZERO | Status::CARRY
-    );
+    assert_eq!(cpu.mem_read(0x01), 0x87);
+    assert_eq!(cpu.status.get(), Status::NEGATIV | Status::CARRY);
 }
 
 #[test]
 fn sdc_basic() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
 
+    // a clear carry borrows one
     cpu.accumulator = 5;
     cpu.debug_load_and_run(vec![0xe9, 0x04, 0x00]);
-    assert_eq!(cpu.accumulator, 1);
+    assert_eq!(cpu.accumulator, 0);
 
     cpu.reset();
     cpu.accumulator = 5;
     cpu.status.set(Status::CARRY);
     cpu.debug_load_and_run(vec![0xe9, 0x04, 0x00]);
-    assert_eq!(cpu.accumulator, 2);
+    assert_eq!(cpu.accumulator, 1);
+    assert_eq!(cpu.status.get(), Status::CARRY);
 }
 
 #[test]
 fn sdc_overflow_and_carry_flag() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
 
     cpu.accumulator = 5;
+    cpu.status.set(Status::CARRY);
     cpu.debug_load_and_run(vec![0xe9, 0x06, 0x00]);
     assert_eq!(cpu.accumulator, 0xff);
     assert_eq!(cpu.status.get(), Status::NEGATIV);
@@ -458,14 +476,21 @@ fn sdc_overflow_and_carry_flag() {
     cpu.reset();
     cpu.accumulator = 5;
     cpu.status.set(Status::CARRY);
-    cpu.debug_load_and_run(vec![0xe9, 0x06, 0x00]);
+    cpu.debug_load_and_run(vec![0xe9, 0x05, 0x00]);
     assert_eq!(cpu.accumulator, 0);
-    assert_eq!(cpu.status.get(), Status::ZERO | Status::CARRY)
+    assert_eq!(cpu.status.get(), Status::ZERO | Status::CARRY);
+
+    cpu.reset();
+    cpu.accumulator = 0x80;
+    cpu.status.set(Status::CARRY);
+    cpu.debug_load_and_run(vec![0xe9, 0x01, 0x00]);
+    assert_eq!(cpu.accumulator, 0x7f);
+    assert_eq!(cpu.status.get(), Status::OVERFLOW | Status::CARRY)
 }
 
 #[test]
 fn sta_stx_sty_store_value() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.accumulator = 0x15;
     cpu.register_x = 0x16;
     cpu.register_y = 0x17;
@@ -477,7 +502,7 @@ fn sta_stx_sty_store_value() {
 
 #[test]
 fn tax_tay() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.accumulator = 0x15;
     cpu.debug_load_and_run(vec![0xaa, 0xa8, 0x00]);
     assert_eq!(cpu.accumulator, cpu.register_x);
@@ -486,34 +511,639 @@ fn tax_tay() {
 
 #[test]
 fn tsx_txa_txs() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.debug_load_and_run(vec![0xba, 0x8a, 0xa9, 0x69, 0xaa, 0x9a, 0x00]);
-    assert_eq!(cpu.stack_pointer, 0x0169);
+    assert_eq!(cpu.stack_pointer, 0x69);
 }
 
 #[test]
 fn bit_with_same_values() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.accumulator = 0b1111_0000;
-    cpu.memory[0x02] = 0b1111_0000;
+    cpu.mem_write(0x02, 0b1111_0000);
     cpu.debug_load_and_run(vec![0x24, 0x02, 0x00]);
     assert_eq!(cpu.status.get(), Status::NEGATIV | Status::OVERFLOW);
 }
 
 #[test]
 fn bit_with_different_values() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.accumulator = 0b0011_0011;
-    cpu.memory[0x02] = 0b0011_0000;
+    cpu.mem_write(0x02, 0b0011_0000);
     cpu.debug_load_and_run(vec![0x24, 0x02, 0x00]);
     assert_eq!(cpu.status.get(), 0);
 }
 
 #[test]
 fn bit_with_different_values_2() {
-    let mut cpu = CPU::new();
+    let mut cpu = CPU::debug_new();
     cpu.accumulator = 0b0011_0011;
-    cpu.memory[0x02] = 0b0000_0000;
+    cpu.mem_write(0x02, 0b0000_0000);
     cpu.debug_load_and_run(vec![0x24, 0x02, 0x00]);
     assert_eq!(cpu.status.get(), Status::ZERO);
 }
+
+#[test]
+fn step_returns_instruction_cycles() {
+    let mut cpu = CPU::debug_new();
+    cpu.load(vec![0xa9, 0x01, 0x8d, 0x00, 0x02, 0xe6, 0x10, 0x00]);
+    cpu.program_counter = 0x0600;
+    assert_eq!(cpu.step(), stepped(2));
+    assert_eq!(cpu.step(), stepped(4));
+    assert_eq!(cpu.step(), stepped(5));
+    assert_eq!(cpu.cycles, 11);
+}
+
+#[test]
+fn indexed_reads_take_extra_cycle_on_page_cross() {
+    let mut cpu = CPU::debug_new();
+    cpu.register_x = 0x01;
+    cpu.register_y = 0x10;
+    cpu.mem_write(0x10, 0xf8);
+    cpu.mem_write(0x11, 0x02);
+    cpu.load(vec![
+        0xbd, 0x00, 0x02, // LDA $0200,X
+        0xbd, 0xff, 0x02, // LDA $02FF,X
+        0xb9, 0xff, 0x02, // LDA $02FF,Y
+        0xb1, 0x10, // LDA ($10),Y
+        0x9d, 0xff, 0x02, // STA $02FF,X
+    ]);
+    cpu.program_counter = 0x0600;
+    assert_eq!(cpu.step(), stepped(4));
+    assert_eq!(cpu.step(), stepped(5));
+    assert_eq!(cpu.step(), stepped(5));
+    assert_eq!(cpu.step(), stepped(6));
+    assert_eq!(cpu.step(), stepped(5));
+}
+
+#[test]
+fn taken_branches_take_extra_cycles() {
+    let mut cpu = CPU::debug_new();
+    cpu.load(vec![
+        0xa9, 0x01, // LDA #$01
+        0xf0, 0x10, // BEQ, not taken
+        0xd0, 0x00, // BNE +0, taken
+        0xd0, 0xf0, // BNE -16, taken into page $05
+    ]);
+    cpu.program_counter = 0x0600;
+    cpu.step().unwrap();
+    assert_eq!(cpu.step(), stepped(2));
+    assert_eq!(cpu.step(), stepped(3));
+    assert_eq!(cpu.step(), stepped(4));
+    assert_eq!(cpu.program_counter, 0x05f8);
+}
+
+#[test]
+fn jmp_and_jsr_timing() {
+    let mut cpu = CPU::debug_new();
+    cpu.load(vec![
+        0x4c, 0x03, 0x06, 0x20, 0x07, 0x06, 0x00, 0x6c, 0x00, 0x02,
+    ]);
+    cpu.mem_write_u16(0x0200, 0x0606);
+    cpu.program_counter = 0x0600;
+    assert_eq!(cpu.step(), stepped(3));
+    assert_eq!(cpu.step(), stepped(6));
+    assert_eq!(cpu.step(), stepped(5));
+    assert_eq!(cpu.program_counter, 0x0606);
+}
+
+#[test]
+fn ppu_runs_three_dots_per_cpu_cycle() {
+    let mut cpu = CPU::debug_new();
+    cpu.load(vec![0xea, 0xad, 0x00, 0x02, 0x00]);
+    cpu.program_counter = 0x0600;
+    cpu.step().unwrap();
+    cpu.step().unwrap();
+    assert_eq!(cpu.bus.ppu.cycle(), 18);
+}
+
+#[test]
+fn oam_dma_stalls_cpu() {
+    let mut cpu = CPU::debug_new();
+    cpu.load(vec![0xa9, 0x02, 0x8d, 0x14, 0x40, 0x00]);
+    cpu.program_counter = 0x0600;
+    cpu.step().unwrap();
+    // the write lands on an even cycle, so no alignment cycle is needed
+    assert_eq!(cpu.step(), stepped(4 + 513));
+    assert_eq!(cpu.bus.cycles(), 519);
+}
+
+#[test]
+fn plp_ignores_break_bits() {
+    let mut cpu = CPU::debug_new();
+    cpu.push(0xff);
+    cpu.debug_load_and_run(vec![0x28, 0x00]);
+    assert_eq!(cpu.status.get(), !(Status::BREAK | Status::BREAK2));
+}
+
+#[test]
+fn brk_pushes_state_and_jumps_through_irq_vector() {
+    let mut cpu = CPU::new();
+    cpu.load(vec![0x00, 0xff, 0xea]);
+    cpu.mem_write_u16(0xfffe, 0x0700);
+    cpu.mem_write(0x0700, 0x40);
+    cpu.program_counter = 0x0600;
+    cpu.status.set(Status::CARRY);
+
+    assert_eq!(cpu.step(), stepped(7));
+    assert_eq!(cpu.program_counter, 0x0700);
+    assert!(cpu.status.contains(Status::INTERRUPT_DISABLE));
+    assert_eq!(cpu.mem_read(0x01fd), 0x06);
+    assert_eq!(cpu.mem_read(0x01fc), 0x02);
+    assert_eq!(
+        cpu.mem_read(0x01fb),
+        Status::CARRY | Status::BREAK | Status::BREAK2
+    );
+
+    // RTI resumes after the padding byte with the old flags
+    assert_eq!(cpu.step(), stepped(6));
+    assert_eq!(cpu.program_counter, 0x0602);
+    assert_eq!(cpu.status.get(), Status::CARRY);
+}
+
+#[test]
+fn nmi_is_serviced_before_next_instruction() {
+    let mut cpu = CPU::new();
+    cpu.load(vec![0xea, 0xea]);
+    cpu.mem_write_u16(0xfffa, 0x0700);
+    cpu.program_counter = 0x0600;
+    cpu.status.set(Status::INTERRUPT_DISABLE);
+
+    cpu.step().unwrap();
+    cpu.bus.set_nmi(true);
+    assert_eq!(cpu.step(), stepped(7));
+    assert_eq!(cpu.program_counter, 0x0700);
+    assert_eq!(cpu.mem_read(0x01fd), 0x06);
+    assert_eq!(cpu.mem_read(0x01fc), 0x01);
+    assert_eq!(
+        cpu.mem_read(0x01fb),
+        Status::INTERRUPT_DISABLE | Status::BREAK2
+    );
+
+    // holding the line does not fire again, a new edge does
+    cpu.mem_write(0x0700, 0xea);
+    cpu.mem_write(0x0701, 0xea);
+    assert_eq!(cpu.step(), stepped(2));
+    cpu.bus.set_nmi(false);
+    cpu.bus.set_nmi(true);
+    assert_eq!(cpu.step(), stepped(7));
+}
+
+#[test]
+fn irq_is_masked_by_interrupt_disable() {
+    let mut cpu = CPU::new();
+    cpu.load(vec![0xea, 0x58, 0xea, 0xea]);
+    cpu.mem_write_u16(0xfffe, 0x0700);
+    cpu.program_counter = 0x0600;
+    cpu.status.set(Status::INTERRUPT_DISABLE);
+    cpu.bus.set_irq(IrqSource::External, true);
+
+    cpu.step().unwrap();
+    assert_eq!(cpu.program_counter, 0x0601);
+    // CLI takes effect after the following instruction
+    cpu.step().unwrap();
+    cpu.step().unwrap();
+    assert_eq!(cpu.program_counter, 0x0603);
+    assert_eq!(cpu.step(), stepped(7));
+    assert_eq!(cpu.program_counter, 0x0700);
+    assert_eq!(cpu.mem_read(0x01fb), Status::BREAK2);
+}
+
+#[test]
+fn released_irq_is_not_serviced() {
+    let mut cpu = CPU::new();
+    cpu.load(vec![0xea, 0xea]);
+    cpu.program_counter = 0x0600;
+    cpu.bus.set_irq(IrqSource::Dmc, true);
+    cpu.bus.set_irq(IrqSource::Dmc, false);
+    cpu.step().unwrap();
+    assert_eq!(cpu.program_counter, 0x0601);
+}
+
+#[test]
+fn ldx_stx_zero_page_y() {
+    let mut cpu = CPU::debug_new();
+    cpu.register_y = 0x02;
+    cpu.mem_write(0x12, 0x55);
+    cpu.debug_load_and_run(vec![0xb6, 0x10, 0x96, 0x20, 0x00]);
+    assert_eq!(cpu.register_x, 0x55);
+    assert_eq!(cpu.mem_read(0x22), 0x55);
+}
+
+#[test]
+fn unofficial_nops_skip_operands() {
+    let mut cpu = CPU::debug_new();
+    cpu.register_x = 0x01;
+    cpu.load(vec![
+        0x1a, 0x80, 0xff, 0x04, 0xff, 0x14, 0xff, 0x0c, 0xff, 0xff, 0x1c, 0xff, 0x02,
+    ]);
+    cpu.program_counter = 0x0600;
+    assert_eq!(cpu.step(), stepped(2));
+    assert_eq!(cpu.step(), stepped(2));
+    assert_eq!(cpu.step(), stepped(3));
+    assert_eq!(cpu.step(), stepped(4));
+    assert_eq!(cpu.step(), stepped(4));
+    assert_eq!(cpu.step(), stepped(5));
+    assert_eq!(cpu.program_counter, 0x060d);
+    assert_eq!(cpu.accumulator, 0);
+}
+
+#[test]
+fn lax_and_sax() {
+    let mut cpu = CPU::debug_new();
+    cpu.mem_write(0x10, 0x83);
+    cpu.debug_load_and_run(vec![
+        0xa7, 0x10, 0xa0, 0x0f, 0x84, 0x20, 0xa9, 0x31, 0x87, 0x11, 0x00,
+    ]);
+    assert_eq!(cpu.register_x, 0x83);
+    assert_eq!(cpu.mem_read(0x11), 0x01);
+    assert_eq!(cpu.status.get(), 0);
+}
+
+#[test]
+fn dcp_decrements_and_compares() {
+    let mut cpu = CPU::debug_new();
+    cpu.accumulator = 0x40;
+    cpu.mem_write(0x10, 0x41);
+    cpu.debug_load_and_run(vec![0xc7, 0x10, 0x00]);
+    assert_eq!(cpu.mem_read(0x10), 0x40);
+    assert_eq!(cpu.status.get(), Status::ZERO | Status::CARRY);
+}
+
+#[test]
+fn isb_increments_and_subtracts() {
+    let mut cpu = CPU::debug_new();
+    cpu.accumulator = 0x10;
+    cpu.status.set(Status::CARRY);
+    cpu.mem_write(0x10, 0x04);
+    cpu.debug_load_and_run(vec![0xe7, 0x10, 0x00]);
+    assert_eq!(cpu.mem_read(0x10), 0x05);
+    assert_eq!(cpu.accumulator, 0x0b);
+    assert_eq!(cpu.status.get(), Status::CARRY);
+}
+
+#[test]
+fn slo_rla_sre_rra() {
+    let mut cpu = CPU::debug_new();
+    cpu.accumulator = 0x01;
+    cpu.mem_write(0x10, 0x81);
+    cpu.debug_load_and_run(vec![0x07, 0x10, 0x00]);
+    assert_eq!(cpu.mem_read(0x10), 0x02);
+    assert_eq!(cpu.accumulator, 0x03);
+    assert_eq!(cpu.status.get(), Status::CARRY);
+
+    let mut cpu = CPU::debug_new();
+    cpu.accumulator = 0xff;
+    cpu.status.set(Status::CARRY);
+    cpu.mem_write(0x10, 0x40);
+    cpu.debug_load_and_run(vec![0x27, 0x10, 0x00]);
+    assert_eq!(cpu.mem_read(0x10), 0x81);
+    assert_eq!(cpu.accumulator, 0x81);
+    assert_eq!(cpu.status.get(), Status::NEGATIV);
+
+    let mut cpu = CPU::debug_new();
+    cpu.accumulator = 0x01;
+    cpu.mem_write(0x10, 0x03);
+    cpu.debug_load_and_run(vec![0x47, 0x10, 0x00]);
+    assert_eq!(cpu.mem_read(0x10), 0x01);
+    assert_eq!(cpu.accumulator, 0x00);
+    assert_eq!(cpu.status.get(), Status::ZERO | Status::CARRY);
+
+    // ROR's carry out is added in by the ADC half
+    let mut cpu = CPU::debug_new();
+    cpu.accumulator = 0x10;
+    cpu.mem_write(0x10, 0x03);
+    cpu.debug_load_and_run(vec![0x67, 0x10, 0x00]);
+    assert_eq!(cpu.mem_read(0x10), 0x01);
+    assert_eq!(cpu.accumulator, 0x12);
+    assert_eq!(cpu.status.get(), 0);
+}
+
+#[test]
+fn anc_alr_arr_axs() {
+    let mut cpu = CPU::debug_new();
+    cpu.accumulator = 0xf0;
+    cpu.debug_load_and_run(vec![0x0b, 0x80, 0x00]);
+    assert_eq!(cpu.accumulator, 0x80);
+    assert_eq!(cpu.status.get(), Status::NEGATIV | Status::CARRY);
+
+    let mut cpu = CPU::debug_new();
+    cpu.accumulator = 0xff;
+    cpu.debug_load_and_run(vec![0x4b, 0x03, 0x00]);
+    assert_eq!(cpu.accumulator, 0x01);
+    assert_eq!(cpu.status.get(), Status::CARRY);
+
+    let mut cpu = CPU::debug_new();
+    cpu.accumulator = 0xff;
+    cpu.status.set(Status::CARRY);
+    cpu.debug_load_and_run(vec![0x6b, 0x80, 0x00]);
+    assert_eq!(cpu.accumulator, 0xc0);
+    assert_eq!(
+        cpu.status.get(),
+        Status::NEGATIV | Status::OVERFLOW | Status::CARRY
+    );
+
+    let mut cpu = CPU::debug_new();
+    cpu.accumulator = 0x0f;
+    cpu.register_x = 0x3c;
+    cpu.debug_load_and_run(vec![0xcb, 0x0d, 0x00]);
+    assert_eq!(cpu.register_x, 0xff);
+    assert_eq!(cpu.status.get(), Status::NEGATIV);
+}
+
+#[test]
+fn sbc_eb_matches_official_sbc() {
+    let mut cpu = CPU::debug_new();
+    cpu.accumulator = 0x10;
+    cpu.status.set(Status::CARRY);
+    cpu.debug_load_and_run(vec![0xeb, 0x01, 0x00]);
+    assert_eq!(cpu.accumulator, 0x0f);
+    assert_eq!(cpu.status.get(), Status::CARRY);
+}
+
+#[test]
+fn unstable_opcodes_follow_policy() {
+    let mut cpu = CPU::debug_new();
+    cpu.accumulator = 0xff;
+    cpu.register_x = 0x0f;
+    cpu.debug_load_and_run(vec![0x8b, 0x3c, 0x00]);
+    assert_eq!(cpu.accumulator, 0x0c);
+
+    let mut cpu = CPU::debug_new();
+    cpu.set_unstable_opcode_policy(UnstableOpcodePolicy::Halt);
+    cpu.accumulator = 0xff;
+    cpu.register_x = 0x0f;
+    cpu.debug_load_and_run(vec![0x8b, 0x3c, 0x00]);
+    assert_eq!(cpu.accumulator, 0xff);
+    assert_eq!(cpu.program_counter, 0x0600);
+}
+
+#[test]
+fn jam_freezes_cpu_until_reset() {
+    let mut cpu = CPU::new();
+    cpu.load(vec![0x02, 0xea]);
+    cpu.mem_write_u16(0xfffa, 0x0700);
+    cpu.program_counter = 0x0600;
+    cpu.step().unwrap();
+    cpu.bus.set_nmi(true);
+    cpu.step().unwrap();
+    cpu.step().unwrap();
+    assert_eq!(cpu.program_counter, 0x0600);
+
+    cpu.mem_write(0x0600, 0xea);
+    cpu.reset();
+    assert_eq!(cpu.step(), stepped(2));
+    assert_eq!(cpu.program_counter, 0x0601);
+
+    let mut cpu = CPU::debug_new();
+    cpu.set_unstable_opcode_policy(UnstableOpcodePolicy::Halt);
+    cpu.debug_load_and_run(vec![0xea, 0x02, 0x00]);
+    assert_eq!(cpu.program_counter, 0x0601);
+}
+
+#[test]
+fn log_policy_queues_reports() {
+    let mut cpu = CPU::new();
+    cpu.set_unstable_opcode_policy(UnstableOpcodePolicy::Log);
+    // XAA #$3C; JAM
+    cpu.load(vec![0x8b, 0x3c, 0x02]);
+    cpu.program_counter = 0x0600;
+    for _ in 0..4 {
+        cpu.step().unwrap();
+    }
+    assert_eq!(
+        cpu.take_opcode_reports(),
+        vec![
+            StopReason::UnstableOpcode {
+                pc: 0x0600,
+                opcode: 0x8b
+            },
+            StopReason::Jammed {
+                pc: 0x0602,
+                opcode: 0x02
+            },
+        ]
+    );
+    assert!(cpu.take_opcode_reports().is_empty());
+}
+
+#[test]
+fn every_opcode_has_a_table_entry() {
+    for (code, opcode) in OPCODES.iter().enumerate() {
+        assert_eq!(opcode.code as usize, code);
+        assert!(opcode.length >= 1);
+        assert!(opcode.cycles >= 2);
+    }
+}
+
+#[test]
+fn opcodes_share_plain_mnemonics() {
+    let official = OPCODES.iter().filter(|opcode| !opcode.unofficial).count();
+    assert_eq!(official, 151);
+
+    assert_eq!(OPCODES[0xEA].instruction, Instruction::Nop);
+    assert!(!OPCODES[0xEA].unofficial);
+    assert_eq!(OPCODES[0x1A].instruction, Instruction::Nop);
+    assert!(OPCODES[0x1A].unofficial);
+    assert_eq!(OPCODES[0xEB].instruction.mnemonic(), "SBC");
+    assert_eq!(OPCODES[0xA7].instruction.to_string(), "LAX");
+}
+
+#[test]
+fn operandless_opcodes_have_their_own_modes() {
+    for opcode in OPCODES.iter() {
+        let operandless = matches!(
+            opcode.mode,
+            AddressingMode::Implied | AddressingMode::Accumulator
+        );
+        assert_eq!(operandless, opcode.length == 1, "${:02X}", opcode.code);
+    }
+    assert_eq!(OPCODES[0x6A].mode, AddressingMode::Accumulator);
+    assert_eq!(OPCODES[0xD0].mode, AddressingMode::Relative);
+    assert_eq!(OPCODES[0x6C].mode, AddressingMode::Indirect);
+    assert_eq!(OPCODES[0x20].mode, AddressingMode::Absolute);
+}
+
+#[test]
+fn run_reports_why_it_stopped() {
+    let mut cpu = CPU::debug_new();
+    assert_eq!(
+        cpu.load_and_run(vec![0xe8, 0xe8, 0x00]),
+        Ok(StopReason::Brk { pc: 0x0602 })
+    );
+
+    let mut cpu = CPU::new();
+    cpu.load(vec![0xe8, 0x02]);
+    cpu.program_counter = 0x0600;
+    assert_eq!(
+        cpu.run(),
+        Ok(StopReason::Jammed {
+            pc: 0x0601,
+            opcode: 0x02
+        })
+    );
+
+    let mut cpu = CPU::debug_new();
+    cpu.set_unstable_opcode_policy(UnstableOpcodePolicy::Halt);
+    cpu.load(vec![0xe8, 0x8b, 0x3c, 0x00]);
+    cpu.program_counter = 0x0600;
+    assert_eq!(
+        cpu.run(),
+        Ok(StopReason::UnstableOpcode {
+            pc: 0x0601,
+            opcode: 0x8b
+        })
+    );
+}
+
+#[test]
+fn run_stops_at_breakpoints_and_resumes() {
+    let mut cpu = CPU::debug_new();
+    // INX; INX; INX; BRK
+    cpu.load(vec![0xe8, 0xe8, 0xe8, 0x00]);
+    cpu.program_counter = 0x0600;
+    cpu.add_breakpoint(0x0602);
+    assert!(cpu.has_breakpoint(0x0602));
+
+    assert_eq!(cpu.run(), Ok(StopReason::Breakpoint { pc: 0x0602 }));
+    assert_eq!(cpu.register_x, 2);
+    // the instruction under the breakpoint runs when resuming
+    assert_eq!(cpu.run(), Ok(StopReason::Brk { pc: 0x0603 }));
+    assert_eq!(cpu.register_x, 3);
+
+    assert!(cpu.remove_breakpoint(0x0602));
+    assert!(!cpu.remove_breakpoint(0x0602));
+    assert_eq!(cpu.breakpoints().count(), 0);
+}
+
+#[test]
+fn run_stops_when_the_cycle_budget_runs_out() {
+    let mut cpu = CPU::debug_new();
+    // loop: INX; JMP loop
+    cpu.load(vec![0xe8, 0x4c, 0x00, 0x06]);
+    cpu.program_counter = 0x0600;
+    let start = cpu.cycles;
+    cpu.set_cycle_budget(Some(50));
+
+    match cpu.run() {
+        Ok(StopReason::CycleBudgetExhausted { cycles }) => {
+            assert_eq!(cycles, cpu.cycles);
+            assert!(cycles >= start + 50 && cycles < start + 55);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    // INX and JMP take 5 cycles together
+    assert_eq!(cpu.register_x, 10);
+}
+
+#[test]
+fn stack_pointer_wraps_within_page_one() {
+    let mut cpu = CPU::debug_new();
+    // PHA; PHA; PLA; PLA
+    cpu.load(vec![0x48, 0x48, 0x68, 0x68, 0x00]);
+    cpu.program_counter = 0x0600;
+    cpu.stack_pointer = 0x00;
+    cpu.accumulator = 0x42;
+    cpu.step().unwrap();
+    assert_eq!(cpu.mem_read(0x0100), 0x42);
+    assert_eq!(cpu.stack_pointer, 0xff);
+    cpu.accumulator = 0x43;
+    cpu.step().unwrap();
+    assert_eq!(cpu.mem_read(0x01ff), 0x43);
+    assert_eq!(cpu.stack_pointer, 0xfe);
+
+    cpu.step().unwrap();
+    assert_eq!(cpu.accumulator, 0x43);
+    assert_eq!(cpu.stack_pointer, 0xff);
+    cpu.step().unwrap();
+    assert_eq!(cpu.accumulator, 0x42);
+    assert_eq!(cpu.stack_pointer, 0x00);
+}
+
+#[test]
+fn jsr_and_rts_wrap_the_stack() {
+    let mut cpu = CPU::debug_new();
+    // JSR $0610; BRK ... $0610: RTS
+    cpu.load(vec![0x20, 0x10, 0x06, 0x00]);
+    cpu.mem_write(0x0610, 0x60);
+    cpu.program_counter = 0x0600;
+    cpu.stack_pointer = 0x00;
+    cpu.step().unwrap();
+    assert_eq!(cpu.mem_read(0x0100), 0x06);
+    assert_eq!(cpu.mem_read(0x01ff), 0x02);
+    assert_eq!(cpu.stack_pointer, 0xfe);
+    cpu.step().unwrap();
+    assert_eq!(cpu.program_counter, 0x0603);
+    assert_eq!(cpu.stack_pointer, 0x00);
+}
+
+#[test]
+fn jsr_and_rts_wrap_the_address_space() {
+    for &core in &[CpuCore::Instruction, CpuCore::CycleAccurate] {
+        // RTS with $FFFF on the stack returns to $0000
+        let mut cpu = CPU::with_bus(FlatBus::new());
+        cpu.set_core(core);
+        cpu.bus.mem_write(0x0200, 0x60);
+        cpu.bus.mem_write(0x01fe, 0xff);
+        cpu.bus.mem_write(0x01ff, 0xff);
+        cpu.program_counter = 0x0200;
+        cpu.stack_pointer = 0xfd;
+        cpu.step().unwrap();
+        assert_eq!(cpu.program_counter, 0x0000, "{:?}", core);
+        assert_eq!(cpu.stack_pointer, 0xff, "{:?}", core);
+
+        // JSR $0300 at $FFFE pushes $0000, the address of its last byte
+        let mut cpu = CPU::with_bus(FlatBus::new());
+        cpu.set_core(core);
+        cpu.bus.mem_write(0xfffe, 0x20);
+        cpu.bus.mem_write(0xffff, 0x00);
+        cpu.bus.mem_write(0x0000, 0x03);
+        cpu.program_counter = 0xfffe;
+        cpu.stack_pointer = 0xff;
+        cpu.step().unwrap();
+        assert_eq!(cpu.program_counter, 0x0300, "{:?}", core);
+        assert_eq!(cpu.bus.peek(0x01ff), 0x00, "{:?}", core);
+        assert_eq!(cpu.bus.peek(0x01fe), 0x00, "{:?}", core);
+    }
+}
+
+#[test]
+fn stack_diagnostics_report_wraps() {
+    let mut cpu = CPU::debug_new();
+    // loop: PHA; JMP loop
+    cpu.load(vec![0x48, 0x4c, 0x00, 0x06]);
+    cpu.program_counter = 0x0600;
+    cpu.set_stack_diagnostics(true);
+    assert_eq!(
+        cpu.run(),
+        Err(CpuError::StackOverflow {
+            pc: 0x0600,
+            opcode: 0x48
+        })
+    );
+    // the push happened and the CPU can go on
+    assert_eq!(cpu.stack_pointer, 0xff);
+    assert_eq!(cpu.program_counter, 0x0601);
+    assert_eq!(cpu.step(), stepped(3));
+
+    let mut cpu = CPU::debug_new();
+    cpu.load(vec![0x68, 0x00]);
+    cpu.program_counter = 0x0600;
+    cpu.stack_pointer = 0xff;
+    cpu.set_stack_diagnostics(true);
+    assert_eq!(
+        cpu.step(),
+        Err(CpuError::StackUnderflow {
+            pc: 0x0600,
+            opcode: 0x68
+        })
+    );
+    assert_eq!(cpu.stack_pointer, 0x00);
+
+    // without them the wrap goes unnoticed, as on the 2A03
+    let mut cpu = CPU::debug_new();
+    cpu.load(vec![0x68, 0x00]);
+    cpu.program_counter = 0x0600;
+    cpu.stack_pointer = 0xff;
+    assert_eq!(cpu.step(), stepped(4));
+}
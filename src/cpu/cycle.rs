@@ -0,0 +1,470 @@
+//! Cycle-accurate execution core, selected with `CpuCore::CycleAccurate`.
+//! Every cycle of an instruction is one bus access in the order of the
+//! 2A03, dummy reads and writes included, and the rest of the system is
+//! clocked after each of them, so mappers and the PPU and APU registers see
+//! the same sequence of accesses as on hardware.
+//!
+//! # 6502 cycle by cycle http://nesdev.org/6502_cpu.txt
+
+use super::opcodes::{Instruction, Opcode};
+use super::unofficial;
+use super::{page_crossed, AddressingMode, Fault, Status, CPU, IRQ_VECTOR, STACK};
+use crate::bus::{CpuBus, Mem};
+
+#[cfg(test)]
+mod cycle_tests;
+
+/// What an instruction does with its memory operand, which decides the
+/// dummy accesses of indexed addressing.
+#[derive(Clone, Copy, PartialEq)]
+enum Operation {
+    Read,
+    Write,
+    ReadModifyWrite,
+}
+
+impl<B: CpuBus> CPU<B> {
+    /// Executes `opcode`, whose byte was fetched by `read_opcode`. Returns
+    /// `false` when the CPU halts, like `interpret`.
+    pub(super) fn interpret_cycles(&mut self, opcode: &Opcode) -> bool {
+        let instruction = opcode.instruction;
+        if instruction.is_unstable() && !self.unstable_opcode_allowed(opcode) {
+            return false;
+        }
+
+        match instruction {
+            Instruction::Brk if !self.halt_on_brk => self.brk_cycles(),
+            Instruction::Jsr => self.jsr_cycles(),
+            Instruction::Rti => self.rti_cycles(),
+            Instruction::Rts => self.rts_cycles(),
+            Instruction::Jmp if opcode.mode == AddressingMode::Indirect => {
+                self.jmp_indirect_cycles()
+            }
+            Instruction::Jmp => {
+                self.program_counter = self.fetch_u16();
+            }
+            Instruction::Php | Instruction::Pha => {
+                self.read(self.program_counter);
+                let data = match instruction {
+                    Instruction::Php => self.status.get() | Status::BREAK | Status::BREAK2,
+                    _ => self.accumulator,
+                };
+                self.push_cycle(data);
+            }
+            Instruction::Plp | Instruction::Pla => {
+                self.read(self.program_counter);
+                self.read(STACK | self.stack_pointer as u16);
+                let data = self.pop_cycle();
+                if instruction == Instruction::Plp {
+                    self.status.insert(data);
+                } else {
+                    self.accumulator = data;
+                    self.update_zero_and_negative_flags(data);
+                }
+            }
+            Instruction::Bpl
+            | Instruction::Bmi
+            | Instruction::Bvc
+            | Instruction::Bvs
+            | Instruction::Bcc
+            | Instruction::Bcs
+            | Instruction::Bne
+            | Instruction::Beq => self.branch_cycles(instruction),
+            // implied and accumulator instructions, BRK halts and JAM work
+            // on registers only, after reading the next byte for nothing
+            _ if matches!(
+                opcode.mode,
+                AddressingMode::Implied | AddressingMode::Accumulator
+            ) =>
+            {
+                self.read(self.program_counter);
+                return self.interpret(opcode);
+            }
+            _ => self.operand_cycles(opcode),
+        }
+        true
+    }
+
+    /// Hardware interrupt sequence, see `interrupt`.
+    pub(super) fn interrupt_cycles(&mut self, vector: u16) {
+        self.read(self.program_counter);
+        self.read(self.program_counter);
+        self.push_cycle((self.program_counter >> 8) as u8);
+        self.push_cycle(self.program_counter as u8);
+        self.push_cycle((self.status.get() | Status::BREAK2) & !Status::BREAK);
+        self.status.set(Status::INTERRUPT_DISABLE);
+        self.program_counter = self.read_u16(vector);
+    }
+
+    /// Fetches the opcode at PC in the first cycle of an instruction.
+    pub(super) fn read_opcode(&mut self) -> u8 {
+        let code = self.read(self.program_counter);
+        self.program_counter = self.program_counter.wrapping_add(1);
+        code
+    }
+
+    /// One cycle reading `address`.
+    fn read(&mut self, address: u16) -> u8 {
+        let data = self.mem_read(address);
+        self.bus.tick(1);
+        data
+    }
+
+    /// One cycle writing `data` to `address`.
+    fn write(&mut self, address: u16, data: u8) {
+        self.mem_write(address, data);
+        self.bus.tick(1);
+    }
+
+    fn read_u16(&mut self, address: u16) -> u16 {
+        let lo = self.read(address);
+        let hi = self.read(address.wrapping_add(1));
+        u16::from_le_bytes([lo, hi])
+    }
+
+    fn push_cycle(&mut self, data: u8) {
+        self.push(data);
+        self.bus.tick(1);
+    }
+
+    fn pop_cycle(&mut self) -> u8 {
+        let data = self.pop();
+        self.bus.tick(1);
+        data
+    }
+
+    /// Reads the byte at PC and moves past it.
+    fn fetch(&mut self) -> u8 {
+        let data = self.read(self.program_counter);
+        self.program_counter = self.program_counter.wrapping_add(1);
+        data
+    }
+
+    fn fetch_u16(&mut self) -> u16 {
+        let lo = self.fetch();
+        let hi = self.fetch();
+        u16::from_le_bytes([lo, hi])
+    }
+
+    fn operand_cycles(&mut self, opcode: &Opcode) {
+        let instruction = opcode.instruction;
+        let operation = operation(instruction);
+        let (address, page_crossed) = self.address_cycles(opcode.mode, operation);
+        match operation {
+            Operation::Read => {
+                let value = self.read(address);
+                self.read_operand_value(instruction, value);
+            }
+            Operation::Write => {
+                let (address, value) = match instruction {
+                    Instruction::Sha => {
+                        let value = self.accumulator & self.register_x;
+                        unofficial::and_high_byte(address, page_crossed, self.register_y, value)
+                    }
+                    Instruction::Tas => {
+                        let value = self.accumulator & self.register_x;
+                        self.stack_pointer = value;
+                        unofficial::and_high_byte(address, page_crossed, self.register_y, value)
+                    }
+                    Instruction::Shy => unofficial::and_high_byte(
+                        address,
+                        page_crossed,
+                        self.register_x,
+                        self.register_y,
+                    ),
+                    Instruction::Shx => unofficial::and_high_byte(
+                        address,
+                        page_crossed,
+                        self.register_y,
+                        self.register_x,
+                    ),
+                    Instruction::Sty => (address, self.register_y),
+                    Instruction::Stx => (address, self.register_x),
+                    Instruction::Sax => (address, self.accumulator & self.register_x),
+                    _ => (address, self.accumulator),
+                };
+                self.write(address, value);
+            }
+            Operation::ReadModifyWrite => {
+                let value = self.read(address);
+                // the unmodified value is written back while the ALU works
+                self.write(address, value);
+                let result = self.modify_value(instruction, value);
+                self.write(address, result);
+            }
+        }
+    }
+
+    /// Runs the addressing cycles of `mode`, leaving PC after the operand.
+    /// Returns the effective address and whether indexing crossed a page.
+    fn address_cycles(&mut self, mode: AddressingMode, operation: Operation) -> (u16, bool) {
+        match mode {
+            AddressingMode::Immediate => {
+                let address = self.program_counter;
+                self.program_counter = self.program_counter.wrapping_add(1);
+                (address, false)
+            }
+            AddressingMode::ZeroPage => (self.fetch() as u16, false),
+            AddressingMode::ZeroPage_X | AddressingMode::ZeroPage_Y => {
+                let index = match mode {
+                    AddressingMode::ZeroPage_X => self.register_x,
+                    _ => self.register_y,
+                };
+                let base = self.fetch();
+                self.read(base as u16);
+                (base.wrapping_add(index) as u16, false)
+            }
+            AddressingMode::Absolute => (self.fetch_u16(), false),
+            AddressingMode::Absolute_X => {
+                let base = self.fetch_u16();
+                self.index_cycles(base, self.register_x, operation)
+            }
+            AddressingMode::Absolute_Y => {
+                let base = self.fetch_u16();
+                self.index_cycles(base, self.register_y, operation)
+            }
+            AddressingMode::Indirect_X => {
+                let pointer = self.fetch();
+                self.read(pointer as u16);
+                let pointer = pointer.wrapping_add(self.register_x);
+                let lo = self.read(pointer as u16);
+                let hi = self.read(pointer.wrapping_add(1) as u16);
+                (u16::from_le_bytes([lo, hi]), false)
+            }
+            AddressingMode::Indirect_Y => {
+                let pointer = self.fetch();
+                let lo = self.read(pointer as u16);
+                let hi = self.read(pointer.wrapping_add(1) as u16);
+                self.index_cycles(u16::from_le_bytes([lo, hi]), self.register_y, operation)
+            }
+            AddressingMode::Implied
+            | AddressingMode::Accumulator
+            | AddressingMode::Relative
+            | AddressingMode::Indirect => {
+                self.fault = Some(Fault::InvalidAddressingMode);
+                (0, false)
+            }
+        }
+    }
+
+    /// The index is added to the low byte first and the high byte fixed in
+    /// the next cycle, which reads from the unfixed address. Reads skip that
+    /// cycle when no page is crossed, writes cannot.
+    fn index_cycles(&mut self, base: u16, index: u8, operation: Operation) -> (u16, bool) {
+        let address = base.wrapping_add(index as u16);
+        let crossed = page_crossed(base, address);
+        if crossed || operation != Operation::Read {
+            self.read(base & 0xFF00 | address & 0x00FF);
+        }
+        (address, crossed)
+    }
+
+    fn read_operand_value(&mut self, instruction: Instruction, value: u8) {
+        match instruction {
+            Instruction::Adc => {
+                self.add_to_accumulator(value);
+                self.update_zero_and_negative_flags(self.accumulator);
+            }
+            Instruction::And => {
+                self.accumulator &= value;
+                self.update_zero_and_negative_flags(self.accumulator);
+            }
+            Instruction::Ora => {
+                self.accumulator |= value;
+                self.update_zero_and_negative_flags(self.accumulator);
+            }
+            Instruction::Eor => {
+                self.accumulator ^= value;
+                self.update_zero_and_negative_flags(self.accumulator);
+            }
+            Instruction::Sbc => self.subtract_from_accumulator(value),
+            Instruction::Cmp => self.compare_values(self.accumulator, value),
+            Instruction::Cpx => self.compare_values(self.register_x, value),
+            Instruction::Cpy => self.compare_values(self.register_y, value),
+            Instruction::Bit => self.test_bits(value),
+            Instruction::Lda => {
+                self.accumulator = value;
+                self.update_zero_and_negative_flags(value);
+            }
+            Instruction::Ldx => {
+                self.register_x = value;
+                self.update_zero_and_negative_flags(value);
+            }
+            Instruction::Ldy => {
+                self.register_y = value;
+                self.update_zero_and_negative_flags(value);
+            }
+            Instruction::Lax => {
+                self.accumulator = value;
+                self.register_x = value;
+                self.update_zero_and_negative_flags(value);
+            }
+            Instruction::Anc => self.anc_value(value),
+            Instruction::Alr => self.alr_value(value),
+            Instruction::Arr => self.arr_value(value),
+            Instruction::Axs => self.axs_value(value),
+            Instruction::Las => self.las_value(value),
+            Instruction::Xaa => self.xaa_value(value),
+            Instruction::Lxa => self.lxa_value(value),
+            // NOPs with an operand read it and ignore it
+            _ => {}
+        }
+    }
+
+    /// Computes the value a read-modify-write instruction writes back, and
+    /// what the unofficial ones do with it afterwards.
+    fn modify_value(&mut self, instruction: Instruction, value: u8) -> u8 {
+        let carry = self.status.contains(Status::CARRY);
+        match instruction {
+            Instruction::Asl => self.shift_left(value, false),
+            Instruction::Rol => self.shift_left(value, carry),
+            Instruction::Lsr => self.shift_right(value, false),
+            Instruction::Ror => self.shift_right(value, carry),
+            Instruction::Inc => {
+                let result = value.wrapping_add(1);
+                self.update_zero_and_negative_flags(result);
+                result
+            }
+            Instruction::Dec => {
+                let result = value.wrapping_sub(1);
+                self.update_zero_and_negative_flags(result);
+                result
+            }
+            Instruction::Slo => {
+                let result = self.shift_left(value, false);
+                self.accumulator |= result;
+                self.update_zero_and_negative_flags(self.accumulator);
+                result
+            }
+            Instruction::Rla => {
+                let result = self.shift_left(value, carry);
+                self.accumulator &= result;
+                self.update_zero_and_negative_flags(self.accumulator);
+                result
+            }
+            Instruction::Sre => {
+                let result = self.shift_right(value, false);
+                self.accumulator ^= result;
+                self.update_zero_and_negative_flags(self.accumulator);
+                result
+            }
+            Instruction::Rra => {
+                let result = self.shift_right(value, carry);
+                self.add_to_accumulator(result);
+                self.update_zero_and_negative_flags(self.accumulator);
+                result
+            }
+            Instruction::Dcp => {
+                let result = value.wrapping_sub(1);
+                self.compare_values(self.accumulator, result);
+                result
+            }
+            // ISB
+            _ => {
+                let result = value.wrapping_add(1);
+                self.subtract_from_accumulator(result);
+                result
+            }
+        }
+    }
+
+    /// Branches read the offset; a taken one reads the next opcode while
+    /// adding it, and reads from the unfixed address when it crosses a page.
+    fn branch_cycles(&mut self, instruction: Instruction) {
+        let (flag, taken_if_set) = match instruction {
+            Instruction::Bpl => (Status::NEGATIV, false),
+            Instruction::Bmi => (Status::NEGATIV, true),
+            Instruction::Bvc => (Status::OVERFLOW, false),
+            Instruction::Bvs => (Status::OVERFLOW, true),
+            Instruction::Bcc => (Status::CARRY, false),
+            Instruction::Bcs => (Status::CARRY, true),
+            Instruction::Bne => (Status::ZERO, false),
+            _ => (Status::ZERO, true),
+        };
+        let offset = self.fetch() as i8;
+        if self.status.contains(flag) != taken_if_set {
+            return;
+        }
+        self.read(self.program_counter);
+        let target = self.program_counter.wrapping_add(offset as u16);
+        if page_crossed(self.program_counter, target) {
+            self.read(self.program_counter & 0xFF00 | target & 0x00FF);
+        }
+        self.program_counter = target;
+    }
+
+    /// The pointer's high byte comes from the same page as the low byte.
+    fn jmp_indirect_cycles(&mut self) {
+        let pointer = self.fetch_u16();
+        let lo = self.read(pointer);
+        let hi = self.read(pointer & 0xFF00 | pointer.wrapping_add(1) & 0x00FF);
+        self.program_counter = u16::from_le_bytes([lo, hi]);
+    }
+
+    /// JSR pushes the address of its last byte, which it reads last.
+    fn jsr_cycles(&mut self) {
+        let lo = self.fetch();
+        self.read(STACK | self.stack_pointer as u16);
+        self.push_cycle((self.program_counter >> 8) as u8);
+        self.push_cycle(self.program_counter as u8);
+        let hi = self.read(self.program_counter);
+        self.program_counter = u16::from_le_bytes([lo, hi]);
+    }
+
+    fn rts_cycles(&mut self) {
+        self.read(self.program_counter);
+        self.read(STACK | self.stack_pointer as u16);
+        let lo = self.pop_cycle();
+        let hi = self.pop_cycle();
+        self.program_counter = u16::from_le_bytes([lo, hi]);
+        self.read(self.program_counter);
+        self.program_counter = self.program_counter.wrapping_add(1);
+    }
+
+    fn rti_cycles(&mut self) {
+        self.read(self.program_counter);
+        self.read(STACK | self.stack_pointer as u16);
+        let flags = self.pop_cycle();
+        self.status.insert(flags);
+        let lo = self.pop_cycle();
+        let hi = self.pop_cycle();
+        self.program_counter = u16::from_le_bytes([lo, hi]);
+    }
+
+    /// BRK reads the padding byte after the opcode, then interrupts with B
+    /// set in the pushed flags.
+    fn brk_cycles(&mut self) {
+        self.fetch();
+        self.push_cycle((self.program_counter >> 8) as u8);
+        self.push_cycle(self.program_counter as u8);
+        self.push_cycle(self.status.get() | Status::BREAK | Status::BREAK2);
+        self.status.set(Status::INTERRUPT_DISABLE);
+        self.program_counter = self.read_u16(IRQ_VECTOR);
+    }
+}
+
+fn operation(instruction: Instruction) -> Operation {
+    match instruction {
+        Instruction::Sta
+        | Instruction::Stx
+        | Instruction::Sty
+        | Instruction::Sax
+        | Instruction::Sha
+        | Instruction::Shx
+        | Instruction::Shy
+        | Instruction::Tas => Operation::Write,
+        Instruction::Asl
+        | Instruction::Rol
+        | Instruction::Lsr
+        | Instruction::Ror
+        | Instruction::Inc
+        | Instruction::Dec
+        | Instruction::Slo
+        | Instruction::Rla
+        | Instruction::Sre
+        | Instruction::Rra
+        | Instruction::Dcp
+        | Instruction::Isb => Operation::ReadModifyWrite,
+        _ => Operation::Read,
+    }
+}
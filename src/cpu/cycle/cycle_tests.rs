@@ -0,0 +1,261 @@
+use crate::bus::{Access, BusAccess, FlatBus, Mem};
+use crate::cpu::{CpuCore, StopReason, CPU};
+
+/// A CPU on a flat bus running the cycle-accurate core, with `program` at
+/// $0600 and the access log on.
+fn cpu_with(program: &[u8]) -> CPU<FlatBus> {
+    let mut cpu = CPU::with_bus(FlatBus::new());
+    cpu.set_core(CpuCore::CycleAccurate);
+    for (offset, &byte) in program.iter().enumerate() {
+        cpu.bus.mem_write(0x0600 + offset as u16, byte);
+    }
+    cpu.program_counter = 0x0600;
+    cpu.bus.log_accesses(true);
+    cpu
+}
+
+/// Accesses written like the cycles of SingleStepTests: `r0600=BD`.
+fn accesses(cpu: &mut CPU<FlatBus>) -> Vec<String> {
+    cpu.bus
+        .take_access_log()
+        .iter()
+        .map(|access| {
+            let kind = match access.access {
+                Access::Read => 'r',
+                Access::Write => 'w',
+            };
+            format!("{}{:04X}={:02X}", kind, access.address, access.data)
+        })
+        .collect()
+}
+
+fn step_cycles(cpu: &mut CPU<FlatBus>) -> u16 {
+    match cpu.step() {
+        Ok(StopReason::Stepped { cycles }) => cycles,
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn indexed_reads_touch_the_unfixed_address_when_crossing_a_page() {
+    // LDA $12F0,X
+    let mut cpu = cpu_with(&[0xBD, 0xF0, 0x12]);
+    cpu.register_x = 0x20;
+    cpu.bus.mem_write(0x1210, 0x11);
+    cpu.bus.mem_write(0x1310, 0x22);
+    cpu.bus.take_access_log();
+
+    assert_eq!(step_cycles(&mut cpu), 5);
+    assert_eq!(
+        accesses(&mut cpu),
+        ["r0600=BD", "r0601=F0", "r0602=12", "r1210=11", "r1310=22"]
+    );
+    assert_eq!(cpu.accumulator, 0x22);
+
+    // without crossing there is no dummy read
+    let mut cpu = cpu_with(&[0xBD, 0x00, 0x12]);
+    cpu.register_x = 0x10;
+    assert_eq!(step_cycles(&mut cpu), 4);
+    assert_eq!(
+        accesses(&mut cpu),
+        ["r0600=BD", "r0601=00", "r0602=12", "r1210=00"]
+    );
+}
+
+#[test]
+fn indexed_writes_always_read_first() {
+    // STA $1200,Y
+    let mut cpu = cpu_with(&[0x99, 0x00, 0x12]);
+    cpu.register_y = 0x10;
+    cpu.accumulator = 0x42;
+    assert_eq!(step_cycles(&mut cpu), 5);
+    assert_eq!(
+        accesses(&mut cpu),
+        ["r0600=99", "r0601=00", "r0602=12", "r1210=00", "w1210=42"]
+    );
+}
+
+#[test]
+fn read_modify_write_writes_the_old_value_first() {
+    // INC $10
+    let mut cpu = cpu_with(&[0xE6, 0x10]);
+    cpu.bus.mem_write(0x0010, 0x7F);
+    cpu.bus.take_access_log();
+    assert_eq!(step_cycles(&mut cpu), 5);
+    assert_eq!(
+        accesses(&mut cpu),
+        ["r0600=E6", "r0601=10", "r0010=7F", "w0010=7F", "w0010=80"]
+    );
+
+    // ASL $10,X reads the unindexed zero page address while adding X
+    let mut cpu = cpu_with(&[0x16, 0xF8]);
+    cpu.register_x = 0x10;
+    cpu.bus.mem_write(0x0008, 0x81);
+    cpu.bus.take_access_log();
+    assert_eq!(step_cycles(&mut cpu), 6);
+    assert_eq!(
+        accesses(&mut cpu),
+        ["r0600=16", "r0601=F8", "r00F8=00", "r0008=81", "w0008=81", "w0008=02"]
+    );
+}
+
+#[test]
+fn subroutines_read_the_stack_for_nothing() {
+    // JSR $0700 ... $0700: RTS
+    let mut cpu = cpu_with(&[0x20, 0x00, 0x07]);
+    cpu.bus.mem_write(0x0700, 0x60);
+    cpu.stack_pointer = 0xFD;
+    cpu.bus.take_access_log();
+
+    assert_eq!(step_cycles(&mut cpu), 6);
+    assert_eq!(
+        accesses(&mut cpu),
+        ["r0600=20", "r0601=00", "r01FD=00", "w01FD=06", "w01FC=02", "r0602=07"]
+    );
+    assert_eq!(cpu.program_counter, 0x0700);
+
+    assert_eq!(step_cycles(&mut cpu), 6);
+    assert_eq!(
+        accesses(&mut cpu),
+        ["r0700=60", "r0701=00", "r01FB=00", "r01FC=02", "r01FD=06", "r0602=07"]
+    );
+    assert_eq!(cpu.program_counter, 0x0603);
+}
+
+#[test]
+fn taken_branches_read_the_next_opcode() {
+    // BNE +$10 from $06F0, crossing into page 7
+    let mut cpu = cpu_with(&[]);
+    cpu.bus.mem_write(0x06F0, 0xD0);
+    cpu.bus.mem_write(0x06F1, 0x10);
+    cpu.program_counter = 0x06F0;
+    cpu.bus.take_access_log();
+
+    assert_eq!(step_cycles(&mut cpu), 4);
+    assert_eq!(
+        accesses(&mut cpu),
+        ["r06F0=D0", "r06F1=10", "r06F2=00", "r0602=00"]
+    );
+    assert_eq!(cpu.program_counter, 0x0702);
+}
+
+#[test]
+fn interrupts_push_after_two_dummy_reads() {
+    let mut cpu = cpu_with(&[0xEA]);
+    cpu.bus.mem_write_u16(0xFFFA, 0x0700);
+    cpu.stack_pointer = 0xFD;
+    cpu.status.insert(0b1100_0001);
+    cpu.bus.set_nmi(true);
+    cpu.bus.take_access_log();
+
+    assert_eq!(step_cycles(&mut cpu), 7);
+    assert_eq!(
+        accesses(&mut cpu),
+        ["r0600=EA", "r0600=EA", "w01FD=06", "w01FC=00", "w01FB=E1", "rFFFA=00", "rFFFB=07"]
+    );
+    assert_eq!(cpu.program_counter, 0x0700);
+}
+
+/// Tiny xorshift generator, so the cases are the same on every run.
+struct Random(u32);
+
+impl Random {
+    fn byte(&mut self) -> u8 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 24) as u8
+    }
+}
+
+#[test]
+fn both_cores_agree_on_every_opcode() {
+    let mut random = Random(0x2A03_2A03);
+    for code in 0..=0xFF_u8 {
+        for case in 0..8 {
+            // random registers, and random bytes where operands, pointers and
+            // the stack are likely to be
+            let pc = u16::from_le_bytes([random.byte(), random.byte()]) | 0x0200;
+            let registers = [random.byte(), random.byte(), random.byte(), random.byte()];
+            let status = random.byte();
+            let mut memory = vec![(pc, code)];
+            for offset in 1..3 {
+                memory.push((pc.wrapping_add(offset), random.byte()));
+            }
+            for address in 0x0000..0x0200 {
+                memory.push((address, random.byte()));
+            }
+
+            let mut results = Vec::new();
+            for &core in &[CpuCore::Instruction, CpuCore::CycleAccurate] {
+                let mut cpu = CPU::with_bus(FlatBus::new());
+                cpu.set_core(core);
+                for &(address, data) in &memory {
+                    cpu.bus.mem_write(address, data);
+                }
+                cpu.program_counter = pc;
+                cpu.accumulator = registers[0];
+                cpu.register_x = registers[1];
+                cpu.register_y = registers[2];
+                cpu.stack_pointer = registers[3];
+                cpu.status.insert(status);
+                cpu.bus.log_accesses(true);
+
+                let result = cpu.step();
+                let log = cpu.bus.take_access_log();
+                if core == CpuCore::CycleAccurate {
+                    if let Ok(StopReason::Stepped { cycles }) = result {
+                        assert_eq!(
+                            cycles as usize,
+                            log.len(),
+                            "${:02X} case {}: one access per cycle",
+                            code,
+                            case
+                        );
+                    }
+                }
+                let written: Vec<(u16, u8)> = log
+                    .iter()
+                    .filter(|access: &&BusAccess| access.access == Access::Write)
+                    .map(|access| (access.address, cpu.bus.peek(access.address)))
+                    .collect();
+                results.push((
+                    result,
+                    cpu.program_counter,
+                    [
+                        cpu.accumulator,
+                        cpu.register_x,
+                        cpu.register_y,
+                        cpu.stack_pointer,
+                        cpu.status.get(),
+                    ],
+                    written,
+                ));
+            }
+            let (fast, accurate) = (&results[0], &results[1]);
+            assert!(
+                fast.0 == accurate.0 && fast.1 == accurate.1 && fast.2 == accurate.2,
+                "${:02X} case {}: instruction core {:?} PC {:04X} regs {:02X?}, \
+                 cycle core {:?} PC {:04X} regs {:02X?}",
+                code,
+                case,
+                fast.0,
+                fast.1,
+                fast.2,
+                accurate.0,
+                accurate.1,
+                accurate.2
+            );
+            // the dummy writes of the cycle core go to the same addresses
+            for write in &fast.3 {
+                assert!(
+                    accurate.3.contains(write),
+                    "${:02X} case {}: write {:04X?} missing from the cycle core",
+                    code,
+                    case,
+                    write
+                );
+            }
+        }
+    }
+}
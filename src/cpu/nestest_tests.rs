@@ -0,0 +1,68 @@
+//! Runs nestest.nes in automation mode and diffs every executed instruction
+//! against the reference log. Neither file is redistributable here, so put
+//! them in `tests/fixtures` (see the README there) and run
+//! `cargo test nestest -- --ignored`.
+
+use super::{trace, CpuCore, Status, CPU};
+use crate::cartridge::Rom;
+use std::path::PathBuf;
+
+fn fixture(name: &str) -> Vec<u8> {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", name]
+        .iter()
+        .collect();
+    std::fs::read(&path).unwrap_or_else(|err| panic!("cannot read {}: {}", path.display(), err))
+}
+
+#[test]
+#[ignore = "needs tests/fixtures/nestest.nes and nestest.log"]
+fn nestest_matches_reference_log() {
+    run_nestest(CpuCore::Instruction);
+}
+
+/// The log is taken between instructions, which the cycle-accurate core
+/// must reach at the same cycles.
+#[test]
+#[ignore = "needs tests/fixtures/nestest.nes and nestest.log"]
+fn nestest_matches_reference_log_cycle_accurate() {
+    run_nestest(CpuCore::CycleAccurate);
+}
+
+fn run_nestest(core: CpuCore) {
+    let rom = fixture("nestest.nes");
+    let log = String::from_utf8(fixture("nestest.log")).expect("nestest.log is not UTF-8");
+
+    let mut cpu = CPU::new();
+    cpu.bus
+        .insert_cartridge(Rom::new(&rom).expect("nestest.nes is not a valid iNES file"))
+        .unwrap();
+    cpu.set_core(core);
+    cpu.reset();
+    // automation mode starts at $C000 instead of the reset vector, with the
+    // power-up state of the reference log
+    cpu.program_counter = 0xC000;
+    cpu.status.insert(Status::INTERRUPT_DISABLE);
+
+    let mut previous = String::new();
+    for (index, expected) in log.lines().map(str::trim_end).enumerate() {
+        if expected.is_empty() {
+            continue;
+        }
+        let actual = trace(&cpu);
+        assert!(
+            actual == expected,
+            "nestest diverged at line {}\n  previous: {}\n  expected: {}\n  actual:   {}",
+            index + 1,
+            previous,
+            expected,
+            actual
+        );
+        previous = actual;
+        cpu.step().unwrap();
+    }
+
+    // nestest leaves the result codes of the official and unofficial opcode
+    // tests in $02 and $03
+    assert_eq!(0, cpu.bus.peek(0x02), "official opcode tests failed");
+    assert_eq!(0, cpu.bus.peek(0x03), "unofficial opcode tests failed");
+}
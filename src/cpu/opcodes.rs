@@ -1,171 +1,385 @@
 use std::fmt::Display;
 
-use super::{AddressingMode, Status, CPU};
+use super::{AddressingMode, Status, CPU, IRQ_VECTOR};
+use crate::bus::{CpuBus, Mem};
+
+/// The operation an opcode performs, official and unofficial ones, whatever
+/// its addressing mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Adc,
+    And,
+    Asl,
+    Bcc,
+    Bcs,
+    Beq,
+    Bit,
+    Bmi,
+    Bne,
+    Bpl,
+    Brk,
+    Bvc,
+    Bvs,
+    Clc,
+    Cld,
+    Cli,
+    Clv,
+    Cmp,
+    Cpx,
+    Cpy,
+    Dec,
+    Dex,
+    Dey,
+    Eor,
+    Inc,
+    Inx,
+    Iny,
+    Jmp,
+    Jsr,
+    Lda,
+    Ldx,
+    Ldy,
+    Lsr,
+    Nop,
+    Ora,
+    Pha,
+    Php,
+    Pla,
+    Plp,
+    Rol,
+    Ror,
+    Rti,
+    Rts,
+    Sbc,
+    Sec,
+    Sed,
+    Sei,
+    Sta,
+    Stx,
+    Sty,
+    Tax,
+    Tay,
+    Tsx,
+    Txa,
+    Txs,
+    Tya,
+    // unofficial
+    Alr,
+    Anc,
+    Arr,
+    Axs,
+    Dcp,
+    Isb,
+    Jam,
+    Las,
+    Lax,
+    Lxa,
+    Rla,
+    Rra,
+    Sax,
+    Sha,
+    Shx,
+    Shy,
+    Slo,
+    Sre,
+    Tas,
+    Xaa,
+}
+
+impl Instruction {
+    /// The assembler name, `"LDA"`.
+    pub fn mnemonic(self) -> &'static str {
+        use Instruction::*;
+        match self {
+            Adc => "ADC",
+            And => "AND",
+            Asl => "ASL",
+            Bcc => "BCC",
+            Bcs => "BCS",
+            Beq => "BEQ",
+            Bit => "BIT",
+            Bmi => "BMI",
+            Bne => "BNE",
+            Bpl => "BPL",
+            Brk => "BRK",
+            Bvc => "BVC",
+            Bvs => "BVS",
+            Clc => "CLC",
+            Cld => "CLD",
+            Cli => "CLI",
+            Clv => "CLV",
+            Cmp => "CMP",
+            Cpx => "CPX",
+            Cpy => "CPY",
+            Dec => "DEC",
+            Dex => "DEX",
+            Dey => "DEY",
+            Eor => "EOR",
+            Inc => "INC",
+            Inx => "INX",
+            Iny => "INY",
+            Jmp => "JMP",
+            Jsr => "JSR",
+            Lda => "LDA",
+            Ldx => "LDX",
+            Ldy => "LDY",
+            Lsr => "LSR",
+            Nop => "NOP",
+            Ora => "ORA",
+            Pha => "PHA",
+            Php => "PHP",
+            Pla => "PLA",
+            Plp => "PLP",
+            Rol => "ROL",
+            Ror => "ROR",
+            Rti => "RTI",
+            Rts => "RTS",
+            Sbc => "SBC",
+            Sec => "SEC",
+            Sed => "SED",
+            Sei => "SEI",
+            Sta => "STA",
+            Stx => "STX",
+            Sty => "STY",
+            Tax => "TAX",
+            Tay => "TAY",
+            Tsx => "TSX",
+            Txa => "TXA",
+            Txs => "TXS",
+            Tya => "TYA",
+            Alr => "ALR",
+            Anc => "ANC",
+            Arr => "ARR",
+            Axs => "AXS",
+            Dcp => "DCP",
+            Isb => "ISB",
+            Jam => "JAM",
+            Las => "LAS",
+            Lax => "LAX",
+            Lxa => "LXA",
+            Rla => "RLA",
+            Rra => "RRA",
+            Sax => "SAX",
+            Sha => "SHA",
+            Shx => "SHX",
+            Shy => "SHY",
+            Slo => "SLO",
+            Sre => "SRE",
+            Tas => "TAS",
+            Xaa => "XAA",
+        }
+    }
+
+    /// Unofficial instructions whose result depends on analog effects and
+    /// differs between chips.
+    pub fn is_unstable(self) -> bool {
+        matches!(
+            self,
+            Instruction::Xaa
+                | Instruction::Lxa
+                | Instruction::Sha
+                | Instruction::Shx
+                | Instruction::Shy
+                | Instruction::Tas
+        )
+    }
+}
 
-#[derive(Clone, Copy)]
-pub struct Opcode<'a> {
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.mnemonic())
+    }
+}
+
+/// What the CPU, the tracer and the disassembler know about an opcode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Opcode {
     pub code: u8,
-    pub mnemonic: &'a str,
+    pub instruction: Instruction,
     pub length: u8,
     pub cycles: u8,
     pub mode: AddressingMode,
+    /// Not documented by MOS; NOP and SBC have unofficial opcodes as well.
+    pub unofficial: bool,
 }
 
-impl<'a> Opcode<'a> {
-    fn new(code: u8, mnemonic: &'a str, length: u8, cycles: u8, mode: AddressingMode) -> Self {
+impl Opcode {
+    const fn new(
+        code: u8,
+        instruction: Instruction,
+        length: u8,
+        cycles: u8,
+        mode: AddressingMode,
+    ) -> Self {
         Opcode {
             code,
-            mnemonic,
+            instruction,
             length,
             cycles,
             mode,
+            unofficial: false,
         }
     }
 
-    fn basic() -> Self {
+    const fn unofficial(
+        code: u8,
+        instruction: Instruction,
+        length: u8,
+        cycles: u8,
+        mode: AddressingMode,
+    ) -> Self {
         Opcode {
-            code: 0x02,
-            mnemonic: "NUL",
-            length: 0,
-            cycles: 0,
-            mode: AddressingMode::Immediate,
+            unofficial: true,
+            ..Opcode::new(code, instruction, length, cycles, mode)
         }
     }
 }
 
-impl<'a> Display for Opcode<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.mnemonic)
-    }
-}
-
-impl<'a> CPU<'a> {
+impl<B: CpuBus> CPU<B> {
     pub fn interpret(&mut self, opcode: &Opcode) -> bool {
-        match opcode.code {
-            0x00 => {
-                self.increment_program_counter(opcode.length);
-                return false;
+        if opcode.instruction.is_unstable() && !self.unstable_opcode_allowed(opcode) {
+            return false;
+        }
+
+        match opcode.instruction {
+            Instruction::Brk => {
+                if self.halt_on_brk {
+                    self.increment_program_counter(opcode.length);
+                    return false;
+                }
+                self.brk();
             }
 
-            0x69 | 0x65 | 0x75 | 0x6D | 0x7D | 0x79 | 0x61 | 0x71 => self.adc(opcode),
+            Instruction::Adc => self.adc(opcode),
+            Instruction::And => self.and(opcode),
+            Instruction::Asl => self.asl(opcode),
 
-            0x29 | 0x25 | 0x35 | 0x2D | 0x3D | 0x39 | 0x21 | 0x31 => self.and(opcode),
+            Instruction::Bcc => self.bcc(),
+            Instruction::Bcs => self.bcs(),
+            Instruction::Beq => self.beq(),
 
-            0x0A | 0x06 | 0x16 | 0x0E | 0x1E => self.asl(opcode),
+            Instruction::Bit => self.bit(opcode),
 
-            0x90 => self.bcc(),
-            0xB0 => self.bcs(),
-            0xF0 => self.beq(),
+            Instruction::Bmi => self.bmi(),
+            Instruction::Bne => self.bne(),
+            Instruction::Bpl => self.bpl(),
+            Instruction::Bvc => self.bvc(),
+            Instruction::Bvs => self.bvs(),
 
-            0x24 | 0x2C => self.bit(opcode),
+            Instruction::Cmp => self.cmp(opcode),
+            Instruction::Cpx => self.cpx(opcode),
+            Instruction::Cpy => self.cpy(opcode),
 
-            0x30 => self.bmi(),
-            0xD0 => self.bne(),
-            0x10 => self.bpl(),
-            0x50 => self.bvc(),
-            0x70 => self.bvs(),
+            Instruction::Clc => self.clc(),
+            Instruction::Cld => self.cld(),
+            Instruction::Cli => self.cli(),
+            Instruction::Clv => self.clv(),
 
-            0xC9 | 0xC5 | 0xD5 | 0xCD | 0xDD | 0xD9 | 0xC1 | 0xD1 => self.cmp(opcode),
+            Instruction::Dec => self.dec(opcode),
+            Instruction::Dex => self.dex(),
+            Instruction::Dey => self.dey(),
 
-            0xE0 | 0xE4 | 0xEC => self.cpx(opcode),
+            Instruction::Eor => self.eor(opcode),
 
-            0xC0 | 0xC4 | 0xCC => self.cpy(opcode),
+            Instruction::Inc => self.inc(opcode),
+            Instruction::Inx => self.inx(opcode),
+            Instruction::Iny => self.iny(opcode),
 
-            0x18 => self.clc(),
-            0xD8 => self.cld(),
-            0x58 => self.cli(),
-            0xB8 => self.clv(),
+            // JMP ($nnnn) has no addressing mode of its own
+            Instruction::Jmp if opcode.mode == AddressingMode::Indirect => self.jmp_indirect(),
+            Instruction::Jmp => self.jmp_absolute(),
+            Instruction::Jsr => self.jsr(),
 
-            0xC6 | 0xD6 | 0xCE | 0xDE => self.dec(opcode),
-            0xCA => self.dex(),
-            0x88 => self.dey(),
+            Instruction::Lda => self.lda(opcode),
+            Instruction::Ldx => self.ldx(opcode),
+            Instruction::Ldy => self.ldy(opcode),
 
-            0x49 | 0x45 | 0x55 | 0x4D | 0x5D | 0x59 | 0x41 | 0x51 => self.eor(opcode),
+            Instruction::Lsr => self.lsr(opcode),
 
-            0xE6 | 0xF6 | 0xEE | 0xFE => self.inc(opcode),
-            0xE8 => self.inx(opcode),
-            0xC8 => self.iny(opcode),
+            // the unofficial NOPs with an operand still read it
+            Instruction::Nop if opcode.mode == AddressingMode::Implied => self.nop(),
+            Instruction::Nop => self.nop_read(opcode),
 
-            0x4C => self.jmp_absolute(),
-            0x6C => self.jmp_indirect(),
-            0x20 => self.jsr(),
+            Instruction::Ora => self.ora(opcode),
 
-            0xA9 | 0xA5 | 0xB5 | 0xAD | 0xBD | 0xB9 | 0xA1 | 0xB1 => self.lda(opcode),
-            0xA2 | 0xA6 | 0xB6 | 0xAE | 0xBE => self.ldx(opcode),
-            0xA0 | 0xA4 | 0xB4 | 0xAC | 0xBC => self.ldy(opcode),
+            Instruction::Pha => self.pha(),
+            Instruction::Php => self.php(),
+            Instruction::Pla => self.pla(),
+            Instruction::Plp => self.plp(),
 
-            0x4A | 0x46 | 0x56 | 0x4E | 0x5E => self.lsr(opcode),
+            Instruction::Rol => self.rol(opcode),
+            Instruction::Ror => self.ror(opcode),
 
-            0xEA => self.nop(),
+            Instruction::Rti => self.rti(),
+            Instruction::Rts => self.rts(),
 
-            0x09 | 0x05 | 0x15 | 0x0D | 0x1D | 0x19 | 0x01 | 0x11 => self.ora(opcode),
+            Instruction::Sbc => self.sbc(opcode),
 
-            0x48 => self.pha(),
-            0x08 => self.php(),
-            0x68 => self.pla(),
-            0x28 => self.plp(),
+            Instruction::Sec => self.sec(),
+            Instruction::Sed => self.sed(),
+            Instruction::Sei => self.sei(),
 
-            0x2A | 0x26 | 0x36 | 0x2E | 0x3E => self.rol(opcode),
-            0x6A | 0x66 | 0x76 | 0x6E | 0x7E => self.ror(opcode),
+            Instruction::Sta => self.sta(opcode),
+            Instruction::Stx => self.stx(opcode),
+            Instruction::Sty => self.sty(opcode),
 
-            0x40 => self.rti(),
-            0x60 => self.rts(),
+            Instruction::Tax => self.tax(),
+            Instruction::Tay => self.tay(),
+            Instruction::Tsx => self.tsx(),
+            Instruction::Txa => self.txa(),
+            Instruction::Txs => self.txs(),
+            Instruction::Tya => self.tya(),
 
-            0xE9 | 0xE5 | 0xF5 | 0xED | 0xFD | 0xF9 | 0xE1 | 0xF1 => self.sbc(opcode),
+            // unofficial opcodes
+            Instruction::Lax => self.lax(opcode),
+            Instruction::Sax => self.sax(opcode),
 
-            0x38 => self.sec(),
-            0xF8 => self.sed(),
-            0x78 => self.sei(),
+            Instruction::Dcp => self.dcp(opcode),
+            Instruction::Isb => self.isb(opcode),
+            Instruction::Slo => self.slo(opcode),
+            Instruction::Rla => self.rla(opcode),
+            Instruction::Sre => self.sre(opcode),
+            Instruction::Rra => self.rra(opcode),
 
-            0x85 | 0x95 | 0x8D | 0x9D | 0x99 | 0x81 | 0x91 => self.sta(opcode),
-            0x86 | 0x96 | 0x8E => self.stx(opcode),
-            0x84 | 0x94 | 0x8C => self.sty(opcode),
+            Instruction::Anc => self.anc(opcode),
+            Instruction::Alr => self.alr(opcode),
+            Instruction::Arr => self.arr(opcode),
+            Instruction::Axs => self.axs(opcode),
+            Instruction::Las => self.las(opcode),
 
-            0xAA => self.tax(),
-            0xA8 => self.tay(),
-            0xBA => self.tsx(),
-            0x8A => self.txa(),
-            0x9A => self.txs(),
-            0x98 => self.tya(),
+            Instruction::Xaa => self.xaa(opcode),
+            Instruction::Lxa => self.lxa(opcode),
+            Instruction::Sha => self.sha(opcode),
+            Instruction::Shy => self.shy(opcode),
+            Instruction::Shx => self.shx(opcode),
+            Instruction::Tas => self.tas(opcode),
 
-            _ => panic!("Unknown opcode: {:#x}", opcode.code),
+            Instruction::Jam => return self.jam(opcode),
         }
 
         true
     }
 
     fn adc(&mut self, opcode: &Opcode) {
-        let address = self.get_operand_address(opcode.mode);
-        let value = self.mem_read(address);
+        let value = self.read_operand(opcode.mode);
         self.add_to_accumulator(value);
         self.update_zero_and_negative_flags(self.accumulator);
         self.increment_program_counter(opcode.length);
     }
 
     fn and(&mut self, opcode: &Opcode) {
-        let address = self.get_operand_address(opcode.mode);
-        let value = self.mem_read(address);
+        let value = self.read_operand(opcode.mode);
         self.accumulator &= value;
         self.update_zero_and_negative_flags(self.accumulator);
         self.increment_program_counter(opcode.length);
     }
 
     fn asl(&mut self, opcode: &Opcode) {
-        let value;
-        if opcode.mode == AddressingMode::None {
-            value = self.accumulator;
+        if opcode.mode == AddressingMode::Accumulator {
+            self.accumulator = self.shift_left(self.accumulator, false);
         } else {
-            let address = self.get_operand_address(opcode.mode);
-            value = self.mem_read(address);
-        }
-
-        let result = (value as u16) << 1;
-        if result > 0xff {
-            self.status.set(Status::CARRY)
+            self.modify_memory(opcode.mode, |cpu, value| cpu.shift_left(value, false));
         }
-
-        self.accumulator = result as u8;
-        self.update_zero_and_negative_flags(self.accumulator);
         self.increment_program_counter(opcode.length);
     }
 
@@ -182,9 +396,13 @@ impl<'a> CPU<'a> {
     }
 
     fn bit(&mut self, opcode: &Opcode) {
-        let address = self.get_operand_address(opcode.mode);
-        let value = self.mem_read(address);
+        let value = self.read_operand(opcode.mode);
+        self.test_bits(value);
+        self.increment_program_counter(opcode.length);
+    }
 
+    /// BIT: Z from A AND `value`, N and V from its top bits.
+    pub(super) fn test_bits(&mut self, value: u8) {
         let result = self.accumulator & value;
         if result == 0 {
             self.status.set(Status::ZERO);
@@ -202,8 +420,6 @@ impl<'a> CPU<'a> {
         } else {
             self.status.reset(Status::OVERFLOW);
         }
-
-        self.increment_program_counter(opcode.length);
     }
 
     fn bmi(&mut self) {
@@ -267,10 +483,7 @@ impl<'a> CPU<'a> {
     }
 
     fn dec(&mut self, opcode: &Opcode) {
-        let address = self.get_operand_address(opcode.mode);
-        let value = self.mem_read(address);
-        let result = value.wrapping_sub(1);
-        self.mem_write(address, result);
+        let result = self.modify_memory(opcode.mode, |_, value| value.wrapping_sub(1));
         self.update_zero_and_negative_flags(result);
         self.increment_program_counter(opcode.length);
     }
@@ -286,8 +499,7 @@ impl<'a> CPU<'a> {
     }
 
     fn eor(&mut self, opcode: &Opcode) {
-        let address = self.get_operand_address(opcode.mode);
-        let value = self.mem_read(address);
+        let value = self.read_operand(opcode.mode);
         let result = self.accumulator ^ value;
         self.accumulator = result;
         self.update_zero_and_negative_flags(result);
@@ -295,10 +507,7 @@ impl<'a> CPU<'a> {
     }
 
     fn inc(&mut self, opcode: &Opcode) {
-        let address = self.get_operand_address(opcode.mode);
-        let value = self.mem_read(address);
-        let result = value.wrapping_add(1);
-        self.mem_write(address, result);
+        let result = self.modify_memory(opcode.mode, |_, value| value.wrapping_add(1));
         self.update_zero_and_negative_flags(result);
         self.increment_program_counter(opcode.length);
     }
@@ -339,14 +548,13 @@ impl<'a> CPU<'a> {
     }
 
     fn jsr(&mut self) {
-        self.push_u16(self.program_counter + 2 - 1);
+        self.push_u16(self.program_counter.wrapping_add(1));
         let address = self.mem_read_u16(self.program_counter);
         self.program_counter = address;
     }
 
     fn lda(&mut self, opcode: &Opcode) {
-        let address = self.get_operand_address(opcode.mode);
-        let value = self.mem_read(address);
+        let value = self.read_operand(opcode.mode);
 
         self.accumulator = value;
         self.update_zero_and_negative_flags(self.accumulator);
@@ -354,8 +562,7 @@ impl<'a> CPU<'a> {
     }
 
     fn ldx(&mut self, opcode: &Opcode) {
-        let address = self.get_operand_address(opcode.mode);
-        let value = self.mem_read(address);
+        let value = self.read_operand(opcode.mode);
 
         self.register_x = value;
         self.update_zero_and_negative_flags(self.register_x);
@@ -363,8 +570,7 @@ impl<'a> CPU<'a> {
     }
 
     fn ldy(&mut self, opcode: &Opcode) {
-        let address = self.get_operand_address(opcode.mode);
-        let value = self.mem_read(address);
+        let value = self.read_operand(opcode.mode);
 
         self.register_y = value;
         self.update_zero_and_negative_flags(self.register_y);
@@ -372,33 +578,18 @@ impl<'a> CPU<'a> {
     }
 
     fn lsr(&mut self, opcode: &Opcode) {
-        let before;
-        if opcode.mode == AddressingMode::None {
-            before = self.accumulator;
-            self.accumulator = self.accumulator >> 1;
+        if opcode.mode == AddressingMode::Accumulator {
+            self.accumulator = self.shift_right(self.accumulator, false);
         } else {
-            let address = self.get_operand_address(opcode.mode);
-            let value = self.mem_read(address);
-            before = value;
-            self.mem_write(address, value >> 1);
-        }
-
-        let carry = before & 0x01;
-        if carry == 1 {
-            self.status.set(Status::CARRY);
-        } else {
-            self.status.reset(Status::CARRY);
+            self.modify_memory(opcode.mode, |cpu, value| cpu.shift_right(value, false));
         }
-
-        self.update_zero_and_negative_flags(before >> 1);
         self.increment_program_counter(opcode.length);
     }
 
     fn nop(&self) {}
 
     fn ora(&mut self, opcode: &Opcode) {
-        let address = self.get_operand_address(opcode.mode);
-        let value = self.mem_read(address);
+        let value = self.read_operand(opcode.mode);
 
         self.accumulator |= value;
         self.update_zero_and_negative_flags(self.accumulator);
@@ -409,8 +600,16 @@ impl<'a> CPU<'a> {
         self.push(self.accumulator);
     }
 
+    fn brk(&mut self) {
+        // BRK skips a padding byte after the opcode
+        self.push_u16(self.program_counter.wrapping_add(1));
+        self.push(self.status.get() | Status::BREAK | Status::BREAK2);
+        self.status.set(Status::INTERRUPT_DISABLE);
+        self.program_counter = self.mem_read_u16(IRQ_VECTOR);
+    }
+
     fn php(&mut self) {
-        self.push(self.status.get());
+        self.push(self.status.get() | Status::BREAK | Status::BREAK2);
     }
 
     fn pla(&mut self) {
@@ -420,123 +619,61 @@ impl<'a> CPU<'a> {
 
     fn plp(&mut self) {
         let data = self.pop();
-        self.status.insert(data);
+        self.status.insert(data & !(Status::BREAK | Status::BREAK2));
     }
 
     fn rol(&mut self, opcode: &Opcode) {
-        let carry;
-        let result;
-        if opcode.mode == AddressingMode::None {
-            carry = self.accumulator & 0x80;
-            self.accumulator = self.accumulator << 1;
-            self.accumulator |= self.status.get() & Status::CARRY;
-            result = self.accumulator;
-        } else {
-            let address = self.get_operand_address(opcode.mode);
-            let mut value = self.mem_read(address);
-            carry = value & 0x80;
-            value = value << 1;
-            value |= self.status.get() & Status::CARRY;
-            self.mem_write(address, value);
-            result = value;
-        }
-
-        if carry == 0x80 {
-            self.status.set(Status::CARRY);
-        } else {
-            self.status.reset(Status::CARRY);
-        }
-        if result & 0x80 == 0x80 {
-            self.status.set(Status::NEGATIV);
+        let carry = self.status.contains(Status::CARRY);
+        if opcode.mode == AddressingMode::Accumulator {
+            self.accumulator = self.shift_left(self.accumulator, carry);
         } else {
-            self.status.reset(Status::NEGATIV);
-        }
-        if self.accumulator == 0 {
-            self.status.set(Status::ZERO);
-        } else {
-            self.status.reset(Status::ZERO);
+            self.modify_memory(opcode.mode, |cpu, value| cpu.shift_left(value, carry));
         }
         self.increment_program_counter(opcode.length);
     }
 
     fn ror(&mut self, opcode: &Opcode) {
-        let carry;
-        let result;
-        if opcode.mode == AddressingMode::None {
-            carry = self.accumulator & 0x01;
-            self.accumulator = self.accumulator >> 1;
-            let old_carry = self.status.get() & Status::CARRY;
-            if old_carry == 1 {
-                self.accumulator |= 0x80;
-            }
-            result = self.accumulator;
-        } else {
-            let address = self.get_operand_address(opcode.mode);
-            let mut value = self.mem_read(address);
-            carry = value & 0x01;
-            value = value >> 1;
-            let old_carry = self.status.get() & Status::CARRY;
-            if old_carry == 1 {
-                value |= 0x80;
-            }
-            self.mem_write(address, value);
-            result = value;
-        }
-
-        if carry == 1 {
-            self.status.set(Status::CARRY);
+        let carry = self.status.contains(Status::CARRY);
+        if opcode.mode == AddressingMode::Accumulator {
+            self.accumulator = self.shift_right(self.accumulator, carry);
         } else {
-            self.status.reset(Status::CARRY);
-        }
-        if result & 0x80 == 0x80 {
-            self.status.set(Status::NEGATIV);
-        } else {
-            self.status.reset(Status::NEGATIV);
-        }
-        if self.accumulator == 0 {
-            self.status.set(Status::ZERO);
-        } else {
-            self.status.reset(Status::ZERO);
+            self.modify_memory(opcode.mode, |cpu, value| cpu.shift_right(value, carry));
         }
         self.increment_program_counter(opcode.length);
     }
 
     fn rti(&mut self) {
         let flags = self.pop();
-        self.status.insert(flags);
-        self.status.reset(Status::BREAK);
-        self.status.insert(Status::BREAK2);
+        self.status
+            .insert(flags & !(Status::BREAK | Status::BREAK2));
 
         self.program_counter = self.pop_u16();
     }
 
     fn rts(&mut self) {
-        self.program_counter = self.pop_u16() + 1;
+        self.program_counter = self.pop_u16().wrapping_add(1);
     }
 
     fn sbc(&mut self, opcode: &Opcode) {
-        let address = self.get_operand_address(opcode.mode);
-        let mut value = self.mem_read(address);
-        value = !value + 1;
-        self.add_to_accumulator(value);
-        self.update_zero_and_negative_flags(self.accumulator);
+        let value = self.read_operand(opcode.mode);
+        self.subtract_from_accumulator(value);
         self.increment_program_counter(opcode.length);
     }
 
     fn sta(&mut self, opcode: &Opcode) {
-        let address = self.get_operand_address(opcode.mode);
+        let (address, _) = self.get_operand_address(opcode.mode);
         self.mem_write(address, self.accumulator);
         self.increment_program_counter(opcode.length);
     }
 
     fn stx(&mut self, opcode: &Opcode) {
-        let address = self.get_operand_address(opcode.mode);
+        let (address, _) = self.get_operand_address(opcode.mode);
         self.mem_write(address, self.register_x);
         self.increment_program_counter(opcode.length);
     }
 
     fn sty(&mut self, opcode: &Opcode) {
-        let address = self.get_operand_address(opcode.mode);
+        let (address, _) = self.get_operand_address(opcode.mode);
         self.mem_write(address, self.register_y);
         self.increment_program_counter(opcode.length);
     }
@@ -552,7 +689,7 @@ impl<'a> CPU<'a> {
     }
 
     fn tsx(&mut self) {
-        self.register_x = self.stack_pointer as u8;
+        self.register_x = self.stack_pointer;
         self.update_zero_and_negative_flags(self.register_x);
     }
 
@@ -562,7 +699,7 @@ impl<'a> CPU<'a> {
     }
 
     fn txs(&mut self) {
-        self.stack_pointer = (self.register_x as u16) | 0x100;
+        self.stack_pointer = self.register_x;
     }
 
     fn tya(&mut self) {
@@ -570,7 +707,7 @@ impl<'a> CPU<'a> {
         self.update_zero_and_negative_flags(self.accumulator);
     }
 
-    fn update_zero_and_negative_flags(&mut self, result: u8) {
+    pub(super) fn update_zero_and_negative_flags(&mut self, result: u8) {
         if result == 0 {
             self.status.set(Status::ZERO);
         } else {
@@ -584,7 +721,7 @@ impl<'a> CPU<'a> {
         }
     }
 
-    fn add_to_accumulator(&mut self, data: u8) {
+    pub(super) fn add_to_accumulator(&mut self, data: u8) {
         let carry = if self.status.get() & 0x01 == 1 { 1 } else { 0 };
         let sum = self.accumulator as u16 + data as u16 + carry;
 
@@ -606,9 +743,12 @@ impl<'a> CPU<'a> {
     }
 
     fn compare(&mut self, opcode: &Opcode, data: u8) {
-        let address = self.get_operand_address(opcode.mode);
-        let value = self.mem_read(address);
+        let value = self.read_operand(opcode.mode);
+        self.compare_values(data, value);
+        self.increment_program_counter(opcode.length);
+    }
 
+    pub(super) fn compare_values(&mut self, data: u8, value: u8) {
         if value <= data {
             self.status.set(Status::CARRY);
         } else {
@@ -616,208 +756,393 @@ impl<'a> CPU<'a> {
         }
 
         self.update_zero_and_negative_flags(data.wrapping_sub(value));
-        self.increment_program_counter(opcode.length);
     }
 
+    /// A - M - (1 - C), which is A + !M + C.
+    pub(super) fn subtract_from_accumulator(&mut self, value: u8) {
+        self.add_to_accumulator(!value);
+        self.update_zero_and_negative_flags(self.accumulator);
+    }
+
+    /// Read-modify-write on the operand, returns the value written back.
+    pub(super) fn modify_memory<F>(&mut self, mode: AddressingMode, modify: F) -> u8
+    where
+        F: FnOnce(&mut Self, u8) -> u8,
+    {
+        let (address, _) = self.get_operand_address(mode);
+        let value = self.mem_read(address);
+        let result = modify(self, value);
+        self.mem_write(address, result);
+        result
+    }
+
+    /// ASL when `carry_in` is false, ROL otherwise.
+    pub(super) fn shift_left(&mut self, value: u8, carry_in: bool) -> u8 {
+        if value & 0x80 != 0 {
+            self.status.set(Status::CARRY);
+        } else {
+            self.status.reset(Status::CARRY);
+        }
+        let result = value << 1 | carry_in as u8;
+        self.update_zero_and_negative_flags(result);
+        result
+    }
+
+    /// LSR when `carry_in` is false, ROR otherwise.
+    pub(super) fn shift_right(&mut self, value: u8, carry_in: bool) -> u8 {
+        if value & 0x01 != 0 {
+            self.status.set(Status::CARRY);
+        } else {
+            self.status.reset(Status::CARRY);
+        }
+        let result = value >> 1 | (carry_in as u8) << 7;
+        self.update_zero_and_negative_flags(result);
+        result
+    }
+
+    /// A taken branch costs one extra cycle, two if the target is on another page.
     fn branch(&mut self, condition: bool) {
         let mut jump: i8 = 0;
         if condition {
             jump = self.mem_read(self.program_counter) as i8;
         }
-        self.program_counter = self
-                .program_counter
-                .wrapping_add(1)
-                .wrapping_add(jump as u16);
-    }
-
-    pub fn create_opcode_table() -> [Opcode<'a>; 0xFF] {
-        let mut opcode_table: [Opcode; 0xFF] = [Opcode::basic(); 0xFF];
-
-        opcode_table[0x69] = Opcode::new(0x69, "ADC", 2, 2, AddressingMode::Immediate);
-        opcode_table[0x65] = Opcode::new(0x65, "ADC", 2, 3, AddressingMode::ZeroPage);
-        opcode_table[0x75] = Opcode::new(0x75, "ADC", 2, 4, AddressingMode::ZeroPage_X);
-        opcode_table[0x6D] = Opcode::new(0x6D, "ADC", 3, 4, AddressingMode::Absolute);
-        opcode_table[0x7D] = Opcode::new(0x7D, "ADC", 3, 4, AddressingMode::Absolute_X);
-        opcode_table[0x79] = Opcode::new(0x79, "ADC", 3, 4, AddressingMode::Absolute_Y);
-        opcode_table[0x61] = Opcode::new(0x61, "ADC", 2, 6, AddressingMode::Indirect_X);
-        opcode_table[0x71] = Opcode::new(0x71, "ADC", 2, 5, AddressingMode::Indirect_Y);
-
-        opcode_table[0x29] = Opcode::new(0x29, "AND", 2, 2, AddressingMode::Immediate);
-        opcode_table[0x25] = Opcode::new(0x25, "AND", 2, 3, AddressingMode::ZeroPage);
-        opcode_table[0x35] = Opcode::new(0x35, "AND", 2, 4, AddressingMode::ZeroPage_X);
-        opcode_table[0x2D] = Opcode::new(0x2D, "AND", 3, 4, AddressingMode::Absolute);
-        opcode_table[0x3D] = Opcode::new(0x3D, "AND", 3, 4, AddressingMode::Absolute_X);
-        opcode_table[0x39] = Opcode::new(0x39, "AND", 3, 4, AddressingMode::Absolute_Y);
-        opcode_table[0x21] = Opcode::new(0x21, "AND", 2, 6, AddressingMode::Indirect_X);
-        opcode_table[0x31] = Opcode::new(0x31, "AND", 2, 5, AddressingMode::Indirect_Y);
-
-        opcode_table[0x0A] = Opcode::new(0x0A, "ASL", 1, 2, AddressingMode::None);
-        opcode_table[0x06] = Opcode::new(0x06, "ASL", 2, 5, AddressingMode::ZeroPage);
-        opcode_table[0x16] = Opcode::new(0x16, "ASL", 2, 6, AddressingMode::ZeroPage_X);
-        opcode_table[0x0E] = Opcode::new(0x0E, "ASL", 1, 6, AddressingMode::Absolute);
-        opcode_table[0x1E] = Opcode::new(0x1E, "ASL", 1, 7, AddressingMode::Absolute_X);
-
-        opcode_table[0x90] = Opcode::new(0x90, "BCC", 2, 2, AddressingMode::None);
-        opcode_table[0xB0] = Opcode::new(0xB0, "BCS", 2, 2, AddressingMode::None);
-        opcode_table[0xF0] = Opcode::new(0xF0, "BEQ", 2, 2, AddressingMode::None);
-
-        opcode_table[0x24] = Opcode::new(0x24, "BIT", 2, 3, AddressingMode::ZeroPage);
-        opcode_table[0x2C] = Opcode::new(0x2C, "BIT", 3, 4, AddressingMode::Absolute);
-
-        opcode_table[0x30] = Opcode::new(0x30, "BMI", 2, 2, AddressingMode::None);
-        opcode_table[0xD0] = Opcode::new(0xD0, "BNE", 2, 2, AddressingMode::None);
-        opcode_table[0x10] = Opcode::new(0x10, "BPL", 2, 2, AddressingMode::None);
-
-        opcode_table[0x00] = Opcode::new(0x00, "BRK", 1, 7, AddressingMode::None);
-
-        opcode_table[0x50] = Opcode::new(0x50, "BVC", 2, 2, AddressingMode::None);
-        opcode_table[0x70] = Opcode::new(0x70, "BVS", 2, 2, AddressingMode::None);
-
-        opcode_table[0x18] = Opcode::new(0x18, "CLC", 1, 2, AddressingMode::None);
-        opcode_table[0xD8] = Opcode::new(0xD8, "CLD", 1, 2, AddressingMode::None);
-        opcode_table[0x58] = Opcode::new(0x58, "CLI", 1, 2, AddressingMode::None);
-        opcode_table[0xB8] = Opcode::new(0xB8, "CLV", 1, 2, AddressingMode::None);
-
-        opcode_table[0xC9] = Opcode::new(0xC9, "CMP", 2, 2, AddressingMode::Immediate);
-        opcode_table[0xC5] = Opcode::new(0xC5, "CMP", 2, 3, AddressingMode::ZeroPage);
-        opcode_table[0xD5] = Opcode::new(0xD5, "CMP", 2, 4, AddressingMode::ZeroPage_X);
-        opcode_table[0xCD] = Opcode::new(0xCD, "CMP", 3, 4, AddressingMode::Absolute);
-        opcode_table[0xDD] = Opcode::new(0xDD, "CMP", 3, 4, AddressingMode::Absolute_X);
-        opcode_table[0xD9] = Opcode::new(0xD9, "CMP", 3, 4, AddressingMode::Absolute_Y);
-        opcode_table[0xC1] = Opcode::new(0xC1, "CMP", 2, 6, AddressingMode::Indirect_X);
-        opcode_table[0xD1] = Opcode::new(0xD1, "CMP", 2, 5, AddressingMode::Indirect_Y);
-
-        opcode_table[0xE0] = Opcode::new(0xE0, "CPX", 2, 2, AddressingMode::Immediate);
-        opcode_table[0xE4] = Opcode::new(0xE4, "CPX", 2, 3, AddressingMode::ZeroPage);
-        opcode_table[0xEC] = Opcode::new(0xEC, "CPX", 3, 4, AddressingMode::Absolute);
-
-        opcode_table[0xC0] = Opcode::new(0xC0, "CPY", 2, 2, AddressingMode::Immediate);
-        opcode_table[0xC4] = Opcode::new(0xC4, "CPY", 2, 3, AddressingMode::ZeroPage);
-        opcode_table[0xCC] = Opcode::new(0xCC, "CPY", 3, 4, AddressingMode::Absolute);
-
-        opcode_table[0xC6] = Opcode::new(0xC6, "DEC", 2, 5, AddressingMode::ZeroPage);
-        opcode_table[0xD6] = Opcode::new(0xD6, "DEC", 2, 6, AddressingMode::ZeroPage_X);
-        opcode_table[0xCE] = Opcode::new(0xCE, "DEC", 3, 6, AddressingMode::Absolute);
-        opcode_table[0xDE] = Opcode::new(0xDE, "DEC", 3, 7, AddressingMode::Absolute_X);
-
-        opcode_table[0xCA] = Opcode::new(0xCA, "DEX", 1, 2, AddressingMode::None);
-        opcode_table[0x88] = Opcode::new(0x88, "DEY", 1, 2, AddressingMode::None);
-
-        opcode_table[0x49] = Opcode::new(0x49, "EOR", 2, 2, AddressingMode::Immediate);
-        opcode_table[0x45] = Opcode::new(0x45, "EOR", 2, 3, AddressingMode::ZeroPage);
-        opcode_table[0x55] = Opcode::new(0x55, "EOR", 2, 4, AddressingMode::ZeroPage_X);
-        opcode_table[0x4D] = Opcode::new(0x4D, "EOR", 3, 4, AddressingMode::Absolute);
-        opcode_table[0x5D] = Opcode::new(0x5D, "EOR", 3, 4, AddressingMode::Absolute_X);
-        opcode_table[0x59] = Opcode::new(0x59, "EOR", 3, 4, AddressingMode::Absolute_Y);
-        opcode_table[0x41] = Opcode::new(0x41, "EOR", 2, 6, AddressingMode::Indirect_X);
-        opcode_table[0x51] = Opcode::new(0x51, "EOR", 2, 5, AddressingMode::Indirect_Y);
-
-        opcode_table[0xE6] = Opcode::new(0xE6, "INC", 2, 5, AddressingMode::ZeroPage);
-        opcode_table[0xF6] = Opcode::new(0xF6, "INC", 2, 6, AddressingMode::ZeroPage_X);
-        opcode_table[0xEE] = Opcode::new(0xEE, "INC", 3, 6, AddressingMode::Absolute);
-        opcode_table[0xFE] = Opcode::new(0xFE, "INC", 3, 7, AddressingMode::Absolute_X);
-
-        opcode_table[0xE8] = Opcode::new(0xE8, "INX", 1, 2, AddressingMode::None);
-        opcode_table[0xC8] = Opcode::new(0xC8, "INY", 1, 2, AddressingMode::None);
-
-        opcode_table[0x4C] = Opcode::new(0x4C, "JMP", 1, 2, AddressingMode::None);
-        opcode_table[0x6C] = Opcode::new(0x6C, "JMP", 1, 2, AddressingMode::None);
-        opcode_table[0x20] = Opcode::new(0x20, "JSR", 1, 2, AddressingMode::None);
-
-        opcode_table[0xA9] = Opcode::new(0xA9, "LDA", 2, 2, AddressingMode::Immediate);
-        opcode_table[0xA5] = Opcode::new(0xA5, "LDA", 2, 3, AddressingMode::ZeroPage);
-        opcode_table[0xB5] = Opcode::new(0xB5, "LDA", 2, 4, AddressingMode::ZeroPage_X);
-        opcode_table[0xAD] = Opcode::new(0xAD, "LDA", 3, 4, AddressingMode::Absolute);
-        opcode_table[0xBD] = Opcode::new(0xBD, "LDA", 3, 4, AddressingMode::Absolute_X);
-        opcode_table[0xB9] = Opcode::new(0xB9, "LDA", 3, 4, AddressingMode::Absolute_Y);
-        opcode_table[0xA1] = Opcode::new(0xA1, "LDA", 2, 6, AddressingMode::Indirect_X);
-        opcode_table[0xB1] = Opcode::new(0xB1, "LDA", 2, 5, AddressingMode::Indirect_Y);
-
-        opcode_table[0xA2] = Opcode::new(0xA2, "LDX", 2, 2, AddressingMode::Immediate);
-        opcode_table[0xA6] = Opcode::new(0xA6, "LDX", 2, 3, AddressingMode::ZeroPage);
-        opcode_table[0xB6] = Opcode::new(0xB6, "LDX", 2, 4, AddressingMode::ZeroPage_X);
-        opcode_table[0xAE] = Opcode::new(0xAE, "LDX", 3, 4, AddressingMode::Absolute);
-        opcode_table[0xBE] = Opcode::new(0xBE, "LDX", 3, 4, AddressingMode::Absolute_Y);
-
-        opcode_table[0xA0] = Opcode::new(0xA0, "LDY", 2, 2, AddressingMode::Immediate);
-        opcode_table[0xA4] = Opcode::new(0xA4, "LDY", 2, 3, AddressingMode::ZeroPage);
-        opcode_table[0xB4] = Opcode::new(0xB4, "LDY", 2, 4, AddressingMode::ZeroPage_X);
-        opcode_table[0xAC] = Opcode::new(0xAC, "LDY", 3, 4, AddressingMode::Absolute);
-        opcode_table[0xBC] = Opcode::new(0xBC, "LDY", 3, 4, AddressingMode::Absolute_Y);
-
-        opcode_table[0x4A] = Opcode::new(0x4A, "LSR", 1, 2, AddressingMode::None);
-        opcode_table[0x46] = Opcode::new(0x46, "LSR", 2, 5, AddressingMode::ZeroPage);
-        opcode_table[0x56] = Opcode::new(0x56, "LSR", 2, 6, AddressingMode::ZeroPage_X);
-        opcode_table[0x4E] = Opcode::new(0x4E, "LSR", 3, 6, AddressingMode::Absolute);
-        opcode_table[0x5E] = Opcode::new(0x5E, "LSR", 3, 7, AddressingMode::Absolute_X);
-
-        opcode_table[0xEA] = Opcode::new(0xEA, "NOP", 1, 2, AddressingMode::None);
-
-        opcode_table[0x09] = Opcode::new(0x09, "ORA", 2, 2, AddressingMode::Immediate);
-        opcode_table[0x05] = Opcode::new(0x05, "ORA", 2, 3, AddressingMode::ZeroPage);
-        opcode_table[0x15] = Opcode::new(0x15, "ORA", 2, 4, AddressingMode::ZeroPage_X);
-        opcode_table[0x0D] = Opcode::new(0x0D, "ORA", 3, 4, AddressingMode::Absolute);
-        opcode_table[0x1D] = Opcode::new(0x1D, "ORA", 3, 4, AddressingMode::Absolute_X);
-        opcode_table[0x19] = Opcode::new(0x19, "ORA", 3, 4, AddressingMode::Absolute_Y);
-        opcode_table[0x01] = Opcode::new(0x01, "ORA", 2, 6, AddressingMode::Indirect_X);
-        opcode_table[0x11] = Opcode::new(0x11, "ORA", 2, 5, AddressingMode::Indirect_Y);
-
-        opcode_table[0x48] = Opcode::new(0x48, "PHA", 1, 3, AddressingMode::None);
-        opcode_table[0x08] = Opcode::new(0x08, "PHP", 1, 3, AddressingMode::None);
-        opcode_table[0x68] = Opcode::new(0x68, "PLA", 1, 4, AddressingMode::None);
-        opcode_table[0x28] = Opcode::new(0x28, "PLP", 1, 4, AddressingMode::None);
-
-        opcode_table[0x2A] = Opcode::new(0x2A, "ROL", 1, 2, AddressingMode::None);
-        opcode_table[0x26] = Opcode::new(0x26, "ROL", 2, 5, AddressingMode::ZeroPage);
-        opcode_table[0x36] = Opcode::new(0x36, "ROL", 2, 6, AddressingMode::ZeroPage_X);
-        opcode_table[0x2E] = Opcode::new(0x2E, "ROL", 3, 6, AddressingMode::Absolute);
-        opcode_table[0x3E] = Opcode::new(0x3E, "ROL", 3, 7, AddressingMode::Absolute_X);
-
-        opcode_table[0x6A] = Opcode::new(0x6A, "ROR", 1, 2, AddressingMode::None);
-        opcode_table[0x66] = Opcode::new(0x66, "ROR", 2, 5, AddressingMode::ZeroPage);
-        opcode_table[0x76] = Opcode::new(0x76, "ROR", 2, 6, AddressingMode::ZeroPage_X);
-        opcode_table[0x6E] = Opcode::new(0x6E, "ROR", 3, 6, AddressingMode::Absolute);
-        opcode_table[0x7E] = Opcode::new(0x7E, "ROR", 3, 7, AddressingMode::Absolute_X);
-
-        opcode_table[0x40] = Opcode::new(0x40, "RTI", 1, 6, AddressingMode::None);
-        opcode_table[0x60] = Opcode::new(0x60, "RTS", 1, 6, AddressingMode::None);
-
-        opcode_table[0xE9] = Opcode::new(0xE9, "SBC", 2, 2, AddressingMode::Immediate);
-        opcode_table[0xE5] = Opcode::new(0xE5, "SBC", 2, 3, AddressingMode::ZeroPage);
-        opcode_table[0xF5] = Opcode::new(0xF5, "SBC", 2, 4, AddressingMode::ZeroPage_X);
-        opcode_table[0xED] = Opcode::new(0xED, "SBC", 3, 4, AddressingMode::Absolute);
-        opcode_table[0xFD] = Opcode::new(0xFD, "SBC", 3, 4, AddressingMode::Absolute_X);
-        opcode_table[0xF9] = Opcode::new(0xF9, "SBC", 3, 4, AddressingMode::Absolute_Y);
-        opcode_table[0xE1] = Opcode::new(0xE1, "SBC", 2, 6, AddressingMode::Indirect_X);
-        opcode_table[0xF1] = Opcode::new(0xF1, "SBC", 2, 5, AddressingMode::Indirect_Y);
-
-        opcode_table[0x38] = Opcode::new(0x38, "SEC", 1, 2, AddressingMode::None);
-        opcode_table[0xF8] = Opcode::new(0xF8, "SED", 1, 2, AddressingMode::None);
-        opcode_table[0x78] = Opcode::new(0x78, "SEI", 1, 2, AddressingMode::None);
-
-        opcode_table[0x85] = Opcode::new(0x85, "STA", 2, 3, AddressingMode::ZeroPage);
-        opcode_table[0x95] = Opcode::new(0x95, "STA", 2, 4, AddressingMode::ZeroPage_X);
-        opcode_table[0x8D] = Opcode::new(0x8D, "STA", 3, 4, AddressingMode::Absolute);
-        opcode_table[0x9D] = Opcode::new(0x9D, "STA", 3, 5, AddressingMode::Absolute_X);
-        opcode_table[0x99] = Opcode::new(0x99, "STA", 3, 5, AddressingMode::Absolute_Y);
-        opcode_table[0x81] = Opcode::new(0x81, "STA", 2, 6, AddressingMode::Indirect_X);
-        opcode_table[0x91] = Opcode::new(0x91, "STA", 2, 6, AddressingMode::Indirect_Y);
-
-        opcode_table[0x86] = Opcode::new(0x86, "STX", 2, 3, AddressingMode::ZeroPage);
-        opcode_table[0x96] = Opcode::new(0x96, "STX", 2, 4, AddressingMode::ZeroPage_X);
-        opcode_table[0x8E] = Opcode::new(0x8E, "STX", 3, 4, AddressingMode::Absolute);
-
-        opcode_table[0x84] = Opcode::new(0x84, "STY", 2, 3, AddressingMode::ZeroPage);
-        opcode_table[0x94] = Opcode::new(0x94, "STY", 2, 4, AddressingMode::ZeroPage_X);
-        opcode_table[0x8C] = Opcode::new(0x8C, "STY", 3, 4, AddressingMode::Absolute);
-
-        opcode_table[0xAA] = Opcode::new(0xAA, "TAX", 1, 2, AddressingMode::None);
-        opcode_table[0xA8] = Opcode::new(0xA8, "TAY", 1, 2, AddressingMode::None);
-        opcode_table[0xBA] = Opcode::new(0xBA, "TSX", 1, 2, AddressingMode::None);
-        opcode_table[0x8A] = Opcode::new(0x8A, "TXA", 1, 2, AddressingMode::None);
-        opcode_table[0x9A] = Opcode::new(0x9A, "TXS", 1, 2, AddressingMode::None);
-        opcode_table[0x98] = Opcode::new(0x98, "TYA", 1, 2, AddressingMode::None);
-
-        opcode_table
+        let next = self.program_counter.wrapping_add(1);
+        self.program_counter = next.wrapping_add(jump as u16);
+
+        if condition {
+            self.extra_cycles += 1;
+            if next & 0xFF00 != self.program_counter & 0xFF00 {
+                self.extra_cycles += 1;
+            }
+        }
     }
 }
+
+/// Every opcode by its value, built at compile time.
+pub static OPCODES: [Opcode; 0x100] = opcode_table();
+
+/// Builds the table `OPCODES` holds. Called at run time it builds it again,
+/// which is what the CPU and the disassembler used to do and what
+/// `benches/dispatch.rs` compares against.
+pub const fn opcode_table() -> [Opcode; 0x100] {
+    use Instruction::*;
+
+    // every slot is overwritten below
+    let mut table = [Opcode::unofficial(0x02, Jam, 1, 2, AddressingMode::Implied); 0x100];
+
+    table[0x69] = Opcode::new(0x69, Adc, 2, 2, AddressingMode::Immediate);
+    table[0x65] = Opcode::new(0x65, Adc, 2, 3, AddressingMode::ZeroPage);
+    table[0x75] = Opcode::new(0x75, Adc, 2, 4, AddressingMode::ZeroPage_X);
+    table[0x6D] = Opcode::new(0x6D, Adc, 3, 4, AddressingMode::Absolute);
+    table[0x7D] = Opcode::new(0x7D, Adc, 3, 4, AddressingMode::Absolute_X);
+    table[0x79] = Opcode::new(0x79, Adc, 3, 4, AddressingMode::Absolute_Y);
+    table[0x61] = Opcode::new(0x61, Adc, 2, 6, AddressingMode::Indirect_X);
+    table[0x71] = Opcode::new(0x71, Adc, 2, 5, AddressingMode::Indirect_Y);
+
+    table[0x29] = Opcode::new(0x29, And, 2, 2, AddressingMode::Immediate);
+    table[0x25] = Opcode::new(0x25, And, 2, 3, AddressingMode::ZeroPage);
+    table[0x35] = Opcode::new(0x35, And, 2, 4, AddressingMode::ZeroPage_X);
+    table[0x2D] = Opcode::new(0x2D, And, 3, 4, AddressingMode::Absolute);
+    table[0x3D] = Opcode::new(0x3D, And, 3, 4, AddressingMode::Absolute_X);
+    table[0x39] = Opcode::new(0x39, And, 3, 4, AddressingMode::Absolute_Y);
+    table[0x21] = Opcode::new(0x21, And, 2, 6, AddressingMode::Indirect_X);
+    table[0x31] = Opcode::new(0x31, And, 2, 5, AddressingMode::Indirect_Y);
+
+    table[0x0A] = Opcode::new(0x0A, Asl, 1, 2, AddressingMode::Accumulator);
+    table[0x06] = Opcode::new(0x06, Asl, 2, 5, AddressingMode::ZeroPage);
+    table[0x16] = Opcode::new(0x16, Asl, 2, 6, AddressingMode::ZeroPage_X);
+    table[0x0E] = Opcode::new(0x0E, Asl, 3, 6, AddressingMode::Absolute);
+    table[0x1E] = Opcode::new(0x1E, Asl, 3, 7, AddressingMode::Absolute_X);
+
+    table[0x90] = Opcode::new(0x90, Bcc, 2, 2, AddressingMode::Relative);
+    table[0xB0] = Opcode::new(0xB0, Bcs, 2, 2, AddressingMode::Relative);
+    table[0xF0] = Opcode::new(0xF0, Beq, 2, 2, AddressingMode::Relative);
+
+    table[0x24] = Opcode::new(0x24, Bit, 2, 3, AddressingMode::ZeroPage);
+    table[0x2C] = Opcode::new(0x2C, Bit, 3, 4, AddressingMode::Absolute);
+
+    table[0x30] = Opcode::new(0x30, Bmi, 2, 2, AddressingMode::Relative);
+    table[0xD0] = Opcode::new(0xD0, Bne, 2, 2, AddressingMode::Relative);
+    table[0x10] = Opcode::new(0x10, Bpl, 2, 2, AddressingMode::Relative);
+
+    table[0x00] = Opcode::new(0x00, Brk, 1, 7, AddressingMode::Implied);
+
+    table[0x50] = Opcode::new(0x50, Bvc, 2, 2, AddressingMode::Relative);
+    table[0x70] = Opcode::new(0x70, Bvs, 2, 2, AddressingMode::Relative);
+
+    table[0x18] = Opcode::new(0x18, Clc, 1, 2, AddressingMode::Implied);
+    table[0xD8] = Opcode::new(0xD8, Cld, 1, 2, AddressingMode::Implied);
+    table[0x58] = Opcode::new(0x58, Cli, 1, 2, AddressingMode::Implied);
+    table[0xB8] = Opcode::new(0xB8, Clv, 1, 2, AddressingMode::Implied);
+
+    table[0xC9] = Opcode::new(0xC9, Cmp, 2, 2, AddressingMode::Immediate);
+    table[0xC5] = Opcode::new(0xC5, Cmp, 2, 3, AddressingMode::ZeroPage);
+    table[0xD5] = Opcode::new(0xD5, Cmp, 2, 4, AddressingMode::ZeroPage_X);
+    table[0xCD] = Opcode::new(0xCD, Cmp, 3, 4, AddressingMode::Absolute);
+    table[0xDD] = Opcode::new(0xDD, Cmp, 3, 4, AddressingMode::Absolute_X);
+    table[0xD9] = Opcode::new(0xD9, Cmp, 3, 4, AddressingMode::Absolute_Y);
+    table[0xC1] = Opcode::new(0xC1, Cmp, 2, 6, AddressingMode::Indirect_X);
+    table[0xD1] = Opcode::new(0xD1, Cmp, 2, 5, AddressingMode::Indirect_Y);
+
+    table[0xE0] = Opcode::new(0xE0, Cpx, 2, 2, AddressingMode::Immediate);
+    table[0xE4] = Opcode::new(0xE4, Cpx, 2, 3, AddressingMode::ZeroPage);
+    table[0xEC] = Opcode::new(0xEC, Cpx, 3, 4, AddressingMode::Absolute);
+
+    table[0xC0] = Opcode::new(0xC0, Cpy, 2, 2, AddressingMode::Immediate);
+    table[0xC4] = Opcode::new(0xC4, Cpy, 2, 3, AddressingMode::ZeroPage);
+    table[0xCC] = Opcode::new(0xCC, Cpy, 3, 4, AddressingMode::Absolute);
+
+    table[0xC6] = Opcode::new(0xC6, Dec, 2, 5, AddressingMode::ZeroPage);
+    table[0xD6] = Opcode::new(0xD6, Dec, 2, 6, AddressingMode::ZeroPage_X);
+    table[0xCE] = Opcode::new(0xCE, Dec, 3, 6, AddressingMode::Absolute);
+    table[0xDE] = Opcode::new(0xDE, Dec, 3, 7, AddressingMode::Absolute_X);
+
+    table[0xCA] = Opcode::new(0xCA, Dex, 1, 2, AddressingMode::Implied);
+    table[0x88] = Opcode::new(0x88, Dey, 1, 2, AddressingMode::Implied);
+
+    table[0x49] = Opcode::new(0x49, Eor, 2, 2, AddressingMode::Immediate);
+    table[0x45] = Opcode::new(0x45, Eor, 2, 3, AddressingMode::ZeroPage);
+    table[0x55] = Opcode::new(0x55, Eor, 2, 4, AddressingMode::ZeroPage_X);
+    table[0x4D] = Opcode::new(0x4D, Eor, 3, 4, AddressingMode::Absolute);
+    table[0x5D] = Opcode::new(0x5D, Eor, 3, 4, AddressingMode::Absolute_X);
+    table[0x59] = Opcode::new(0x59, Eor, 3, 4, AddressingMode::Absolute_Y);
+    table[0x41] = Opcode::new(0x41, Eor, 2, 6, AddressingMode::Indirect_X);
+    table[0x51] = Opcode::new(0x51, Eor, 2, 5, AddressingMode::Indirect_Y);
+
+    table[0xE6] = Opcode::new(0xE6, Inc, 2, 5, AddressingMode::ZeroPage);
+    table[0xF6] = Opcode::new(0xF6, Inc, 2, 6, AddressingMode::ZeroPage_X);
+    table[0xEE] = Opcode::new(0xEE, Inc, 3, 6, AddressingMode::Absolute);
+    table[0xFE] = Opcode::new(0xFE, Inc, 3, 7, AddressingMode::Absolute_X);
+
+    table[0xE8] = Opcode::new(0xE8, Inx, 1, 2, AddressingMode::Implied);
+    table[0xC8] = Opcode::new(0xC8, Iny, 1, 2, AddressingMode::Implied);
+
+    table[0x4C] = Opcode::new(0x4C, Jmp, 3, 3, AddressingMode::Absolute);
+    table[0x6C] = Opcode::new(0x6C, Jmp, 3, 5, AddressingMode::Indirect);
+    table[0x20] = Opcode::new(0x20, Jsr, 3, 6, AddressingMode::Absolute);
+
+    table[0xA9] = Opcode::new(0xA9, Lda, 2, 2, AddressingMode::Immediate);
+    table[0xA5] = Opcode::new(0xA5, Lda, 2, 3, AddressingMode::ZeroPage);
+    table[0xB5] = Opcode::new(0xB5, Lda, 2, 4, AddressingMode::ZeroPage_X);
+    table[0xAD] = Opcode::new(0xAD, Lda, 3, 4, AddressingMode::Absolute);
+    table[0xBD] = Opcode::new(0xBD, Lda, 3, 4, AddressingMode::Absolute_X);
+    table[0xB9] = Opcode::new(0xB9, Lda, 3, 4, AddressingMode::Absolute_Y);
+    table[0xA1] = Opcode::new(0xA1, Lda, 2, 6, AddressingMode::Indirect_X);
+    table[0xB1] = Opcode::new(0xB1, Lda, 2, 5, AddressingMode::Indirect_Y);
+
+    table[0xA2] = Opcode::new(0xA2, Ldx, 2, 2, AddressingMode::Immediate);
+    table[0xA6] = Opcode::new(0xA6, Ldx, 2, 3, AddressingMode::ZeroPage);
+    table[0xB6] = Opcode::new(0xB6, Ldx, 2, 4, AddressingMode::ZeroPage_Y);
+    table[0xAE] = Opcode::new(0xAE, Ldx, 3, 4, AddressingMode::Absolute);
+    table[0xBE] = Opcode::new(0xBE, Ldx, 3, 4, AddressingMode::Absolute_Y);
+
+    table[0xA0] = Opcode::new(0xA0, Ldy, 2, 2, AddressingMode::Immediate);
+    table[0xA4] = Opcode::new(0xA4, Ldy, 2, 3, AddressingMode::ZeroPage);
+    table[0xB4] = Opcode::new(0xB4, Ldy, 2, 4, AddressingMode::ZeroPage_X);
+    table[0xAC] = Opcode::new(0xAC, Ldy, 3, 4, AddressingMode::Absolute);
+    table[0xBC] = Opcode::new(0xBC, Ldy, 3, 4, AddressingMode::Absolute_Y);
+
+    table[0x4A] = Opcode::new(0x4A, Lsr, 1, 2, AddressingMode::Accumulator);
+    table[0x46] = Opcode::new(0x46, Lsr, 2, 5, AddressingMode::ZeroPage);
+    table[0x56] = Opcode::new(0x56, Lsr, 2, 6, AddressingMode::ZeroPage_X);
+    table[0x4E] = Opcode::new(0x4E, Lsr, 3, 6, AddressingMode::Absolute);
+    table[0x5E] = Opcode::new(0x5E, Lsr, 3, 7, AddressingMode::Absolute_X);
+
+    table[0xEA] = Opcode::new(0xEA, Nop, 1, 2, AddressingMode::Implied);
+
+    table[0x09] = Opcode::new(0x09, Ora, 2, 2, AddressingMode::Immediate);
+    table[0x05] = Opcode::new(0x05, Ora, 2, 3, AddressingMode::ZeroPage);
+    table[0x15] = Opcode::new(0x15, Ora, 2, 4, AddressingMode::ZeroPage_X);
+    table[0x0D] = Opcode::new(0x0D, Ora, 3, 4, AddressingMode::Absolute);
+    table[0x1D] = Opcode::new(0x1D, Ora, 3, 4, AddressingMode::Absolute_X);
+    table[0x19] = Opcode::new(0x19, Ora, 3, 4, AddressingMode::Absolute_Y);
+    table[0x01] = Opcode::new(0x01, Ora, 2, 6, AddressingMode::Indirect_X);
+    table[0x11] = Opcode::new(0x11, Ora, 2, 5, AddressingMode::Indirect_Y);
+
+    table[0x48] = Opcode::new(0x48, Pha, 1, 3, AddressingMode::Implied);
+    table[0x08] = Opcode::new(0x08, Php, 1, 3, AddressingMode::Implied);
+    table[0x68] = Opcode::new(0x68, Pla, 1, 4, AddressingMode::Implied);
+    table[0x28] = Opcode::new(0x28, Plp, 1, 4, AddressingMode::Implied);
+
+    table[0x2A] = Opcode::new(0x2A, Rol, 1, 2, AddressingMode::Accumulator);
+    table[0x26] = Opcode::new(0x26, Rol, 2, 5, AddressingMode::ZeroPage);
+    table[0x36] = Opcode::new(0x36, Rol, 2, 6, AddressingMode::ZeroPage_X);
+    table[0x2E] = Opcode::new(0x2E, Rol, 3, 6, AddressingMode::Absolute);
+    table[0x3E] = Opcode::new(0x3E, Rol, 3, 7, AddressingMode::Absolute_X);
+
+    table[0x6A] = Opcode::new(0x6A, Ror, 1, 2, AddressingMode::Accumulator);
+    table[0x66] = Opcode::new(0x66, Ror, 2, 5, AddressingMode::ZeroPage);
+    table[0x76] = Opcode::new(0x76, Ror, 2, 6, AddressingMode::ZeroPage_X);
+    table[0x6E] = Opcode::new(0x6E, Ror, 3, 6, AddressingMode::Absolute);
+    table[0x7E] = Opcode::new(0x7E, Ror, 3, 7, AddressingMode::Absolute_X);
+
+    table[0x40] = Opcode::new(0x40, Rti, 1, 6, AddressingMode::Implied);
+    table[0x60] = Opcode::new(0x60, Rts, 1, 6, AddressingMode::Implied);
+
+    table[0xE9] = Opcode::new(0xE9, Sbc, 2, 2, AddressingMode::Immediate);
+    table[0xE5] = Opcode::new(0xE5, Sbc, 2, 3, AddressingMode::ZeroPage);
+    table[0xF5] = Opcode::new(0xF5, Sbc, 2, 4, AddressingMode::ZeroPage_X);
+    table[0xED] = Opcode::new(0xED, Sbc, 3, 4, AddressingMode::Absolute);
+    table[0xFD] = Opcode::new(0xFD, Sbc, 3, 4, AddressingMode::Absolute_X);
+    table[0xF9] = Opcode::new(0xF9, Sbc, 3, 4, AddressingMode::Absolute_Y);
+    table[0xE1] = Opcode::new(0xE1, Sbc, 2, 6, AddressingMode::Indirect_X);
+    table[0xF1] = Opcode::new(0xF1, Sbc, 2, 5, AddressingMode::Indirect_Y);
+
+    table[0x38] = Opcode::new(0x38, Sec, 1, 2, AddressingMode::Implied);
+    table[0xF8] = Opcode::new(0xF8, Sed, 1, 2, AddressingMode::Implied);
+    table[0x78] = Opcode::new(0x78, Sei, 1, 2, AddressingMode::Implied);
+
+    table[0x85] = Opcode::new(0x85, Sta, 2, 3, AddressingMode::ZeroPage);
+    table[0x95] = Opcode::new(0x95, Sta, 2, 4, AddressingMode::ZeroPage_X);
+    table[0x8D] = Opcode::new(0x8D, Sta, 3, 4, AddressingMode::Absolute);
+    table[0x9D] = Opcode::new(0x9D, Sta, 3, 5, AddressingMode::Absolute_X);
+    table[0x99] = Opcode::new(0x99, Sta, 3, 5, AddressingMode::Absolute_Y);
+    table[0x81] = Opcode::new(0x81, Sta, 2, 6, AddressingMode::Indirect_X);
+    table[0x91] = Opcode::new(0x91, Sta, 2, 6, AddressingMode::Indirect_Y);
+
+    table[0x86] = Opcode::new(0x86, Stx, 2, 3, AddressingMode::ZeroPage);
+    table[0x96] = Opcode::new(0x96, Stx, 2, 4, AddressingMode::ZeroPage_Y);
+    table[0x8E] = Opcode::new(0x8E, Stx, 3, 4, AddressingMode::Absolute);
+
+    table[0x84] = Opcode::new(0x84, Sty, 2, 3, AddressingMode::ZeroPage);
+    table[0x94] = Opcode::new(0x94, Sty, 2, 4, AddressingMode::ZeroPage_X);
+    table[0x8C] = Opcode::new(0x8C, Sty, 3, 4, AddressingMode::Absolute);
+
+    table[0xAA] = Opcode::new(0xAA, Tax, 1, 2, AddressingMode::Implied);
+    table[0xA8] = Opcode::new(0xA8, Tay, 1, 2, AddressingMode::Implied);
+    table[0xBA] = Opcode::new(0xBA, Tsx, 1, 2, AddressingMode::Implied);
+    table[0x8A] = Opcode::new(0x8A, Txa, 1, 2, AddressingMode::Implied);
+    table[0x9A] = Opcode::new(0x9A, Txs, 1, 2, AddressingMode::Implied);
+    table[0x98] = Opcode::new(0x98, Tya, 1, 2, AddressingMode::Implied);
+
+    // unofficial opcodes http://www.oxyron.de/html/opcodes02.html
+
+    table[0x1A] = Opcode::unofficial(0x1A, Nop, 1, 2, AddressingMode::Implied);
+    table[0x3A] = Opcode::unofficial(0x3A, Nop, 1, 2, AddressingMode::Implied);
+    table[0x5A] = Opcode::unofficial(0x5A, Nop, 1, 2, AddressingMode::Implied);
+    table[0x7A] = Opcode::unofficial(0x7A, Nop, 1, 2, AddressingMode::Implied);
+    table[0xDA] = Opcode::unofficial(0xDA, Nop, 1, 2, AddressingMode::Implied);
+    table[0xFA] = Opcode::unofficial(0xFA, Nop, 1, 2, AddressingMode::Implied);
+    table[0x80] = Opcode::unofficial(0x80, Nop, 2, 2, AddressingMode::Immediate);
+    table[0x82] = Opcode::unofficial(0x82, Nop, 2, 2, AddressingMode::Immediate);
+    table[0x89] = Opcode::unofficial(0x89, Nop, 2, 2, AddressingMode::Immediate);
+    table[0xC2] = Opcode::unofficial(0xC2, Nop, 2, 2, AddressingMode::Immediate);
+    table[0xE2] = Opcode::unofficial(0xE2, Nop, 2, 2, AddressingMode::Immediate);
+    table[0x04] = Opcode::unofficial(0x04, Nop, 2, 3, AddressingMode::ZeroPage);
+    table[0x44] = Opcode::unofficial(0x44, Nop, 2, 3, AddressingMode::ZeroPage);
+    table[0x64] = Opcode::unofficial(0x64, Nop, 2, 3, AddressingMode::ZeroPage);
+    table[0x14] = Opcode::unofficial(0x14, Nop, 2, 4, AddressingMode::ZeroPage_X);
+    table[0x34] = Opcode::unofficial(0x34, Nop, 2, 4, AddressingMode::ZeroPage_X);
+    table[0x54] = Opcode::unofficial(0x54, Nop, 2, 4, AddressingMode::ZeroPage_X);
+    table[0x74] = Opcode::unofficial(0x74, Nop, 2, 4, AddressingMode::ZeroPage_X);
+    table[0xD4] = Opcode::unofficial(0xD4, Nop, 2, 4, AddressingMode::ZeroPage_X);
+    table[0xF4] = Opcode::unofficial(0xF4, Nop, 2, 4, AddressingMode::ZeroPage_X);
+    table[0x0C] = Opcode::unofficial(0x0C, Nop, 3, 4, AddressingMode::Absolute);
+    table[0x1C] = Opcode::unofficial(0x1C, Nop, 3, 4, AddressingMode::Absolute_X);
+    table[0x3C] = Opcode::unofficial(0x3C, Nop, 3, 4, AddressingMode::Absolute_X);
+    table[0x5C] = Opcode::unofficial(0x5C, Nop, 3, 4, AddressingMode::Absolute_X);
+    table[0x7C] = Opcode::unofficial(0x7C, Nop, 3, 4, AddressingMode::Absolute_X);
+    table[0xDC] = Opcode::unofficial(0xDC, Nop, 3, 4, AddressingMode::Absolute_X);
+    table[0xFC] = Opcode::unofficial(0xFC, Nop, 3, 4, AddressingMode::Absolute_X);
+
+    table[0xA7] = Opcode::unofficial(0xA7, Lax, 2, 3, AddressingMode::ZeroPage);
+    table[0xB7] = Opcode::unofficial(0xB7, Lax, 2, 4, AddressingMode::ZeroPage_Y);
+    table[0xAF] = Opcode::unofficial(0xAF, Lax, 3, 4, AddressingMode::Absolute);
+    table[0xBF] = Opcode::unofficial(0xBF, Lax, 3, 4, AddressingMode::Absolute_Y);
+    table[0xA3] = Opcode::unofficial(0xA3, Lax, 2, 6, AddressingMode::Indirect_X);
+    table[0xB3] = Opcode::unofficial(0xB3, Lax, 2, 5, AddressingMode::Indirect_Y);
+
+    table[0x87] = Opcode::unofficial(0x87, Sax, 2, 3, AddressingMode::ZeroPage);
+    table[0x97] = Opcode::unofficial(0x97, Sax, 2, 4, AddressingMode::ZeroPage_Y);
+    table[0x8F] = Opcode::unofficial(0x8F, Sax, 3, 4, AddressingMode::Absolute);
+    table[0x83] = Opcode::unofficial(0x83, Sax, 2, 6, AddressingMode::Indirect_X);
+
+    table[0xEB] = Opcode::unofficial(0xEB, Sbc, 2, 2, AddressingMode::Immediate);
+
+    table[0xC7] = Opcode::unofficial(0xC7, Dcp, 2, 5, AddressingMode::ZeroPage);
+    table[0xD7] = Opcode::unofficial(0xD7, Dcp, 2, 6, AddressingMode::ZeroPage_X);
+    table[0xCF] = Opcode::unofficial(0xCF, Dcp, 3, 6, AddressingMode::Absolute);
+    table[0xDF] = Opcode::unofficial(0xDF, Dcp, 3, 7, AddressingMode::Absolute_X);
+    table[0xDB] = Opcode::unofficial(0xDB, Dcp, 3, 7, AddressingMode::Absolute_Y);
+    table[0xC3] = Opcode::unofficial(0xC3, Dcp, 2, 8, AddressingMode::Indirect_X);
+    table[0xD3] = Opcode::unofficial(0xD3, Dcp, 2, 8, AddressingMode::Indirect_Y);
+
+    table[0xE7] = Opcode::unofficial(0xE7, Isb, 2, 5, AddressingMode::ZeroPage);
+    table[0xF7] = Opcode::unofficial(0xF7, Isb, 2, 6, AddressingMode::ZeroPage_X);
+    table[0xEF] = Opcode::unofficial(0xEF, Isb, 3, 6, AddressingMode::Absolute);
+    table[0xFF] = Opcode::unofficial(0xFF, Isb, 3, 7, AddressingMode::Absolute_X);
+    table[0xFB] = Opcode::unofficial(0xFB, Isb, 3, 7, AddressingMode::Absolute_Y);
+    table[0xE3] = Opcode::unofficial(0xE3, Isb, 2, 8, AddressingMode::Indirect_X);
+    table[0xF3] = Opcode::unofficial(0xF3, Isb, 2, 8, AddressingMode::Indirect_Y);
+
+    table[0x07] = Opcode::unofficial(0x07, Slo, 2, 5, AddressingMode::ZeroPage);
+    table[0x17] = Opcode::unofficial(0x17, Slo, 2, 6, AddressingMode::ZeroPage_X);
+    table[0x0F] = Opcode::unofficial(0x0F, Slo, 3, 6, AddressingMode::Absolute);
+    table[0x1F] = Opcode::unofficial(0x1F, Slo, 3, 7, AddressingMode::Absolute_X);
+    table[0x1B] = Opcode::unofficial(0x1B, Slo, 3, 7, AddressingMode::Absolute_Y);
+    table[0x03] = Opcode::unofficial(0x03, Slo, 2, 8, AddressingMode::Indirect_X);
+    table[0x13] = Opcode::unofficial(0x13, Slo, 2, 8, AddressingMode::Indirect_Y);
+
+    table[0x27] = Opcode::unofficial(0x27, Rla, 2, 5, AddressingMode::ZeroPage);
+    table[0x37] = Opcode::unofficial(0x37, Rla, 2, 6, AddressingMode::ZeroPage_X);
+    table[0x2F] = Opcode::unofficial(0x2F, Rla, 3, 6, AddressingMode::Absolute);
+    table[0x3F] = Opcode::unofficial(0x3F, Rla, 3, 7, AddressingMode::Absolute_X);
+    table[0x3B] = Opcode::unofficial(0x3B, Rla, 3, 7, AddressingMode::Absolute_Y);
+    table[0x23] = Opcode::unofficial(0x23, Rla, 2, 8, AddressingMode::Indirect_X);
+    table[0x33] = Opcode::unofficial(0x33, Rla, 2, 8, AddressingMode::Indirect_Y);
+
+    table[0x47] = Opcode::unofficial(0x47, Sre, 2, 5, AddressingMode::ZeroPage);
+    table[0x57] = Opcode::unofficial(0x57, Sre, 2, 6, AddressingMode::ZeroPage_X);
+    table[0x4F] = Opcode::unofficial(0x4F, Sre, 3, 6, AddressingMode::Absolute);
+    table[0x5F] = Opcode::unofficial(0x5F, Sre, 3, 7, AddressingMode::Absolute_X);
+    table[0x5B] = Opcode::unofficial(0x5B, Sre, 3, 7, AddressingMode::Absolute_Y);
+    table[0x43] = Opcode::unofficial(0x43, Sre, 2, 8, AddressingMode::Indirect_X);
+    table[0x53] = Opcode::unofficial(0x53, Sre, 2, 8, AddressingMode::Indirect_Y);
+
+    table[0x67] = Opcode::unofficial(0x67, Rra, 2, 5, AddressingMode::ZeroPage);
+    table[0x77] = Opcode::unofficial(0x77, Rra, 2, 6, AddressingMode::ZeroPage_X);
+    table[0x6F] = Opcode::unofficial(0x6F, Rra, 3, 6, AddressingMode::Absolute);
+    table[0x7F] = Opcode::unofficial(0x7F, Rra, 3, 7, AddressingMode::Absolute_X);
+    table[0x7B] = Opcode::unofficial(0x7B, Rra, 3, 7, AddressingMode::Absolute_Y);
+    table[0x63] = Opcode::unofficial(0x63, Rra, 2, 8, AddressingMode::Indirect_X);
+    table[0x73] = Opcode::unofficial(0x73, Rra, 2, 8, AddressingMode::Indirect_Y);
+
+    table[0x0B] = Opcode::unofficial(0x0B, Anc, 2, 2, AddressingMode::Immediate);
+    table[0x2B] = Opcode::unofficial(0x2B, Anc, 2, 2, AddressingMode::Immediate);
+
+    table[0x4B] = Opcode::unofficial(0x4B, Alr, 2, 2, AddressingMode::Immediate);
+
+    table[0x6B] = Opcode::unofficial(0x6B, Arr, 2, 2, AddressingMode::Immediate);
+
+    table[0xCB] = Opcode::unofficial(0xCB, Axs, 2, 2, AddressingMode::Immediate);
+
+    table[0xBB] = Opcode::unofficial(0xBB, Las, 3, 4, AddressingMode::Absolute_Y);
+
+    table[0x8B] = Opcode::unofficial(0x8B, Xaa, 2, 2, AddressingMode::Immediate);
+
+    table[0xAB] = Opcode::unofficial(0xAB, Lxa, 2, 2, AddressingMode::Immediate);
+
+    table[0x9F] = Opcode::unofficial(0x9F, Sha, 3, 5, AddressingMode::Absolute_Y);
+    table[0x93] = Opcode::unofficial(0x93, Sha, 2, 6, AddressingMode::Indirect_Y);
+
+    table[0x9C] = Opcode::unofficial(0x9C, Shy, 3, 5, AddressingMode::Absolute_X);
+
+    table[0x9E] = Opcode::unofficial(0x9E, Shx, 3, 5, AddressingMode::Absolute_Y);
+
+    table[0x9B] = Opcode::unofficial(0x9B, Tas, 3, 5, AddressingMode::Absolute_Y);
+
+    table[0x02] = Opcode::unofficial(0x02, Jam, 1, 2, AddressingMode::Implied);
+    table[0x12] = Opcode::unofficial(0x12, Jam, 1, 2, AddressingMode::Implied);
+    table[0x22] = Opcode::unofficial(0x22, Jam, 1, 2, AddressingMode::Implied);
+    table[0x32] = Opcode::unofficial(0x32, Jam, 1, 2, AddressingMode::Implied);
+    table[0x42] = Opcode::unofficial(0x42, Jam, 1, 2, AddressingMode::Implied);
+    table[0x52] = Opcode::unofficial(0x52, Jam, 1, 2, AddressingMode::Implied);
+    table[0x62] = Opcode::unofficial(0x62, Jam, 1, 2, AddressingMode::Implied);
+    table[0x72] = Opcode::unofficial(0x72, Jam, 1, 2, AddressingMode::Implied);
+    table[0x92] = Opcode::unofficial(0x92, Jam, 1, 2, AddressingMode::Implied);
+    table[0xB2] = Opcode::unofficial(0xB2, Jam, 1, 2, AddressingMode::Implied);
+    table[0xD2] = Opcode::unofficial(0xD2, Jam, 1, 2, AddressingMode::Implied);
+    table[0xF2] = Opcode::unofficial(0xF2, Jam, 1, 2, AddressingMode::Implied);
+
+    table
+}
@@ -0,0 +1,142 @@
+use super::{AddressingMode, Instruction, Status, CPU, OPCODES};
+
+#[cfg(test)]
+mod trace_tests;
+
+/// One line of the canonical nestest.log format for the instruction at PC,
+/// without executing it:
+///
+/// ```text
+/// C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+/// ```
+///
+/// Memory is read with `Bus::peek`, so tracing has no side effects on PPU
+/// or mapper registers.
+pub fn trace(cpu: &CPU) -> String {
+    let bus = &cpu.bus;
+    let pc = cpu.program_counter;
+    let opcode = &OPCODES[bus.peek(pc) as usize];
+
+    let bytes: Vec<u8> = (0..opcode.length as u16)
+        .map(|offset| bus.peek(pc.wrapping_add(offset)))
+        .collect();
+    let hex = bytes
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let operand = match (opcode.instruction, opcode.mode) {
+        (_, AddressingMode::Implied) => String::new(),
+        (_, AddressingMode::Accumulator) => "A".to_string(),
+        (_, AddressingMode::Relative) => {
+            // branches: show the target
+            let offset = bytes[1] as i8;
+            let target = pc.wrapping_add(2).wrapping_add(offset as u16);
+            format!("${:04X}", target)
+        }
+        (_, AddressingMode::Indirect) => {
+            let address = u16::from_le_bytes([bytes[1], bytes[2]]);
+            // JMP ($xxFF) takes the high byte from the start of the same page
+            let hi_address = (address & 0xFF00) | (address.wrapping_add(1) & 0x00FF);
+            let target = u16::from_le_bytes([bus.peek(address), bus.peek(hi_address)]);
+            format!("(${:04X}) = {:04X}", address, target)
+        }
+        // jump targets are not operands, nestest.log shows no value for them
+        (Instruction::Jmp | Instruction::Jsr, AddressingMode::Absolute) => {
+            format!("${:04X}", u16::from_le_bytes([bytes[1], bytes[2]]))
+        }
+        (_, mode) => format_operand(cpu, mode, &bytes),
+    };
+
+    let star = if opcode.unofficial { "*" } else { "" };
+    let mnemonic = format!("{}{}", star, opcode.instruction.mnemonic());
+    let assembly = format!("{:04X}  {:8} {:>4} {}", pc, hex, mnemonic, operand);
+    format!(
+        "{:47} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:3},{:3} CYC:{}",
+        assembly.trim_end(),
+        cpu.accumulator,
+        cpu.register_x,
+        cpu.register_y,
+        cpu.status.get() | Status::BREAK2,
+        cpu.stack_pointer,
+        bus.ppu.scanline(),
+        bus.ppu.cycle(),
+        cpu.cycles,
+    )
+}
+
+fn format_operand(cpu: &CPU, mode: AddressingMode, bytes: &[u8]) -> String {
+    let bus = &cpu.bus;
+    let zero_page = bytes[1];
+    let absolute = || u16::from_le_bytes([bytes[1], bytes[2]]);
+    let pointer = |base: u8| {
+        u16::from_le_bytes([bus.peek(base as u16), bus.peek(base.wrapping_add(1) as u16)])
+    };
+
+    match mode {
+        AddressingMode::Immediate => format!("#${:02X}", zero_page),
+        AddressingMode::ZeroPage => {
+            format!("${:02X} = {:02X}", zero_page, bus.peek(zero_page as u16))
+        }
+        AddressingMode::ZeroPage_X | AddressingMode::ZeroPage_Y => {
+            let (register, name) = if mode == AddressingMode::ZeroPage_X {
+                (cpu.register_x, "X")
+            } else {
+                (cpu.register_y, "Y")
+            };
+            let address = zero_page.wrapping_add(register);
+            format!(
+                "${:02X},{} @ {:02X} = {:02X}",
+                zero_page,
+                name,
+                address,
+                bus.peek(address as u16)
+            )
+        }
+        AddressingMode::Absolute => {
+            format!("${:04X} = {:02X}", absolute(), bus.peek(absolute()))
+        }
+        AddressingMode::Absolute_X | AddressingMode::Absolute_Y => {
+            let (register, name) = if mode == AddressingMode::Absolute_X {
+                (cpu.register_x, "X")
+            } else {
+                (cpu.register_y, "Y")
+            };
+            let address = absolute().wrapping_add(register as u16);
+            format!(
+                "${:04X},{} @ {:04X} = {:02X}",
+                absolute(),
+                name,
+                address,
+                bus.peek(address)
+            )
+        }
+        AddressingMode::Indirect_X => {
+            let base = zero_page.wrapping_add(cpu.register_x);
+            let address = pointer(base);
+            format!(
+                "(${:02X},X) @ {:02X} = {:04X} = {:02X}",
+                zero_page,
+                base,
+                address,
+                bus.peek(address)
+            )
+        }
+        AddressingMode::Indirect_Y => {
+            let base = pointer(zero_page);
+            let address = base.wrapping_add(cpu.register_y as u16);
+            format!(
+                "(${:02X}),Y = {:04X} @ {:04X} = {:02X}",
+                zero_page,
+                base,
+                address,
+                bus.peek(address)
+            )
+        }
+        AddressingMode::Implied
+        | AddressingMode::Accumulator
+        | AddressingMode::Relative
+        | AddressingMode::Indirect => String::new(),
+    }
+}
@@ -0,0 +1,124 @@
+use super::trace;
+use crate::bus::Mem;
+use crate::cpu::{Status, CPU};
+
+fn run_traced(cpu: &mut CPU) -> Vec<String> {
+    let mut result = vec![];
+    cpu.run_with_callback(|cpu| result.push(trace(cpu)))
+        .unwrap();
+    result
+}
+
+#[test]
+fn formats_registers_and_timing() {
+    let mut cpu = CPU::debug_new();
+    cpu.mem_write(0x64, 0xa2);
+    cpu.mem_write(0x65, 0x01);
+    cpu.mem_write(0x66, 0xca);
+    cpu.mem_write(0x67, 0x88);
+    cpu.mem_write(0x68, 0x00);
+    cpu.program_counter = 0x64;
+    cpu.accumulator = 1;
+    cpu.register_x = 2;
+    cpu.register_y = 3;
+    cpu.status.set(Status::INTERRUPT_DISABLE);
+
+    let result = run_traced(&mut cpu);
+    assert_eq!(
+        "0064  A2 01     LDX #$01                        A:01 X:02 Y:03 P:24 SP:FD PPU:  0,  0 CYC:0",
+        result[0]
+    );
+    assert_eq!(
+        "0066  CA        DEX                             A:01 X:01 Y:03 P:24 SP:FD PPU:  0,  6 CYC:2",
+        result[1]
+    );
+    assert_eq!(
+        "0067  88        DEY                             A:01 X:00 Y:03 P:26 SP:FD PPU:  0, 12 CYC:4",
+        result[2]
+    );
+}
+
+#[test]
+fn formats_memory_operands() {
+    let mut cpu = CPU::debug_new();
+    // ORA ($33),Y
+    cpu.mem_write(0x64, 0x11);
+    cpu.mem_write(0x65, 0x33);
+    cpu.mem_write(0x33, 0x00);
+    cpu.mem_write(0x34, 0x04);
+    cpu.mem_write(0x400, 0xaa);
+    cpu.program_counter = 0x64;
+
+    let result = run_traced(&mut cpu);
+    assert_eq!(
+        "0064  11 33     ORA ($33),Y = 0400 @ 0400 = AA  A:00 X:00 Y:00 P:20 SP:FD PPU:  0,  0 CYC:0",
+        result[0]
+    );
+}
+
+#[test]
+fn formats_every_addressing_mode() {
+    let mut cpu = CPU::debug_new();
+    cpu.load(vec![
+        0x0a, // ASL A
+        0xb5, 0x10, // LDA $10,X
+        0xb6, 0xff, // LDX $FF,Y
+        0xa1, 0x20, // LDA ($20,X)
+        0xbd, 0x00, 0x02, // LDA $0200,X
+        0x8d, 0x00, 0x02, // STA $0200
+        0xd0, 0x00, // BNE
+        0x6c, 0xff, 0x02, // JMP ($02FF)
+    ]);
+    cpu.program_counter = 0x0600;
+    cpu.register_x = 0x01;
+    cpu.register_y = 0x02;
+    cpu.mem_write(0x11, 0x11);
+    cpu.mem_write(0x01, 0x01);
+    cpu.mem_write_u16(0x21, 0x0300);
+    cpu.mem_write(0x0201, 0x22);
+    cpu.mem_write(0x02ff, 0x34);
+    cpu.mem_write(0x0200, 0x12);
+
+    let mut result = vec![];
+    for _ in 0..7 {
+        result.push(trace(&cpu)[..47].trim_end().to_string());
+        cpu.step().unwrap();
+    }
+    assert_eq!(
+        result,
+        vec![
+            "0600  0A        ASL A",
+            "0601  B5 10     LDA $10,X @ 11 = 11",
+            "0603  B6 FF     LDX $FF,Y @ 01 = 01",
+            "0605  A1 20     LDA ($20,X) @ 21 = 0300 = 00",
+            "0607  BD 00 02  LDA $0200,X @ 0201 = 22",
+            "060A  8D 00 02  STA $0200 = 12",
+            "060D  D0 00     BNE $060F",
+        ]
+    );
+    assert_eq!(
+        &trace(&cpu)[..47],
+        "060F  6C FF 02  JMP ($02FF) = 2234             "
+    );
+}
+
+#[test]
+fn unofficial_mnemonics_are_starred() {
+    let mut cpu = CPU::debug_new();
+    cpu.load(vec![0x04, 0x10, 0x00]);
+    cpu.program_counter = 0x0600;
+    assert!(trace(&cpu).starts_with("0600  04 10    *NOP $10 = 00"));
+}
+
+#[test]
+fn tracing_has_no_side_effects() {
+    let mut cpu = CPU::debug_new();
+    cpu.load(vec![0xad, 0x02, 0x20, 0x00]);
+    cpu.program_counter = 0x0600;
+    while cpu.bus.ppu.scanline() != 242 {
+        cpu.bus.tick(1);
+    }
+    trace(&cpu);
+    cpu.step().unwrap();
+    assert_eq!(cpu.accumulator & 0x80, 0x80);
+}
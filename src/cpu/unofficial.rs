@@ -0,0 +1,255 @@
+use super::opcodes::Opcode;
+use super::{Status, StopReason, UnstableOpcodePolicy, CPU, MAX_OPCODE_REPORTS};
+use crate::bus::{CpuBus, Mem};
+
+/// The value XAA and LXA OR into A before the AND; it varies between chips,
+/// $EE is what most NES CPUs show.
+const MAGIC: u8 = 0xEE;
+
+/// # Unofficial opcodes http://www.oxyron.de/html/opcodes02.html
+///
+/// Most of them combine two official instructions sharing an addressing mode,
+/// e.g. DCP is DEC followed by CMP on the same memory operand.
+impl<B: CpuBus> CPU<B> {
+    pub(super) fn unstable_opcode_allowed(&mut self, opcode: &Opcode) -> bool {
+        match self.unstable_opcode_policy {
+            UnstableOpcodePolicy::Execute => true,
+            UnstableOpcodePolicy::Log => {
+                self.report_opcode(StopReason::UnstableOpcode {
+                    pc: self.program_counter.wrapping_sub(1),
+                    opcode: opcode.code,
+                });
+                true
+            }
+            UnstableOpcodePolicy::Halt => {
+                self.program_counter = self.program_counter.wrapping_sub(1);
+                false
+            }
+        }
+    }
+
+    fn report_opcode(&mut self, report: StopReason) {
+        if self.opcode_reports.len() < MAX_OPCODE_REPORTS {
+            self.opcode_reports.push(report);
+        }
+    }
+
+    /// KIL/JAM: the CPU stops fetching and only a reset gets it going again.
+    pub(super) fn jam(&mut self, opcode: &Opcode) -> bool {
+        self.program_counter = self.program_counter.wrapping_sub(1);
+        match self.unstable_opcode_policy {
+            UnstableOpcodePolicy::Execute => {}
+            UnstableOpcodePolicy::Log => {
+                if !self.jammed {
+                    self.report_opcode(StopReason::Jammed {
+                        pc: self.program_counter,
+                        opcode: opcode.code,
+                    });
+                }
+            }
+            UnstableOpcodePolicy::Halt => return false,
+        }
+        self.jammed = true;
+        true
+    }
+
+    /// NOPs with an operand still perform the read, page-cross penalty included.
+    pub(super) fn nop_read(&mut self, opcode: &Opcode) {
+        self.read_operand(opcode.mode);
+        self.increment_program_counter(opcode.length);
+    }
+
+    pub(super) fn lax(&mut self, opcode: &Opcode) {
+        let value = self.read_operand(opcode.mode);
+        self.accumulator = value;
+        self.register_x = value;
+        self.update_zero_and_negative_flags(value);
+        self.increment_program_counter(opcode.length);
+    }
+
+    pub(super) fn sax(&mut self, opcode: &Opcode) {
+        let (address, _) = self.get_operand_address(opcode.mode);
+        self.mem_write(address, self.accumulator & self.register_x);
+        self.increment_program_counter(opcode.length);
+    }
+
+    pub(super) fn dcp(&mut self, opcode: &Opcode) {
+        let value = self.modify_memory(opcode.mode, |_, value| value.wrapping_sub(1));
+        self.compare_values(self.accumulator, value);
+        self.increment_program_counter(opcode.length);
+    }
+
+    pub(super) fn isb(&mut self, opcode: &Opcode) {
+        let value = self.modify_memory(opcode.mode, |_, value| value.wrapping_add(1));
+        self.subtract_from_accumulator(value);
+        self.increment_program_counter(opcode.length);
+    }
+
+    pub(super) fn slo(&mut self, opcode: &Opcode) {
+        let value = self.modify_memory(opcode.mode, |cpu, value| cpu.shift_left(value, false));
+        self.accumulator |= value;
+        self.update_zero_and_negative_flags(self.accumulator);
+        self.increment_program_counter(opcode.length);
+    }
+
+    pub(super) fn rla(&mut self, opcode: &Opcode) {
+        let carry = self.status.contains(Status::CARRY);
+        let value = self.modify_memory(opcode.mode, |cpu, value| cpu.shift_left(value, carry));
+        self.accumulator &= value;
+        self.update_zero_and_negative_flags(self.accumulator);
+        self.increment_program_counter(opcode.length);
+    }
+
+    pub(super) fn sre(&mut self, opcode: &Opcode) {
+        let value = self.modify_memory(opcode.mode, |cpu, value| cpu.shift_right(value, false));
+        self.accumulator ^= value;
+        self.update_zero_and_negative_flags(self.accumulator);
+        self.increment_program_counter(opcode.length);
+    }
+
+    pub(super) fn rra(&mut self, opcode: &Opcode) {
+        let carry = self.status.contains(Status::CARRY);
+        let value = self.modify_memory(opcode.mode, |cpu, value| cpu.shift_right(value, carry));
+        self.add_to_accumulator(value);
+        self.update_zero_and_negative_flags(self.accumulator);
+        self.increment_program_counter(opcode.length);
+    }
+
+    pub(super) fn anc(&mut self, opcode: &Opcode) {
+        let value = self.read_operand(opcode.mode);
+        self.anc_value(value);
+        self.increment_program_counter(opcode.length);
+    }
+
+    pub(super) fn anc_value(&mut self, value: u8) {
+        self.accumulator &= value;
+        self.update_zero_and_negative_flags(self.accumulator);
+        self.set_flag(Status::CARRY, self.accumulator & 0x80 != 0);
+    }
+
+    pub(super) fn alr(&mut self, opcode: &Opcode) {
+        let value = self.read_operand(opcode.mode);
+        self.alr_value(value);
+        self.increment_program_counter(opcode.length);
+    }
+
+    pub(super) fn alr_value(&mut self, value: u8) {
+        self.accumulator = self.shift_right(self.accumulator & value, false);
+    }
+
+    pub(super) fn arr(&mut self, opcode: &Opcode) {
+        let value = self.read_operand(opcode.mode);
+        self.arr_value(value);
+        self.increment_program_counter(opcode.length);
+    }
+
+    /// AND then ROR, with C taken from bit 6 and V from bit 6 XOR bit 5.
+    pub(super) fn arr_value(&mut self, value: u8) {
+        let carry = self.status.contains(Status::CARRY) as u8;
+        let result = (self.accumulator & value) >> 1 | carry << 7;
+        self.accumulator = result;
+        self.update_zero_and_negative_flags(result);
+        self.set_flag(Status::CARRY, result & 0x40 != 0);
+        self.set_flag(Status::OVERFLOW, ((result >> 6) ^ (result >> 5)) & 1 != 0);
+    }
+
+    pub(super) fn axs(&mut self, opcode: &Opcode) {
+        let value = self.read_operand(opcode.mode);
+        self.axs_value(value);
+        self.increment_program_counter(opcode.length);
+    }
+
+    /// X = (A AND X) - M, setting flags like CMP and ignoring the carry.
+    pub(super) fn axs_value(&mut self, value: u8) {
+        let and = self.accumulator & self.register_x;
+        self.set_flag(Status::CARRY, and >= value);
+        self.register_x = and.wrapping_sub(value);
+        self.update_zero_and_negative_flags(self.register_x);
+    }
+
+    pub(super) fn las(&mut self, opcode: &Opcode) {
+        let value = self.read_operand(opcode.mode);
+        self.las_value(value);
+        self.increment_program_counter(opcode.length);
+    }
+
+    pub(super) fn las_value(&mut self, value: u8) {
+        let value = value & self.stack_pointer;
+        self.accumulator = value;
+        self.register_x = value;
+        self.stack_pointer = value;
+        self.update_zero_and_negative_flags(value);
+    }
+
+    pub(super) fn xaa(&mut self, opcode: &Opcode) {
+        let value = self.read_operand(opcode.mode);
+        self.xaa_value(value);
+        self.increment_program_counter(opcode.length);
+    }
+
+    pub(super) fn xaa_value(&mut self, value: u8) {
+        self.accumulator = (self.accumulator | MAGIC) & self.register_x & value;
+        self.update_zero_and_negative_flags(self.accumulator);
+    }
+
+    pub(super) fn lxa(&mut self, opcode: &Opcode) {
+        let value = self.read_operand(opcode.mode);
+        self.lxa_value(value);
+        self.increment_program_counter(opcode.length);
+    }
+
+    pub(super) fn lxa_value(&mut self, value: u8) {
+        self.accumulator = (self.accumulator | MAGIC) & value;
+        self.register_x = self.accumulator;
+        self.update_zero_and_negative_flags(self.accumulator);
+    }
+
+    pub(super) fn sha(&mut self, opcode: &Opcode) {
+        let value = self.accumulator & self.register_x;
+        self.store_and_high_byte(opcode, self.register_y, value);
+    }
+
+    pub(super) fn shy(&mut self, opcode: &Opcode) {
+        self.store_and_high_byte(opcode, self.register_x, self.register_y);
+    }
+
+    pub(super) fn shx(&mut self, opcode: &Opcode) {
+        self.store_and_high_byte(opcode, self.register_y, self.register_x);
+    }
+
+    pub(super) fn tas(&mut self, opcode: &Opcode) {
+        let value = self.accumulator & self.register_x;
+        self.stack_pointer = value;
+        self.store_and_high_byte(opcode, self.register_y, value);
+    }
+
+    fn store_and_high_byte(&mut self, opcode: &Opcode, index: u8, value: u8) {
+        let (address, page_crossed) = self.get_operand_address(opcode.mode);
+        let (address, result) = and_high_byte(address, page_crossed, index, value);
+        self.mem_write(address, result);
+        self.increment_program_counter(opcode.length);
+    }
+
+    pub(super) fn set_flag(&mut self, flag: u8, value: bool) {
+        if value {
+            self.status.set(flag);
+        } else {
+            self.status.reset(flag);
+        }
+    }
+}
+
+/// What SHA, SHX, SHY and TAS store at `address`, indexed by `index`:
+/// `value` AND (high byte of the unindexed address + 1). When the indexing
+/// crossed a page the stored value also replaces the high byte of the
+/// target address. Returns the address and the value.
+pub(super) fn and_high_byte(address: u16, page_crossed: bool, index: u8, value: u8) -> (u16, u8) {
+    let base = address.wrapping_sub(index as u16);
+    let result = value & ((base >> 8) as u8).wrapping_add(1);
+    let address = if page_crossed {
+        (result as u16) << 8 | (address & 0x00FF)
+    } else {
+        address
+    };
+    (address, result)
+}
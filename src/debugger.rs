@@ -0,0 +1,493 @@
+//! Interactive debugger for the CPU: a command loop reading from any
+//! `BufRead` and writing to any `Write`, so a frontend can put it on a
+//! terminal and tests can script it. Addresses and values are hex, with an
+//! optional `$` or `0x` prefix; counts are decimal.
+
+use crate::bus::{Access, BusAccess, Mem};
+use crate::cpu::{trace, CpuError, Instruction as Operation, StopReason, CPU, OPCODES};
+use crate::disasm::{self, Instruction};
+use std::io::{self, BufRead, Write};
+
+#[cfg(test)]
+mod debugger_tests;
+
+/// Status register bits by name, highest first.
+const FLAGS: [(char, u8); 8] = [
+    ('n', 0b1000_0000),
+    ('v', 0b0100_0000),
+    ('-', 0b0010_0000),
+    ('b', 0b0001_0000),
+    ('d', 0b0000_1000),
+    ('i', 0b0000_0100),
+    ('z', 0b0000_0010),
+    ('c', 0b0000_0001),
+];
+
+const HELP: &str = "\
+step [n]              execute n instructions (s)
+next                  step over a JSR (n)
+finish                run until the current subroutine returns (out)
+continue              run until a breakpoint, watchpoint or halt (c)
+frame [n]             run until n more frames are rendered
+break <addr>          stop when PC reaches addr (b)
+delete [addr]         remove one or all breakpoints
+breakpoints           list breakpoints and watchpoints
+watch <addr> [r|w|rw] stop when the CPU reads or writes addr
+unwatch <addr>        remove the watchpoints on addr
+stackcheck [on|off]   stop when SP wraps around page 1
+regs                  show registers and flags (r)
+set <reg> <value>     set a, x, y, sp, pc, p or a flag (n v d i z c)
+mem <addr> [len]      hex dump len bytes (m)
+poke <addr> <byte>..  write bytes to memory
+disasm [addr] [n]     disassemble n instructions, around PC by default (d)
+quit                  leave the debugger (q)
+An empty line repeats the last command.
+";
+
+enum Error {
+    /// A mistake in the command, shown to the user.
+    Command(String),
+    Io(io::Error),
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Command(message)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// Why execution stopped.
+enum Stop {
+    Done,
+    Breakpoint(u16),
+    Watchpoint(BusAccess),
+    Cpu(StopReason),
+    Error(CpuError),
+}
+
+#[derive(Default)]
+pub struct Debugger {
+    last_command: String,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Debugger::default()
+    }
+
+    /// Prompts for commands until `quit` or the end of `input`.
+    pub fn run<R: BufRead, W: Write>(
+        &mut self,
+        cpu: &mut CPU,
+        input: R,
+        mut output: W,
+    ) -> io::Result<()> {
+        writeln!(output, "{}", trace(cpu))?;
+        let mut lines = input.lines();
+        loop {
+            write!(output, "(nes) ")?;
+            output.flush()?;
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => return Ok(()),
+            };
+            if !self.execute(cpu, &line, &mut output)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Runs one command line. Returns `false` for `quit`. Mistakes in the
+    /// command are reported on `output`, only I/O errors are returned.
+    pub fn execute(
+        &mut self,
+        cpu: &mut CPU,
+        line: &str,
+        output: &mut dyn Write,
+    ) -> io::Result<bool> {
+        let line = match line.trim() {
+            "" => self.last_command.clone(),
+            line => {
+                self.last_command = line.to_string();
+                line.to_string()
+            }
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return Ok(true),
+        };
+        if let "quit" | "q" | "exit" = command {
+            return Ok(false);
+        }
+        match self.command(cpu, command, args, output) {
+            Ok(()) => Ok(true),
+            Err(Error::Command(message)) => writeln!(output, "error: {}", message).map(|_| true),
+            Err(Error::Io(err)) => Err(err),
+        }
+    }
+
+    fn command(
+        &mut self,
+        cpu: &mut CPU,
+        command: &str,
+        args: &[&str],
+        output: &mut dyn Write,
+    ) -> Result<(), Error> {
+        let address = || parse_hex(required(args.first(), "an address")?);
+        let stop = match command {
+            "step" | "s" => {
+                let mut count = optional(args.first(), parse_count, 1)?;
+                Some(run_until(cpu, |_, _, _| {
+                    count -= 1;
+                    count == 0
+                }))
+            }
+            "next" | "n" => {
+                let pc = cpu.program_counter;
+                let opcode = &OPCODES[cpu.bus.peek(pc) as usize];
+                Some(if opcode.instruction == Operation::Jsr {
+                    let return_address = pc.wrapping_add(opcode.length as u16);
+                    let stack = cpu.stack_pointer;
+                    run_until(cpu, |cpu, _, _| {
+                        cpu.program_counter == return_address && cpu.stack_pointer >= stack
+                    })
+                } else {
+                    run_until(cpu, |_, _, _| true)
+                })
+            }
+            "finish" | "out" => {
+                let stack = cpu.stack_pointer;
+                Some(run_until(cpu, |cpu, opcode, _| {
+                    let instruction = OPCODES[opcode as usize].instruction;
+                    matches!(instruction, Operation::Rts | Operation::Rti)
+                        && cpu.stack_pointer > stack
+                }))
+            }
+            "continue" | "c" => Some(run_until(cpu, |_, _, _| false)),
+            "frame" => {
+                let mut count = optional(args.first(), parse_count, 1)?;
+                Some(run_until(cpu, |_, _, frame| {
+                    if frame {
+                        count -= 1;
+                    }
+                    count == 0
+                }))
+            }
+            "break" | "b" => {
+                let address = address()?;
+                cpu.add_breakpoint(address);
+                writeln!(output, "Breakpoint at ${:04X}", address)?;
+                None
+            }
+            "delete" => {
+                if args.is_empty() {
+                    cpu.clear_breakpoints();
+                } else {
+                    let address = address()?;
+                    if !cpu.remove_breakpoint(address) {
+                        return Err(format!("no breakpoint at ${:04X}", address).into());
+                    }
+                }
+                None
+            }
+            "breakpoints" => {
+                for address in cpu.breakpoints() {
+                    writeln!(output, "break ${:04X}", address)?;
+                }
+                for (address, access) in cpu.bus.watchpoints() {
+                    let access = match access {
+                        Access::Read => "r",
+                        Access::Write => "w",
+                    };
+                    writeln!(output, "watch ${:04X} {}", address, access)?;
+                }
+                None
+            }
+            "watch" => {
+                let address = address()?;
+                let accesses: &[Access] = match args.get(1).copied().unwrap_or("rw") {
+                    "r" => &[Access::Read],
+                    "w" => &[Access::Write],
+                    "rw" => &[Access::Read, Access::Write],
+                    other => return Err(format!("expected r, w or rw, not {}", other).into()),
+                };
+                for &access in accesses {
+                    cpu.bus.watch(address, access);
+                }
+                None
+            }
+            "unwatch" => {
+                let address = address()?;
+                if !cpu.bus.unwatch(address) {
+                    return Err(format!("no watchpoint at ${:04X}", address).into());
+                }
+                None
+            }
+            "stackcheck" => {
+                match args.first().copied() {
+                    None => {}
+                    Some("on") => cpu.set_stack_diagnostics(true),
+                    Some("off") => cpu.set_stack_diagnostics(false),
+                    Some(other) => return Err(format!("expected on or off, not {}", other).into()),
+                }
+                let state = if cpu.stack_diagnostics() { "on" } else { "off" };
+                writeln!(output, "Stack check is {}", state)?;
+                None
+            }
+            "regs" | "r" => {
+                writeln!(output, "{}", registers(cpu))?;
+                None
+            }
+            "set" => {
+                let register = required(args.first(), "a register")?;
+                let value = parse_hex(required(args.get(1), "a value")?)?;
+                set_register(cpu, register, value)?;
+                writeln!(output, "{}", registers(cpu))?;
+                None
+            }
+            "mem" | "m" => {
+                let address = address()?;
+                let length = optional(args.get(1), parse_count, 64)?;
+                dump(cpu, address, length, output)?;
+                None
+            }
+            "poke" => {
+                let address = address()?;
+                if args.len() < 2 {
+                    return Err("poke needs the bytes to write".to_string().into());
+                }
+                let bytes = args[1..]
+                    .iter()
+                    .map(|arg| parse_hex(arg).and_then(byte))
+                    .collect::<Result<Vec<u8>, String>>()?;
+                for (offset, data) in bytes.into_iter().enumerate() {
+                    cpu.bus.mem_write(address.wrapping_add(offset as u16), data);
+                }
+                None
+            }
+            "disasm" | "d" => {
+                let start = match args.first() {
+                    Some(arg) => parse_hex(arg)?,
+                    None => start_before(cpu, cpu.program_counter, 3),
+                };
+                let count = optional(args.get(1), parse_count, 10)?;
+                disassemble(cpu, start, count, output)?;
+                None
+            }
+            "help" | "h" | "?" => {
+                write!(output, "{}", HELP)?;
+                None
+            }
+            _ => return Err(format!("unknown command {}, try help", command).into()),
+        };
+        if let Some(stop) = stop {
+            report(cpu, stop, output)?;
+        }
+        Ok(())
+    }
+}
+
+/// Steps until `done(cpu, opcode, frame)` returns true, where `opcode`
+/// is the byte that was at PC before the step and `frame` tells if the
+/// step completed a frame, or until something else stops execution.
+fn run_until<F>(cpu: &mut CPU, mut done: F) -> Stop
+where
+    F: FnMut(&CPU, u8, bool) -> bool,
+{
+    cpu.bus.take_watch_hits();
+    loop {
+        let opcode = cpu.bus.peek(cpu.program_counter);
+        let result = cpu.step();
+        let frame = cpu.bus.poll_frame_complete();
+        if let Some(hit) = cpu.bus.take_watch_hits().into_iter().next() {
+            return Stop::Watchpoint(hit);
+        }
+        match result {
+            Ok(StopReason::Stepped { .. }) => {}
+            Ok(reason) => return Stop::Cpu(reason),
+            Err(err) => return Stop::Error(err),
+        }
+        if done(cpu, opcode, frame) {
+            return Stop::Done;
+        }
+        if cpu.has_breakpoint(cpu.program_counter) {
+            return Stop::Breakpoint(cpu.program_counter);
+        }
+    }
+}
+
+fn disassemble(cpu: &CPU, start: u16, count: usize, output: &mut dyn Write) -> io::Result<()> {
+    let mut address = start;
+    for _ in 0..count {
+        let instruction = instruction(cpu, address);
+        let marker = match (address == cpu.program_counter, cpu.has_breakpoint(address)) {
+            (true, _) => '>',
+            (false, true) => '*',
+            (false, false) => ' ',
+        };
+        let text =
+            instruction.format(|address| disasm::hardware_register(address).map(str::to_string));
+        writeln!(output, "{} {}", marker, text)?;
+        address = address.wrapping_add(instruction.length());
+    }
+    Ok(())
+}
+
+fn report(cpu: &CPU, stop: Stop, output: &mut dyn Write) -> io::Result<()> {
+    match stop {
+        Stop::Done => {}
+        Stop::Breakpoint(address) => writeln!(output, "Breakpoint at ${:04X}", address)?,
+        Stop::Watchpoint(hit) => {
+            let access = match hit.access {
+                Access::Read => "Read",
+                Access::Write => "Write",
+            };
+            writeln!(
+                output,
+                "{} of ${:02X} at ${:04X}",
+                access, hit.data, hit.address
+            )?
+        }
+        Stop::Cpu(reason) => writeln!(output, "CPU stopped: {}", reason)?,
+        Stop::Error(err) => writeln!(output, "CPU error: {}", err)?,
+    }
+    writeln!(output, "{}", trace(cpu))
+}
+
+fn registers(cpu: &CPU) -> String {
+    let status = cpu.status.get() | 0b0010_0000;
+    let flags: String = FLAGS
+        .iter()
+        .map(|&(name, bit)| {
+            if status & bit != 0 {
+                name.to_ascii_uppercase()
+            } else {
+                name
+            }
+        })
+        .collect();
+    format!(
+        "A:{:02X} X:{:02X} Y:{:02X} SP:{:02X} PC:{:04X} P:{:02X} [{}] CYC:{}",
+        cpu.accumulator,
+        cpu.register_x,
+        cpu.register_y,
+        cpu.stack_pointer,
+        cpu.program_counter,
+        status,
+        flags,
+        cpu.cycles
+    )
+}
+
+fn set_register(cpu: &mut CPU, register: &str, value: u16) -> Result<(), String> {
+    if register == "pc" {
+        cpu.program_counter = value;
+        return Ok(());
+    }
+    let value = byte(value)?;
+    match register {
+        "a" => cpu.accumulator = value,
+        "x" => cpu.register_x = value,
+        "y" => cpu.register_y = value,
+        "sp" => cpu.stack_pointer = value,
+        "p" => cpu.status.insert(value),
+        _ => {
+            let bit = FLAGS
+                .iter()
+                .find(|&&(name, _)| name != '-' && name != 'b' && register == name.to_string())
+                .map(|&(_, bit)| bit)
+                .ok_or(format!("unknown register {}", register))?;
+            let status = cpu.status.get();
+            match value {
+                0 => cpu.status.insert(status & !bit),
+                1 => cpu.status.insert(status | bit),
+                _ => return Err("flags are 0 or 1".to_string()),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Hex dump through `Bus::peek`, 16 bytes a line.
+fn dump(cpu: &CPU, address: u16, length: usize, output: &mut dyn Write) -> io::Result<()> {
+    let bytes: Vec<u8> = (0..length)
+        .map(|offset| cpu.bus.peek(address.wrapping_add(offset as u16)))
+        .collect();
+    for (line, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let text: String = chunk
+            .iter()
+            .map(|&byte| match byte {
+                0x20..=0x7E => byte as char,
+                _ => '.',
+            })
+            .collect();
+        let start = address.wrapping_add(line as u16 * 16);
+        writeln!(output, "{:04X}  {:47}  {}", start, hex.join(" "), text)?;
+    }
+    Ok(())
+}
+
+fn instruction(cpu: &CPU, address: u16) -> Instruction {
+    disasm::decode(address, |address| cpu.bus.peek(address))
+}
+
+/// Where to start disassembling to show about `count` instructions before
+/// `pc`. Code can be decoded from any byte, so this takes the furthest
+/// start whose instructions line up with `pc`.
+fn start_before(cpu: &CPU, pc: u16, count: u16) -> u16 {
+    (1..=count * 3)
+        .rev()
+        .map(|distance| pc.wrapping_sub(distance))
+        .find(|&start| {
+            let mut address = start;
+            let mut instructions = 0;
+            while address != pc && pc.wrapping_sub(address) <= count * 3 {
+                address = address.wrapping_add(instruction(cpu, address).length());
+                instructions += 1;
+            }
+            address == pc && instructions <= count
+        })
+        .unwrap_or(pc)
+}
+
+fn parse_hex(text: &str) -> Result<u16, String> {
+    let digits = text
+        .strip_prefix('$')
+        .or_else(|| text.strip_prefix("0x"))
+        .unwrap_or(text);
+    u16::from_str_radix(digits, 16).map_err(|_| format!("invalid hex number {}", text))
+}
+
+fn parse_count(text: &str) -> Result<usize, String> {
+    text.parse()
+        .ok()
+        .filter(|&count| count > 0)
+        .ok_or(format!("invalid count {}", text))
+}
+
+fn byte(value: u16) -> Result<u8, String> {
+    if value > 0xFF {
+        return Err(format!("${:X} does not fit in a byte", value));
+    }
+    Ok(value as u8)
+}
+
+fn required<'a>(arg: Option<&&'a str>, what: &str) -> Result<&'a str, String> {
+    arg.copied().ok_or(format!("expected {}", what))
+}
+
+fn optional<T>(
+    arg: Option<&&str>,
+    parse: fn(&str) -> Result<T, String>,
+    default: T,
+) -> Result<T, String> {
+    arg.map_or(Ok(default), |arg| parse(arg))
+}
@@ -0,0 +1,175 @@
+use super::Debugger;
+use crate::cpu::CPU;
+
+/// ```text
+/// 0600  JSR $0609
+/// 0603  INX
+/// 0604  STA $0200
+/// 0607  BRK
+/// 0608  NOP
+/// 0609  LDA #$42
+/// 060B  INY
+/// 060C  RTS
+/// ```
+fn program() -> CPU {
+    let mut cpu = CPU::debug_new();
+    cpu.load(vec![
+        0x20, 0x09, 0x06, 0xE8, 0x8D, 0x00, 0x02, 0x00, 0xEA, 0xA9, 0x42, 0xC8, 0x60,
+    ]);
+    cpu.reset();
+    cpu
+}
+
+/// Runs the commands in `script` and returns what the debugger printed.
+fn session(cpu: &mut CPU, script: &str) -> String {
+    let mut output = Vec::new();
+    Debugger::new()
+        .run(cpu, script.as_bytes(), &mut output)
+        .unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn steps_instructions() {
+    let mut cpu = program();
+    session(&mut cpu, "step\nstep 2\n");
+    assert_eq!(0x060C, cpu.program_counter);
+    assert_eq!(1, cpu.register_y);
+}
+
+#[test]
+fn empty_line_repeats_the_last_command() {
+    let mut cpu = program();
+    session(&mut cpu, "step\n\n");
+    assert_eq!(0x060B, cpu.program_counter);
+}
+
+#[test]
+fn next_steps_over_subroutines() {
+    let mut cpu = program();
+    session(&mut cpu, "next\n");
+    assert_eq!(0x0603, cpu.program_counter);
+    assert_eq!(0x42, cpu.accumulator);
+}
+
+#[test]
+fn finish_runs_until_the_subroutine_returns() {
+    let mut cpu = program();
+    session(&mut cpu, "step\nfinish\n");
+    assert_eq!(0x0603, cpu.program_counter);
+    assert_eq!(0xFD, cpu.stack_pointer);
+}
+
+#[test]
+fn continue_stops_at_breakpoints_and_halts() {
+    let mut cpu = program();
+    let output = session(&mut cpu, "break 604\ncontinue\n");
+    assert!(output.contains("Breakpoint at $0604"), "{}", output);
+    assert_eq!(0x0604, cpu.program_counter);
+
+    let output = session(&mut cpu, "continue\n");
+    assert!(output.contains("CPU stopped: BRK at $0607"), "{}", output);
+}
+
+#[test]
+fn stackcheck_stops_when_the_stack_wraps() {
+    let mut cpu = program();
+    cpu.stack_pointer = 0x00;
+    let output = session(&mut cpu, "stackcheck on\ncontinue\n");
+    assert!(output.contains("Stack check is on"), "{}", output);
+    assert!(
+        output.contains("CPU error: stack overflow in opcode $20 at $0600"),
+        "{}",
+        output
+    );
+    assert_eq!(0x0609, cpu.program_counter);
+    assert_eq!(0xFE, cpu.stack_pointer);
+}
+
+#[test]
+fn watchpoints_stop_after_the_access() {
+    let mut cpu = program();
+    let output = session(&mut cpu, "watch $0200 w\nbreakpoints\ncontinue\n");
+    assert!(output.contains("watch $0200 w"), "{}", output);
+    assert!(output.contains("Write of $42 at $0200"), "{}", output);
+    assert_eq!(0x0607, cpu.program_counter);
+
+    let output = session(&mut cpu, "unwatch 200\nunwatch 200\n");
+    assert!(
+        output.contains("error: no watchpoint at $0200"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn edits_registers_and_flags() {
+    let mut cpu = program();
+    let output = session(
+        &mut cpu,
+        "set a 7f\nset sp $80\nset c 1\nset pc 0x603\nset q 1\n",
+    );
+    assert_eq!(0x7F, cpu.accumulator);
+    assert_eq!(0x80, cpu.stack_pointer);
+    assert_eq!(0x0603, cpu.program_counter);
+    assert!(
+        output.contains("A:7F X:00 Y:00 SP:80 PC:0603 P:21 [nv-bdizC]"),
+        "{}",
+        output
+    );
+    assert!(output.contains("error: unknown register q"), "{}", output);
+}
+
+#[test]
+fn dumps_and_pokes_memory() {
+    let mut cpu = program();
+    let output = session(&mut cpu, "poke 10 48 69\nmem 10 4\npoke 10 100\n");
+    assert_eq!(0x69, cpu.bus.peek(0x0011));
+    assert!(
+        output.contains(&format!("0010  {:47}  Hi..", "48 69 00 00")),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("error: $100 does not fit in a byte"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn disassembles_from_an_address() {
+    let mut cpu = program();
+    let mut debugger = Debugger::new();
+    let mut output = Vec::new();
+    for line in &["break 604", "disasm 600 4"] {
+        assert!(debugger.execute(&mut cpu, line, &mut output).unwrap());
+    }
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(
+        output.lines().skip(1).collect::<Vec<_>>(),
+        vec![
+            "> 0600  20 09 06  JSR $0609",
+            "  0603  E8        INX",
+            "* 0604  8D 00 02  STA $0200",
+            "  0607  00        BRK",
+        ]
+    );
+}
+
+#[test]
+fn disassembles_around_pc() {
+    let mut cpu = program();
+    let output = session(&mut cpu, "next\nnext\ndisasm\n");
+    assert!(output.contains("  0600  20 09 06  JSR $0609"), "{}", output);
+    assert!(output.contains("> 0604  8D 00 02  STA $0200"), "{}", output);
+}
+
+#[test]
+fn reports_unknown_commands() {
+    let mut cpu = program();
+    let output = session(&mut cpu, "jump\nstep x\n");
+    assert!(output.contains("error: unknown command jump"), "{}", output);
+    assert!(output.contains("error: invalid count x"), "{}", output);
+    assert_eq!(0x0600, cpu.program_counter);
+}
@@ -0,0 +1,239 @@
+//! 6502 disassembler built on the CPU's opcode table, so it knows the same
+//! instructions, unofficial ones included (marked with `*` as in nestest.log).
+
+use crate::cpu::{AddressingMode, OPCODES};
+use std::fmt;
+
+#[cfg(test)]
+mod disasm_tests;
+
+/// Memory-mapped registers of the PPU, APU and controllers by the names
+/// used on the nesdev wiki.
+pub const HARDWARE_REGISTERS: [(u16, &str); 30] = [
+    (0x2000, "PPUCTRL"),
+    (0x2001, "PPUMASK"),
+    (0x2002, "PPUSTATUS"),
+    (0x2003, "OAMADDR"),
+    (0x2004, "OAMDATA"),
+    (0x2005, "PPUSCROLL"),
+    (0x2006, "PPUADDR"),
+    (0x2007, "PPUDATA"),
+    (0x4000, "SQ1_VOL"),
+    (0x4001, "SQ1_SWEEP"),
+    (0x4002, "SQ1_LO"),
+    (0x4003, "SQ1_HI"),
+    (0x4004, "SQ2_VOL"),
+    (0x4005, "SQ2_SWEEP"),
+    (0x4006, "SQ2_LO"),
+    (0x4007, "SQ2_HI"),
+    (0x4008, "TRI_LINEAR"),
+    (0x400A, "TRI_LO"),
+    (0x400B, "TRI_HI"),
+    (0x400C, "NOISE_VOL"),
+    (0x400E, "NOISE_LO"),
+    (0x400F, "NOISE_HI"),
+    (0x4010, "DMC_FREQ"),
+    (0x4011, "DMC_RAW"),
+    (0x4012, "DMC_START"),
+    (0x4013, "DMC_LEN"),
+    (0x4014, "OAMDMA"),
+    (0x4015, "SND_CHN"),
+    (0x4016, "JOY1"),
+    (0x4017, "JOY2"),
+];
+
+/// The name of the hardware register at `address`, if any.
+pub fn hardware_register(address: u16) -> Option<&'static str> {
+    HARDWARE_REGISTERS
+        .iter()
+        .find(|&&(register, _)| register == address)
+        .map(|&(_, name)| name)
+}
+
+/// Operand of a decoded instruction, one variant per way it is written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+    Implied,
+    Accumulator,
+    Immediate(u8),
+    ZeroPage(u8),
+    ZeroPageX(u8),
+    ZeroPageY(u8),
+    Absolute(u16),
+    AbsoluteX(u16),
+    AbsoluteY(u16),
+    /// `JMP ($nnnn)`.
+    Indirect(u16),
+    IndirectX(u8),
+    IndirectY(u8),
+    /// Branches, holding the target rather than the offset.
+    Relative(u16),
+    /// A byte that does not start a complete instruction, written `.db $nn`.
+    Data(u8),
+}
+
+impl Operand {
+    /// The address the operand names, for labelling it.
+    pub fn address(&self) -> Option<u16> {
+        match *self {
+            Operand::ZeroPage(address)
+            | Operand::ZeroPageX(address)
+            | Operand::ZeroPageY(address)
+            | Operand::IndirectX(address)
+            | Operand::IndirectY(address) => Some(address as u16),
+            Operand::Absolute(address)
+            | Operand::AbsoluteX(address)
+            | Operand::AbsoluteY(address)
+            | Operand::Indirect(address)
+            | Operand::Relative(address) => Some(address),
+            Operand::Implied | Operand::Accumulator | Operand::Immediate(_) | Operand::Data(_) => {
+                None
+            }
+        }
+    }
+
+    /// Writes the operand with its address replaced by `label`, if given.
+    pub fn format(&self, label: Option<&str>) -> String {
+        let zero_page = |address: u8| match label {
+            Some(label) => label.to_string(),
+            None => format!("${:02X}", address),
+        };
+        let absolute = |address: u16| match label {
+            Some(label) => label.to_string(),
+            None => format!("${:04X}", address),
+        };
+        match *self {
+            Operand::Implied => String::new(),
+            Operand::Accumulator => "A".to_string(),
+            Operand::Immediate(value) => format!("#${:02X}", value),
+            Operand::ZeroPage(address) => zero_page(address),
+            Operand::ZeroPageX(address) => format!("{},X", zero_page(address)),
+            Operand::ZeroPageY(address) => format!("{},Y", zero_page(address)),
+            Operand::Absolute(address) | Operand::Relative(address) => absolute(address),
+            Operand::AbsoluteX(address) => format!("{},X", absolute(address)),
+            Operand::AbsoluteY(address) => format!("{},Y", absolute(address)),
+            Operand::Indirect(address) => format!("({})", absolute(address)),
+            Operand::IndirectX(address) => format!("({},X)", zero_page(address)),
+            Operand::IndirectY(address) => format!("({}),Y", zero_page(address)),
+            Operand::Data(value) => format!("${:02X}", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub mnemonic: &'static str,
+    /// Written with a `*` in front of the mnemonic, as nestest.log does.
+    pub unofficial: bool,
+    pub operand: Operand,
+}
+
+impl Instruction {
+    pub fn length(&self) -> u16 {
+        self.bytes.len() as u16
+    }
+
+    /// `C000  4C F5 C5  JMP reset`, with the operand address named by
+    /// `labels` when it returns a name for it.
+    pub fn format<F>(&self, labels: F) -> String
+    where
+        F: Fn(u16) -> Option<String>,
+    {
+        let label = self.operand.address().and_then(labels);
+        let hex: Vec<String> = self
+            .bytes
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        let star = if self.unofficial { "*" } else { "" };
+        let text = format!(
+            "{:04X}  {:8} {:>4} {}",
+            self.address,
+            hex.join(" "),
+            format!("{}{}", star, self.mnemonic),
+            self.operand.format(label.as_deref())
+        );
+        text.trim_end().to_string()
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.format(|_| None))
+    }
+}
+
+/// Decodes the instruction at `address`, reading memory through `read`.
+pub fn decode<F>(address: u16, read: F) -> Instruction
+where
+    F: Fn(u16) -> u8,
+{
+    decode_with(address, |offset| Some(read(address.wrapping_add(offset))))
+}
+
+/// Decodes `bytes` as code loaded at `origin`, one instruction after the
+/// other. Bytes at the end that do not make a whole instruction come out
+/// as `.db` data.
+pub fn disassemble(bytes: &[u8], origin: u16) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut position = 0;
+    while position < bytes.len() {
+        let address = origin.wrapping_add(position as u16);
+        let instruction = decode_with(address, |offset| {
+            bytes.get(position + offset as usize).copied()
+        });
+        position += instruction.bytes.len();
+        instructions.push(instruction);
+    }
+    instructions
+}
+
+fn decode_with<F>(address: u16, read: F) -> Instruction
+where
+    F: Fn(u16) -> Option<u8>,
+{
+    let code = read(0).unwrap_or_default();
+    let opcode = &OPCODES[code as usize];
+    let bytes: Option<Vec<u8>> = (0..opcode.length.max(1) as u16).map(&read).collect();
+    let bytes = match bytes {
+        Some(bytes) => bytes,
+        None => {
+            return Instruction {
+                address,
+                bytes: vec![code],
+                mnemonic: ".db",
+                unofficial: false,
+                operand: Operand::Data(code),
+            }
+        }
+    };
+
+    let zero_page = bytes.get(1).copied().unwrap_or_default();
+    let absolute = u16::from_le_bytes([zero_page, bytes.get(2).copied().unwrap_or_default()]);
+    let operand = match opcode.mode {
+        AddressingMode::Implied => Operand::Implied,
+        AddressingMode::Accumulator => Operand::Accumulator,
+        AddressingMode::Relative => {
+            Operand::Relative(address.wrapping_add(2).wrapping_add(zero_page as i8 as u16))
+        }
+        AddressingMode::Indirect => Operand::Indirect(absolute),
+        AddressingMode::Immediate => Operand::Immediate(zero_page),
+        AddressingMode::ZeroPage => Operand::ZeroPage(zero_page),
+        AddressingMode::ZeroPage_X => Operand::ZeroPageX(zero_page),
+        AddressingMode::ZeroPage_Y => Operand::ZeroPageY(zero_page),
+        AddressingMode::Absolute => Operand::Absolute(absolute),
+        AddressingMode::Absolute_X => Operand::AbsoluteX(absolute),
+        AddressingMode::Absolute_Y => Operand::AbsoluteY(absolute),
+        AddressingMode::Indirect_X => Operand::IndirectX(zero_page),
+        AddressingMode::Indirect_Y => Operand::IndirectY(zero_page),
+    };
+    Instruction {
+        address,
+        bytes,
+        mnemonic: opcode.instruction.mnemonic(),
+        unofficial: opcode.unofficial,
+        operand,
+    }
+}
@@ -0,0 +1,91 @@
+use super::{decode, disassemble, hardware_register, Instruction, Operand};
+
+fn listing(bytes: &[u8], origin: u16) -> Vec<String> {
+    disassemble(bytes, origin)
+        .iter()
+        .map(Instruction::to_string)
+        .collect()
+}
+
+#[test]
+fn formats_every_addressing_mode() {
+    let code = [
+        0xEA, // NOP
+        0x0A, // ASL A
+        0xA9, 0x10, // LDA #$10
+        0xA5, 0x20, // LDA $20
+        0xB5, 0x21, // LDA $21,X
+        0xB6, 0x22, // LDX $22,Y
+        0xAD, 0x00, 0x20, // LDA $2000
+        0xBD, 0x34, 0x12, // LDA $1234,X
+        0xB9, 0x78, 0x56, // LDA $5678,Y
+        0xA1, 0x40, // LDA ($40,X)
+        0xB1, 0x41, // LDA ($41),Y
+        0x6C, 0xFF, 0x02, // JMP ($02FF)
+        0x20, 0x00, 0xC0, // JSR $C000
+        0x04, 0x50, // *NOP $50
+    ];
+    assert_eq!(
+        listing(&code, 0x8000),
+        vec![
+            "8000  EA        NOP",
+            "8001  0A        ASL A",
+            "8002  A9 10     LDA #$10",
+            "8004  A5 20     LDA $20",
+            "8006  B5 21     LDA $21,X",
+            "8008  B6 22     LDX $22,Y",
+            "800A  AD 00 20  LDA $2000",
+            "800D  BD 34 12  LDA $1234,X",
+            "8010  B9 78 56  LDA $5678,Y",
+            "8013  A1 40     LDA ($40,X)",
+            "8015  B1 41     LDA ($41),Y",
+            "8017  6C FF 02  JMP ($02FF)",
+            "801A  20 00 C0  JSR $C000",
+            "801D  04 50    *NOP $50",
+        ]
+    );
+}
+
+#[test]
+fn branches_show_their_target() {
+    // BNE back to itself, BPL forward over the next instruction
+    let instructions = disassemble(&[0xD0, 0xFE, 0x10, 0x01, 0xEA, 0xEA], 0xC000);
+    assert_eq!(instructions[0].operand, Operand::Relative(0xC000));
+    assert_eq!(instructions[1].operand, Operand::Relative(0xC005));
+    assert_eq!(instructions[1].to_string(), "C002  10 01     BPL $C005");
+}
+
+#[test]
+fn incomplete_instructions_at_the_end_are_data() {
+    assert_eq!(
+        listing(&[0xEA, 0xAD, 0x00], 0xFFFD),
+        vec![
+            "FFFD  EA        NOP",
+            "FFFE  AD        .db $AD",
+            "FFFF  00        BRK"
+        ]
+    );
+}
+
+#[test]
+fn decodes_through_a_read_function() {
+    let memory = [0x8D, 0x14, 0x40];
+    let instruction = decode(0x0001, |address| memory[address as usize - 1]);
+    assert_eq!(instruction.bytes, memory.to_vec());
+    assert_eq!(instruction.operand, Operand::Absolute(0x4014));
+}
+
+#[test]
+fn labels_replace_operand_addresses() {
+    let instructions = disassemble(&[0x8D, 0x14, 0x40, 0x9D, 0x00, 0x20, 0xB1, 0x10], 0x8000);
+    let labels = |address| hardware_register(address).map(str::to_string);
+    assert_eq!(instructions[0].format(labels), "8000  8D 14 40  STA OAMDMA");
+    assert_eq!(
+        instructions[1].format(labels),
+        "8003  9D 00 20  STA PPUCTRL,X"
+    );
+    assert_eq!(
+        instructions[2].format(|_| Some("pointer".to_string())),
+        "8006  B1 10     LDA (pointer),Y"
+    );
+}
@@ -0,0 +1,93 @@
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+#[cfg(test)]
+mod joypad_tests;
+
+/// Buttons of the standard controller, with the bit each one occupies in the
+/// order the shift register reports them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JoypadButton {
+    A = 0b0000_0001,
+    B = 0b0000_0010,
+    Select = 0b0000_0100,
+    Start = 0b0000_1000,
+    Up = 0b0001_0000,
+    Down = 0b0010_0000,
+    Left = 0b0100_0000,
+    Right = 0b1000_0000,
+}
+
+/// # Standard controller http://wiki.nesdev.com/w/index.php/Standard_controller
+///
+/// Writing 1 to bit 0 of $4016 (strobe) keeps reloading the shift register
+/// with the button states; writing 0 freezes it. Each read of $4016 (port 1)
+/// or $4017 (port 2) then returns the next button on bit 0, in the order
+/// A, B, Select, Start, Up, Down, Left, Right, and 1 once all eight have been
+/// read. While the strobe is high every read returns A.
+#[derive(Default)]
+pub struct Joypad {
+    strobe: bool,
+    shift_register: u8,
+    button_status: u8,
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Joypad::default()
+    }
+
+    pub fn set_button_pressed(&mut self, button: JoypadButton, pressed: bool) {
+        if pressed {
+            self.button_status |= button as u8;
+        } else {
+            self.button_status &= !(button as u8);
+        }
+    }
+
+    pub fn is_pressed(&self, button: JoypadButton) -> bool {
+        self.button_status & button as u8 != 0
+    }
+
+    /// The register latches the buttons while the strobe is high, so the
+    /// state at the falling edge is what the following reads see.
+    pub fn write(&mut self, data: u8) {
+        if self.strobe {
+            self.shift_register = self.button_status;
+        }
+        self.strobe = data & 1 != 0;
+        if self.strobe {
+            self.shift_register = self.button_status;
+        }
+    }
+
+    /// Returns the current button on bit 0 and shifts to the next one.
+    pub fn read(&mut self) -> u8 {
+        let bit = self.peek();
+        if !self.strobe {
+            // the official controller shifts in 1s behind the buttons
+            self.shift_register = self.shift_register >> 1 | 0b1000_0000;
+        }
+        bit
+    }
+
+    /// The bit `read` would return, without shifting.
+    pub fn peek(&self) -> u8 {
+        if self.strobe {
+            self.button_status & 1
+        } else {
+            self.shift_register & 1
+        }
+    }
+
+    /// The held buttons are left out: they follow the player, not the state.
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.strobe);
+        state.write_u8(self.shift_register);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.strobe = state.read_bool()?;
+        self.shift_register = state.read_u8()?;
+        Ok(())
+    }
+}
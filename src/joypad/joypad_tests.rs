@@ -0,0 +1,62 @@
+use super::{Joypad, JoypadButton};
+
+fn read_all(joypad: &mut Joypad) -> Vec<u8> {
+    (0..8).map(|_| joypad.read()).collect()
+}
+
+#[test]
+fn reports_buttons_in_order() {
+    let mut joypad = Joypad::new();
+    joypad.set_button_pressed(JoypadButton::A, true);
+    joypad.set_button_pressed(JoypadButton::Start, true);
+    joypad.set_button_pressed(JoypadButton::Left, true);
+
+    joypad.write(1);
+    joypad.write(0);
+    assert_eq!(read_all(&mut joypad), vec![1, 0, 0, 1, 0, 0, 1, 0]);
+}
+
+#[test]
+fn returns_one_after_eight_reads() {
+    let mut joypad = Joypad::new();
+    joypad.write(1);
+    joypad.write(0);
+    read_all(&mut joypad);
+    assert_eq!(joypad.read(), 1);
+    assert_eq!(joypad.read(), 1);
+}
+
+#[test]
+fn strobe_high_keeps_returning_a() {
+    let mut joypad = Joypad::new();
+    joypad.set_button_pressed(JoypadButton::A, true);
+    joypad.set_button_pressed(JoypadButton::B, true);
+    joypad.write(1);
+    assert_eq!(joypad.read(), 1);
+    assert_eq!(joypad.read(), 1);
+
+    joypad.set_button_pressed(JoypadButton::A, false);
+    assert_eq!(joypad.read(), 0);
+}
+
+#[test]
+fn state_is_latched_when_strobe_falls() {
+    let mut joypad = Joypad::new();
+    joypad.set_button_pressed(JoypadButton::B, true);
+    joypad.write(1);
+    joypad.write(0);
+    joypad.set_button_pressed(JoypadButton::A, true);
+    joypad.set_button_pressed(JoypadButton::B, false);
+
+    assert_eq!(read_all(&mut joypad), vec![0, 1, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(joypad.peek(), 1);
+}
+
+#[test]
+fn release_clears_button() {
+    let mut joypad = Joypad::new();
+    joypad.set_button_pressed(JoypadButton::Right, true);
+    assert!(joypad.is_pressed(JoypadButton::Right));
+    joypad.set_button_pressed(JoypadButton::Right, false);
+    assert!(!joypad.is_pressed(JoypadButton::Right));
+}
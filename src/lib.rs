@@ -0,0 +1,18 @@
+//! Emulation core of the NES: CPU, bus, PPU, APU, cartridges and mappers,
+//! with no dependency on a windowing or audio library. A frontend drives a
+//! `cpu::CPU`, takes pictures from `bus.ppu.frame` and samples from
+//! `bus.apu`, and feeds input into `bus.joypad1`/`bus.joypad2`.
+
+pub mod apu;
+pub mod audio;
+pub mod bus;
+pub mod cartridge;
+pub mod cpu;
+pub mod debugger;
+pub mod disasm;
+pub mod joypad;
+pub mod mapper;
+pub mod ppu;
+pub mod rewind;
+pub mod savestate;
+pub mod test_rom;
@@ -0,0 +1,246 @@
+mod cnrom;
+mod flat;
+mod mmc1;
+mod mmc3;
+mod nrom;
+mod uxrom;
+
+#[cfg(test)]
+mod mapper_tests;
+
+use std::ops::Range;
+
+use crate::cartridge::{Mirroring, Rom, RomError};
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+pub use cnrom::Cnrom;
+pub use flat::FlatMemory;
+pub use mmc1::Mmc1;
+pub use mmc3::Mmc3;
+pub use nrom::Nrom;
+pub use uxrom::Uxrom;
+
+const PRG_RAM: u16 = 0x6000;
+const PRG_RAM_END: u16 = 0x7FFF;
+const PRG_ROM: u16 = 0x8000;
+const PRG_RAM_SIZE: usize = 0x2000;
+const TRAINER_OFFSET: usize = 0x1000;
+const CHR_RAM_SIZE: usize = 0x2000;
+
+/// # Mapper https://wiki.nesdev.com/w/index.php/Mapper
+///
+/// Cartridge hardware seen by the CPU at $4020-$FFFF and by the PPU at
+/// $0000-$1FFF (pattern tables). Bank switching registers are written through
+/// `cpu_write`, the PPU asks for the nametable layout through `mirroring`.
+pub trait Mapper {
+    fn cpu_read(&self, address: u16) -> u8;
+
+    fn cpu_write(&mut self, address: u16, data: u8);
+
+    fn ppu_read(&self, address: u16) -> u8;
+
+    fn ppu_write(&mut self, address: u16, data: u8);
+
+    fn mirroring(&self) -> Mirroring;
+
+    /// State of the cartridge IRQ line, `true` while asserted.
+    fn irq(&self) -> bool {
+        false
+    }
+
+    /// Called by the PPU once per rendered scanline.
+    fn notify_scanline(&mut self) {}
+
+    /// The work RAM at $6000-$7FFF, `None` for boards without it.
+    fn prg_ram(&self) -> Option<&PrgRam> {
+        None
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut PrgRam> {
+        None
+    }
+
+    /// PRG RAM kept alive by the cartridge's battery while the console is
+    /// off, `None` for boards without one.
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.prg_ram().and_then(PrgRam::battery_ram)
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.prg_ram_mut().and_then(PrgRam::battery_ram_mut)
+    }
+
+    /// Returns `true` once after the battery RAM changed.
+    fn take_battery_ram_changed(&mut self) -> bool {
+        self.prg_ram_mut().is_some_and(PrgRam::take_changed)
+    }
+
+    /// Writes the board's registers, PRG RAM and CHR RAM for a save state.
+    fn save_state(&self, state: &mut StateWriter);
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError>;
+}
+
+pub fn create(rom: Rom) -> Result<Box<dyn Mapper>, RomError> {
+    match rom.mapper {
+        0 => Ok(Box::new(Nrom::new(rom))),
+        1 => Ok(Box::new(Mmc1::new(rom))),
+        2 => Ok(Box::new(Uxrom::new(rom))),
+        3 => Ok(Box::new(Cnrom::new(rom))),
+        4 => Ok(Box::new(Mmc3::new(rom))),
+        mapper => Err(RomError::UnsupportedMapper { mapper }),
+    }
+}
+
+/// Pattern table memory: CHR ROM from the file, or CHR RAM when the file has none.
+struct Chr {
+    data: Vec<u8>,
+    writable: bool,
+}
+
+impl Chr {
+    fn new(rom: &mut Rom) -> Self {
+        if rom.chr_rom.is_empty() {
+            let size = (rom.chr_ram_size + rom.chr_nvram_size).max(CHR_RAM_SIZE);
+            Chr {
+                data: vec![0; size],
+                writable: true,
+            }
+        } else {
+            Chr {
+                data: std::mem::take(&mut rom.chr_rom),
+                writable: false,
+            }
+        }
+    }
+
+    fn read(&self, bank: usize, bank_size: usize, address: u16) -> u8 {
+        self.data[bank_address(self.data.len(), bank, bank_size, address)]
+    }
+
+    fn write(&mut self, bank: usize, bank_size: usize, address: u16, data: u8) {
+        if self.writable {
+            let index = bank_address(self.data.len(), bank, bank_size, address);
+            self.data[index] = data;
+        }
+    }
+
+    /// Only CHR RAM is part of a save state; CHR ROM comes from the file.
+    fn save_state(&self, state: &mut StateWriter) {
+        if self.writable {
+            state.write_bytes(&self.data);
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        if self.writable {
+            state.read_bytes_into(&mut self.data, "CHR RAM size")?;
+        }
+        Ok(())
+    }
+}
+
+/// Work RAM at $6000-$7FFF, with the 512-byte trainer preloaded at $7000.
+/// The volatile RAM comes first and the battery-backed NVRAM after it; only
+/// the NVRAM is the game's save memory. A trainer needs RAM to live in, so
+/// its cartridges get the whole 8 KiB window whatever the header declares.
+pub struct PrgRam {
+    data: Vec<u8>,
+    nvram: Range<usize>,
+    changed: bool,
+}
+
+impl PrgRam {
+    pub fn new(rom: &Rom) -> Self {
+        let nvram = if rom.battery {
+            rom.prg_ram_size..rom.prg_ram_size + rom.prg_nvram_size
+        } else {
+            0..0
+        };
+        let mut size = rom.prg_ram_size + rom.prg_nvram_size;
+        if rom.trainer.is_some() {
+            size = size.max(PRG_RAM_SIZE);
+        }
+        let mut data = vec![0; size];
+        if let Some(trainer) = &rom.trainer {
+            data[TRAINER_OFFSET..TRAINER_OFFSET + trainer.len()].copy_from_slice(trainer);
+        }
+        PrgRam {
+            data,
+            nvram,
+            changed: false,
+        }
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        if self.data.is_empty() {
+            return 0;
+        }
+        self.data[(address - PRG_RAM) as usize % self.data.len()]
+    }
+
+    pub fn write(&mut self, address: u16, data: u8) {
+        if !self.data.is_empty() {
+            let index = (address - PRG_RAM) as usize % self.data.len();
+            self.changed |= self.data[index] != data && self.nvram.contains(&index);
+            self.data[index] = data;
+        }
+    }
+
+    fn has_battery(&self) -> bool {
+        !self.nvram.is_empty()
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        if self.has_battery() {
+            Some(&self.data[self.nvram.clone()])
+        } else {
+            None
+        }
+    }
+
+    fn battery_ram_mut(&mut self) -> Option<&mut [u8]> {
+        if self.has_battery() {
+            Some(&mut self.data[self.nvram.clone()])
+        } else {
+            None
+        }
+    }
+
+    fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed) && self.has_battery()
+    }
+
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.data);
+    }
+
+    /// A loaded state brings its own save memory, which needs flushing too.
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_bytes_into(&mut self.data, "PRG RAM size")?;
+        self.changed = true;
+        Ok(())
+    }
+}
+
+fn save_mirroring(state: &mut StateWriter, mirroring: Mirroring) {
+    state.write_u8(mirroring as u8);
+}
+
+fn load_mirroring(state: &mut StateReader) -> Result<Mirroring, SaveStateError> {
+    match state.read_u8()? {
+        0 => Ok(Mirroring::Vertical),
+        1 => Ok(Mirroring::Horizontal),
+        2 => Ok(Mirroring::FourScreen),
+        3 => Ok(Mirroring::SingleScreenLower),
+        4 => Ok(Mirroring::SingleScreenUpper),
+        _ => Err(SaveStateError::Invalid("mirroring")),
+    }
+}
+
+/// Index into `len` bytes of memory for `address` inside `bank`, where bank
+/// numbers past the end of the memory wrap around like on the real boards.
+fn bank_address(len: usize, bank: usize, bank_size: usize, address: u16) -> usize {
+    let banks = (len / bank_size).max(1);
+    ((bank % banks) * bank_size + (address as usize % bank_size)) % len
+}
@@ -0,0 +1,83 @@
+use super::{Chr, Mapper, PrgRam, PRG_RAM, PRG_RAM_END, PRG_ROM};
+use crate::cartridge::{Mirroring, Rom};
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+const PRG_BANK_SIZE: usize = 0x8000;
+const CHR_BANK_SIZE: usize = 0x2000;
+
+/// # CNROM (mapper 3) https://wiki.nesdev.com/w/index.php/CNROM
+///
+/// Fixed PRG ROM like NROM, any write to $8000-$FFFF selects the 8 KiB CHR bank.
+pub struct Cnrom {
+    prg_rom: Vec<u8>,
+    prg_ram: PrgRam,
+    chr: Chr,
+    mirroring: Mirroring,
+    chr_bank: usize,
+}
+
+impl Cnrom {
+    pub fn new(mut rom: Rom) -> Self {
+        Cnrom {
+            prg_ram: PrgRam::new(&rom),
+            chr: Chr::new(&mut rom),
+            mirroring: rom.screen_mirroring,
+            prg_rom: rom.prg_rom,
+            chr_bank: 0,
+        }
+    }
+}
+
+impl Mapper for Cnrom {
+    fn cpu_read(&self, address: u16) -> u8 {
+        match address {
+            PRG_RAM..=PRG_RAM_END => self.prg_ram.read(address),
+            PRG_ROM..=0xFFFF => {
+                let index = super::bank_address(self.prg_rom.len(), 0, PRG_BANK_SIZE, address);
+                self.prg_rom[index]
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, data: u8) {
+        match address {
+            PRG_RAM..=PRG_RAM_END => self.prg_ram.write(address, data),
+            PRG_ROM..=0xFFFF => self.chr_bank = data as usize,
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, address: u16) -> u8 {
+        self.chr.read(self.chr_bank, CHR_BANK_SIZE, address)
+    }
+
+    fn ppu_write(&mut self, address: u16, data: u8) {
+        self.chr.write(self.chr_bank, CHR_BANK_SIZE, address, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn prg_ram(&self) -> Option<&PrgRam> {
+        Some(&self.prg_ram)
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut PrgRam> {
+        Some(&mut self.prg_ram)
+    }
+
+    fn save_state(&self, state: &mut StateWriter) {
+        self.prg_ram.save_state(state);
+        self.chr.save_state(state);
+        state.write_u32(self.chr_bank as u32);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.prg_ram.load_state(state)?;
+        self.chr.load_state(state)?;
+        self.chr_bank = state.read_u32()? as usize;
+        Ok(())
+    }
+}
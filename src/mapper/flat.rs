@@ -0,0 +1,64 @@
+use super::{Chr, Mapper};
+use crate::cartridge::Mirroring;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+const CARTRIDGE_SPACE: u16 = 0x4020;
+const CHR_BANK_SIZE: usize = 0x2000;
+
+/// Not a real board: the whole $4020-$FFFF range is plain RAM and the
+/// pattern tables are 8 KiB of CHR RAM. Used when no cartridge is inserted,
+/// which is enough for the demo programs and the unit tests.
+pub struct FlatMemory {
+    memory: Vec<u8>,
+    chr: Chr,
+}
+
+impl FlatMemory {
+    pub fn new() -> Self {
+        FlatMemory {
+            memory: vec![0; 0x10000 - CARTRIDGE_SPACE as usize],
+            chr: Chr {
+                data: vec![0; CHR_BANK_SIZE],
+                writable: true,
+            },
+        }
+    }
+}
+
+impl Default for FlatMemory {
+    fn default() -> Self {
+        FlatMemory::new()
+    }
+}
+
+impl Mapper for FlatMemory {
+    fn cpu_read(&self, address: u16) -> u8 {
+        self.memory[(address - CARTRIDGE_SPACE) as usize]
+    }
+
+    fn cpu_write(&mut self, address: u16, data: u8) {
+        self.memory[(address - CARTRIDGE_SPACE) as usize] = data;
+    }
+
+    fn ppu_read(&self, address: u16) -> u8 {
+        self.chr.read(0, CHR_BANK_SIZE, address)
+    }
+
+    fn ppu_write(&mut self, address: u16, data: u8) {
+        self.chr.write(0, CHR_BANK_SIZE, address, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        Mirroring::Horizontal
+    }
+
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.memory);
+        self.chr.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_bytes_into(&mut self.memory, "memory size")?;
+        self.chr.load_state(state)
+    }
+}
@@ -0,0 +1,264 @@
+use super::{create, Cnrom, Mapper, Mmc1, Mmc3, Nrom, Uxrom};
+use crate::cartridge::{Mirroring, Rom, RomBuilder, RomError};
+
+/// Builds an image where every byte of 8 KiB PRG bank `n` holds `n`
+/// and every byte of 1 KiB CHR bank `n` holds `n`.
+fn builder(mapper: u8, prg_16k_banks: u8, chr_8k_banks: u8) -> RomBuilder {
+    RomBuilder::new()
+        .mapper(mapper)
+        .prg_rom(
+            (0..prg_16k_banks as usize * 0x4000)
+                .map(|i| (i / 0x2000) as u8)
+                .collect(),
+        )
+        .chr_rom(
+            (0..chr_8k_banks as usize * 0x2000)
+                .map(|i| (i / 0x0400) as u8)
+                .collect(),
+        )
+}
+
+fn test_rom(mapper: u8, prg_16k_banks: u8, chr_8k_banks: u8) -> Rom {
+    builder(mapper, prg_16k_banks, chr_8k_banks).rom().unwrap()
+}
+
+fn mmc1_write(mapper: &mut Mmc1, address: u16, value: u8) {
+    for bit in 0..5 {
+        mapper.cpu_write(address, (value >> bit) & 1);
+    }
+}
+
+#[test]
+fn create_picks_mapper_from_header() {
+    assert!(create(test_rom(0, 1, 1)).is_ok());
+    assert!(create(test_rom(4, 2, 1)).is_ok());
+    assert_eq!(
+        create(test_rom(5, 1, 1)).err().map(|err| err.to_string()),
+        Some(RomError::UnsupportedMapper { mapper: 5 }.to_string())
+    );
+}
+
+#[test]
+fn nrom_mirrors_16k_prg_and_keeps_header_mirroring() {
+    let mapper = Nrom::new(
+        builder(0, 1, 1)
+            .mirroring(Mirroring::Vertical)
+            .rom()
+            .unwrap(),
+    );
+    assert_eq!(mapper.cpu_read(0x8000), 0);
+    assert_eq!(mapper.cpu_read(0xA000), 1);
+    assert_eq!(mapper.cpu_read(0xC000), 0);
+    assert_eq!(mapper.cpu_read(0xE000), 1);
+    assert_eq!(mapper.ppu_read(0x1C00), 7);
+    assert_eq!(mapper.mirroring(), Mirroring::Vertical);
+}
+
+#[test]
+fn nrom_chr_rom_is_read_only_and_chr_ram_is_writable() {
+    let mut mapper = Nrom::new(test_rom(0, 2, 1));
+    mapper.ppu_write(0x0000, 0xFF);
+    assert_eq!(mapper.ppu_read(0x0000), 0);
+
+    let mut mapper = Nrom::new(test_rom(0, 2, 0));
+    mapper.ppu_write(0x0000, 0xFF);
+    assert_eq!(mapper.ppu_read(0x0000), 0xFF);
+}
+
+#[test]
+fn nrom_prg_ram() {
+    let mut mapper = Nrom::new(test_rom(0, 1, 1));
+    mapper.cpu_write(0x6000, 0x42);
+    assert_eq!(mapper.cpu_read(0x6000), 0x42);
+}
+
+#[test]
+fn trainer_is_loaded_without_declared_prg_ram() {
+    let rom = builder(0, 1, 1)
+        .trainer(vec![0x7F; 512])
+        .prg_ram(0, 0)
+        .rom()
+        .unwrap();
+    assert_eq!(rom.prg_ram_size, 0);
+    let mapper = Nrom::new(rom);
+    assert_eq!(mapper.cpu_read(0x6FFF), 0x00);
+    assert_eq!(mapper.cpu_read(0x7000), 0x7F);
+    assert_eq!(mapper.cpu_read(0x71FF), 0x7F);
+}
+
+#[test]
+fn uxrom_switches_lower_bank_and_fixes_last() {
+    let mut mapper = Uxrom::new(test_rom(2, 8, 0));
+    assert_eq!(mapper.cpu_read(0x8000), 0);
+    assert_eq!(mapper.cpu_read(0xC000), 14);
+    assert_eq!(mapper.cpu_read(0xE000), 15);
+
+    mapper.cpu_write(0x8000, 3);
+    assert_eq!(mapper.cpu_read(0x8000), 6);
+    assert_eq!(mapper.cpu_read(0xA000), 7);
+    assert_eq!(mapper.cpu_read(0xC000), 14);
+}
+
+#[test]
+fn cnrom_switches_chr_bank() {
+    let mut mapper = Cnrom::new(test_rom(3, 2, 4));
+    assert_eq!(mapper.ppu_read(0x0000), 0);
+
+    mapper.cpu_write(0xFFFF, 2);
+    assert_eq!(mapper.ppu_read(0x0000), 16);
+    assert_eq!(mapper.ppu_read(0x1C00), 23);
+    assert_eq!(mapper.cpu_read(0xC000), 2);
+}
+
+#[test]
+fn mmc1_power_up_fixes_last_prg_bank() {
+    let mapper = Mmc1::new(test_rom(1, 8, 2));
+    assert_eq!(mapper.cpu_read(0x8000), 0);
+    assert_eq!(mapper.cpu_read(0xC000), 14);
+}
+
+#[test]
+fn mmc1_serial_writes_select_prg_bank() {
+    let mut mapper = Mmc1::new(test_rom(1, 8, 2));
+    mmc1_write(&mut mapper, 0xE000, 3);
+    assert_eq!(mapper.cpu_read(0x8000), 6);
+    assert_eq!(mapper.cpu_read(0xC000), 14);
+
+    // fix first bank at $8000, switch $C000
+    mmc1_write(&mut mapper, 0x8000, 0b0_1000);
+    assert_eq!(mapper.cpu_read(0x8000), 0);
+    assert_eq!(mapper.cpu_read(0xC000), 6);
+
+    // 32 KiB mode ignores the low bank bit
+    mmc1_write(&mut mapper, 0x8000, 0b0_0000);
+    assert_eq!(mapper.cpu_read(0x8000), 4);
+    assert_eq!(mapper.cpu_read(0xC000), 6);
+}
+
+#[test]
+fn mmc1_reset_bit_clears_partial_write() {
+    let mut mapper = Mmc1::new(test_rom(1, 8, 2));
+    mapper.cpu_write(0xE000, 1);
+    mapper.cpu_write(0xE000, 1);
+    mapper.cpu_write(0xE000, 0x80);
+    mmc1_write(&mut mapper, 0xE000, 2);
+    assert_eq!(mapper.cpu_read(0x8000), 4);
+}
+
+#[test]
+fn mmc1_chr_modes_and_mirroring() {
+    let mut mapper = Mmc1::new(test_rom(1, 2, 4));
+
+    // 8 KiB mode ignores the low bit of CHR bank 0
+    mmc1_write(&mut mapper, 0x8000, 0b0_0010);
+    mmc1_write(&mut mapper, 0xA000, 3);
+    assert_eq!(mapper.ppu_read(0x0000), 8);
+    assert_eq!(mapper.ppu_read(0x1000), 12);
+    assert_eq!(mapper.mirroring(), Mirroring::Vertical);
+
+    // 4 KiB mode uses both registers
+    mmc1_write(&mut mapper, 0x8000, 0b1_0011);
+    mmc1_write(&mut mapper, 0xC000, 5);
+    assert_eq!(mapper.ppu_read(0x0000), 12);
+    assert_eq!(mapper.ppu_read(0x1000), 20);
+    assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+
+    mmc1_write(&mut mapper, 0x8000, 0b1_0001);
+    assert_eq!(mapper.mirroring(), Mirroring::SingleScreenUpper);
+}
+
+#[test]
+fn mmc1_prg_ram_can_be_disabled() {
+    let mut mapper = Mmc1::new(test_rom(1, 2, 1));
+    mapper.cpu_write(0x6000, 0x42);
+    assert_eq!(mapper.cpu_read(0x6000), 0x42);
+
+    mmc1_write(&mut mapper, 0xE000, 0b1_0000);
+    assert_eq!(mapper.cpu_read(0x6000), 0);
+}
+
+#[test]
+fn mmc3_prg_banks_in_both_modes() {
+    let mut mapper = Mmc3::new(test_rom(4, 8, 8));
+    mapper.cpu_write(0x8000, 6);
+    mapper.cpu_write(0x8001, 3);
+    mapper.cpu_write(0x8000, 7);
+    mapper.cpu_write(0x8001, 5);
+    assert_eq!(mapper.cpu_read(0x8000), 3);
+    assert_eq!(mapper.cpu_read(0xA000), 5);
+    assert_eq!(mapper.cpu_read(0xC000), 14);
+    assert_eq!(mapper.cpu_read(0xE000), 15);
+
+    mapper.cpu_write(0x8000, 0b0100_0110);
+    assert_eq!(mapper.cpu_read(0x8000), 14);
+    assert_eq!(mapper.cpu_read(0xA000), 5);
+    assert_eq!(mapper.cpu_read(0xC000), 3);
+    assert_eq!(mapper.cpu_read(0xE000), 15);
+}
+
+#[test]
+fn mmc3_chr_banks_and_inversion() {
+    let mut mapper = Mmc3::new(test_rom(4, 2, 8));
+    for (register, bank) in [(0, 9), (1, 12), (2, 20), (3, 21), (4, 22), (5, 23)].iter() {
+        mapper.cpu_write(0x8000, *register);
+        mapper.cpu_write(0x8001, *bank);
+    }
+    assert_eq!(mapper.ppu_read(0x0000), 8);
+    assert_eq!(mapper.ppu_read(0x0400), 9);
+    assert_eq!(mapper.ppu_read(0x0800), 12);
+    assert_eq!(mapper.ppu_read(0x0C00), 13);
+    assert_eq!(mapper.ppu_read(0x1000), 20);
+    assert_eq!(mapper.ppu_read(0x1C00), 23);
+
+    mapper.cpu_write(0x8000, 0b1000_0000);
+    assert_eq!(mapper.ppu_read(0x0000), 20);
+    assert_eq!(mapper.ppu_read(0x0C00), 23);
+    assert_eq!(mapper.ppu_read(0x1000), 8);
+    assert_eq!(mapper.ppu_read(0x1C00), 13);
+}
+
+#[test]
+fn mmc3_mirroring_register() {
+    let mut mapper = Mmc3::new(test_rom(4, 2, 1));
+    mapper.cpu_write(0xA000, 1);
+    assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+    mapper.cpu_write(0xA000, 0);
+    assert_eq!(mapper.mirroring(), Mirroring::Vertical);
+}
+
+#[test]
+fn mmc3_scanline_irq_counter() {
+    let mut mapper = Mmc3::new(test_rom(4, 2, 1));
+    mapper.cpu_write(0xC000, 3);
+    mapper.cpu_write(0xC001, 0);
+    mapper.cpu_write(0xE001, 0);
+
+    // first clock reloads the counter with the latch
+    mapper.notify_scanline();
+    mapper.notify_scanline();
+    mapper.notify_scanline();
+    assert!(!mapper.irq());
+    mapper.notify_scanline();
+    assert!(mapper.irq());
+
+    // acknowledging clears the line, counter reloads and keeps counting
+    mapper.cpu_write(0xE000, 0);
+    assert!(!mapper.irq());
+    mapper.cpu_write(0xE001, 0);
+    for _ in 0..3 {
+        mapper.notify_scanline();
+        assert!(!mapper.irq());
+    }
+    mapper.notify_scanline();
+    assert!(mapper.irq());
+}
+
+#[test]
+fn mmc3_disabled_irq_never_fires() {
+    let mut mapper = Mmc3::new(test_rom(4, 2, 1));
+    mapper.cpu_write(0xC000, 1);
+    for _ in 0..10 {
+        mapper.notify_scanline();
+    }
+    assert!(!mapper.irq());
+}
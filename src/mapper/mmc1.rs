@@ -0,0 +1,190 @@
+use super::{Chr, Mapper, PrgRam, PRG_RAM, PRG_RAM_END, PRG_ROM};
+use crate::cartridge::{Mirroring, Rom};
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_BANK_SIZE: usize = 0x1000;
+
+/// # MMC1 (mapper 1) https://wiki.nesdev.com/w/index.php/MMC1
+///
+/// Registers are loaded serially: five writes to $8000-$FFFF shift bit 0 into
+/// a shift register, the fifth write copies it to the register picked by
+/// address bits 13-14. A write with bit 7 set resets the shift register.
+///
+///  $8000-$9FFF: control  ---C PPMM
+///               |||| ||++- mirroring (0: one-screen lower, 1: one-screen upper,
+///               ||||        2: vertical, 3: horizontal)
+///               ||||++---- PRG mode (0, 1: 32 KiB, 2: fix first bank at $8000,
+///               ||||        3: fix last bank at $C000)
+///               |||+------ CHR mode (0: one 8 KiB bank, 1: two 4 KiB banks)
+///  $A000-$BFFF: CHR bank 0
+///  $C000-$DFFF: CHR bank 1
+///  $E000-$FFFF: PRG bank (bits 0-3), PRG RAM disable (bit 4)
+pub struct Mmc1 {
+    prg_rom: Vec<u8>,
+    prg_ram: PrgRam,
+    chr: Chr,
+    shift_register: u8,
+    shift_count: u8,
+    control: u8,
+    chr_bank_0: u8,
+    chr_bank_1: u8,
+    prg_bank: u8,
+}
+
+impl Mmc1 {
+    const SHIFT_RESET: u8 = 0b1000_0000;
+    const PRG_RAM_DISABLE: u8 = 0b0001_0000;
+
+    pub fn new(mut rom: Rom) -> Self {
+        Mmc1 {
+            prg_ram: PrgRam::new(&rom),
+            chr: Chr::new(&mut rom),
+            prg_rom: rom.prg_rom,
+            shift_register: 0,
+            shift_count: 0,
+            // power-up state fixes the last bank at $C000
+            control: 0x0C,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_bank: 0,
+        }
+    }
+
+    fn write_register(&mut self, address: u16, data: u8) {
+        match address {
+            0x8000..=0x9FFF => self.control = data,
+            0xA000..=0xBFFF => self.chr_bank_0 = data,
+            0xC000..=0xDFFF => self.chr_bank_1 = data,
+            _ => self.prg_bank = data,
+        }
+    }
+
+    fn prg_bank_for(&self, address: u16) -> usize {
+        let bank = (self.prg_bank & 0x0F) as usize;
+        let last_bank = (self.prg_rom.len() / PRG_BANK_SIZE).max(1) - 1;
+        let upper_half = address >= 0xC000;
+
+        match (self.control >> 2) & 0b11 {
+            0 | 1 => (bank & !1) | upper_half as usize,
+            2 => {
+                if upper_half {
+                    bank
+                } else {
+                    0
+                }
+            }
+            _ => {
+                if upper_half {
+                    last_bank
+                } else {
+                    bank
+                }
+            }
+        }
+    }
+
+    fn chr_bank_for(&self, address: u16) -> usize {
+        let upper_half = address >= 0x1000;
+        if self.control & 0b1_0000 == 0 {
+            (self.chr_bank_0 & !1) as usize | upper_half as usize
+        } else if upper_half {
+            self.chr_bank_1 as usize
+        } else {
+            self.chr_bank_0 as usize
+        }
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn cpu_read(&self, address: u16) -> u8 {
+        match address {
+            PRG_RAM..=PRG_RAM_END if self.prg_bank & Mmc1::PRG_RAM_DISABLE == 0 => {
+                self.prg_ram.read(address)
+            }
+            PRG_ROM..=0xFFFF => {
+                let bank = self.prg_bank_for(address);
+                self.prg_rom[super::bank_address(self.prg_rom.len(), bank, PRG_BANK_SIZE, address)]
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, data: u8) {
+        match address {
+            PRG_RAM..=PRG_RAM_END if self.prg_bank & Mmc1::PRG_RAM_DISABLE == 0 => {
+                self.prg_ram.write(address, data)
+            }
+            PRG_ROM..=0xFFFF => {
+                if data & Mmc1::SHIFT_RESET != 0 {
+                    self.shift_register = 0;
+                    self.shift_count = 0;
+                    self.control |= 0x0C;
+                    return;
+                }
+
+                self.shift_register |= (data & 1) << self.shift_count;
+                self.shift_count += 1;
+                if self.shift_count == 5 {
+                    self.write_register(address, self.shift_register);
+                    self.shift_register = 0;
+                    self.shift_count = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, address: u16) -> u8 {
+        self.chr
+            .read(self.chr_bank_for(address), CHR_BANK_SIZE, address)
+    }
+
+    fn ppu_write(&mut self, address: u16, data: u8) {
+        let bank = self.chr_bank_for(address);
+        self.chr.write(bank, CHR_BANK_SIZE, address, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0b11 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn prg_ram(&self) -> Option<&PrgRam> {
+        Some(&self.prg_ram)
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut PrgRam> {
+        Some(&mut self.prg_ram)
+    }
+
+    fn save_state(&self, state: &mut StateWriter) {
+        self.prg_ram.save_state(state);
+        self.chr.save_state(state);
+        state.write_u8(self.shift_register);
+        state.write_u8(self.shift_count);
+        state.write_u8(self.control);
+        state.write_u8(self.chr_bank_0);
+        state.write_u8(self.chr_bank_1);
+        state.write_u8(self.prg_bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.prg_ram.load_state(state)?;
+        self.chr.load_state(state)?;
+        self.shift_register = state.read_u8()?;
+        self.shift_count = state.read_u8()?;
+        if self.shift_count >= 5 {
+            return Err(SaveStateError::Invalid("MMC1 shift count"));
+        }
+        self.control = state.read_u8()?;
+        self.chr_bank_0 = state.read_u8()?;
+        self.chr_bank_1 = state.read_u8()?;
+        self.prg_bank = state.read_u8()?;
+        Ok(())
+    }
+}
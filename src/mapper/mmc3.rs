@@ -0,0 +1,223 @@
+use super::{Chr, Mapper, PrgRam, PRG_RAM, PRG_RAM_END, PRG_ROM};
+use crate::cartridge::{Mirroring, Rom};
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+const PRG_BANK_SIZE: usize = 0x2000;
+const CHR_BANK_SIZE: usize = 0x0400;
+
+/// # MMC3 (mapper 4) https://wiki.nesdev.com/w/index.php/MMC3
+///
+///  $8000-$9FFE even: bank select  CP-- -RRR
+///                    |||       +++- bank register R0-R7 updated by the next bank data write
+///                    |+----------- PRG mode (0: $8000 swappable, 1: $C000 swappable)
+///                    +------------ CHR A12 inversion
+///  $8001-$9FFF odd:  bank data
+///  $A000-$BFFE even: mirroring (0: vertical, 1: horizontal)
+///  $A001-$BFFF odd:  PRG RAM protect
+///  $C000-$DFFE even: IRQ latch
+///  $C001-$DFFF odd:  IRQ reload
+///  $E000-$FFFE even: IRQ disable and acknowledge
+///  $E001-$FFFF odd:  IRQ enable
+pub struct Mmc3 {
+    prg_rom: Vec<u8>,
+    prg_ram: PrgRam,
+    chr: Chr,
+    four_screen: bool,
+    bank_select: u8,
+    registers: [u8; 8],
+    mirroring: Mirroring,
+    prg_ram_protect: u8,
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+}
+
+impl Mmc3 {
+    const PRG_MODE: u8 = 0b0100_0000;
+    const CHR_INVERSION: u8 = 0b1000_0000;
+    const PRG_RAM_ENABLE: u8 = 0b1000_0000;
+    const PRG_RAM_WRITE_PROTECT: u8 = 0b0100_0000;
+
+    pub fn new(mut rom: Rom) -> Self {
+        Mmc3 {
+            prg_ram: PrgRam::new(&rom),
+            chr: Chr::new(&mut rom),
+            four_screen: rom.screen_mirroring == Mirroring::FourScreen,
+            mirroring: rom.screen_mirroring,
+            prg_rom: rom.prg_rom,
+            bank_select: 0,
+            registers: [0, 2, 4, 5, 6, 7, 0, 1],
+            prg_ram_protect: Mmc3::PRG_RAM_ENABLE,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+        }
+    }
+
+    fn prg_bank_for(&self, address: u16) -> usize {
+        let second_last = (self.prg_rom.len() / PRG_BANK_SIZE).max(2) - 2;
+        let swap_c000 = self.bank_select & Mmc3::PRG_MODE != 0;
+        match address {
+            0x8000..=0x9FFF if swap_c000 => second_last,
+            0x8000..=0x9FFF => self.registers[6] as usize,
+            0xA000..=0xBFFF => self.registers[7] as usize,
+            0xC000..=0xDFFF if swap_c000 => self.registers[6] as usize,
+            0xC000..=0xDFFF => second_last,
+            _ => second_last + 1,
+        }
+    }
+
+    fn chr_bank_for(&self, address: u16) -> usize {
+        // With inversion the two 2 KiB banks move to $1000-$1FFF
+        let address = if self.bank_select & Mmc3::CHR_INVERSION != 0 {
+            address ^ 0x1000
+        } else {
+            address
+        };
+        match address {
+            0x0000..=0x03FF => (self.registers[0] & 0xFE) as usize,
+            0x0400..=0x07FF => (self.registers[0] | 0x01) as usize,
+            0x0800..=0x0BFF => (self.registers[1] & 0xFE) as usize,
+            0x0C00..=0x0FFF => (self.registers[1] | 0x01) as usize,
+            0x1000..=0x13FF => self.registers[2] as usize,
+            0x1400..=0x17FF => self.registers[3] as usize,
+            0x1800..=0x1BFF => self.registers[4] as usize,
+            _ => self.registers[5] as usize,
+        }
+    }
+
+    fn write_register(&mut self, address: u16, data: u8) {
+        let even = address & 1 == 0;
+        match (address, even) {
+            (0x8000..=0x9FFF, true) => self.bank_select = data,
+            (0x8000..=0x9FFF, false) => {
+                self.registers[(self.bank_select & 0b111) as usize] = data;
+            }
+            (0xA000..=0xBFFF, true) => {
+                if !self.four_screen {
+                    self.mirroring = if data & 1 == 0 {
+                        Mirroring::Vertical
+                    } else {
+                        Mirroring::Horizontal
+                    };
+                }
+            }
+            (0xA000..=0xBFFF, false) => self.prg_ram_protect = data,
+            (0xC000..=0xDFFF, true) => self.irq_latch = data,
+            (0xC000..=0xDFFF, false) => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            (_, true) => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            (_, false) => self.irq_enabled = true,
+        }
+    }
+}
+
+impl Mapper for Mmc3 {
+    fn cpu_read(&self, address: u16) -> u8 {
+        match address {
+            PRG_RAM..=PRG_RAM_END if self.prg_ram_protect & Mmc3::PRG_RAM_ENABLE != 0 => {
+                self.prg_ram.read(address)
+            }
+            PRG_ROM..=0xFFFF => {
+                let bank = self.prg_bank_for(address);
+                self.prg_rom[super::bank_address(self.prg_rom.len(), bank, PRG_BANK_SIZE, address)]
+            }
+            _ => 0,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, data: u8) {
+        match address {
+            PRG_RAM..=PRG_RAM_END => {
+                let writable = Mmc3::PRG_RAM_ENABLE;
+                let protect = Mmc3::PRG_RAM_ENABLE | Mmc3::PRG_RAM_WRITE_PROTECT;
+                if self.prg_ram_protect & protect == writable {
+                    self.prg_ram.write(address, data);
+                }
+            }
+            PRG_ROM..=0xFFFF => self.write_register(address, data),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, address: u16) -> u8 {
+        self.chr
+            .read(self.chr_bank_for(address), CHR_BANK_SIZE, address)
+    }
+
+    fn ppu_write(&mut self, address: u16, data: u8) {
+        let bank = self.chr_bank_for(address);
+        self.chr.write(bank, CHR_BANK_SIZE, address, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn notify_scanline(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+
+    fn prg_ram(&self) -> Option<&PrgRam> {
+        Some(&self.prg_ram)
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut PrgRam> {
+        Some(&mut self.prg_ram)
+    }
+
+    fn save_state(&self, state: &mut StateWriter) {
+        self.prg_ram.save_state(state);
+        self.chr.save_state(state);
+        state.write_u8(self.bank_select);
+        for &register in &self.registers {
+            state.write_u8(register);
+        }
+        super::save_mirroring(state, self.mirroring);
+        state.write_u8(self.prg_ram_protect);
+        state.write_u8(self.irq_latch);
+        state.write_u8(self.irq_counter);
+        state.write_bool(self.irq_reload);
+        state.write_bool(self.irq_enabled);
+        state.write_bool(self.irq_pending);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.prg_ram.load_state(state)?;
+        self.chr.load_state(state)?;
+        self.bank_select = state.read_u8()?;
+        for register in self.registers.iter_mut() {
+            *register = state.read_u8()?;
+        }
+        self.mirroring = super::load_mirroring(state)?;
+        self.prg_ram_protect = state.read_u8()?;
+        self.irq_latch = state.read_u8()?;
+        self.irq_counter = state.read_u8()?;
+        self.irq_reload = state.read_bool()?;
+        self.irq_enabled = state.read_bool()?;
+        self.irq_pending = state.read_bool()?;
+        Ok(())
+    }
+}
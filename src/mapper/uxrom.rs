@@ -0,0 +1,88 @@
+use super::{Chr, Mapper, PrgRam, PRG_RAM, PRG_RAM_END, PRG_ROM};
+use crate::cartridge::{Mirroring, Rom};
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_BANK_SIZE: usize = 0x2000;
+const PRG_ROM_FIXED: u16 = 0xC000;
+
+/// # UxROM (mapper 2) https://wiki.nesdev.com/w/index.php/UxROM
+///
+///  $8000-$BFFF: switchable 16 KiB PRG bank, selected by any write to $8000-$FFFF
+///  $C000-$FFFF: fixed to the last 16 KiB PRG bank
+pub struct Uxrom {
+    prg_rom: Vec<u8>,
+    prg_ram: PrgRam,
+    chr: Chr,
+    mirroring: Mirroring,
+    prg_bank: usize,
+}
+
+impl Uxrom {
+    pub fn new(mut rom: Rom) -> Self {
+        Uxrom {
+            prg_ram: PrgRam::new(&rom),
+            chr: Chr::new(&mut rom),
+            mirroring: rom.screen_mirroring,
+            prg_rom: rom.prg_rom,
+            prg_bank: 0,
+        }
+    }
+
+    fn last_prg_bank(&self) -> usize {
+        (self.prg_rom.len() / PRG_BANK_SIZE).max(1) - 1
+    }
+}
+
+impl Mapper for Uxrom {
+    fn cpu_read(&self, address: u16) -> u8 {
+        let bank = match address {
+            PRG_RAM..=PRG_RAM_END => return self.prg_ram.read(address),
+            PRG_ROM..=0xBFFF => self.prg_bank,
+            PRG_ROM_FIXED..=0xFFFF => self.last_prg_bank(),
+            _ => return 0,
+        };
+        self.prg_rom[super::bank_address(self.prg_rom.len(), bank, PRG_BANK_SIZE, address)]
+    }
+
+    fn cpu_write(&mut self, address: u16, data: u8) {
+        match address {
+            PRG_RAM..=PRG_RAM_END => self.prg_ram.write(address, data),
+            PRG_ROM..=0xFFFF => self.prg_bank = data as usize,
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&self, address: u16) -> u8 {
+        self.chr.read(0, CHR_BANK_SIZE, address)
+    }
+
+    fn ppu_write(&mut self, address: u16, data: u8) {
+        self.chr.write(0, CHR_BANK_SIZE, address, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn prg_ram(&self) -> Option<&PrgRam> {
+        Some(&self.prg_ram)
+    }
+
+    fn prg_ram_mut(&mut self) -> Option<&mut PrgRam> {
+        Some(&mut self.prg_ram)
+    }
+
+    fn save_state(&self, state: &mut StateWriter) {
+        self.prg_ram.save_state(state);
+        self.chr.save_state(state);
+        state.write_u32(self.prg_bank as u32);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.prg_ram.load_state(state)?;
+        self.chr.load_state(state)?;
+        self.prg_bank = state.read_u32()? as usize;
+        Ok(())
+    }
+}
@@ -0,0 +1,620 @@
+mod frame;
+mod palette;
+
+#[cfg(test)]
+mod ppu_tests;
+
+use crate::cartridge::Mirroring;
+use crate::mapper::Mapper;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+pub use frame::Frame;
+pub use palette::SYSTEM_PALETTE;
+
+const DOTS_PER_SCANLINE: u16 = 341;
+const VISIBLE_SCANLINES: u16 = 240;
+const VBLANK_SCANLINE: u16 = 241;
+const PRE_RENDER_SCANLINE: u16 = 261;
+const SPRITES_PER_SCANLINE: usize = 8;
+
+const NAMETABLES: u16 = 0x2000;
+const NAMETABLES_MIRRORS_END: u16 = 0x3EFF;
+const PALETTES: u16 = 0x3F00;
+
+/// # PPU registers http://wiki.nesdev.com/w/index.php/PPU_registers
+///
+///  $2000 PPUCTRL    VPHB SINN  NMI enable (V), PPU master/slave (P), sprite height (H),
+///                              background tile select (B), sprite tile select (S),
+///                              increment mode (I), nametable select (NN)
+///  $2001 PPUMASK    BGRs bMmG  color emphasis (BGR), sprite enable (s), background enable (b),
+///                              sprite left column enable (M), background left column enable (m),
+///                              greyscale (G)
+///  $2002 PPUSTATUS  VSO- ----  vblank (V), sprite 0 hit (S), sprite overflow (O)
+///  $2003 OAMADDR    aaaa aaaa  OAM read/write address
+///  $2004 OAMDATA    dddd dddd  OAM data read/write
+///  $2005 PPUSCROLL  xxxx xxxx  fine scroll position (two writes: X scroll, Y scroll)
+///  $2006 PPUADDR    aaaa aaaa  PPU read/write address (two writes: most significant byte, least significant byte)
+///  $2007 PPUDATA    dddd dddd  PPU data read/write
+///  $4014 OAMDMA     aaaa aaaa  OAM DMA high address
+///
+/// Scrolling uses the internal `v`, `t`, `x` and `w` registers described in
+/// http://wiki.nesdev.com/w/index.php/PPU_scrolling
+///
+///  yyy NN YYYYY XXXXX
+///  ||| || ||||| +++++-- coarse X scroll
+///  ||| || +++++-------- coarse Y scroll
+///  ||| ++-------------- nametable select
+///  +++----------------- fine Y scroll
+pub struct NesPPU {
+    vram: [u8; 4096],
+    palette_table: [u8; 32],
+    oam_data: [u8; 256],
+    oam_addr: u8,
+
+    ctrl: u8,
+    mask: u8,
+    status: u8,
+
+    v: u16,
+    t: u16,
+    fine_x: u8,
+    w: bool,
+    internal_data_buffer: u8,
+    open_bus: u8,
+
+    scanline: u16,
+    cycle: u16,
+    odd_frame: bool,
+    sprite_zero_hit_at: Option<u16>,
+    nmi_pending: bool,
+    frame_complete: bool,
+
+    pub frame: Frame,
+}
+
+impl NesPPU {
+    const CTRL_NAMETABLE: u8 = 0b0000_0011;
+    const CTRL_VRAM_INCREMENT: u8 = 0b0000_0100;
+    const CTRL_SPRITE_TABLE: u8 = 0b0000_1000;
+    const CTRL_BACKGROUND_TABLE: u8 = 0b0001_0000;
+    const CTRL_SPRITE_SIZE: u8 = 0b0010_0000;
+    const CTRL_GENERATE_NMI: u8 = 0b1000_0000;
+
+    const MASK_GREYSCALE: u8 = 0b0000_0001;
+    const MASK_BACKGROUND_LEFT: u8 = 0b0000_0010;
+    const MASK_SPRITES_LEFT: u8 = 0b0000_0100;
+    const MASK_BACKGROUND: u8 = 0b0000_1000;
+    const MASK_SPRITES: u8 = 0b0001_0000;
+
+    const STATUS_SPRITE_OVERFLOW: u8 = 0b0010_0000;
+    const STATUS_SPRITE_ZERO_HIT: u8 = 0b0100_0000;
+    const STATUS_VBLANK: u8 = 0b1000_0000;
+
+    pub fn new() -> Self {
+        NesPPU {
+            vram: [0; 4096],
+            palette_table: [0; 32],
+            oam_data: [0; 256],
+            oam_addr: 0,
+            ctrl: 0,
+            mask: 0,
+            status: 0,
+            v: 0,
+            t: 0,
+            fine_x: 0,
+            w: false,
+            internal_data_buffer: 0,
+            open_bus: 0,
+            scanline: 0,
+            cycle: 0,
+            odd_frame: false,
+            sprite_zero_hit_at: None,
+            nmi_pending: false,
+            frame_complete: false,
+            frame: Frame::new(),
+        }
+    }
+
+    /// The frame buffer is left out; it is redrawn by the next frame.
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.vram);
+        state.write_bytes(&self.palette_table);
+        state.write_bytes(&self.oam_data);
+        state.write_u8(self.oam_addr);
+        state.write_u8(self.ctrl);
+        state.write_u8(self.mask);
+        state.write_u8(self.status);
+        state.write_u16(self.v);
+        state.write_u16(self.t);
+        state.write_u8(self.fine_x);
+        state.write_bool(self.w);
+        state.write_u8(self.internal_data_buffer);
+        state.write_u8(self.open_bus);
+        state.write_u16(self.scanline);
+        state.write_u16(self.cycle);
+        state.write_bool(self.odd_frame);
+        state.write_option_u16(self.sprite_zero_hit_at);
+        state.write_bool(self.nmi_pending);
+        state.write_bool(self.frame_complete);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_bytes_into(&mut self.vram, "VRAM size")?;
+        state.read_bytes_into(&mut self.palette_table, "palette size")?;
+        state.read_bytes_into(&mut self.oam_data, "OAM size")?;
+        self.oam_addr = state.read_u8()?;
+        self.ctrl = state.read_u8()?;
+        self.mask = state.read_u8()?;
+        self.status = state.read_u8()?;
+        self.v = state.read_u16()?;
+        self.t = state.read_u16()?;
+        self.fine_x = state.read_u8()?;
+        self.w = state.read_bool()?;
+        self.internal_data_buffer = state.read_u8()?;
+        self.open_bus = state.read_u8()?;
+        self.scanline = state.read_u16()?;
+        self.cycle = state.read_u16()?;
+        if self.scanline > PRE_RENDER_SCANLINE || self.cycle >= DOTS_PER_SCANLINE {
+            return Err(SaveStateError::Invalid("PPU position"));
+        }
+        self.odd_frame = state.read_bool()?;
+        self.sprite_zero_hit_at = state.read_option_u16()?;
+        self.nmi_pending = state.read_bool()?;
+        self.frame_complete = state.read_bool()?;
+        Ok(())
+    }
+
+    pub fn scanline(&self) -> u16 {
+        self.scanline
+    }
+
+    pub fn cycle(&self) -> u16 {
+        self.cycle
+    }
+
+    /// Returns `true` once per vblank NMI and clears it.
+    pub fn poll_nmi(&mut self) -> bool {
+        std::mem::replace(&mut self.nmi_pending, false)
+    }
+
+    /// Returns `true` once per finished frame and clears it.
+    pub fn poll_frame_complete(&mut self) -> bool {
+        std::mem::replace(&mut self.frame_complete, false)
+    }
+
+    pub fn open_bus(&self) -> u8 {
+        self.open_bus
+    }
+
+    pub fn write_to_ctrl(&mut self, value: u8) {
+        self.open_bus = value;
+        let nmi_was_enabled = self.ctrl & NesPPU::CTRL_GENERATE_NMI != 0;
+        self.ctrl = value;
+        self.t = (self.t & 0xF3FF) | (((value & NesPPU::CTRL_NAMETABLE) as u16) << 10);
+
+        // Enabling NMI during vblank fires it immediately
+        if !nmi_was_enabled
+            && value & NesPPU::CTRL_GENERATE_NMI != 0
+            && self.status & NesPPU::STATUS_VBLANK != 0
+        {
+            self.nmi_pending = true;
+        }
+    }
+
+    pub fn write_to_mask(&mut self, value: u8) {
+        self.open_bus = value;
+        self.mask = value;
+    }
+
+    /// PPUSTATUS is read-only, a write only fills the data bus latch.
+    pub fn write_to_status(&mut self, value: u8) {
+        self.open_bus = value;
+    }
+
+    pub fn read_status(&mut self) -> u8 {
+        let data = (self.status & 0xE0) | (self.open_bus & 0x1F);
+        self.status &= !NesPPU::STATUS_VBLANK;
+        self.w = false;
+        self.open_bus = data;
+        data
+    }
+
+    pub fn write_to_oam_addr(&mut self, value: u8) {
+        self.open_bus = value;
+        self.oam_addr = value;
+    }
+
+    pub fn write_to_oam_data(&mut self, value: u8) {
+        self.open_bus = value;
+        self.oam_data[self.oam_addr as usize] = value;
+        self.oam_addr = self.oam_addr.wrapping_add(1);
+    }
+
+    pub fn read_oam_data(&mut self) -> u8 {
+        let mut data = self.oam_data[self.oam_addr as usize];
+        // Unimplemented bits of the sprite attribute byte read back as 0
+        if self.oam_addr & 0b11 == 2 {
+            data &= 0xE3;
+        }
+        self.open_bus = data;
+        data
+    }
+
+    pub fn write_oam_dma(&mut self, data: &[u8; 256]) {
+        for byte in data.iter() {
+            self.oam_data[self.oam_addr as usize] = *byte;
+            self.oam_addr = self.oam_addr.wrapping_add(1);
+        }
+    }
+
+    pub fn write_to_scroll(&mut self, value: u8) {
+        self.open_bus = value;
+        if !self.w {
+            self.t = (self.t & 0xFFE0) | (value >> 3) as u16;
+            self.fine_x = value & 0b111;
+        } else {
+            self.t =
+                (self.t & 0x8C1F) | ((value & 0b111) as u16) << 12 | ((value & 0xF8) as u16) << 2;
+        }
+        self.w = !self.w;
+    }
+
+    pub fn write_to_ppu_addr(&mut self, value: u8) {
+        self.open_bus = value;
+        if !self.w {
+            self.t = (self.t & 0x00FF) | ((value & 0x3F) as u16) << 8;
+        } else {
+            self.t = (self.t & 0xFF00) | value as u16;
+            self.v = self.t;
+        }
+        self.w = !self.w;
+    }
+
+    pub fn write_to_data(&mut self, value: u8, mapper: &mut dyn Mapper) {
+        self.open_bus = value;
+        let address = self.v & 0x3FFF;
+        match address {
+            0..=0x1FFF => mapper.ppu_write(address, value),
+            NAMETABLES..=NAMETABLES_MIRRORS_END => {
+                let index = self.mirror_vram_addr(address, mapper.mirroring());
+                self.vram[index] = value;
+            }
+            _ => self.palette_table[NesPPU::palette_index(address)] = value,
+        }
+        self.increment_vram_addr();
+    }
+
+    pub fn read_data(&mut self, mapper: &mut dyn Mapper) -> u8 {
+        let address = self.v & 0x3FFF;
+        self.increment_vram_addr();
+
+        let data = match address {
+            0..=0x1FFF => {
+                let result = self.internal_data_buffer;
+                self.internal_data_buffer = mapper.ppu_read(address);
+                result
+            }
+            NAMETABLES..=NAMETABLES_MIRRORS_END => {
+                let result = self.internal_data_buffer;
+                self.internal_data_buffer =
+                    self.vram[self.mirror_vram_addr(address, mapper.mirroring())];
+                result
+            }
+            _ => {
+                // Palette reads are not buffered, the buffer is filled with
+                // the nametable byte "underneath" the palette instead
+                let underneath = address - 0x1000;
+                self.internal_data_buffer =
+                    self.vram[self.mirror_vram_addr(underneath, mapper.mirroring())];
+                (self.palette_table[NesPPU::palette_index(address)] & 0x3F) | (self.open_bus & 0xC0)
+            }
+        };
+        self.open_bus = data;
+        data
+    }
+
+    /// Advances the PPU by `dots` PPU cycles (three per CPU cycle on NTSC).
+    pub fn tick(&mut self, dots: u16, mapper: &mut dyn Mapper) {
+        for _ in 0..dots {
+            self.step(mapper);
+        }
+    }
+
+    fn step(&mut self, mapper: &mut dyn Mapper) {
+        let rendering = self.rendering_enabled();
+
+        match self.scanline {
+            line if line < VISIBLE_SCANLINES => {
+                if self.cycle == 1 {
+                    self.render_scanline(mapper);
+                }
+                if let Some(hit_at) = self.sprite_zero_hit_at {
+                    if self.cycle >= hit_at {
+                        self.status |= NesPPU::STATUS_SPRITE_ZERO_HIT;
+                        self.sprite_zero_hit_at = None;
+                    }
+                }
+                if rendering {
+                    self.update_scroll_position(mapper);
+                }
+            }
+            VBLANK_SCANLINE if self.cycle == 1 => {
+                self.status |= NesPPU::STATUS_VBLANK;
+                self.frame_complete = true;
+                if self.ctrl & NesPPU::CTRL_GENERATE_NMI != 0 {
+                    self.nmi_pending = true;
+                }
+            }
+            PRE_RENDER_SCANLINE => {
+                if self.cycle == 1 {
+                    self.status &= !(NesPPU::STATUS_VBLANK
+                        | NesPPU::STATUS_SPRITE_ZERO_HIT
+                        | NesPPU::STATUS_SPRITE_OVERFLOW);
+                }
+                if rendering {
+                    self.update_scroll_position(mapper);
+                    if (280..=304).contains(&self.cycle) {
+                        // copy vertical bits from t to v
+                        self.v = (self.v & 0x041F) | (self.t & 0x7BE0);
+                    }
+                    // The pre-render line is one dot shorter on odd frames
+                    if self.cycle == 339 && self.odd_frame {
+                        self.cycle = 340;
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        self.cycle += 1;
+        if self.cycle >= DOTS_PER_SCANLINE {
+            self.cycle = 0;
+            self.scanline += 1;
+            if self.scanline > PRE_RENDER_SCANLINE {
+                self.scanline = 0;
+                self.odd_frame = !self.odd_frame;
+            }
+        }
+    }
+
+    fn update_scroll_position(&mut self, mapper: &mut dyn Mapper) {
+        match self.cycle {
+            256 => self.increment_y(),
+            257 => {
+                // copy horizontal bits from t to v
+                self.v = (self.v & 0x7BE0) | (self.t & 0x041F);
+            }
+            260 => mapper.notify_scanline(),
+            _ => {}
+        }
+    }
+
+    fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+            return;
+        }
+        self.v &= !0x7000;
+        let mut coarse_y = (self.v & 0x03E0) >> 5;
+        if coarse_y == 29 {
+            coarse_y = 0;
+            self.v ^= 0x0800;
+        } else if coarse_y == 31 {
+            coarse_y = 0;
+        } else {
+            coarse_y += 1;
+        }
+        self.v = (self.v & !0x03E0) | (coarse_y << 5);
+    }
+
+    fn increment_vram_addr(&mut self) {
+        let step = if self.ctrl & NesPPU::CTRL_VRAM_INCREMENT != 0 {
+            32
+        } else {
+            1
+        };
+        self.v = self.v.wrapping_add(step) & 0x7FFF;
+    }
+
+    fn rendering_enabled(&self) -> bool {
+        self.mask & (NesPPU::MASK_BACKGROUND | NesPPU::MASK_SPRITES) != 0
+    }
+
+    fn render_scanline(&mut self, mapper: &mut dyn Mapper) {
+        let y = self.scanline as usize;
+        let mut background = [0_u8; 256];
+        let mut sprites = [None; 256];
+
+        if self.mask & NesPPU::MASK_BACKGROUND != 0 {
+            self.render_background_line(mapper, &mut background);
+            if self.mask & NesPPU::MASK_BACKGROUND_LEFT == 0 {
+                background[..8].iter_mut().for_each(|pixel| *pixel = 0);
+            }
+        }
+        if self.mask & NesPPU::MASK_SPRITES != 0 {
+            self.render_sprite_line(mapper, &mut sprites);
+            if self.mask & NesPPU::MASK_SPRITES_LEFT == 0 {
+                sprites[..8].iter_mut().for_each(|pixel| *pixel = None);
+            }
+        }
+
+        for x in 0..256 {
+            let bg_pixel = background[x];
+            let bg_opaque = bg_pixel & 0b11 != 0;
+
+            let palette_entry = match sprites[x] {
+                Some(sprite) if !bg_opaque || !sprite.behind_background => sprite.pixel,
+                _ if bg_opaque => bg_pixel,
+                _ => 0,
+            };
+
+            if let Some(sprite) = sprites[x] {
+                if sprite.sprite_zero
+                    && bg_opaque
+                    && x != 255
+                    && self.sprite_zero_hit_at.is_none()
+                    && self.status & NesPPU::STATUS_SPRITE_ZERO_HIT == 0
+                {
+                    self.sprite_zero_hit_at = Some(x as u16 + 1);
+                }
+            }
+
+            let mut color =
+                self.palette_table[NesPPU::palette_index(PALETTES + palette_entry as u16)];
+            if self.mask & NesPPU::MASK_GREYSCALE != 0 {
+                color &= 0x30;
+            }
+            self.frame
+                .set_pixel(x, y, SYSTEM_PALETTE[(color & 0x3F) as usize]);
+        }
+    }
+
+    /// Fills `line` with background palette entries (palette * 4 + color) of the current scanline.
+    fn render_background_line(&self, mapper: &mut dyn Mapper, line: &mut [u8; 256]) {
+        let mirroring = mapper.mirroring();
+        let pattern_table = if self.ctrl & NesPPU::CTRL_BACKGROUND_TABLE != 0 {
+            0x1000
+        } else {
+            0
+        };
+        let fine_y = (self.v >> 12) & 0b111;
+        let mut v = self.v;
+
+        for tile in 0..33 {
+            let tile_index = self.vram[self.mirror_vram_addr(NAMETABLES | (v & 0x0FFF), mirroring)];
+            let attribute_address = 0x23C0 | (v & 0x0C00) | ((v >> 4) & 0x38) | ((v >> 2) & 0x07);
+            let attribute = self.vram[self.mirror_vram_addr(attribute_address, mirroring)];
+            let shift = ((v >> 4) & 0b100) | (v & 0b10);
+            let palette = (attribute >> shift) & 0b11;
+
+            let pattern_address = pattern_table + tile_index as u16 * 16 + fine_y;
+            let lo = mapper.ppu_read(pattern_address);
+            let hi = mapper.ppu_read(pattern_address + 8);
+
+            for bit in 0..8 {
+                let x = (tile * 8 + bit) as isize - self.fine_x as isize;
+                if !(0..256).contains(&x) {
+                    continue;
+                }
+                let color = ((lo >> (7 - bit)) & 1) | (((hi >> (7 - bit)) & 1) << 1);
+                line[x as usize] = if color == 0 { 0 } else { palette * 4 + color };
+            }
+
+            // increment coarse X, switching horizontal nametable on wrap
+            if v & 0x001F == 31 {
+                v &= !0x001F;
+                v ^= 0x0400;
+            } else {
+                v += 1;
+            }
+        }
+    }
+
+    fn render_sprite_line(
+        &mut self,
+        mapper: &mut dyn Mapper,
+        line: &mut [Option<SpritePixel>; 256],
+    ) {
+        let height = if self.ctrl & NesPPU::CTRL_SPRITE_SIZE != 0 {
+            16
+        } else {
+            8
+        };
+        let scanline = self.scanline as i16;
+
+        let mut found = 0;
+        for sprite in 0..64 {
+            let oam = &self.oam_data[sprite * 4..sprite * 4 + 4];
+            let row = scanline - 1 - oam[0] as i16;
+            if !(0..height).contains(&row) {
+                continue;
+            }
+            if found == SPRITES_PER_SCANLINE {
+                self.status |= NesPPU::STATUS_SPRITE_OVERFLOW;
+                break;
+            }
+            found += 1;
+
+            let tile = oam[1] as u16;
+            let attributes = oam[2];
+            let sprite_x = oam[3] as usize;
+            let flip_horizontal = attributes & 0b0100_0000 != 0;
+            let flip_vertical = attributes & 0b1000_0000 != 0;
+
+            let row = if flip_vertical { height - 1 - row } else { row } as u16;
+            let pattern_address = if height == 16 {
+                let table = (tile & 1) * 0x1000;
+                let tile = (tile & 0xFE) + row / 8;
+                table + tile * 16 + row % 8
+            } else {
+                let table = if self.ctrl & NesPPU::CTRL_SPRITE_TABLE != 0 {
+                    0x1000
+                } else {
+                    0
+                };
+                table + tile * 16 + row
+            };
+            let lo = mapper.ppu_read(pattern_address);
+            let hi = mapper.ppu_read(pattern_address + 8);
+
+            for bit in 0..8 {
+                let x = sprite_x + bit;
+                if x > 255 || line[x].is_some() {
+                    continue;
+                }
+                let shift = if flip_horizontal { bit } else { 7 - bit };
+                let color = ((lo >> shift) & 1) | (((hi >> shift) & 1) << 1);
+                if color == 0 {
+                    continue;
+                }
+                line[x] = Some(SpritePixel {
+                    pixel: 0x10 + (attributes & 0b11) * 4 + color,
+                    behind_background: attributes & 0b0010_0000 != 0,
+                    sprite_zero: sprite == 0,
+                });
+            }
+        }
+    }
+
+    /// Maps $2000-$3EFF to an offset in the internal nametable memory.
+    ///
+    ///  Horizontal:        Vertical:
+    ///    [ A ] [ a ]        [ A ] [ B ]
+    ///    [ B ] [ b ]        [ a ] [ b ]
+    fn mirror_vram_addr(&self, address: u16, mirroring: Mirroring) -> usize {
+        let vram_index = (address - NAMETABLES) & 0x0FFF;
+        let nametable = vram_index / 0x0400;
+        let offset = vram_index & 0x03FF;
+        let physical_table = match (mirroring, nametable) {
+            (Mirroring::Vertical, table) => table & 1,
+            (Mirroring::Horizontal, table) => table >> 1,
+            (Mirroring::SingleScreenLower, _) => 0,
+            (Mirroring::SingleScreenUpper, _) => 1,
+            (Mirroring::FourScreen, table) => table,
+        };
+        (physical_table * 0x0400 + offset) as usize
+    }
+
+    /// $3F10/$3F14/$3F18/$3F1C are mirrors of $3F00/$3F04/$3F08/$3F0C.
+    fn palette_index(address: u16) -> usize {
+        let index = (address & 0x1F) as usize;
+        if index >= 0x10 && index & 0b11 == 0 {
+            index - 0x10
+        } else {
+            index
+        }
+    }
+}
+
+impl Default for NesPPU {
+    fn default() -> Self {
+        NesPPU::new()
+    }
+}
+
+#[derive(Clone, Copy)]
+struct SpritePixel {
+    pixel: u8,
+    behind_background: bool,
+    sprite_zero: bool,
+}
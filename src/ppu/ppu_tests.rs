@@ -0,0 +1,349 @@
+use super::{Frame, NesPPU, SYSTEM_PALETTE};
+use crate::cartridge::Mirroring;
+use crate::mapper::Mapper;
+use crate::savestate::{SaveStateError, StateReader, StateWriter};
+
+/// 8 KiB of CHR RAM with a fixed nametable layout.
+struct TestMapper {
+    chr: Vec<u8>,
+    mirroring: Mirroring,
+    scanlines: usize,
+}
+
+impl TestMapper {
+    fn new(mirroring: Mirroring) -> Self {
+        TestMapper {
+            chr: vec![0; 0x2000],
+            mirroring,
+            scanlines: 0,
+        }
+    }
+}
+
+impl Mapper for TestMapper {
+    fn cpu_read(&self, _address: u16) -> u8 {
+        0
+    }
+
+    fn cpu_write(&mut self, _address: u16, _data: u8) {}
+
+    fn ppu_read(&self, address: u16) -> u8 {
+        self.chr[address as usize]
+    }
+
+    fn ppu_write(&mut self, address: u16, data: u8) {
+        self.chr[address as usize] = data;
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn notify_scanline(&mut self) {
+        self.scanlines += 1;
+    }
+
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.chr);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_bytes_into(&mut self.chr, "CHR RAM size")
+    }
+}
+
+fn set_address(ppu: &mut NesPPU, address: u16) {
+    ppu.write_to_ppu_addr((address >> 8) as u8);
+    ppu.write_to_ppu_addr((address & 0xFF) as u8);
+}
+
+fn run_until(ppu: &mut NesPPU, mapper: &mut TestMapper, scanline: u16, cycle: u16) {
+    while ppu.scanline() != scanline || ppu.cycle() != cycle {
+        ppu.tick(1, mapper);
+    }
+}
+
+/// Runs until the start of the next vblank, after a full pre-render line
+/// has loaded the scroll position into `v`.
+fn render_frame(ppu: &mut NesPPU, mapper: &mut TestMapper) {
+    run_until(ppu, mapper, 261, 0);
+    run_until(ppu, mapper, 241, 0);
+}
+
+/// Tile 1 is a solid block of color 1, palette 0 color 1 is white.
+fn solid_tile_setup(ppu: &mut NesPPU, mapper: &mut TestMapper) {
+    for row in 0..8 {
+        mapper.chr[16 + row] = 0xFF;
+    }
+    set_address(ppu, 0x3F00);
+    ppu.write_to_data(0x0F, mapper);
+    ppu.write_to_data(0x30, mapper);
+}
+
+#[test]
+fn ppudata_reads_are_buffered() {
+    let mut mapper = TestMapper::new(Mirroring::Horizontal);
+    let mut ppu = NesPPU::new();
+    set_address(&mut ppu, 0x2305);
+    ppu.write_to_data(0x66, &mut mapper);
+
+    set_address(&mut ppu, 0x2305);
+    ppu.read_data(&mut mapper);
+    assert_eq!(ppu.read_data(&mut mapper), 0x66);
+}
+
+#[test]
+fn ppudata_increments_by_32() {
+    let mut mapper = TestMapper::new(Mirroring::Horizontal);
+    let mut ppu = NesPPU::new();
+    ppu.write_to_ctrl(0b100);
+    set_address(&mut ppu, 0x2000);
+    ppu.write_to_data(0x11, &mut mapper);
+    ppu.write_to_data(0x22, &mut mapper);
+
+    ppu.write_to_ctrl(0);
+    set_address(&mut ppu, 0x2020);
+    ppu.read_data(&mut mapper);
+    assert_eq!(ppu.read_data(&mut mapper), 0x22);
+}
+
+#[test]
+fn horizontal_and_vertical_mirroring() {
+    let mut mapper = TestMapper::new(Mirroring::Horizontal);
+    let mut ppu = NesPPU::new();
+    set_address(&mut ppu, 0x2405);
+    ppu.write_to_data(0x66, &mut mapper);
+    set_address(&mut ppu, 0x2005);
+    ppu.read_data(&mut mapper);
+    assert_eq!(ppu.read_data(&mut mapper), 0x66);
+
+    let mut mapper = TestMapper::new(Mirroring::Vertical);
+    let mut ppu = NesPPU::new();
+    set_address(&mut ppu, 0x2805);
+    ppu.write_to_data(0x77, &mut mapper);
+    set_address(&mut ppu, 0x2005);
+    ppu.read_data(&mut mapper);
+    assert_eq!(ppu.read_data(&mut mapper), 0x77);
+
+    // $3000-$3EFF mirrors $2000-$2EFF
+    set_address(&mut ppu, 0x3005);
+    ppu.read_data(&mut mapper);
+    assert_eq!(ppu.read_data(&mut mapper), 0x77);
+}
+
+#[test]
+fn palette_reads_are_not_buffered_and_mirror_backdrop() {
+    let mut mapper = TestMapper::new(Mirroring::Horizontal);
+    let mut ppu = NesPPU::new();
+    set_address(&mut ppu, 0x3F10);
+    ppu.write_to_data(0x21, &mut mapper);
+
+    set_address(&mut ppu, 0x3F00);
+    assert_eq!(ppu.read_data(&mut mapper), 0x21);
+    set_address(&mut ppu, 0x3F30);
+    assert_eq!(ppu.read_data(&mut mapper), 0x21);
+}
+
+#[test]
+fn chr_is_read_through_the_mapper() {
+    let mut mapper = TestMapper::new(Mirroring::Horizontal);
+    mapper.chr[0x1234] = 0x99;
+    let mut ppu = NesPPU::new();
+    set_address(&mut ppu, 0x1234);
+    ppu.read_data(&mut mapper);
+    assert_eq!(ppu.read_data(&mut mapper), 0x99);
+}
+
+#[test]
+fn status_read_resets_write_latch() {
+    let mut mapper = TestMapper::new(Mirroring::Horizontal);
+    let mut ppu = NesPPU::new();
+    ppu.write_to_ppu_addr(0x21);
+    ppu.read_status();
+    set_address(&mut ppu, 0x2305);
+    ppu.write_to_data(0x66, &mut mapper);
+
+    set_address(&mut ppu, 0x2305);
+    ppu.read_data(&mut mapper);
+    assert_eq!(ppu.read_data(&mut mapper), 0x66);
+}
+
+#[test]
+fn scroll_writes_fill_temporary_address() {
+    let mut ppu = NesPPU::new();
+    ppu.write_to_ctrl(0b10);
+    ppu.write_to_scroll(0b0111_1101);
+    ppu.write_to_scroll(0b0101_1110);
+    assert_eq!(ppu.t, 0b0110_1001_0110_1111);
+    assert_eq!(ppu.fine_x, 0b101);
+    assert!(!ppu.w);
+}
+
+#[test]
+fn vblank_sets_status_and_raises_nmi() {
+    let mut mapper = TestMapper::new(Mirroring::Horizontal);
+    let mut ppu = NesPPU::new();
+    ppu.write_to_ctrl(0b1000_0000);
+
+    run_until(&mut ppu, &mut mapper, 241, 1);
+    assert!(!ppu.poll_nmi());
+    ppu.tick(1, &mut mapper);
+    assert!(ppu.poll_nmi());
+    assert!(!ppu.poll_nmi());
+    assert!(ppu.poll_frame_complete());
+
+    assert_eq!(ppu.read_status() & 0x80, 0x80);
+    assert_eq!(ppu.read_status() & 0x80, 0);
+}
+
+#[test]
+fn pre_render_line_clears_vblank() {
+    let mut mapper = TestMapper::new(Mirroring::Horizontal);
+    let mut ppu = NesPPU::new();
+    run_until(&mut ppu, &mut mapper, 261, 2);
+    assert_eq!(ppu.status & 0x80, 0);
+    assert!(ppu.poll_frame_complete());
+}
+
+#[test]
+fn enabling_nmi_during_vblank_raises_it() {
+    let mut mapper = TestMapper::new(Mirroring::Horizontal);
+    let mut ppu = NesPPU::new();
+    run_until(&mut ppu, &mut mapper, 250, 0);
+    assert!(!ppu.poll_nmi());
+    ppu.write_to_ctrl(0b1000_0000);
+    assert!(ppu.poll_nmi());
+}
+
+#[test]
+fn oam_data_and_dma() {
+    let mut ppu = NesPPU::new();
+    ppu.write_to_oam_addr(0x10);
+    ppu.write_to_oam_data(0x66);
+    ppu.write_to_oam_addr(0x10);
+    assert_eq!(ppu.read_oam_data(), 0x66);
+
+    let mut data = [0; 256];
+    data[0] = 0x77;
+    data[255] = 0x88;
+    ppu.write_to_oam_addr(0x80);
+    ppu.write_oam_dma(&data);
+    ppu.write_to_oam_addr(0x80);
+    assert_eq!(ppu.read_oam_data(), 0x77);
+    ppu.write_to_oam_addr(0x7F);
+    assert_eq!(ppu.read_oam_data(), 0x88);
+}
+
+#[test]
+fn renders_background_tile_with_palette() {
+    let mut mapper = TestMapper::new(Mirroring::Horizontal);
+    let mut ppu = NesPPU::new();
+    solid_tile_setup(&mut ppu, &mut mapper);
+    set_address(&mut ppu, 0x2021);
+    ppu.write_to_data(1, &mut mapper);
+    ppu.write_to_scroll(0);
+    ppu.write_to_scroll(0);
+    ppu.write_to_mask(0b0000_1010);
+
+    render_frame(&mut ppu, &mut mapper);
+    assert_eq!(ppu.frame.pixel(8, 8), SYSTEM_PALETTE[0x30]);
+    assert_eq!(ppu.frame.pixel(15, 15), SYSTEM_PALETTE[0x30]);
+    assert_eq!(ppu.frame.pixel(16, 8), SYSTEM_PALETTE[0x0F]);
+    assert_eq!(ppu.frame.pixel(8, 16), SYSTEM_PALETTE[0x0F]);
+    assert_eq!(ppu.frame.data.len(), Frame::WIDTH * Frame::HEIGHT * 3);
+}
+
+#[test]
+fn fine_x_scroll_shifts_background() {
+    let mut mapper = TestMapper::new(Mirroring::Horizontal);
+    let mut ppu = NesPPU::new();
+    solid_tile_setup(&mut ppu, &mut mapper);
+    set_address(&mut ppu, 0x2001);
+    ppu.write_to_data(1, &mut mapper);
+    set_address(&mut ppu, 0x2000);
+    ppu.write_to_scroll(3);
+    ppu.write_to_scroll(0);
+    ppu.write_to_mask(0b0000_1010);
+
+    render_frame(&mut ppu, &mut mapper);
+    assert_eq!(ppu.frame.pixel(4, 0), SYSTEM_PALETTE[0x0F]);
+    assert_eq!(ppu.frame.pixel(5, 0), SYSTEM_PALETTE[0x30]);
+    assert_eq!(ppu.frame.pixel(12, 0), SYSTEM_PALETTE[0x30]);
+    assert_eq!(ppu.frame.pixel(13, 0), SYSTEM_PALETTE[0x0F]);
+}
+
+#[test]
+fn sprite_zero_hit_is_set_on_opaque_overlap() {
+    let mut mapper = TestMapper::new(Mirroring::Horizontal);
+    let mut ppu = NesPPU::new();
+    solid_tile_setup(&mut ppu, &mut mapper);
+    set_address(&mut ppu, 0x2021);
+    ppu.write_to_data(1, &mut mapper);
+    set_address(&mut ppu, 0x2000);
+    // sprite 0 at (12, 11) using the solid tile
+    ppu.write_oam_dma(&{
+        let mut oam = [0xFF; 256];
+        oam[..4].copy_from_slice(&[10, 1, 0, 12]);
+        oam
+    });
+    ppu.write_to_mask(0b0001_1110);
+
+    run_until(&mut ppu, &mut mapper, 11, 12);
+    assert_eq!(ppu.status & 0x40, 0);
+    run_until(&mut ppu, &mut mapper, 11, 14);
+    assert_eq!(ppu.status & 0x40, 0x40);
+
+    run_until(&mut ppu, &mut mapper, 261, 2);
+    assert_eq!(ppu.status & 0x40, 0);
+}
+
+#[test]
+fn more_than_eight_sprites_set_overflow() {
+    let mut mapper = TestMapper::new(Mirroring::Horizontal);
+    let mut ppu = NesPPU::new();
+    let mut oam = [0xFF; 256];
+    for sprite in 0..8 {
+        oam[sprite * 4..sprite * 4 + 4].copy_from_slice(&[20, 0, 0, sprite as u8 * 8]);
+    }
+    ppu.write_oam_dma(&oam);
+    ppu.write_to_mask(0b0001_0000);
+    render_frame(&mut ppu, &mut mapper);
+    assert_eq!(ppu.status & 0x20, 0);
+
+    oam[8 * 4..8 * 4 + 4].copy_from_slice(&[20, 0, 0, 100]);
+    ppu.write_oam_dma(&oam);
+    render_frame(&mut ppu, &mut mapper);
+    assert_eq!(ppu.status & 0x20, 0x20);
+}
+
+#[test]
+fn sprite_flips_and_priority() {
+    let mut mapper = TestMapper::new(Mirroring::Horizontal);
+    let mut ppu = NesPPU::new();
+    // tile 2: only the leftmost column of the top row is set
+    mapper.chr[32] = 0x80;
+    set_address(&mut ppu, 0x3F11);
+    ppu.write_to_data(0x16, &mut mapper);
+    let mut oam = [0xFF; 256];
+    oam[..4].copy_from_slice(&[49, 2, 0b0000_0000, 40]);
+    oam[4..8].copy_from_slice(&[59, 2, 0b1100_0000, 40]);
+    ppu.write_oam_dma(&oam);
+    ppu.write_to_mask(0b0001_0100);
+
+    run_until(&mut ppu, &mut mapper, 241, 0);
+    assert_eq!(ppu.frame.pixel(40, 50), SYSTEM_PALETTE[0x16]);
+    assert_eq!(ppu.frame.pixel(47, 67), SYSTEM_PALETTE[0x16]);
+    assert_eq!(ppu.frame.pixel(40, 60), SYSTEM_PALETTE[0x00]);
+}
+
+#[test]
+fn mapper_sees_one_clock_per_rendered_scanline() {
+    let mut mapper = TestMapper::new(Mirroring::Horizontal);
+    let mut ppu = NesPPU::new();
+    run_until(&mut ppu, &mut mapper, 241, 0);
+    assert_eq!(mapper.scanlines, 0);
+
+    ppu.write_to_mask(0b0001_1000);
+    render_frame(&mut ppu, &mut mapper);
+    assert_eq!(mapper.scanlines, 241);
+}
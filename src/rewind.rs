@@ -0,0 +1,185 @@
+//! History of save states for stepping the game backwards. The newest
+//! snapshot is kept whole; each older one is stored as the XOR against the
+//! snapshot that followed it, with the runs of zero bytes (the memory that
+//! did not change) squeezed out. Consecutive frames differ in a few hundred
+//! bytes, so a snapshot usually costs a small fraction of a full state.
+
+use crate::cpu::CPU;
+use std::collections::VecDeque;
+
+#[cfg(test)]
+mod rewind_tests;
+
+pub struct Rewind {
+    interval: u32,
+    capacity: usize,
+    memory_limit: usize,
+    frames: u32,
+    latest: Option<Vec<u8>>,
+    /// Oldest first; applying the last one to `latest` gives the snapshot
+    /// before it.
+    deltas: VecDeque<Vec<u8>>,
+    delta_bytes: usize,
+}
+
+impl Rewind {
+    /// Keeps a snapshot of every `interval` frames, at most `capacity` of
+    /// them and at most `memory_limit` bytes; the oldest go first.
+    pub fn new(interval: u32, capacity: usize, memory_limit: usize) -> Self {
+        Rewind {
+            interval: interval.max(1),
+            capacity: capacity.max(1),
+            memory_limit,
+            frames: 0,
+            latest: None,
+            deltas: VecDeque::new(),
+            delta_bytes: 0,
+        }
+    }
+
+    /// Called once per frame; takes a snapshot every `interval` frames.
+    pub fn record(&mut self, cpu: &CPU) {
+        self.frames += 1;
+        if self.frames >= self.interval {
+            self.frames = 0;
+            self.push(cpu.save_state());
+        }
+    }
+
+    /// Loads the newest snapshot into `cpu` and drops it, so that the next
+    /// call goes further back. The oldest snapshot is kept, so holding
+    /// rewind at the end of the history stays there. Returns `false` when
+    /// there is no history.
+    pub fn step_back(&mut self, cpu: &mut CPU) -> bool {
+        let latest = match self.latest.as_mut() {
+            Some(latest) => latest,
+            None => return false,
+        };
+        if cpu.load_state(latest).is_err() {
+            // a cartridge change makes the history useless
+            self.clear();
+            return false;
+        }
+        if let Some(delta) = self.deltas.pop_back() {
+            self.delta_bytes -= delta.len();
+            apply_delta(latest, &delta);
+        }
+        self.frames = 0;
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.delta_bytes = 0;
+        self.frames = 0;
+    }
+
+    /// Number of snapshots held.
+    pub fn len(&self) -> usize {
+        self.latest.as_ref().map_or(0, |_| self.deltas.len() + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    /// Frames of history the snapshots cover.
+    pub fn frames(&self) -> u64 {
+        self.len() as u64 * self.interval as u64
+    }
+
+    /// Bytes taken by the snapshots.
+    pub fn memory_usage(&self) -> usize {
+        self.latest.as_ref().map_or(0, Vec::len) + self.delta_bytes
+    }
+
+    fn push(&mut self, state: Vec<u8>) {
+        if let Some(previous) = self.latest.take() {
+            if previous.len() == state.len() {
+                let delta = delta(&previous, &state);
+                self.delta_bytes += delta.len();
+                self.deltas.push_back(delta);
+            } else {
+                self.clear();
+            }
+        }
+        self.latest = Some(state);
+
+        while self.len() > self.capacity
+            || (self.memory_usage() > self.memory_limit && !self.deltas.is_empty())
+        {
+            if let Some(oldest) = self.deltas.pop_front() {
+                self.delta_bytes -= oldest.len();
+            }
+        }
+    }
+}
+
+/// Encodes `previous XOR current` as a sequence of
+/// (unchanged byte count, changed byte count, changed bytes XORed) records,
+/// counts in LEB128.
+fn delta(previous: &[u8], current: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    let mut position = 0;
+    while position < current.len() {
+        let unchanged = previous[position..]
+            .iter()
+            .zip(&current[position..])
+            .take_while(|(a, b)| a == b)
+            .count();
+        position += unchanged;
+        let changed = previous[position..]
+            .iter()
+            .zip(&current[position..])
+            .take_while(|(a, b)| a != b)
+            .count();
+        write_length(&mut delta, unchanged);
+        write_length(&mut delta, changed);
+        delta.extend(
+            previous[position..position + changed]
+                .iter()
+                .zip(&current[position..position + changed])
+                .map(|(a, b)| a ^ b),
+        );
+        position += changed;
+    }
+    delta
+}
+
+/// Turns `state` into the snapshot `delta` was made against.
+fn apply_delta(state: &mut [u8], delta: &[u8]) {
+    let mut input = delta;
+    let mut position = 0;
+    while !input.is_empty() {
+        position += read_length(&mut input);
+        let changed = read_length(&mut input);
+        for (byte, change) in state[position..position + changed].iter_mut().zip(input) {
+            *byte ^= change;
+        }
+        input = &input[changed..];
+        position += changed;
+    }
+}
+
+fn write_length(output: &mut Vec<u8>, mut length: usize) {
+    while length >= 0x80 {
+        output.push(length as u8 | 0x80);
+        length >>= 7;
+    }
+    output.push(length as u8);
+}
+
+fn read_length(input: &mut &[u8]) -> usize {
+    let mut length = 0;
+    let mut shift = 0;
+    while let Some((&byte, rest)) = input.split_first() {
+        *input = rest;
+        length |= ((byte & 0x7F) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    length
+}
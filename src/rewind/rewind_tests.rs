@@ -0,0 +1,115 @@
+use super::{apply_delta, delta, Rewind};
+use crate::cpu::CPU;
+
+/// Counts up at $00 forever.
+fn counter() -> CPU {
+    let mut cpu = CPU::new();
+    cpu.load(vec![
+        0xE6, 0x00, // INC $00
+        0x4C, 0x00, 0x06, // JMP $0600
+    ]);
+    cpu.reset();
+    cpu
+}
+
+/// Runs long enough to change the counter.
+fn run(cpu: &mut CPU) {
+    for _ in 0..10 {
+        cpu.step().unwrap();
+    }
+}
+
+#[test]
+fn delta_restores_the_previous_state() {
+    let previous: Vec<u8> = (0..1000).map(|i| (i * 7 % 251) as u8).collect();
+    let mut current = previous.clone();
+    current[0] ^= 1;
+    current[500..700].iter_mut().for_each(|byte| *byte = !*byte);
+    current[999] = 0;
+
+    let encoded = delta(&previous, &current);
+    assert!(encoded.len() < 220, "delta is {} bytes", encoded.len());
+    apply_delta(&mut current, &encoded);
+    assert_eq!(previous, current);
+}
+
+#[test]
+fn unchanged_state_has_an_empty_delta() {
+    let state = vec![0x55; 300];
+    assert_eq!(delta(&state, &state), vec![0x80 | 0x2C, 0x02, 0x00]);
+}
+
+#[test]
+fn steps_back_through_the_snapshots() {
+    let mut cpu = counter();
+    let mut rewind = Rewind::new(1, 100, usize::MAX);
+    let mut counters = Vec::new();
+    for _ in 0..5 {
+        run(&mut cpu);
+        rewind.record(&cpu);
+        counters.push(cpu.bus.peek(0x00));
+    }
+    assert_eq!(5, rewind.len());
+
+    for &expected in counters.iter().rev() {
+        assert!(rewind.step_back(&mut cpu));
+        assert_eq!(expected, cpu.bus.peek(0x00));
+    }
+    // the oldest snapshot stays
+    assert!(rewind.step_back(&mut cpu));
+    assert_eq!(counters[0], cpu.bus.peek(0x00));
+    assert_eq!(1, rewind.len());
+}
+
+#[test]
+fn snapshots_every_interval() {
+    let cpu = counter();
+    let mut rewind = Rewind::new(3, 100, usize::MAX);
+    for _ in 0..7 {
+        rewind.record(&cpu);
+    }
+    assert_eq!(2, rewind.len());
+    assert_eq!(6, rewind.frames());
+}
+
+#[test]
+fn history_is_bounded_by_count() {
+    let mut cpu = counter();
+    let mut rewind = Rewind::new(1, 4, usize::MAX);
+    let mut counters = Vec::new();
+    for _ in 0..10 {
+        run(&mut cpu);
+        rewind.record(&cpu);
+        counters.push(cpu.bus.peek(0x00));
+    }
+    assert_eq!(4, rewind.len());
+
+    while rewind.len() > 1 {
+        rewind.step_back(&mut cpu);
+    }
+    rewind.step_back(&mut cpu);
+    assert_eq!(counters[6], cpu.bus.peek(0x00));
+}
+
+#[test]
+fn history_is_bounded_by_memory() {
+    let mut cpu = counter();
+    let state_size = cpu.save_state().len();
+    let mut rewind = Rewind::new(1, 1000, state_size + 100);
+    for _ in 0..200 {
+        run(&mut cpu);
+        rewind.record(&cpu);
+    }
+    assert!(rewind.memory_usage() <= state_size + 100);
+    assert!(rewind.len() > 1);
+    assert!(rewind.len() < 200);
+}
+
+#[test]
+fn empty_history_does_nothing() {
+    let mut cpu = counter();
+    let mut rewind = Rewind::new(1, 10, usize::MAX);
+    assert!(!rewind.step_back(&mut cpu));
+    assert!(rewind.is_empty());
+    assert_eq!(0, rewind.memory_usage());
+}
@@ -0,0 +1,238 @@
+//! Binary save state format. A state starts with a header, followed by the
+//! state of each component in a fixed order:
+//!
+//!  Bytes  Contents
+//!  0-3    "NESS"
+//!  4-7    format version, little endian
+//!  8-11   fingerprint of the inserted cartridge's ROM, little endian
+//!  12-    CPU registers, then the bus: RAM, PPU, APU, controllers and the
+//!         mapper's registers, PRG RAM and CHR RAM
+//!
+//! Multi-byte values are little endian. Variable sized memories are stored
+//! with their length, which has to match the memory of the running
+//! cartridge. Whatever the components leave out (the frame buffer, queued
+//! audio samples, held buttons) belongs to the frontend.
+
+use std::fmt::Display;
+
+#[cfg(test)]
+mod savestate_tests;
+
+pub const SIGNATURE: [u8; 4] = *b"NESS";
+/// Bumped whenever the layout of any component changes; states of other
+/// versions are refused.
+pub const VERSION: u32 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveStateError {
+    InvalidSignature,
+    UnsupportedVersion {
+        found: u32,
+        supported: u32,
+    },
+    /// The state was saved with a different cartridge inserted.
+    CartridgeMismatch {
+        found: u32,
+        expected: u32,
+    },
+    Truncated,
+    Invalid(&'static str),
+}
+
+impl Display for SaveStateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveStateError::InvalidSignature => write!(f, "not a save state"),
+            SaveStateError::UnsupportedVersion { found, supported } => write!(
+                f,
+                "save state version {} is not supported, expected version {}",
+                found, supported
+            ),
+            SaveStateError::CartridgeMismatch { found, expected } => write!(
+                f,
+                "save state belongs to another cartridge (ROM fingerprint {:08X}, inserted {:08X})",
+                found, expected
+            ),
+            SaveStateError::Truncated => write!(f, "save state is truncated"),
+            SaveStateError::Invalid(what) => write!(f, "save state is corrupt: invalid {}", what),
+        }
+    }
+}
+
+impl std::error::Error for SaveStateError {}
+
+/// FNV-1a hash of the cartridge contents, stored in a state to recognize
+/// the game it was saved from.
+pub fn fingerprint(data: &[&[u8]]) -> u32 {
+    data.iter()
+        .flat_map(|part| part.iter())
+        .fold(0x811C_9DC5, |hash, &byte| {
+            (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+        })
+}
+
+#[derive(Default)]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        StateWriter::default()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn write_header(&mut self, fingerprint: u32) {
+        self.data.extend_from_slice(&SIGNATURE);
+        self.write_u32(VERSION);
+        self.write_u32(fingerprint);
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_option_u8(&mut self, value: Option<u8>) {
+        self.write_bool(value.is_some());
+        self.write_u8(value.unwrap_or(0));
+    }
+
+    pub fn write_option_u16(&mut self, value: Option<u16>) {
+        self.write_bool(value.is_some());
+        self.write_u16(value.unwrap_or(0));
+    }
+
+    /// Writes a memory block preceded by its length.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_u32(bytes.len() as u32);
+        self.data.extend_from_slice(bytes);
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        StateReader { data, position: 0 }
+    }
+
+    /// Checks the header against this build and the inserted cartridge.
+    pub fn read_header(&mut self, fingerprint: u32) -> Result<(), SaveStateError> {
+        if self.take(SIGNATURE.len()).ok() != Some(&SIGNATURE[..]) {
+            return Err(SaveStateError::InvalidSignature);
+        }
+        let version = self.read_u32()?;
+        if version != VERSION {
+            return Err(SaveStateError::UnsupportedVersion {
+                found: version,
+                supported: VERSION,
+            });
+        }
+        let found = self.read_u32()?;
+        if found != fingerprint {
+            return Err(SaveStateError::CartridgeMismatch {
+                found,
+                expected: fingerprint,
+            });
+        }
+        Ok(())
+    }
+
+    /// Fails unless the whole state has been consumed.
+    pub fn finish(&self) -> Result<(), SaveStateError> {
+        if self.position == self.data.len() {
+            Ok(())
+        } else {
+            Err(SaveStateError::Invalid("trailing data"))
+        }
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], SaveStateError> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|&end| end <= self.data.len())
+            .ok_or(SaveStateError::Truncated)?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], SaveStateError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, SaveStateError> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SaveStateError::Invalid("boolean")),
+        }
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, SaveStateError> {
+        Ok(u16::from_le_bytes(self.take_array()?))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, SaveStateError> {
+        Ok(u32::from_le_bytes(self.take_array()?))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, SaveStateError> {
+        Ok(u64::from_le_bytes(self.take_array()?))
+    }
+
+    pub fn read_option_u8(&mut self) -> Result<Option<u8>, SaveStateError> {
+        let present = self.read_bool()?;
+        let value = self.read_u8()?;
+        Ok(if present { Some(value) } else { None })
+    }
+
+    pub fn read_option_u16(&mut self) -> Result<Option<u16>, SaveStateError> {
+        let present = self.read_bool()?;
+        let value = self.read_u16()?;
+        Ok(if present { Some(value) } else { None })
+    }
+
+    /// Reads a block written by `write_bytes` into `memory`, which must have
+    /// the length of the block.
+    pub fn read_bytes_into(
+        &mut self,
+        memory: &mut [u8],
+        what: &'static str,
+    ) -> Result<(), SaveStateError> {
+        if self.read_u32()? as usize != memory.len() {
+            return Err(SaveStateError::Invalid(what));
+        }
+        memory.copy_from_slice(self.take(memory.len())?);
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+use super::{SaveStateError, StateWriter, VERSION};
+use crate::cartridge::{Rom, RomBuilder};
+use crate::cpu::CPU;
+
+/// MMC1 image with PRG RAM and CHR RAM, whose program keeps the CPU, RAM,
+/// PRG RAM, the PPU, the APU and NMIs busy.
+fn rom(fill: u8) -> Rom {
+    let program = [
+        0x78, // SEI
+        0xA9, 0x0F, 0x8D, 0x15, 0x40, // LDA #$0F / STA $4015
+        0xA9, 0xBF, 0x8D, 0x00, 0x40, // LDA #$BF / STA $4000
+        0xA9, 0x80, 0x8D, 0x00, 0x20, // LDA #$80 / STA $2000: NMI on vblank
+        0xE6, 0x00, // loop: INC $00
+        0xEE, 0x00, 0x60, // INC $6000
+        0xA5, 0x00, 0x8D, 0x07, 0x20, // LDA $00 / STA $2007
+        0x4C, 0x10, 0xC0, // JMP loop
+    ];
+    let nmi = [
+        0xE6, 0x01, // INC $01
+        0x40, // RTI
+    ];
+    let mut prg = vec![fill; 0x4000];
+    prg[0x100..0x100 + nmi.len()].copy_from_slice(&nmi);
+    RomBuilder::new()
+        .mapper(1)
+        .prg_rom(prg)
+        .chr_rom(Vec::new())
+        .program(0xC000, &program)
+        .vectors(0xC100, 0xC000, 0x0000)
+        .rom()
+        .unwrap()
+}
+
+fn machine(fill: u8) -> CPU {
+    let mut cpu = CPU::new();
+    cpu.bus.insert_cartridge(rom(fill)).unwrap();
+    cpu.reset();
+    cpu
+}
+
+fn run_frames(cpu: &mut CPU, frames: u32) {
+    let mut done = 0;
+    while done < frames {
+        cpu.step().unwrap();
+        if cpu.bus.poll_frame_complete() {
+            done += 1;
+        }
+    }
+}
+
+#[test]
+fn loading_a_state_replays_the_same_future() {
+    let mut cpu = machine(0xEA);
+    run_frames(&mut cpu, 3);
+    let saved = cpu.save_state();
+
+    run_frames(&mut cpu, 2);
+    let expected = cpu.save_state();
+    let frame = cpu.bus.ppu.frame.data.clone();
+
+    cpu.load_state(&saved).unwrap();
+    assert_eq!(saved, cpu.save_state());
+    run_frames(&mut cpu, 2);
+    assert_eq!(expected, cpu.save_state());
+    assert_eq!(frame, cpu.bus.ppu.frame.data);
+}
+
+/// Zero page counters of the main loop and the NMI handler, and the PRG RAM one.
+fn counters(cpu: &CPU) -> [u8; 3] {
+    [0x0000, 0x0001, 0x6000].map(|address| cpu.bus.peek(address))
+}
+
+#[test]
+fn restores_registers_and_memory() {
+    let mut cpu = machine(0xEA);
+    run_frames(&mut cpu, 2);
+    let saved = cpu.save_state();
+    let registers = (cpu.accumulator, cpu.program_counter, cpu.cycles);
+    let memory = counters(&cpu);
+
+    run_frames(&mut cpu, 1);
+    assert_ne!(memory, counters(&cpu));
+
+    cpu.load_state(&saved).unwrap();
+    assert_eq!(
+        registers,
+        (cpu.accumulator, cpu.program_counter, cpu.cycles)
+    );
+    assert_eq!(memory, counters(&cpu));
+}
+
+/// Loads `state` expecting `error`, and checks the machine was not touched.
+fn assert_refused(cpu: &mut CPU, state: &[u8], error: SaveStateError) {
+    let before = cpu.save_state();
+    assert_eq!(Err(error), cpu.load_state(state));
+    assert_eq!(before, cpu.save_state());
+}
+
+#[test]
+fn refuses_other_versions() {
+    let mut cpu = machine(0xEA);
+    let mut state = cpu.save_state();
+    state[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+    run_frames(&mut cpu, 1);
+
+    let error = SaveStateError::UnsupportedVersion {
+        found: VERSION + 1,
+        supported: VERSION,
+    };
+    assert_refused(&mut cpu, &state, error);
+}
+
+#[test]
+fn refuses_data_that_is_not_a_state() {
+    let mut cpu = machine(0xEA);
+    assert_refused(&mut cpu, b"NES\x1a", SaveStateError::InvalidSignature);
+    assert_refused(&mut cpu, &[], SaveStateError::InvalidSignature);
+}
+
+#[test]
+fn refuses_states_of_other_cartridges() {
+    let state = machine(0xEA).save_state();
+    let mut cpu = machine(0x00);
+
+    match cpu.load_state(&state) {
+        Err(SaveStateError::CartridgeMismatch { .. }) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn rolls_back_a_truncated_state() {
+    let mut cpu = machine(0xEA);
+    run_frames(&mut cpu, 1);
+    let state = cpu.save_state();
+    run_frames(&mut cpu, 1);
+
+    assert_refused(
+        &mut cpu,
+        &state[..state.len() - 1],
+        SaveStateError::Truncated,
+    );
+    assert_refused(
+        &mut cpu,
+        &state[..state.len() / 2],
+        SaveStateError::Truncated,
+    );
+}
+
+#[test]
+fn rolls_back_a_corrupt_state() {
+    let mut cpu = machine(0xEA);
+    let state = cpu.save_state();
+    run_frames(&mut cpu, 1);
+
+    let mut trailing = state.clone();
+    trailing.push(0);
+    assert_refused(
+        &mut cpu,
+        &trailing,
+        SaveStateError::Invalid("trailing data"),
+    );
+
+    // the halted flag follows the header, A, P, PC, SP, X, Y and the cycles
+    let mut halted = state;
+    halted[27] = 2;
+    assert_refused(&mut cpu, &halted, SaveStateError::Invalid("boolean"));
+}
+
+#[test]
+fn refuses_apu_timers_that_would_underflow() {
+    let mut cpu = machine(0xEA);
+    run_frames(&mut cpu, 1);
+    let state = cpu.save_state();
+    let mut apu = StateWriter::new();
+    cpu.bus.apu.save_state(&mut apu);
+    let apu = apu.into_bytes();
+    let start = state
+        .windows(apu.len())
+        .position(|window| window == &apu[..])
+        .expect("APU state not found");
+    run_frames(&mut cpu, 1);
+
+    // the noise channel follows two pulse channels of 21 bytes and the
+    // triangle of 12, the DMC follows the noise channel's 16
+    let noise_period = start + 55;
+    let dmc_rate = start + 72;
+    let dmc_bits = start + 88;
+    let corruptions: [(usize, &[u8], &str); 6] = [
+        (noise_period, &[0, 0], "noise period"),
+        (noise_period, &[5, 0], "noise period"),
+        (dmc_rate, &[0, 0], "DMC rate"),
+        (dmc_rate, &[1, 0], "DMC rate"),
+        (dmc_bits, &[0], "DMC bit counter"),
+        (dmc_bits, &[9], "DMC bit counter"),
+    ];
+    for &(offset, bytes, what) in &corruptions {
+        let mut corrupt = state.clone();
+        corrupt[offset..offset + bytes.len()].copy_from_slice(bytes);
+        assert_refused(&mut cpu, &corrupt, SaveStateError::Invalid(what));
+    }
+}
+
+#[test]
+fn formats_errors() {
+    let error = SaveStateError::UnsupportedVersion {
+        found: 7,
+        supported: 1,
+    };
+    assert_eq!(
+        "save state version 7 is not supported, expected version 1",
+        error.to_string()
+    );
+    assert_eq!(
+        "save state is corrupt: invalid boolean",
+        SaveStateError::Invalid("boolean").to_string()
+    );
+}
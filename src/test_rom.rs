@@ -0,0 +1,102 @@
+//! Headless runner for the test ROMs of blargg and kevtris that report
+//! through the $6000 protocol: a status byte at $6000, the signature
+//! DE B0 61 at $6001-$6003 and a NUL-terminated message from $6004.
+
+use crate::cartridge::{Rom, RomError};
+use crate::cpu::{CpuCore, CpuError, StopReason, CPU};
+
+#[cfg(test)]
+mod test_rom_tests;
+
+const STATUS: u16 = 0x6000;
+const SIGNATURE: u16 = 0x6001;
+const TEXT: u16 = 0x6004;
+const TEXT_END: u16 = 0x7FFF;
+
+const SIGNATURE_BYTES: [u8; 3] = [0xDE, 0xB0, 0x61];
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_RESET_REQUESTED: u8 = 0x81;
+/// The ROMs ask for the reset button to be pressed no sooner than 100 ms
+/// after the request.
+const RESET_DELAY_FRAMES: u32 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TestRomStatus {
+    Passed,
+    /// The ROM finished with a non-zero result code.
+    Failed(u8),
+    /// The frame limit ran out before the ROM finished.
+    TimedOut,
+    /// The CPU stopped before the ROM finished, on a JAM opcode for example.
+    Stopped(StopReason),
+    /// The CPU hit an error it cannot recover from.
+    Crashed(CpuError),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestRomResult {
+    pub status: TestRomStatus,
+    /// The message the ROM left at $6004, usually the test names and the
+    /// reason of a failure.
+    pub text: String,
+    pub frames: u32,
+}
+
+/// Boots `rom` on the CPU `core` and runs it until it reports a result or
+/// `max_frames` frames have been rendered.
+pub fn run_test_rom(rom: Rom, max_frames: u32, core: CpuCore) -> Result<TestRomResult, RomError> {
+    let mut cpu = CPU::new();
+    cpu.set_core(core);
+    cpu.bus.insert_cartridge(rom)?;
+    cpu.reset();
+
+    let mut frames = 0;
+    let mut reset_at = None;
+    let status = loop {
+        match cpu.step() {
+            Ok(StopReason::Stepped { .. }) => {}
+            Ok(reason) => break TestRomStatus::Stopped(reason),
+            Err(err) => break TestRomStatus::Crashed(err),
+        }
+        if !cpu.bus.poll_frame_complete() {
+            continue;
+        }
+        frames += 1;
+
+        if has_signature(&cpu) {
+            match cpu.bus.peek(STATUS) {
+                STATUS_RUNNING => {}
+                STATUS_RESET_REQUESTED => {
+                    let at = *reset_at.get_or_insert(frames + RESET_DELAY_FRAMES);
+                    if frames >= at {
+                        reset_at = None;
+                        cpu.reset();
+                    }
+                }
+                0 => break TestRomStatus::Passed,
+                code => break TestRomStatus::Failed(code),
+            }
+        }
+        if frames >= max_frames {
+            break TestRomStatus::TimedOut;
+        }
+    };
+
+    Ok(TestRomResult {
+        status,
+        text: read_text(&cpu),
+        frames,
+    })
+}
+
+fn has_signature(cpu: &CPU) -> bool {
+    (0..3).all(|i| cpu.bus.peek(SIGNATURE + i) == SIGNATURE_BYTES[i as usize])
+}
+
+fn read_text(cpu: &CPU) -> String {
+    let bytes: Vec<u8> = (TEXT..=TEXT_END)
+        .map(|address| cpu.bus.peek(address))
+        .take_while(|&byte| byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
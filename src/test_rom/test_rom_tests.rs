@@ -0,0 +1,156 @@
+use super::{run_test_rom, TestRomStatus};
+use crate::cartridge::{Rom, RomBuilder};
+use crate::cpu::CpuCore;
+use std::path::PathBuf;
+
+const MAX_FRAMES: u32 = 60 * 60;
+
+const PROGRAM_START: u16 = 0xC001;
+
+/// Builds an NROM image that masks interrupts, like the real test ROMs do
+/// first thing, and then runs `program` from `PROGRAM_START`.
+fn test_rom(program: &[u8]) -> Rom {
+    let mut code = vec![0x78];
+    code.extend_from_slice(program);
+    RomBuilder::new().program(0xC000, &code).rom().unwrap()
+}
+
+/// LDA #value / STA address
+fn store(address: u16, value: u8) -> Vec<u8> {
+    vec![0xA9, value, 0x8D, address as u8, (address >> 8) as u8]
+}
+
+fn report(code: u8, text: &str) -> Vec<u8> {
+    let mut program = store(0x6000, 0x80);
+    for (i, &byte) in [0xDE, 0xB0, 0x61].iter().enumerate() {
+        program.extend(store(0x6001 + i as u16, byte));
+    }
+    for (i, byte) in text.bytes().chain(Some(0)).enumerate() {
+        program.extend(store(0x6004 + i as u16, byte));
+    }
+    program.extend(store(0x6000, code));
+    program
+}
+
+fn spin(program: &mut Vec<u8>) {
+    let address = PROGRAM_START + program.len() as u16;
+    program.extend(&[0x4C, address as u8, (address >> 8) as u8]);
+}
+
+#[test]
+fn reports_pass_and_text() {
+    let mut program = report(0, "ok\n");
+    spin(&mut program);
+
+    let result = run_test_rom(test_rom(&program), MAX_FRAMES, CpuCore::Instruction).unwrap();
+    assert_eq!(TestRomStatus::Passed, result.status);
+    assert_eq!("ok\n", result.text);
+    assert_eq!(1, result.frames);
+}
+
+#[test]
+fn reports_failure_code() {
+    let mut program = report(3, "failed");
+    spin(&mut program);
+
+    let result = run_test_rom(test_rom(&program), MAX_FRAMES, CpuCore::Instruction).unwrap();
+    assert_eq!(TestRomStatus::Failed(3), result.status);
+    assert_eq!("failed", result.text);
+}
+
+#[test]
+fn times_out_without_a_result() {
+    let mut program = report(0x80, "running");
+    spin(&mut program);
+
+    let result = run_test_rom(test_rom(&program), 10, CpuCore::Instruction).unwrap();
+    assert_eq!(TestRomStatus::TimedOut, result.status);
+    assert_eq!("running", result.text);
+    assert_eq!(10, result.frames);
+}
+
+#[test]
+fn ignores_status_without_signature() {
+    let mut program = store(0x6000, 0);
+    spin(&mut program);
+
+    let result = run_test_rom(test_rom(&program), 10, CpuCore::Instruction).unwrap();
+    assert_eq!(TestRomStatus::TimedOut, result.status);
+}
+
+#[test]
+fn presses_reset_when_requested() {
+    // INC $6100 / LDA $6100 / CMP #2 / BNE request
+    let mut program = vec![0xEE, 0x00, 0x61, 0xAD, 0x00, 0x61, 0xC9, 0x02, 0xD0, 0x00];
+    program.extend(report(0, "after reset"));
+    spin(&mut program);
+    program[9] = (program.len() - 10) as u8;
+    program.extend(report(0x81, "press reset"));
+    spin(&mut program);
+
+    let result = run_test_rom(test_rom(&program), MAX_FRAMES, CpuCore::Instruction).unwrap();
+    assert_eq!(TestRomStatus::Passed, result.status);
+    assert_eq!("after reset", result.text);
+    assert!(result.frames > super::RESET_DELAY_FRAMES);
+}
+
+fn run_fixture(path: &str, core: CpuCore) {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", path]
+        .iter()
+        .collect();
+    let raw = std::fs::read(&path)
+        .unwrap_or_else(|err| panic!("cannot read {}: {}", path.display(), err));
+
+    let rom = Rom::new(&raw).unwrap();
+    let result = run_test_rom(rom, MAX_FRAMES, core).unwrap();
+    assert!(
+        result.status == TestRomStatus::Passed,
+        "{} ended with {:?} after {} frames:\n{}",
+        path.display(),
+        result.status,
+        result.frames,
+        result.text
+    );
+}
+
+macro_rules! test_roms {
+    ($core:ident: $($name:ident => $path:expr,)*) => {
+        $(
+            #[test]
+            #[ignore = "needs the test ROMs in tests/fixtures"]
+            fn $name() {
+                run_fixture($path, CpuCore::$core);
+            }
+        )*
+    };
+}
+
+test_roms! {
+    Instruction:
+    instr_basics => "instr_test-v5/rom_singles/01-basics.nes",
+    instr_implied => "instr_test-v5/rom_singles/02-implied.nes",
+    instr_immediate => "instr_test-v5/rom_singles/03-immediate.nes",
+    instr_zero_page => "instr_test-v5/rom_singles/04-zero_page.nes",
+    instr_zp_xy => "instr_test-v5/rom_singles/05-zp_xy.nes",
+    instr_absolute => "instr_test-v5/rom_singles/06-absolute.nes",
+    instr_abs_xy => "instr_test-v5/rom_singles/07-abs_xy.nes",
+    instr_ind_x => "instr_test-v5/rom_singles/08-ind_x.nes",
+    instr_ind_y => "instr_test-v5/rom_singles/09-ind_y.nes",
+    instr_branches => "instr_test-v5/rom_singles/10-branches.nes",
+    instr_stack => "instr_test-v5/rom_singles/11-stack.nes",
+    instr_jmp_jsr => "instr_test-v5/rom_singles/12-jmp_jsr.nes",
+    instr_rts => "instr_test-v5/rom_singles/13-rts.nes",
+    instr_rti => "instr_test-v5/rom_singles/14-rti.nes",
+    instr_brk => "instr_test-v5/rom_singles/15-brk.nes",
+    instr_special => "instr_test-v5/rom_singles/16-special.nes",
+    apu_test => "apu_test/apu_test.nes",
+}
+
+// these check the dummy accesses only the cycle-accurate core makes
+test_roms! {
+    CycleAccurate:
+    cpu_dummy_reads => "cpu_dummy_reads/cpu_dummy_reads.nes",
+    cpu_dummy_writes_ppumem => "cpu_dummy_writes/cpu_dummy_writes_ppumem.nes",
+    cpu_dummy_writes_oam => "cpu_dummy_writes/cpu_dummy_writes_oam.nes",
+    instr_timing_cycle_accurate => "instr_timing/instr_timing.nes",
+}
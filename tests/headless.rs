@@ -0,0 +1,90 @@
+//! Drives the core through its public API only, the way test harnesses and
+//! batch tools embedding `nes_core` do. Runs without the `sdl` feature.
+
+use nes_core::bus::Mem;
+use nes_core::cartridge::RomBuilder;
+use nes_core::cpu::CPU;
+use nes_core::joypad::JoypadButton;
+use nes_core::ppu::Frame;
+
+/// 32 KiB NROM image running `program` from $8000.
+fn rom(program: &[u8]) -> RomBuilder {
+    RomBuilder::new()
+        .prg_rom(vec![0xEA; 0x8000])
+        .program(0x8000, program)
+}
+
+fn run_frames(cpu: &mut CPU, frames: u32) {
+    let mut done = 0;
+    while done < frames {
+        cpu.step().unwrap();
+        if cpu.bus.poll_frame_complete() {
+            done += 1;
+        }
+    }
+}
+
+#[test]
+fn runs_a_cartridge_and_renders_frames() {
+    let program = [
+        0x78, // SEI
+        0xA9, 0x3F, 0x8D, 0x06, 0x20, // LDA #$3F / STA $2006
+        0xA9, 0x00, 0x8D, 0x06, 0x20, // LDA #$00 / STA $2006
+        0xA9, 0x16, 0x8D, 0x07, 0x20, // LDA #$16 / STA $2007: red backdrop
+        0xA9, 0x08, 0x8D, 0x01, 0x20, // LDA #$08 / STA $2001: show background
+        0x4C, 0x15, 0x80, // JMP $8015
+    ];
+    let mut cpu = CPU::new();
+    cpu.bus
+        .insert_cartridge(rom(&program).rom().unwrap())
+        .unwrap();
+    cpu.reset();
+
+    run_frames(&mut cpu, 2);
+    assert_eq!(cpu.program_counter, 0x8015);
+    assert_eq!(
+        cpu.bus.ppu.frame.data.len(),
+        Frame::WIDTH * Frame::HEIGHT * 3
+    );
+    assert_eq!(&cpu.bus.ppu.frame.data[..3], &[0xFF, 0x22, 0x00]);
+    assert!(!cpu.bus.apu.take_samples().is_empty());
+}
+
+#[test]
+fn reads_joypads() {
+    // strobe, then read A and B from port 1 into $00 and $01
+    let program = [
+        0x78, 0xA9, 0x01, 0x8D, 0x16, 0x40, 0xA9, 0x00, 0x8D, 0x16, 0x40, 0xAD, 0x16, 0x40, 0x85,
+        0x00, 0xAD, 0x16, 0x40, 0x85, 0x01, 0x4C, 0x15, 0x80,
+    ];
+    let mut cpu = CPU::new();
+    cpu.bus
+        .insert_cartridge(rom(&program).rom().unwrap())
+        .unwrap();
+    cpu.bus.joypad1.set_button_pressed(JoypadButton::B, true);
+    cpu.reset();
+
+    run_frames(&mut cpu, 1);
+    assert_eq!(cpu.mem_read(0x00), 0x40);
+    assert_eq!(cpu.mem_read(0x01), 0x41);
+}
+
+#[test]
+fn injects_and_extracts_battery_saves() {
+    // doubles the byte at $6000 and stores it at $6001
+    let program = [
+        0x78, 0xAD, 0x00, 0x60, 0x0A, 0x8D, 0x01, 0x60, 0x4C, 0x08, 0x80,
+    ];
+    let mut cpu = CPU::new();
+    cpu.bus
+        .insert_cartridge(rom(&program).battery(true).rom().unwrap())
+        .unwrap();
+    let mut save = vec![0; 0x2000];
+    save[0] = 0x21;
+    cpu.bus.load_battery_ram(&save).unwrap();
+    cpu.reset();
+
+    run_frames(&mut cpu, 1);
+    assert!(cpu.bus.take_battery_ram_changed());
+    assert_eq!(&cpu.bus.battery_ram().unwrap()[..2], &[0x21, 0x42]);
+}
@@ -0,0 +1,297 @@
+//! Runs the SingleStepTests (formerly ProcessorTests) vectors for the NES's
+//! 6502: for every opcode, thousands of cases giving the registers and
+//! memory before and after one instruction and the bus activity of each
+//! cycle. The vectors are not redistributed here, put them in
+//! `tests/fixtures/nes6502/v1` (see the README there) and run
+//! `cargo test --test single_step -- --ignored`.
+//!
+//! `tests/fixtures/nes6502/sample` holds a few hand-written cases in the
+//! same format for LDA #imm, ROR A, STA (zp),Y and INC abs,X, so that the
+//! comparison itself runs with the other tests.
+//!
+//! `single_step_bus_cycles` runs the vectors on the cycle-accurate core
+//! (`CpuCore::CycleAccurate`) and also compares the reads and writes of
+//! every cycle, dummy accesses included. `SINGLE_STEP_OPCODES=a9,6a` only
+//! runs the listed opcodes.
+
+use nes_core::bus::{Access, BusAccess, FlatBus, Mem};
+use nes_core::cpu::{CpuCore, Instruction, CPU, OPCODES};
+use nes_core::disasm;
+use serde::Deserialize;
+use std::fmt::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+/// Opcodes whose results depend on the chip (XAA, LXA, SHA, SHX, SHY, TAS)
+/// or that lock it up (JAM). Their results are reported but do not fail
+/// the test.
+fn unstable(opcode: u8) -> bool {
+    let instruction = OPCODES[opcode as usize].instruction;
+    instruction.is_unstable() || instruction == Instruction::Jam
+}
+
+#[derive(Deserialize)]
+struct Case {
+    name: String,
+    initial: State,
+    #[serde(rename = "final")]
+    expected: State,
+    /// (address, data, "read" or "write") for every cycle.
+    cycles: Vec<(u16, u8, String)>,
+}
+
+#[derive(Deserialize, Clone, PartialEq)]
+struct State {
+    pc: u16,
+    s: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    ram: Vec<(u16, u8)>,
+}
+
+impl State {
+    fn registers(&self) -> String {
+        format!(
+            "PC:{:04X} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
+            self.pc, self.a, self.x, self.y, self.p, self.s
+        )
+    }
+}
+
+fn fixtures(set: &str) -> PathBuf {
+    [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "fixtures",
+        "nes6502",
+        set,
+    ]
+    .iter()
+    .collect()
+}
+
+fn selected_opcodes() -> Vec<u8> {
+    match std::env::var("SINGLE_STEP_OPCODES") {
+        Ok(list) => list
+            .split(',')
+            .map(|code| u8::from_str_radix(code.trim(), 16).expect("SINGLE_STEP_OPCODES"))
+            .collect(),
+        Err(_) => (0..=0xFF).collect(),
+    }
+}
+
+/// Runs one case; returns a description of what differs, if anything.
+fn run_case(cpu: &mut CPU<FlatBus>, case: &Case, compare_bus: bool) -> Option<String> {
+    let initial = &case.initial;
+    cpu.reset();
+    cpu.program_counter = initial.pc;
+    cpu.stack_pointer = initial.s;
+    cpu.accumulator = initial.a;
+    cpu.register_x = initial.x;
+    cpu.register_y = initial.y;
+    cpu.status.insert(initial.p);
+    for &(address, data) in &initial.ram {
+        cpu.bus.mem_write(address, data);
+    }
+    cpu.bus.take_access_log();
+
+    let start = cpu.cycles;
+    let result = cpu.step();
+    let cycles = cpu.cycles - start;
+
+    // B and bit 5 are not flags, only what PHP and BRK push shows them
+    let actual = State {
+        pc: cpu.program_counter,
+        s: cpu.stack_pointer,
+        a: cpu.accumulator,
+        x: cpu.register_x,
+        y: cpu.register_y,
+        p: cpu.status.get() | 0x30,
+        ram: case
+            .expected
+            .ram
+            .iter()
+            .map(|&(address, _)| (address, cpu.bus.peek(address)))
+            .collect(),
+    };
+    let expected = State {
+        p: case.expected.p | 0x30,
+        ..case.expected.clone()
+    };
+    let accesses = cpu.bus.take_access_log();
+    let bus_matches = !compare_bus || same_accesses(&accesses, &case.cycles);
+    let error = result.err();
+    if actual == expected && bus_matches && error.is_none() {
+        return None;
+    }
+
+    let mut report = format!(
+        "case \"{}\"\n  initial:  {}\n  expected: {}\n  actual:   {}\n",
+        case.name,
+        initial.registers(),
+        expected.registers(),
+        actual.registers()
+    );
+    if let Some(err) = error {
+        let _ = writeln!(report, "  CPU error: {}", err);
+    }
+    for (&(address, want), &(_, got)) in expected.ram.iter().zip(&actual.ram) {
+        if want != got {
+            let _ = writeln!(
+                report,
+                "  ${:04X}: expected {:02X}, actual {:02X}",
+                address, want, got
+            );
+        }
+    }
+    if !bus_matches {
+        let _ = writeln!(
+            report,
+            "  expected {} cycles: {}\n  actual {} cycles:   {}",
+            case.cycles.len(),
+            expected_accesses(&case.cycles),
+            cycles,
+            actual_accesses(&accesses)
+        );
+    }
+    Some(report)
+}
+
+fn same_accesses(actual: &[BusAccess], expected: &[(u16, u8, String)]) -> bool {
+    actual.len() == expected.len()
+        && actual
+            .iter()
+            .zip(expected)
+            .all(|(access, (address, data, kind))| {
+                let actual_kind = match access.access {
+                    Access::Read => "read",
+                    Access::Write => "write",
+                };
+                access.address == *address && access.data == *data && actual_kind == kind
+            })
+}
+
+fn expected_accesses(cycles: &[(u16, u8, String)]) -> String {
+    cycles
+        .iter()
+        .map(|(address, data, kind)| format!("{}{:04X}={:02X}", &kind[..1], address, data))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn actual_accesses(accesses: &[BusAccess]) -> String {
+    accesses
+        .iter()
+        .map(|access| {
+            let kind = match access.access {
+                Access::Read => 'r',
+                Access::Write => 'w',
+            };
+            format!("{}{:04X}={:02X}", kind, access.address, access.data)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[test]
+fn sample_cases() {
+    let directory = fixtures("sample");
+    run_vectors(&directory, &[0xA9, 0x6A, 0x91, 0xFE], false);
+    run_vectors(&directory, &[0xA9, 0x6A, 0x91, 0xFE], true);
+}
+
+#[test]
+#[ignore = "needs the SingleStepTests vectors in tests/fixtures/nes6502/v1"]
+fn single_step_tests() {
+    run_vectors(&vectors(), &selected_opcodes(), false);
+}
+
+#[test]
+#[ignore = "needs the SingleStepTests vectors in tests/fixtures/nes6502/v1"]
+fn single_step_bus_cycles() {
+    run_vectors(&vectors(), &selected_opcodes(), true);
+}
+
+fn vectors() -> PathBuf {
+    let directory = fixtures("v1");
+    assert!(
+        directory.is_dir(),
+        "SingleStepTests vectors not found in {}",
+        directory.display()
+    );
+    directory
+}
+
+/// Runs every case of `opcodes` from `directory`, on the cycle-accurate core
+/// with its bus activity compared if `compare_bus`; fails on a mismatch in
+/// any opcode that is not unstable, or on a missing file.
+fn run_vectors(directory: &Path, opcodes: &[u8], compare_bus: bool) {
+    let mut cpu = CPU::with_bus(FlatBus::new());
+    cpu.bus.log_accesses(compare_bus);
+    if compare_bus {
+        cpu.set_core(CpuCore::CycleAccurate);
+    }
+
+    let mut failures = Vec::new();
+    for &opcode in opcodes {
+        let path = directory.join(format!("{:02x}.json", opcode));
+        let json = std::fs::read(&path)
+            .unwrap_or_else(|err| panic!("cannot read {}: {}", path.display(), err));
+        let cases: Vec<Case> = serde_json::from_slice(&json)
+            .unwrap_or_else(|err| panic!("{} is not valid: {}", path.display(), err));
+
+        let mut passed = 0;
+        let mut first_mismatch = None;
+        // a panicking case is a failure like any other, without the noise
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(|_| {}));
+        for case in &cases {
+            let result =
+                panic::catch_unwind(AssertUnwindSafe(|| run_case(&mut cpu, case, compare_bus)));
+            let report = result.unwrap_or_else(|payload| {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                Some(format!("case \"{}\" panicked: {}\n", case.name, message))
+            });
+            match report {
+                None => passed += 1,
+                Some(report) => {
+                    first_mismatch.get_or_insert(report);
+                }
+            }
+        }
+        panic::set_hook(hook);
+
+        let mnemonic = disasm::decode(0, |_| opcode).mnemonic;
+        let unstable = unstable(opcode);
+        eprintln!(
+            "{:02X} {:5} {:5}/{} passed{}",
+            opcode,
+            mnemonic,
+            passed,
+            cases.len(),
+            if unstable {
+                " (unstable, not checked)"
+            } else {
+                ""
+            }
+        );
+        if let Some(report) = first_mismatch {
+            eprint!("{}", report);
+            if !unstable {
+                failures.push(format!("{:02X} {}: {}", opcode, mnemonic, report));
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} opcodes failed, first mismatch of each:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}